@@ -0,0 +1,24 @@
+#![no_main]
+
+use chaum_pedersen::utils::{bigint_from_hex, bigint_from_hex_bounded};
+use libfuzzer_sys::fuzz_target;
+
+/// Byte length of the largest supported group, 4096 bits
+const MAX_BYTES: usize = 512;
+
+// Malformed hex has to come back as an error, whatever parses survives a round trip
+fuzz_target!(|data: &[u8]| {
+    let Ok(input) = std::str::from_utf8(data) else {
+        return;
+    };
+
+    let parsed = bigint_from_hex(input);
+    if let Ok(value) = &parsed {
+        assert_eq!(bigint_from_hex(&value.to_str_radix(16)).as_ref(), Ok(value));
+    }
+
+    // The bound only ever rejects more
+    if let Ok(value) = bigint_from_hex_bounded(input, MAX_BYTES) {
+        assert_eq!(parsed, Ok(value));
+    }
+});
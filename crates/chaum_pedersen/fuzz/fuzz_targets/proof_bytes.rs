@@ -0,0 +1,17 @@
+#![no_main]
+
+use chaum_pedersen::proof::Proof;
+use libfuzzer_sys::fuzz_target;
+
+// Every field is prefixed with the byte length, the first prefix is taken as the expected one so
+// well-formed blobs of any group size get past the length checks
+fuzz_target!(|data: &[u8]| {
+    let byte_len = match data {
+        [hi, lo, ..] => u16::from_be_bytes([*hi, *lo]) as usize,
+        _ => 0,
+    };
+
+    if let Ok(proof) = Proof::from_bytes(data, byte_len) {
+        assert_eq!(proof.to_bytes(byte_len).unwrap(), data);
+    }
+});
@@ -0,0 +1,22 @@
+#![no_main]
+
+use chaum_pedersen::ecc_chaum_pedersen::EccChaumPedersen;
+use libfuzzer_sys::fuzz_target;
+
+// The same input is fed to every decoder of the non-interactive protocol, points that decode
+// re-encode to a string decoding to the same point
+fuzz_target!(|data: &[u8]| {
+    let Ok(input) = std::str::from_utf8(data) else {
+        return;
+    };
+
+    if let Ok(point) = EccChaumPedersen::from_base64(input) {
+        let encoded = EccChaumPedersen::to_base64(&point);
+        assert_eq!(EccChaumPedersen::from_base64(&encoded), Ok(point));
+    }
+    if let Ok(point) = EccChaumPedersen::from_pem(input) {
+        let encoded = EccChaumPedersen::to_pem(&point);
+        assert_eq!(EccChaumPedersen::from_pem(&encoded), Ok(point));
+    }
+    let _ = EccChaumPedersen::scalar_from_json(input);
+});
@@ -1,74 +1,321 @@
-use std::sync::Arc;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, PoisonError};
 
+#[cfg(feature = "std-async")]
+use futures::{Stream, StreamExt};
 use lazy_static::lazy_static;
 use num_bigint::{BigInt, Sign, ToBigInt};
-use sha2::{Digest, Sha512};
-use tokio::try_join;
+use rand_core::{CryptoRng, CryptoRngCore, RngCore};
+#[cfg(feature = "rayon")]
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256, Sha512};
 
-use crate::ChaumPedersenTrait;
-use crate::utils::generate_random_bigint;
+use crate::core_math;
+use crate::hashing;
+use crate::proof::{MultiProof, Proof};
+use crate::utils::{generate_random_bigint, generate_random_bigint_with};
+use crate::{ChaumPedersenTrait, CpError, CpResult, InteractiveVerifier};
 
-// https://www.rfc-editor.org/rfc/rfc3526#page-3 2048-bt MODP Group
 lazy_static! {
+    /// https://www.rfc-editor.org/rfc/rfc3526#section-3 2048-bit MODP Group
+    pub static ref MODP_2048: BigInt = BigInt::parse_bytes(b"FFFFFFFFFFFFFFFFC90FDAA22168C234C4C6628B80DC1CD129024E088A67CC74020BBEA63B139B22514A08798E3404DDEF9519B3CD3A431B302B0A6DF25F14374FE1356D6D51C245E485B576625E7EC6F44C42E9A637ED6B0BFF5CB6F406B7EDEE386BFB5A899FA5AE9F24117C4B1FE649286651ECE45B3DC2007CB8A163BF0598DA48361C55D39A69163FA8FD24CF5F83655D23DCA3AD961C62F356208552BB9ED529077096966D670C354E4ABC9804F1746C08CA18217C32905E462E36CE3BE39E772C180E86039B2783A2EC07A28FB5C55DF06F4C52C9DE2BCBF6955817183995497CEA956AE515D2261898FA051015728E5A8AACAA68FFFFFFFFFFFFFFFF", 16).unwrap();
+    /// https://www.rfc-editor.org/rfc/rfc3526#section-4 3072-bit MODP Group
+    pub static ref MODP_3072: BigInt = BigInt::parse_bytes(b"FFFFFFFFFFFFFFFFC90FDAA22168C234C4C6628B80DC1CD129024E088A67CC74020BBEA63B139B22514A08798E3404DDEF9519B3CD3A431B302B0A6DF25F14374FE1356D6D51C245E485B576625E7EC6F44C42E9A637ED6B0BFF5CB6F406B7EDEE386BFB5A899FA5AE9F24117C4B1FE649286651ECE45B3DC2007CB8A163BF0598DA48361C55D39A69163FA8FD24CF5F83655D23DCA3AD961C62F356208552BB9ED529077096966D670C354E4ABC9804F1746C08CA18217C32905E462E36CE3BE39E772C180E86039B2783A2EC07A28FB5C55DF06F4C52C9DE2BCBF6955817183995497CEA956AE515D2261898FA051015728E5A8AAAC42DAD33170D04507A33A85521ABDF1CBA64ECFB850458DBEF0A8AEA71575D060C7DB3970F85A6E1E4C7ABF5AE8CDB0933D71E8C94E04A25619DCEE3D2261AD2EE6BF12FFA06D98A0864D87602733EC86A64521F2B18177B200CBBE117577A615D6C770988C0BAD946E208E24FA074E5AB3143DB5BFCE0FD108E4B82D120A93AD2CAFFFFFFFFFFFFFFFF", 16).unwrap();
+    /// https://www.rfc-editor.org/rfc/rfc3526#section-5 4096-bit MODP Group
+    pub static ref MODP_4096: BigInt = BigInt::parse_bytes(b"FFFFFFFFFFFFFFFFC90FDAA22168C234C4C6628B80DC1CD129024E088A67CC74020BBEA63B139B22514A08798E3404DDEF9519B3CD3A431B302B0A6DF25F14374FE1356D6D51C245E485B576625E7EC6F44C42E9A637ED6B0BFF5CB6F406B7EDEE386BFB5A899FA5AE9F24117C4B1FE649286651ECE45B3DC2007CB8A163BF0598DA48361C55D39A69163FA8FD24CF5F83655D23DCA3AD961C62F356208552BB9ED529077096966D670C354E4ABC9804F1746C08CA18217C32905E462E36CE3BE39E772C180E86039B2783A2EC07A28FB5C55DF06F4C52C9DE2BCBF6955817183995497CEA956AE515D2261898FA051015728E5A8AAAC42DAD33170D04507A33A85521ABDF1CBA64ECFB850458DBEF0A8AEA71575D060C7DB3970F85A6E1E4C7ABF5AE8CDB0933D71E8C94E04A25619DCEE3D2261AD2EE6BF12FFA06D98A0864D87602733EC86A64521F2B18177B200CBBE117577A615D6C770988C0BAD946E208E24FA074E5AB3143DB5BFCE0FD108E4B82D120A92108011A723C12A787E6D788719A10BDBA5B2699C327186AF4E23C1A946834B6150BDA2583E9CA2AD44CE8DBBBC2DB04DE8EF92E8EFC141FBECAA6287C59474E6BC05D99B2964FA090C3A2233BA186515BE7ED1F612970CEE2D7AFB81BDD762170481CD0069127D5B05AA993B4EA988D8FDDC186FFB7DC90A6C08F4DF435C934063199FFFFFFFFFFFFFFFF", 16).unwrap();
         /// Order of the cyclic group G, must be a large prime number.
         /// The elements of the group range from 0 to q - 1. Arithmetic operations are performed module q.
-    pub static ref P: BigInt = BigInt::parse_bytes(b"FFFFFFFFFFFFFFFFC90FDAA22168C234C4C6628B80DC1CD129024E088A67CC74020BBEA63B139B22514A08798E3404DDEF9519B3CD3A431B302B0A6DF25F14374FE1356D6D51C245E485B576625E7EC6F44C42E9A637ED6B0BFF5CB6F406B7EDEE386BFB5A899FA5AE9F24117C4B1FE649286651ECE45B3DC2007CB8A163BF0598DA48361C55D39A69163FA8FD24CF5F83655D23DCA3AD961C62F356208552BB9ED529077096966D670C354E4ABC9804F1746C08CA18217C32905E462E36CE3BE39E772C180E86039B2783A2EC07A28FB5C55DF06F4C52C9DE2BCBF6955817183995497CEA956AE515D2261898FA051015728E5A8AAAC42DAD33170D04507A33A85521ABDF1CBA64ECFB850458DBEF0A8AEA71575D060C7DB3970F85A6E1E4C7ABF5AE8CDB0933D71E8C94E04A25619DCEE3D2261AD2EE6BF12FFA06D98A0864D87602733EC86A64521F2B18177B200CBBE117577A615D6C770988C0BAD946E208E24FA074E5AB3143DB5BFCE0FD108E4B82D120A93AD2CAFFFFFFFFFFFFFFFF", 16).unwrap();
+        /// This is the 3072-bit MODP Group, see `Group` for the selectable sizes.
+    pub static ref P: BigInt = MODP_3072.clone();
     pub static ref G: BigInt = 2.to_bigint().unwrap();
     pub static ref H: BigInt = 3.to_bigint().unwrap();
+    /// Order of the subgroup generated by `G` and `H`, `Q = (P - 1) / 2`
+    pub static ref Q: BigInt = (&*P - BigInt::from(1)) / BigInt::from(2);
 }
 
-#[derive(Debug)]
+/// Bit length `p` may have unless another maximum is passed to `ChaumPedersen::from_params`, twice
+/// the largest built-in group
+pub const DEFAULT_MAX_P_BITS: u64 = 8192;
+
+/// Size from which `verify_batch` goes through rayon with the `rayon` feature, below it the tasks
+/// per proof are cheaper than handing the batch to the pool
+pub const PARALLEL_BATCH_THRESHOLD: usize = 64;
+
+/// Safe prime `p = 2^64 - 1469 = 2q + 1` of `ChaumPedersen::new_test_group`
+pub const TEST_GROUP_P: u64 = 0xffff_ffff_ffff_fa43;
+/// `q = 2^63 - 735`, prime
+pub const TEST_GROUP_Q: u64 = 0x7fff_ffff_ffff_fd21;
+/// `4 = 2^2` and `9 = 3^2` are squares, so both are in the subgroup of order `q`. `2` itself
+/// isn't since `p ≡ 3 mod 8`.
+pub const TEST_GROUP_G: u64 = 4;
+pub const TEST_GROUP_H: u64 = 9;
+
+/// Domain separation of the commitment hash in `ChaumPedersen::verifier_bind_challenge`
+const CHALLENGE_BINDING_DOMAIN: &[u8] = b"chaum-pedersen-challenge-binding";
+
+/// Shared between a blocking verification and the future awaiting it. Dropping the future sets it
+/// and the verification gives up with `CpError::Cancelled` before its next exponentiation, instead
+/// of computing a result nobody reads.
+#[derive(Debug, Clone, Default)]
+pub struct CancelFlag {
+    cancelled: Arc<AtomicBool>,
+    gave_up: Arc<AtomicBool>,
+}
+
+impl CancelFlag {
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Relaxed)
+    }
+
+    /// Whether a verification saw the flag and stopped early
+    pub fn gave_up(&self) -> bool {
+        self.gave_up.load(Ordering::Relaxed)
+    }
+
+    fn check(&self) -> CpResult<()> {
+        if self.is_cancelled() {
+            self.gave_up.store(true, Ordering::Relaxed);
+            return Err(CpError::Cancelled);
+        }
+        Ok(())
+    }
+}
+
+/// Cancels the flag when the future holding it is dropped, a finished verification doesn't
+/// check it anymore
+#[cfg(feature = "std-async")]
+struct CancelOnDrop(CancelFlag);
+
+#[cfg(feature = "std-async")]
+impl Drop for CancelOnDrop {
+    fn drop(&mut self) {
+        self.0.cancel();
+    }
+}
+
+/// RFC 3526 MODP group the interactive protocol runs in, all of them use `g = 2` and `h = 3`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Group {
+    #[default]
+    Modp2048,
+    Modp3072,
+    Modp4096,
+}
+
+impl Group {
+    pub const ALL: [Group; 3] = [Group::Modp2048, Group::Modp3072, Group::Modp4096];
+
+    /// Group of users registered before the group was selectable, it was the built-in `P`
+    pub const LEGACY: Group = Group::Modp3072;
+
+    /// Bit length of the prime, also the wire representation of the group
+    pub fn bits(&self) -> u32 {
+        match self {
+            Group::Modp2048 => 2048,
+            Group::Modp3072 => 3072,
+            Group::Modp4096 => 4096,
+        }
+    }
+
+    pub fn from_bits(bits: u32) -> CpResult<Self> {
+        Group::ALL
+            .into_iter()
+            .find(|group| group.bits() == bits)
+            .ok_or_else(|| CpError::InvalidParams(format!("unsupported group size {}", bits)))
+    }
+
+    pub fn prime(&self) -> &'static BigInt {
+        match self {
+            Group::Modp2048 => &MODP_2048,
+            Group::Modp3072 => &MODP_3072,
+            Group::Modp4096 => &MODP_4096,
+        }
+    }
+
+    pub fn protocol(&self) -> ChaumPedersen {
+        ChaumPedersen::new(self.prime().clone(), G.clone(), H.clone())
+    }
+}
+
+impl std::str::FromStr for Group {
+    type Err = CpError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let bits = s
+            .parse::<u32>()
+            .map_err(|_| CpError::InvalidParams(format!("unsupported group size {:?}", s)))?;
+        Group::from_bits(bits)
+    }
+}
+
+impl std::fmt::Display for Group {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.bits())
+    }
+}
+
+/// Digest used to derive the secret `x` from a password
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashAlg {
+    Sha256,
+    Sha512,
+}
+
+#[derive(Debug, Clone)]
 pub struct ChaumPedersen {
     /// Order of cyclic group G, large prime number
     pub p: Arc<BigInt>,
-    /// The largest prime divisor of `p - 1`, `q` specifies the size of the cyclic subgroup
-    pub q: BigInt,
+    /// The largest prime divisor of `p - 1`, `q` specifies the size of the cyclic subgroup.
+    /// `p` is a safe prime so `q = (p - 1) / 2`, use `order()` to read it.
+    q: BigInt,
     /// Generator of the group which is an element when raised to the power of `q - 1` it generates every element in the group.
     /// {g^0, g^1, g^2, g^3, ..., g^{q-1}}
     pub g: Arc<BigInt>,
     /// Distinct generator from `g` in Chaum-Pedersen protocol `h` is used for proving that the exponent `x` for `g` is the same as for `h`
     /// `y1 = g^x` and `y2 = h^x` then `y1 == y2`
     pub h: Arc<BigInt>,
+    /// Source of the commitment nonce `k`, `thread_rng` if none was injected with `with_rng`
+    rng: Option<CommitmentRng>,
+}
+
+/// Randomness source for the commitment nonce `k`, e.g. the RNG of an HSM. The clones of a
+/// `ChaumPedersen` share it, each draw takes a lock.
+#[derive(Clone)]
+pub struct CommitmentRng(Arc<Mutex<dyn CryptoRngCore + Send>>);
+
+impl CommitmentRng {
+    pub fn new<R: CryptoRngCore + Send + 'static>(rng: R) -> Self {
+        Self(Arc::new(Mutex::new(rng)))
+    }
+}
+
+impl fmt::Debug for CommitmentRng {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("CommitmentRng")
+    }
+}
+
+/// Outcome of `ChaumPedersen::audit`, the two verification equations checked separately
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AuditReport {
+    /// `g^s * y1^c mod p`
+    pub r1: BigInt,
+    /// `h^s * y2^c mod p`
+    pub r2: BigInt,
+    /// `r1` equals the commitment of the proof
+    pub r1_holds: bool,
+    /// `r2` equals the commitment of the proof
+    pub r2_holds: bool,
+}
+
+impl AuditReport {
+    /// Both equations hold, the proof verifies against the keys
+    pub fn holds(&self) -> bool {
+        self.r1_holds && self.r2_holds
+    }
+}
+
+/// Outcome of `ChaumPedersen::verify_proof_verbose`, the commitments `t1`, `t2` recomputed from the
+/// keys next to the commitments `r1`, `r2` of the proof
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerboseVerification {
+    /// `t1 == r1 && t2 == r2`, what `verify_interactive` returns
+    pub ok: bool,
+    /// `g^s * y1^c mod p`
+    pub t1: BigInt,
+    pub r1: BigInt,
+    /// `h^s * y2^c mod p`
+    pub t2: BigInt,
+    pub r2: BigInt,
+}
+
+/// Serializable view of the group parameters of a `ChaumPedersen` instance,
+/// used to ship agreed-upon parameters between services.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct CpParams {
+    pub p: BigInt,
+    pub g: BigInt,
+    pub h: BigInt,
+    pub q: BigInt,
+}
+
+impl From<&ChaumPedersen> for CpParams {
+    fn from(cp: &ChaumPedersen) -> Self {
+        Self {
+            p: (*cp.p).clone(),
+            g: (*cp.g).clone(),
+            h: (*cp.h).clone(),
+            q: cp.order().clone(),
+        }
+    }
+}
+
+impl TryFrom<CpParams> for ChaumPedersen {
+    type Error = CpError;
+
+    /// See `ChaumPedersen::from_params`, `p` is limited to `DEFAULT_MAX_P_BITS`
+    fn try_from(params: CpParams) -> Result<Self, Self::Error> {
+        ChaumPedersen::from_params(params, DEFAULT_MAX_P_BITS)
+    }
+}
+
+/// Public keys `(g^x mod p, h^x mod p)` of `secret_x` in the group of `params`, the same as
+/// `ChaumPedersen::generate_public_keys` without building an instance. `params` aren't validated.
+pub fn derive_public_keys(params: &CpParams, secret_x: &BigInt) -> (BigInt, BigInt) {
+    (
+        core_math::public_key(&params.g, secret_x, &params.p),
+        core_math::public_key(&params.h, secret_x, &params.p),
+    )
 }
 
 impl ChaumPedersenTrait for ChaumPedersen {
     type Point = BigInt;
     type Scalar = BigInt;
 
+    #[cfg(feature = "std-async")]
     async fn generate_public_keys(
         &self,
         secret_scalar: Self::Scalar,
-    ) -> (Self::Point, Self::Point) {
-        let g = self.g.clone();
-        let h = self.h.clone();
-        let p = self.p.clone();
-
-        // Asynchronously calculate the public keys
-        let compute_public_keys = tokio::spawn(async move {
-            let y1 = g.modpow(&secret_scalar, &*p);
-            let y2 = h.modpow(&secret_scalar, &*p);
-            (y1, y2)
-        });
-
-        compute_public_keys
+    ) -> CpResult<(Self::Point, Self::Point)> {
+        let cp = self.clone();
+        tokio::task::spawn_blocking(move || cp.generate_public_keys_sync(secret_scalar))
             .await
-            .expect("failed to compute public keys (y1, y2)")
+            .map_err(|e| CpError::TaskJoin(e.to_string()))?
     }
 
-    async fn prover_commit(&self) -> (Self::Point, Option<Self::Point>, Option<Self::Point>) {
-        let modpow_closure = |base: Arc<BigInt>, exp: Arc<BigInt>, modulo: Arc<BigInt>| {
-            tokio::spawn(async move { base.modpow(&*exp, &modulo) })
-        };
-
-        // Random `k`
-        let k = generate_random_bigint(&self.q);
-
-        let r1 = modpow_closure(self.g.clone(), Arc::new(k.clone()), self.p.clone());
-        let r2 = modpow_closure(self.h.clone(), Arc::new(k.clone()), self.p.clone());
+    fn generate_public_keys_sync(
+        &self,
+        secret_scalar: Self::Scalar,
+    ) -> CpResult<(Self::Point, Self::Point)> {
+        let y1 = core_math::public_key(&self.g, &secret_scalar, &self.p);
+        let y2 = core_math::public_key(&self.h, &secret_scalar, &self.p);
+        Ok((y1, y2))
+    }
 
-        let result = try_join!(r1, r2).unwrap();
+    #[cfg(feature = "std-async")]
+    async fn prover_commit(&self) -> CpResult<(Self::Scalar, Self::Point, Self::Point)> {
+        let cp = self.clone();
+        tokio::task::spawn_blocking(move || cp.prover_commit_sync())
+            .await
+            .map_err(|e| CpError::TaskJoin(e.to_string()))?
+    }
 
-        (k, Some(result.0), Some(result.1))
+    /// Draws `k` from the injected `CommitmentRng`, if there is one
+    fn prover_commit_sync(&self) -> CpResult<(Self::Scalar, Self::Point, Self::Point)> {
+        match &self.rng {
+            Some(CommitmentRng(rng)) => {
+                // A panic can't leave an RNG in a state that's unsafe to draw from
+                let mut rng = rng.lock().unwrap_or_else(PoisonError::into_inner);
+                self.prover_commit_with(&mut *rng)
+            }
+            None => self.prover_commit_with(&mut rand::thread_rng()),
+        }
     }
 
     fn prover_solve_challenge(
@@ -77,103 +324,521 @@ impl ChaumPedersenTrait for ChaumPedersen {
         challenge: Self::Scalar,
         secret_x: Self::Scalar,
     ) -> Self::Scalar {
-        // The idea behind adding `random_k + &self.q` is that it will be larger than `challenge * secret_x` for sure, so `random_k = q - challenge * secret_x` will be non-negative.
-        let s = (random_k + &self.q - (challenge * secret_x)) % &self.q;
-        let zero = 0.to_bigint().unwrap();
+        core_math::solve(&random_k, &challenge, &secret_x, &self.q)
+    }
+}
 
-        // We're still checking for negative result, but this way we may avoid some negative intermediate values.
-        if s < zero {
-            s + &self.q
-        } else {
-            s
-        }
+impl InteractiveVerifier for ChaumPedersen {
+    /// Dropping the returned future, e.g. when the client disconnects or a timeout fires, stops
+    /// the blocking task, see `verify_interactive_with_cancel`
+    #[cfg(feature = "std-async")]
+    async fn verify_interactive(&self, proof: Proof, y1: BigInt, y2: BigInt) -> CpResult<bool> {
+        self.verify_interactive_with_cancel(proof, y1, y2, CancelFlag::default())
+            .await
     }
 
-    async fn verify_proof(
-        &self,
-        s: Self::Scalar,
-        c: Self::Scalar,
-        y1: Self::Point,
-        y2: Self::Point,
-        r1: Option<Self::Scalar>,
-        r2: Option<Self::Scalar>,
-    ) -> bool {
-        let verify_closure = |base1: Arc<BigInt>,
-                              exp1: Arc<BigInt>,
-                              base2: Arc<BigInt>,
-                              exp2: Arc<BigInt>,
-                              modulo: Arc<BigInt>| {
-            tokio::spawn(async move {
-                (base1.modpow(&*exp1, &modulo) * base2.modpow(&*exp2, &modulo)) % &*modulo
-            })
-        };
+    fn verify_interactive_sync(&self, proof: Proof, y1: BigInt, y2: BigInt) -> CpResult<bool> {
+        self.verify_interactive_cancellable(proof, y1, y2, &CancelFlag::default())
+    }
+}
 
-        let s = Arc::new(s);
-        let c = Arc::new(c);
-        let y1 = Arc::new(y1);
-        let y2 = Arc::new(y2);
+impl ChaumPedersen {
+    /// Same as `try_new` for parameters known to be valid, e.g. the built-in groups.
+    /// Panics where `try_new` fails.
+    pub fn new(p: BigInt, g: BigInt, h: BigInt) -> Self {
+        Self::try_new(p, g, h).unwrap_or_else(|e| panic!("{}", e))
+    }
 
-        // We directly use the variables in the first closure and clone them for the second
-        let t1 = verify_closure(
-            self.g.clone(),
-            s.clone(),
-            y1.clone(),
-            c.clone(),
-            self.p.clone(),
-        );
-        let t2 = verify_closure(
-            self.h.clone(),
-            s.clone(),
-            y2.clone(),
-            c.clone(),
-            self.p.clone(),
-        );
+    /// `p` has to be a safe prime `p = 2q + 1`, in debug builds the generators are validated against `q`.
+    /// `p` longer than `DEFAULT_MAX_P_BITS` is rejected with `CpError::ParamsTooLarge`.
+    pub fn try_new(p: BigInt, g: BigInt, h: BigInt) -> CpResult<Self> {
+        Self::check_size(&p, DEFAULT_MAX_P_BITS)?;
+        let cp = Self::from_parts(p, g, h);
 
-        let (t1, t2) = try_join!(t1, t2).unwrap();
+        #[cfg(debug_assertions)]
+        cp.validate()?;
 
-        r1.map(|val| t1 == val).unwrap_or(false) && r2.map(|val| t2 == val).unwrap_or(false)
+        Ok(cp)
     }
-}
 
-impl ChaumPedersen {
-    pub fn new(p: BigInt, g: BigInt, h: BigInt) -> Self {
-        let q = &p - BigInt::from(1);
+    /// 64-bit group for tests and fuzzing, see `TEST_GROUP_P`. A discrete logarithm in it is
+    /// cheap to compute, never use it for real proofs.
+    pub fn new_test_group() -> Self {
+        Self::new(
+            BigInt::from(TEST_GROUP_P),
+            BigInt::from(TEST_GROUP_G),
+            BigInt::from(TEST_GROUP_H),
+        )
+    }
+
+    /// Validated instance of untrusted `params`. `q` is re-derived from `p` and has to match the
+    /// serialized value. `p` longer than `max_bits` is rejected before anything is computed in the
+    /// group.
+    pub fn from_params(params: CpParams, max_bits: u64) -> CpResult<Self> {
+        Self::check_size(&params.p, max_bits)?;
+        if params.p <= BigInt::from(1) {
+            return Err(CpError::InvalidParams(
+                "p must be greater than 1".to_string(),
+            ));
+        }
+
+        let cp = ChaumPedersen::from_parts(params.p, params.g, params.h);
+        if *cp.order() != params.q {
+            return Err(CpError::InvalidParams("q does not match p".to_string()));
+        }
+        cp.validate()?;
+
+        Ok(cp)
+    }
+
+    fn check_size(p: &BigInt, max_bits: u64) -> CpResult<()> {
+        if p.bits() > max_bits {
+            return Err(CpError::ParamsTooLarge {
+                bits: p.bits(),
+                max_bits,
+            });
+        }
+        Ok(())
+    }
+
+    fn from_parts(p: BigInt, g: BigInt, h: BigInt) -> Self {
+        let q = (&p - BigInt::from(1)) / BigInt::from(2);
         Self {
             p: Arc::new(p),
             g: Arc::new(g),
             h: Arc::new(h),
             q,
+            rng: None,
+        }
+    }
+
+    /// Draws the commitment nonce `k` of `prover_commit` and of every `Prover` over this
+    /// protocol from `rng` instead of `thread_rng`
+    pub fn with_rng(self, rng: CommitmentRng) -> Self {
+        Self {
+            rng: Some(rng),
+            ..self
         }
     }
 
-    /// Hash function to convert byte slices to `BigInt` values
+    /// Byte length of `p`, the fixed width of every field in the binary wire format
+    pub fn byte_len(&self) -> usize {
+        self.p.bits().div_ceil(8) as usize
+    }
+
+    /// Order `q` of the subgroup generated by `g` and `h`, challenges and solutions are reduced mod `q`
+    pub fn order(&self) -> &BigInt {
+        &self.q
+    }
+
+    /// Checks that `1 < g, h < p` and that both generators are in the subgroup of order `q`, `g^q ≡ 1 mod p`
+    pub fn validate(&self) -> CpResult<()> {
+        self.validate_generator("g", &self.g)?;
+        self.validate_generator("h", &self.h)
+    }
+
+    fn validate_generator(&self, name: &str, generator: &BigInt) -> CpResult<()> {
+        let one = BigInt::from(1);
+        if *generator <= one || *generator >= *self.p {
+            return Err(CpError::InvalidParams(format!(
+                "generator {} must be in range 1 < {} < p",
+                name, name
+            )));
+        }
+        if generator.modpow(&self.q, &self.p) != one {
+            return Err(CpError::InvalidParams(format!(
+                "generator {} is not in the subgroup of order q",
+                name
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Hash function to convert byte slices to `BigInt` values, the result is reduced mod `Q`
     pub fn hash(input: &[u8]) -> BigInt {
-        let mut hasher = Sha512::new();
-        hasher.update(input);
-        let result = hasher.finalize();
-        BigInt::from_bytes_le(Sign::Plus, result.as_slice())
+        Self::hash_with(HashAlg::Sha512, input)
+    }
+
+    /// Hash function to convert byte slices to `BigInt` values with the chosen digest,
+    /// the result is reduced mod `Q` so it's a valid scalar of the RFC group.
+    pub fn hash_with(alg: HashAlg, input: &[u8]) -> BigInt {
+        Self::hash_mod(alg, input, &Q)
+    }
+
+    /// Same as `hash` but reduced mod the order of this instance's group
+    pub fn hash_to_scalar(&self, input: &[u8]) -> BigInt {
+        Self::hash_mod(HashAlg::Sha512, input, &self.q)
+    }
+
+    /// Reduces the digest mod `q`, a zero is not a valid secret so the digest is re-hashed until it's non-zero
+    fn hash_mod(alg: HashAlg, input: &[u8], q: &BigInt) -> BigInt {
+        let digest = |bytes: &[u8]| match alg {
+            HashAlg::Sha256 => Sha256::digest(bytes).to_vec(),
+            HashAlg::Sha512 => Sha512::digest(bytes).to_vec(),
+        };
+
+        let mut result = digest(input);
+        loop {
+            let x = BigInt::from_bytes_le(Sign::Plus, &result) % q;
+            if x != BigInt::from(0) {
+                return x;
+            }
+            result = digest(&result);
+        }
+    }
+
+    /// Same as `prover_commit_sync` but `k` is drawn from `rng`, tests can pass a seeded rng
+    pub fn prover_commit_with<R: RngCore + CryptoRng + ?Sized>(
+        &self,
+        rng: &mut R,
+    ) -> CpResult<(BigInt, BigInt, BigInt)> {
+        // Random `k`
+        let k = generate_random_bigint_with(rng, &self.q);
+        let (r1, r2) = self.commit_with(&k);
+
+        Ok((k, r1, r2))
+    }
+
+    /// Commitment `r1 = g^k mod p`, `r2 = h^k mod p` for a caller supplied `k`
+    pub fn commit_with(&self, k: &BigInt) -> (BigInt, BigInt) {
+        (
+            core_math::public_key(&self.g, k, &self.p),
+            core_math::public_key(&self.h, k, &self.p),
+        )
     }
 
     pub fn verifier_generate_challenge(&self) -> BigInt {
         generate_random_bigint(&self.q)
     }
+
+    /// Challenge of a quorum of verifiers, each contributing a random seed, reduced mod `q`. It's
+    /// uniform as long as one seed is, provided every verifier commits to its seed before any is
+    /// revealed, otherwise the last one to reveal picks the challenge. The order of `seeds`
+    /// doesn't matter, at least one is needed.
+    pub fn verifier_generate_challenge_from_seeds(&self, seeds: &[[u8; 32]]) -> CpResult<BigInt> {
+        if seeds.is_empty() {
+            return Err(CpError::InvalidParams(
+                "at least one challenge seed is needed".to_string(),
+            ));
+        }
+        Ok(self.hash_to_scalar(&hashing::hash_challenge_seeds(seeds)))
+    }
+
+    /// Challenge bound to the commitment `(r1, r2)` and `context`, e.g. the user it's issued to:
+    /// `c = u + H(r1, r2, context) mod q` with `u` drawn uniformly by the verifier.
+    ///
+    /// Soundness rests on `u` alone, it keeps `c` uniform in `Z_q` and unknown to the prover until
+    /// the commitment is fixed, whatever the hash is. The hash term makes the issued `c` a
+    /// function of the commitment it answers, so a challenge doesn't carry over to a commitment
+    /// swapped in on the way, even if `u` repeats.
+    pub fn verifier_bind_challenge(&self, r1: &BigInt, r2: &BigInt, context: &[u8]) -> BigInt {
+        self.verifier_bind_challenge_with(&mut rand::thread_rng(), r1, r2, context)
+    }
+
+    /// Same as `verifier_bind_challenge` but `u` is drawn from `rng`, tests can pass a seeded rng
+    pub fn verifier_bind_challenge_with<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+        r1: &BigInt,
+        r2: &BigInt,
+        context: &[u8],
+    ) -> BigInt {
+        let binding = self.hash_to_scalar(&hashing::hash_transcript(&Self::transcript_bytes(
+            r1, r2, context,
+        )));
+
+        loop {
+            let c = (generate_random_bigint_with(rng, &self.q) + &binding) % &self.q;
+            if c != BigInt::from(0) {
+                return c;
+            }
+        }
+    }
+
+    /// Public keys `yi = gi^x mod p` for every generator `gi`
+    pub fn public_keys_multi(&self, generators: &[BigInt], x: &BigInt) -> CpResult<Vec<BigInt>> {
+        self.validate_generators(generators)?;
+        Ok(self.commit_multi(generators, x))
+    }
+
+    /// Commitment `ri = gi^k mod p` for every generator `gi`
+    pub fn commit_multi(&self, generators: &[BigInt], k: &BigInt) -> Vec<BigInt> {
+        core_math::commit(generators, k, &self.p)
+    }
+
+    /// Proves that the public keys of `x` share one discrete log across `generators`. The
+    /// commitments of `k` have to reach the verifier before it picks `challenge`, `k` is never reused.
+    pub fn prove_multi(
+        &self,
+        generators: &[BigInt],
+        k: BigInt,
+        challenge: BigInt,
+        x: BigInt,
+    ) -> CpResult<MultiProof> {
+        self.validate_generators(generators)?;
+        let commitments = self.commit_multi(generators, &k);
+        let s = self.prover_solve_challenge(k, challenge.clone(), x);
+
+        Ok(MultiProof::new(commitments, challenge, s))
+    }
+
+    /// Input of the transcript hash `verifier_bind_challenge` mixes into the challenge, the domain
+    /// followed by `r1`, `r2` and `context`, each as an 8 byte big-endian length and the bytes.
+    /// `r1` and `r2` are big-endian without leading zeros.
+    pub fn transcript_bytes(r1: &BigInt, r2: &BigInt, context: &[u8]) -> Vec<u8> {
+        let mut transcript = CHALLENGE_BINDING_DOMAIN.to_vec();
+        // Length prefixed, so no two inputs share an encoding
+        for part in [&r1.to_bytes_be().1, &r2.to_bytes_be().1, context] {
+            transcript.extend_from_slice(&(part.len() as u64).to_be_bytes());
+            transcript.extend_from_slice(part);
+        }
+        transcript
+    }
+
+    /// Verifies a stream of `(proof, y1, y2)` with at most `concurrency` proofs in flight, each on
+    /// the blocking pool like `verify_interactive`. Results come out in completion order, tagged
+    /// with the position of the proof in `input`. A proof that fails with an error, e.g. a key out
+    /// of range, is reported as invalid.
+    #[cfg(feature = "std-async")]
+    pub fn verify_stream<S>(
+        &self,
+        input: S,
+        concurrency: usize,
+    ) -> impl Stream<Item = (usize, bool)>
+    where
+        S: Stream<Item = (Proof, BigInt, BigInt)>,
+    {
+        let cp = self.clone();
+        input
+            .enumerate()
+            .map(move |(index, (proof, y1, y2))| {
+                let cp = cp.clone();
+                async move {
+                    let is_valid = cp.verify_interactive(proof, y1, y2).await;
+                    (index, is_valid.unwrap_or(false))
+                }
+            })
+            // `buffer_unordered(0)` never polls the input
+            .buffer_unordered(concurrency.max(1))
+    }
+
+    /// Verifies every `(proof, y1, y2)` of `batch`, the results are in the order of `batch`. Errors
+    /// count as invalid like in `verify_stream`. With the `rayon` feature a batch of at least
+    /// `PARALLEL_BATCH_THRESHOLD` proofs is spread over the CPU cores from one blocking task
+    /// instead of getting a task per proof, whose overhead dominates for large batches.
+    #[cfg(feature = "std-async")]
+    pub async fn verify_batch(&self, batch: Vec<(Proof, BigInt, BigInt)>) -> Vec<bool> {
+        #[cfg(feature = "rayon")]
+        if batch.len() >= PARALLEL_BATCH_THRESHOLD {
+            return self.verify_batch_parallel(batch).await;
+        }
+        self.verify_batch_tasks(batch).await
+    }
+
+    /// `verify_interactive` of each proof, all in flight at once
+    #[cfg(feature = "std-async")]
+    async fn verify_batch_tasks(&self, batch: Vec<(Proof, BigInt, BigInt)>) -> Vec<bool> {
+        let verifications = batch.into_iter().map(|(proof, y1, y2)| async move {
+            self.verify_interactive(proof, y1, y2)
+                .await
+                .unwrap_or(false)
+        });
+        futures::future::join_all(verifications).await
+    }
+
+    /// `verify_interactive_sync` of each proof on the rayon pool, from one blocking task so the
+    /// runtime isn't stalled while rayon works through the batch
+    #[cfg(feature = "rayon")]
+    async fn verify_batch_parallel(&self, batch: Vec<(Proof, BigInt, BigInt)>) -> Vec<bool> {
+        let len = batch.len();
+        let cp = self.clone();
+        tokio::task::spawn_blocking(move || {
+            batch
+                .into_par_iter()
+                .map(|(proof, y1, y2)| cp.verify_interactive_sync(proof, y1, y2).unwrap_or(false))
+                .collect()
+        })
+        .await
+        .unwrap_or_else(|_| vec![false; len])
+    }
+
+    /// Recomputes the commitment of `proof` from each public key instead of only verifying it, a
+    /// key that doesn't belong to the secret the proof was made with shows up as the equation
+    /// that fails. Nothing is validated, malformed keys or scalars just don't hold.
+    pub fn audit(&self, y1: &BigInt, y2: &BigInt, proof: &Proof) -> AuditReport {
+        let r1 = core_math::recompute_commitment(&self.g, y1, &proof.c, &proof.s, &self.p);
+        let r2 = core_math::recompute_commitment(&self.h, y2, &proof.c, &proof.s, &self.p);
+
+        AuditReport {
+            r1_holds: r1 == proof.r1,
+            r2_holds: r2 == proof.r2,
+            r1,
+            r2,
+        }
+    }
+
+    /// Dry run of `verify_interactive_sync` for debugging a prover, the same inputs are rejected but
+    /// the recomputed commitments are returned instead of only whether they match
+    pub fn verify_proof_verbose(
+        &self,
+        proof: &Proof,
+        y1: &BigInt,
+        y2: &BigInt,
+    ) -> CpResult<VerboseVerification> {
+        self.validate_public_key(y1)?;
+        self.validate_public_key(y2)?;
+        self.validate_scalar(&proof.s)?;
+        self.validate_challenge(&proof.c)?;
+
+        let report = self.audit(y1, y2, proof);
+        Ok(VerboseVerification {
+            ok: report.holds(),
+            t1: report.r1,
+            r1: proof.r1.clone(),
+            t2: report.r2,
+            r2: proof.r2.clone(),
+        })
+    }
+
+    /// `verify_interactive` on the blocking pool, `cancel` is set when the returned future is
+    /// dropped before the verification finished
+    #[cfg(feature = "std-async")]
+    pub async fn verify_interactive_with_cancel(
+        &self,
+        proof: Proof,
+        y1: BigInt,
+        y2: BigInt,
+        cancel: CancelFlag,
+    ) -> CpResult<bool> {
+        let _guard = CancelOnDrop(cancel.clone());
+        let cp = self.clone();
+        tokio::task::spawn_blocking(move || {
+            cp.verify_interactive_cancellable(proof, y1, y2, &cancel)
+        })
+        .await
+        .map_err(|e| CpError::TaskJoin(e.to_string()))?
+    }
+
+    /// `verify_interactive_sync` that checks `cancel` before the exponentiations of each equation
+    pub fn verify_interactive_cancellable(
+        &self,
+        proof: Proof,
+        y1: BigInt,
+        y2: BigInt,
+        cancel: &CancelFlag,
+    ) -> CpResult<bool> {
+        let generators = [(*self.g).clone(), (*self.h).clone()];
+        self.verify_bases(&generators, &MultiProof::from(proof), &[y1, y2], cancel)
+    }
+
+    /// Verifies `proof` against one public key per generator, `gi^s * yi^c ≡ ri mod p` for all `i`
+    pub fn verify_multi(
+        &self,
+        generators: &[BigInt],
+        proof: &MultiProof,
+        keys: &[BigInt],
+    ) -> CpResult<bool> {
+        self.validate_generators(generators)?;
+        if keys.len() != generators.len() {
+            return Err(CpError::InvalidParams(format!(
+                "expected {} public keys, got {}",
+                generators.len(),
+                keys.len()
+            )));
+        }
+
+        self.verify_bases(generators, proof, keys, &CancelFlag::default())
+    }
+
+    /// `verify_multi` for generators that are known to be valid. The equations are checked one by
+    /// one like `core_math::verify`, `cancel` before each of them.
+    fn verify_bases(
+        &self,
+        generators: &[BigInt],
+        proof: &MultiProof,
+        keys: &[BigInt],
+        cancel: &CancelFlag,
+    ) -> CpResult<bool> {
+        for y in keys {
+            self.validate_public_key(y)?;
+        }
+        self.validate_scalar(&proof.s)?;
+        self.validate_challenge(&proof.c)?;
+
+        if generators.len() != keys.len() || generators.len() != proof.commitments.len() {
+            return Ok(false);
+        }
+        for ((g, y), r) in generators.iter().zip(keys).zip(&proof.commitments) {
+            cancel.check()?;
+            if !core_math::verify_equation(g, y, r, &proof.c, &proof.s, &self.p) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// At least two generators, each one checked like `g` and `h` in `validate`
+    pub fn validate_generators(&self, generators: &[BigInt]) -> CpResult<()> {
+        if generators.len() < 2 {
+            return Err(CpError::InvalidParams(
+                "at least two generators are required".to_string(),
+            ));
+        }
+
+        for (i, generator) in generators.iter().enumerate() {
+            self.validate_generator(&format!("g{}", i + 1), generator)?;
+        }
+        Ok(())
+    }
+
+    /// Public keys must be group elements in the range `1 < y < p`
+    fn validate_public_key(&self, y: &BigInt) -> CpResult<()> {
+        if *y <= BigInt::from(1) || *y >= *self.p {
+            return Err(CpError::InvalidPublicKey);
+        }
+        Ok(())
+    }
+
+    /// Solutions must be in the range `0 <= v < q`
+    fn validate_scalar(&self, v: &BigInt) -> CpResult<()> {
+        if *v < BigInt::from(0) || *v >= self.q {
+            return Err(CpError::OutOfRange);
+        }
+        Ok(())
+    }
+
+    /// Challenges must be in the range `0 < c < q`, `c = 0` leaves `r = g^s` for any `s` and proves
+    /// nothing about `x`. Any other challenge the prover picks itself can be answered without `x`
+    /// too, the verifier has to make sure it issued `c`.
+    fn validate_challenge(&self, c: &BigInt) -> CpResult<()> {
+        if *c == BigInt::from(0) {
+            return Err(CpError::OutOfRange);
+        }
+        self.validate_scalar(c)
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use rand_chacha::rand_core::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::prover::Prover;
+
     use super::*;
 
+    #[cfg(feature = "std-async")]
     #[tokio::test]
     async fn proof() {
-        let cp = ChaumPedersen::new(P.clone(), G.clone(), H.clone());
+        let cp = ChaumPedersen::new_test_group();
 
         // Register
-        // echo -n "nyancat" | openssl dgst -sha512
-        let secret_x = ChaumPedersen::hash(b"nyancat");
-        let (y1, y2) = cp.generate_public_keys(secret_x.clone()).await;
+        let secret_x = cp.hash_to_scalar(b"nyancat");
+        let (y1, y2) = cp.generate_public_keys(secret_x.clone()).await.unwrap();
 
         // Prover Commit
-        let (k, r1, r2) = cp.prover_commit().await;
+        let (k, r1, r2) = cp.prover_commit().await.unwrap();
 
         // Verifier send challenge
         let challenge = cp.verifier_generate_challenge();
@@ -182,31 +847,634 @@ mod tests {
         let solution = cp.prover_solve_challenge(k, challenge.clone(), secret_x);
 
         // Verify
-        let is_valid = cp
-            .verify_proof(
-                solution.clone(),
-                challenge.clone(),
-                y1,
-                y2,
-                Some(r1.clone().unwrap()),
-                Some(r2.clone().unwrap()),
-            )
-            .await;
+        let proof = Proof::new(r1, r2, challenge, solution);
+        let is_valid = cp.verify_interactive(proof.clone(), y1, y2).await.unwrap();
         assert_eq!(is_valid, true);
-        let invalid_secret_x = ChaumPedersen::hash(b"nyandog");
-        let (invalid_y1, invalid_y2) = cp.generate_public_keys(invalid_secret_x).await;
+        let invalid_secret_x = cp.hash_to_scalar(b"nyandog");
+        let (invalid_y1, invalid_y2) = cp.generate_public_keys(invalid_secret_x).await.unwrap();
+
+        assert_eq!(
+            cp.verify_interactive(proof, invalid_y1, invalid_y2).await,
+            Ok(false)
+        );
+    }
+
+    #[cfg(feature = "std-async")]
+    #[tokio::test]
+    async fn sync_matches_async() {
+        let cp = ChaumPedersen::new_test_group();
+        let secret_x = cp.hash_to_scalar(b"nyancat");
+
+        let keys = cp.generate_public_keys(secret_x.clone()).await.unwrap();
+        let keys_sync = cp.generate_public_keys_sync(secret_x.clone()).unwrap();
+        assert_eq!(keys, keys_sync);
+        let (y1, y2) = keys;
+
+        let (k, r1, r2) = cp.prover_commit_sync().unwrap();
+        let c = cp.verifier_generate_challenge();
+        let s = cp.prover_solve_challenge(k, c.clone(), secret_x);
+
+        let proof = Proof::new(r1, r2, c, s);
+        for (y1, y2) in [(y1.clone(), y2.clone()), (y2, y1)] {
+            let is_valid = cp
+                .verify_interactive(proof.clone(), y1.clone(), y2.clone())
+                .await;
+            let is_valid_sync = cp.verify_interactive_sync(proof.clone(), y1, y2);
+            assert_eq!(is_valid, is_valid_sync);
+        }
+    }
+
+    #[test]
+    fn seeded_commit() {
+        let cp = ChaumPedersen::new(P.clone(), G.clone(), H.clone());
+
+        let first = cp
+            .prover_commit_with(&mut ChaCha8Rng::seed_from_u64(42))
+            .unwrap();
+        let second = cp
+            .prover_commit_with(&mut ChaCha8Rng::seed_from_u64(42))
+            .unwrap();
+        let other = cp
+            .prover_commit_with(&mut ChaCha8Rng::seed_from_u64(43))
+            .unwrap();
+
+        assert_eq!(first, second);
+        assert_ne!(first.0, other.0);
+    }
+
+    #[test]
+    fn injected_rng() {
+        let cp = ChaumPedersen::new_test_group()
+            .with_rng(CommitmentRng::new(ChaCha8Rng::seed_from_u64(42)));
+        let mut expected = ChaCha8Rng::seed_from_u64(42);
+
+        let (k, r1, r2) = cp.prover_commit_sync().unwrap();
+        assert_eq!(k, BigInt::from(0x5748_5fda_395d_5ba2u64));
+        assert_eq!(r1, BigInt::from(0xa2d3_8b8d_5d36_1346u64));
+        assert_eq!(r2, BigInt::from(0xaf56_be82_d262_d6a8u64));
+        assert_eq!((k, r1, r2), cp.prover_commit_with(&mut expected).unwrap());
+
+        // Clones and provers over the protocol draw from the same source
+        let (k, _, _) = cp.clone().prover_commit_sync().unwrap();
+        assert_eq!(k, cp.prover_commit_with(&mut expected).unwrap().0);
+        let commitment = Prover::new(&cp).commit_sync().unwrap();
+        let (_, r1, r2) = cp.prover_commit_with(&mut expected).unwrap();
+        assert_eq!(commitment, (r1, r2));
+    }
+
+    #[test]
+    fn hash_with() {
+        for alg in [HashAlg::Sha256, HashAlg::Sha512] {
+            let x = ChaumPedersen::hash_with(alg, b"nyancat");
+            assert_eq!(x, ChaumPedersen::hash_with(alg, b"nyancat"));
+            assert!(x > BigInt::from(0) && x < *Q);
+        }
+
+        assert_ne!(
+            ChaumPedersen::hash_with(HashAlg::Sha256, b"nyancat"),
+            ChaumPedersen::hash_with(HashAlg::Sha512, b"nyancat")
+        );
+        assert_eq!(
+            ChaumPedersen::hash_with(HashAlg::Sha512, b"nyancat"),
+            ChaumPedersen::hash(b"nyancat")
+        );
+    }
+
+    #[test]
+    fn hash_reduced() {
+        // Safe prime `23 = 2 * 11 + 1`, both 2 and 3 generate the subgroup of order 11
+        let cp = ChaumPedersen::new(BigInt::from(23), BigInt::from(2), BigInt::from(3));
+        let q = cp.order();
+
+        let raw = BigInt::from_bytes_le(Sign::Plus, &Sha512::digest(b"nyancat"));
+        assert!(raw > *q);
+
+        for input in [
+            b"nyancat".as_slice(),
+            b"nyandog".as_slice(),
+            b"".as_slice(),
+            b"cat".as_slice(),
+        ] {
+            let x = cp.hash_to_scalar(input);
+            assert!(x > BigInt::from(0) && x < *q);
+        }
+    }
+
+    #[test]
+    fn test_group() {
+        let cp = ChaumPedersen::new_test_group();
+        assert_eq!(cp.validate(), Ok(()));
+        assert_eq!(*cp.order(), BigInt::from(TEST_GROUP_Q));
+        assert_eq!(cp.p.bits(), 64);
+        assert_eq!(cp.byte_len(), 8);
+        // The order of `g` is `q` and not a divisor of it, `q` is prime
+        assert_eq!(cp.g.modpow(cp.order(), &cp.p), BigInt::from(1));
+        assert_ne!(*cp.g, BigInt::from(1));
+
+        let params = CpParams::from(&cp);
+        assert_eq!(ChaumPedersen::try_from(params).unwrap().order(), cp.order());
+    }
+
+    #[test]
+    fn params_round_trip() {
+        let cp = ChaumPedersen::new(P.clone(), G.clone(), H.clone());
+        let params = CpParams::from(&cp);
+
+        let json = serde_json::to_string(&params).unwrap();
+        let from_json: CpParams = serde_json::from_str(&json).unwrap();
+        assert_eq!(from_json, params);
+
+        let bytes = bincode::serialize(&params).unwrap();
+        let from_bincode: CpParams = bincode::deserialize(&bytes).unwrap();
+        assert_eq!(from_bincode, params);
+
+        let restored: ChaumPedersen = from_bincode.try_into().unwrap();
+        assert_eq!(*restored.p, *cp.p);
+        assert_eq!(*restored.g, *cp.g);
+        assert_eq!(*restored.h, *cp.h);
+        assert_eq!(restored.order(), cp.order());
+    }
+
+    #[test]
+    fn validate_generators() {
+        let cp = ChaumPedersen::new(P.clone(), G.clone(), H.clone());
+        assert_eq!(cp.validate(), Ok(()));
+        assert_eq!(cp.order() * BigInt::from(2) + BigInt::from(1), *P);
+
+        // `p - 1` has order 2, it is not in the subgroup of order `q`
+        let mut bogus = cp.clone();
+        bogus.h = Arc::new(&*P - BigInt::from(1));
+        assert!(matches!(bogus.validate(), Err(CpError::InvalidParams(_))));
+
+        let mut bogus = cp.clone();
+        bogus.h = Arc::new(BigInt::from(1));
+        assert!(matches!(bogus.validate(), Err(CpError::InvalidParams(_))));
+    }
+
+    #[cfg(feature = "std-async")]
+    #[tokio::test]
+    async fn verify_stream() {
+        let cp = ChaumPedersen::new_test_group();
+        let x = cp.hash_to_scalar(b"nyancat");
+        let (y1, y2) = cp.generate_public_keys_sync(x.clone()).unwrap();
+
+        let expected = [true, false, true, true, false, false, true];
+        let mut proofs = Vec::new();
+        for valid in expected {
+            let (k, r1, r2) = cp.prover_commit_sync().unwrap();
+            let c = cp.verifier_generate_challenge();
+            let s = cp.prover_solve_challenge(k, c.clone(), x.clone());
+            let s = if valid { s } else { s + 1 };
+            proofs.push((Proof::new(r1, r2, c, s), y1.clone(), y2.clone()));
+        }
+
+        let mut results: Vec<_> = cp
+            .verify_stream(futures::stream::iter(proofs), 3)
+            .collect()
+            .await;
+        results.sort();
+        assert_eq!(
+            results,
+            expected.into_iter().enumerate().collect::<Vec<_>>()
+        );
+    }
+
+    #[cfg(feature = "rayon")]
+    #[tokio::test]
+    async fn verify_batch() {
+        let cp = ChaumPedersen::new_test_group();
+        let x = cp.hash_to_scalar(b"nyancat");
+        let (y1, y2) = cp.generate_public_keys_sync(x.clone()).unwrap();
+
+        let mut batch = Vec::new();
+        let mut expected = Vec::new();
+        for i in 0..PARALLEL_BATCH_THRESHOLD * 2 {
+            let (k, r1, r2) = cp.prover_commit_sync().unwrap();
+            let c = cp.verifier_generate_challenge();
+            let s = cp.prover_solve_challenge(k, c.clone(), x.clone());
+            let proof = Proof::new(r1, r2, c, s.clone());
+            match i % 3 {
+                0 => batch.push((proof, y1.clone(), y2.clone())),
+                1 => batch.push((Proof { s: s + 1, ..proof }, y1.clone(), y2.clone())),
+                // Out of range key, an error
+                _ => batch.push((proof, (*cp.p).clone(), y2.clone())),
+            }
+            expected.push(i % 3 == 0);
+        }
+
+        let tasks = cp.verify_batch_tasks(batch.clone()).await;
+        assert_eq!(tasks, expected);
+        assert_eq!(cp.verify_batch_parallel(batch.clone()).await, tasks);
+        assert_eq!(cp.verify_batch(batch).await, tasks);
+    }
+
+    #[test]
+    fn bound_challenge() {
+        let cp = Group::Modp2048.protocol();
+        let (_, r1, r2) = cp.prover_commit_sync().unwrap();
+        let challenge = |r1: &BigInt, context: &[u8]| {
+            cp.verifier_bind_challenge_with(&mut ChaCha8Rng::seed_from_u64(42), r1, &r2, context)
+        };
+
+        let c = challenge(&r1, b"Nyan");
+        assert_eq!(c, challenge(&r1, b"Nyan"));
+        assert!(c > BigInt::from(0) && &c < cp.order());
+        // Same server randomness, another `r1` or user
+        assert_ne!(c, challenge(&(&r1 + 1), b"Nyan"));
+        assert_ne!(c, challenge(&r1, b"Nyanko"));
+        // Fresh randomness for every call
+        assert_ne!(
+            cp.verifier_bind_challenge(&r1, &r2, b"Nyan"),
+            cp.verifier_bind_challenge(&r1, &r2, b"Nyan")
+        );
+    }
+
+    #[test]
+    fn transcript() {
+        let cp = Group::Modp2048.protocol();
+        let (r1, r2) = (BigInt::from(0x0102), BigInt::from(3));
+        let transcript = ChaumPedersen::transcript_bytes(&r1, &r2, b"Nyan");
+
+        let mut expected = CHALLENGE_BINDING_DOMAIN.to_vec();
+        expected.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 2, 1, 2]);
+        expected.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 1, 3]);
+        expected.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 4]);
+        expected.extend_from_slice(b"Nyan");
+        assert_eq!(transcript, expected);
 
+        // The challenge is the verifier's randomness plus the hash of exactly these bytes
+        let c =
+            cp.verifier_bind_challenge_with(&mut ChaCha8Rng::seed_from_u64(42), &r1, &r2, b"Nyan");
+        let u = generate_random_bigint_with(&mut ChaCha8Rng::seed_from_u64(42), cp.order());
         assert_eq!(
-            cp.verify_proof(
-                solution,
-                challenge,
-                invalid_y1,
-                invalid_y2,
-                Some(r1.unwrap()),
-                Some(r2.unwrap()),
+            (c - u + cp.order()) % cp.order(),
+            cp.hash_to_scalar(&hashing::hash_transcript(&transcript))
+        );
+    }
+
+    #[test]
+    fn audit() {
+        let cp = ChaumPedersen::new_test_group();
+        let x = cp.hash_to_scalar(b"nyancat");
+        let (y1, y2) = cp.generate_public_keys_sync(x.clone()).unwrap();
+        let (k, r1, r2) = cp.prover_commit_sync().unwrap();
+        let c = cp.verifier_generate_challenge();
+        let s = cp.prover_solve_challenge(k, c.clone(), x);
+        let proof = Proof::new(r1.clone(), r2.clone(), c, s);
+
+        let report = cp.audit(&y1, &y2, &proof);
+        assert!(report.holds());
+        assert_eq!((report.r1, report.r2), (r1.clone(), r2.clone()));
+
+        // `y2` of another secret, only the equation of `h` fails
+        let (_, other_y2) = cp
+            .generate_public_keys_sync(cp.hash_to_scalar(b"nyandog"))
+            .unwrap();
+        let report = cp.audit(&y1, &other_y2, &proof);
+        assert!(report.r1_holds);
+        assert!(!report.r2_holds);
+        assert!(!report.holds());
+        assert_eq!(report.r1, r1);
+        assert_ne!(report.r2, r2);
+    }
+
+    #[test]
+    fn verify_proof_verbose() {
+        let cp = ChaumPedersen::new_test_group();
+        let x = cp.hash_to_scalar(b"nyancat");
+        let (y1, y2) = cp.generate_public_keys_sync(x.clone()).unwrap();
+        let (k, r1, r2) = cp.prover_commit_sync().unwrap();
+        let c = cp.verifier_generate_challenge();
+
+        let s = cp.prover_solve_challenge(k.clone(), c.clone(), x);
+        let proof = Proof::new(r1.clone(), r2.clone(), c.clone(), s);
+        let verification = cp.verify_proof_verbose(&proof, &y1, &y2).unwrap();
+        assert!(verification.ok);
+        assert_eq!((&verification.t1, &verification.t2), (&r1, &r2));
+        assert_eq!(
+            cp.verify_interactive_sync(proof, y1.clone(), y2.clone()),
+            Ok(true)
+        );
+
+        // Solved with the wrong secret
+        let s = cp.prover_solve_challenge(k, c.clone(), cp.hash_to_scalar(b"nyandog"));
+        let proof = Proof::new(r1.clone(), r2.clone(), c.clone(), s);
+        let verification = cp.verify_proof_verbose(&proof, &y1, &y2).unwrap();
+        assert!(!verification.ok);
+        assert_eq!(verification.r1, r1);
+        assert_ne!(verification.t1, verification.r1);
+        assert_ne!(verification.t2, verification.r2);
+
+        let out_of_range = Proof::new(r1, r2, c, cp.order().clone());
+        assert_eq!(
+            cp.verify_proof_verbose(&out_of_range, &y1, &y2),
+            Err(CpError::OutOfRange)
+        );
+    }
+
+    #[test]
+    fn multi_base() {
+        let cp = Group::Modp2048.protocol();
+        // Squares are quadratic residues, they are in the subgroup of order `q`
+        let generators = [BigInt::from(2), BigInt::from(3), BigInt::from(25)];
+        let x = cp.hash_to_scalar(b"nyancat");
+        let keys = cp.public_keys_multi(&generators, &x).unwrap();
+        let (y1, y2) = cp.generate_public_keys_sync(x.clone()).unwrap();
+        assert_eq!(keys[..2], [y1, y2]);
+
+        let k = cp.hash_to_scalar(b"nonce");
+        let c = cp.verifier_generate_challenge();
+        let proof = cp.prove_multi(&generators, k, c, x).unwrap();
+        assert_eq!(proof.commitments.len(), 3);
+        assert_eq!(cp.verify_multi(&generators, &proof, &keys), Ok(true));
+
+        // One key of another secret
+        let other = cp
+            .public_keys_multi(&generators, &cp.hash_to_scalar(b"nyandog"))
+            .unwrap();
+        let mut wrong = keys.clone();
+        wrong[2] = other[2].clone();
+        assert_eq!(cp.verify_multi(&generators, &proof, &wrong), Ok(false));
+
+        let mut missing = proof.clone();
+        missing.commitments.pop();
+        assert_eq!(cp.verify_multi(&generators, &missing, &keys), Ok(false));
+        assert!(matches!(
+            cp.verify_multi(&generators, &proof, &keys[..2]),
+            Err(CpError::InvalidParams(_))
+        ));
+        assert!(matches!(
+            cp.verify_multi(&generators[..1], &proof, &keys[..1]),
+            Err(CpError::InvalidParams(_))
+        ));
+
+        // `p - 1` has order 2
+        let bogus = [BigInt::from(2), &*MODP_2048 - BigInt::from(1)];
+        assert_eq!(
+            cp.validate_generators(&bogus),
+            Err(CpError::InvalidParams(
+                "generator g2 is not in the subgroup of order q".to_string()
+            ))
+        );
+    }
+
+    #[test]
+    fn groups() {
+        assert_eq!("2048".parse::<Group>(), Ok(Group::Modp2048));
+        assert_eq!("3072".parse::<Group>(), Ok(Group::Modp3072));
+        assert_eq!("4096".parse::<Group>(), Ok(Group::Modp4096));
+        assert!(matches!(
+            "1024".parse::<Group>(),
+            Err(CpError::InvalidParams(_))
+        ));
+        assert!(matches!(
+            "modp".parse::<Group>(),
+            Err(CpError::InvalidParams(_))
+        ));
+        assert_eq!(Group::default(), Group::Modp2048);
+        assert_eq!(*Group::LEGACY.prime(), *P);
+
+        for group in Group::ALL {
+            assert_eq!(group.to_string().parse::<Group>(), Ok(group));
+            assert_eq!(group.prime().bits(), group.bits() as u64);
+            let cp = group.protocol();
+            assert_eq!(cp.validate(), Ok(()));
+            assert_eq!(cp.byte_len(), group.bits() as usize / 8);
+        }
+    }
+
+    #[test]
+    fn challenge_from_seeds() {
+        let cp = ChaumPedersen::new_test_group();
+        let mut rng = ChaCha8Rng::seed_from_u64(7);
+        let seeds: Vec<[u8; 32]> = (0..3)
+            .map(|_| {
+                let mut seed = [0u8; 32];
+                rng.fill_bytes(&mut seed);
+                seed
+            })
+            .collect();
+
+        let c = cp.verifier_generate_challenge_from_seeds(&seeds).unwrap();
+        assert!(c > BigInt::from(0) && c < *cp.order());
+        assert_eq!(
+            cp.verifier_generate_challenge_from_seeds(&seeds),
+            Ok(c.clone())
+        );
+
+        let reordered = [seeds[2], seeds[0], seeds[1]];
+        assert_eq!(
+            cp.verifier_generate_challenge_from_seeds(&reordered),
+            Ok(c.clone())
+        );
+
+        // Every seed counts, a repeated one doesn't cancel out
+        assert_ne!(
+            cp.verifier_generate_challenge_from_seeds(&seeds[..2]),
+            Ok(c.clone())
+        );
+        assert_ne!(
+            cp.verifier_generate_challenge_from_seeds(&[seeds[0], seeds[0], seeds[1]]),
+            cp.verifier_generate_challenge_from_seeds(&seeds[1..2])
+        );
+
+        // The challenge verifies like a drawn one
+        let x = cp.hash_to_scalar(b"nyancat");
+        let (y1, y2) = cp.generate_public_keys_sync(x.clone()).unwrap();
+        let (k, r1, r2) = cp.prover_commit_with(&mut rng).unwrap();
+        let s = cp.prover_solve_challenge(k, c.clone(), x);
+        assert_eq!(
+            cp.verify_interactive_sync(Proof::new(r1, r2, c, s), y1, y2),
+            Ok(true)
+        );
+
+        assert!(matches!(
+            cp.verifier_generate_challenge_from_seeds(&[]),
+            Err(CpError::InvalidParams(_))
+        ));
+    }
+
+    #[test]
+    fn derive_public_keys_parity() {
+        for group in [Group::Modp2048, Group::Modp3072] {
+            let cp = group.protocol();
+            let params = CpParams::from(&cp);
+            let x = ChaumPedersen::hash(b"nyancat");
+
+            assert_eq!(
+                derive_public_keys(&params, &x),
+                cp.generate_public_keys_sync(x.clone()).unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn params_invalid() {
+        let cp = ChaumPedersen::new(P.clone(), G.clone(), H.clone());
+
+        let mut params = CpParams::from(&cp);
+        params.q = BigInt::from(11);
+        assert!(matches!(
+            ChaumPedersen::try_from(params),
+            Err(CpError::InvalidParams(_))
+        ));
+
+        let mut params = CpParams::from(&cp);
+        params.h = P.clone();
+        assert!(matches!(
+            ChaumPedersen::try_from(params),
+            Err(CpError::InvalidParams(_))
+        ));
+
+        let mut params = CpParams::from(&cp);
+        params.g = &*P - BigInt::from(1);
+        assert!(matches!(
+            ChaumPedersen::try_from(params),
+            Err(CpError::InvalidParams(_))
+        ));
+    }
+
+    #[test]
+    fn params_too_large() {
+        // Nothing is computed in the group, `p` doesn't even have to be prime
+        let p = BigInt::from(1) << 8192u32;
+        let params = CpParams {
+            q: (&p - BigInt::from(1)) / BigInt::from(2),
+            p,
+            g: G.clone(),
+            h: H.clone(),
+        };
+        assert_eq!(
+            ChaumPedersen::try_from(params).unwrap_err(),
+            CpError::ParamsTooLarge {
+                bits: 8193,
+                max_bits: DEFAULT_MAX_P_BITS
+            }
+        );
+
+        let params = CpParams::from(&Group::Modp4096.protocol());
+        assert_eq!(
+            ChaumPedersen::from_params(params.clone(), 3072).unwrap_err(),
+            CpError::ParamsTooLarge {
+                bits: 4096,
+                max_bits: 3072
+            }
+        );
+        assert!(ChaumPedersen::from_params(params, 4096).is_ok());
+
+        assert_eq!(
+            ChaumPedersen::try_new(BigInt::from(1) << 8192u32, G.clone(), H.clone()).unwrap_err(),
+            CpError::ParamsTooLarge {
+                bits: 8193,
+                max_bits: DEFAULT_MAX_P_BITS
+            }
+        );
+    }
+
+    #[cfg(feature = "std-async")]
+    #[tokio::test]
+    async fn cancelled_verification() {
+        // An equation in the largest group takes a few milliseconds, long enough to drop the
+        // future while it's computed
+        let cp = Group::Modp4096.protocol();
+        let x = cp.hash_to_scalar(b"nyancat");
+        let (y1, y2) = cp.generate_public_keys_sync(x.clone()).unwrap();
+        let (k, r1, r2) = cp.prover_commit_sync().unwrap();
+        let c = cp.verifier_generate_challenge();
+        let s = cp.prover_solve_challenge(k, c.clone(), x);
+        let proof = Proof::new(r1, r2, c, s);
+
+        let cancel = CancelFlag::default();
+        cancel.cancel();
+        assert_eq!(
+            cp.verify_interactive_cancellable(proof.clone(), y1.clone(), y2.clone(), &cancel),
+            Err(CpError::Cancelled)
+        );
+        assert!(cancel.gave_up());
+
+        // Only dropping an unfinished verification cancels it
+        let cancel = CancelFlag::default();
+        let verification = cp.verify_interactive_with_cancel(
+            proof.clone(),
+            y1.clone(),
+            y2.clone(),
+            cancel.clone(),
+        );
+        assert_eq!(verification.await, Ok(true));
+        assert!(!cancel.gave_up());
+
+        let cancel = CancelFlag::default();
+        let verification = cp.verify_interactive_with_cancel(proof, y1, y2, cancel.clone());
+        let timeout = std::time::Duration::from_millis(1);
+        assert!(tokio::time::timeout(timeout, verification).await.is_err());
+        assert!(cancel.is_cancelled());
+        // The blocking task stops at the next equation instead of finishing the verification
+        for _ in 0..500 {
+            if cancel.gave_up() {
+                break;
+            }
+            tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        }
+        assert!(cancel.gave_up());
+    }
+
+    #[cfg(feature = "std-async")]
+    #[tokio::test]
+    async fn invalid_inputs() {
+        let cp = ChaumPedersen::new(P.clone(), G.clone(), H.clone());
+        let secret_x = ChaumPedersen::hash(b"nyancat");
+        let (y1, y2) = cp.generate_public_keys(secret_x).await.unwrap();
+        let (_, r1, r2) = cp.prover_commit().await.unwrap();
+        let c = cp.verifier_generate_challenge();
+        let proof = |c: BigInt, s: BigInt| Proof::new(r1.clone(), r2.clone(), c, s);
+
+        // y1 = 0 is not a group element
+        assert_eq!(
+            cp.verify_interactive(
+                proof(c.clone(), BigInt::from(1)),
+                BigInt::from(0),
+                y2.clone()
             )
+            .await,
+            Err(CpError::InvalidPublicKey)
+        );
+
+        // y2 = p is outside of the group
+        assert_eq!(
+            cp.verify_interactive(proof(c.clone(), BigInt::from(1)), y1.clone(), P.clone())
                 .await,
-            false
+            Err(CpError::InvalidPublicKey)
+        );
+
+        // s = q is not reduced
+        assert_eq!(
+            cp.verify_interactive(proof(c.clone(), cp.order().clone()), y1.clone(), y2.clone())
+                .await,
+            Err(CpError::OutOfRange)
+        );
+
+        // negative challenge
+        assert_eq!(
+            cp.verify_interactive(
+                proof(BigInt::from(-1), BigInt::from(1)),
+                y1.clone(),
+                y2.clone()
+            )
+            .await,
+            Err(CpError::OutOfRange)
+        );
+
+        // c = 0 holds for r1 = g^s and r2 = h^s whatever the keys are
+        let s = BigInt::from(7);
+        let simulated = Proof::new(
+            cp.g.modpow(&s, &cp.p),
+            cp.h.modpow(&s, &cp.p),
+            BigInt::from(0),
+            s,
+        );
+        assert_eq!(
+            cp.verify_interactive(simulated, y1, y2).await,
+            Err(CpError::OutOfRange)
         );
     }
 }
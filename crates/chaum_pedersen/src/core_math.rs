@@ -0,0 +1,92 @@
+//! Equations of the interactive protocol on plain `BigInt`s, only `core` and `alloc` are used so
+//! the module builds without `std`. Nothing here validates its inputs, that's up to the caller,
+//! see `ChaumPedersen` for the checked API.
+
+use alloc::vec::Vec;
+use core::mem;
+
+use num_bigint::BigInt;
+
+/// Public key `y = g^x mod p`
+pub fn public_key(g: &BigInt, x: &BigInt, p: &BigInt) -> BigInt {
+    g.modpow(x, p)
+}
+
+/// Commitment `ri = gi^k mod p` for every generator `gi`
+pub fn commit(generators: &[BigInt], k: &BigInt, p: &BigInt) -> Vec<BigInt> {
+    generators.iter().map(|g| g.modpow(k, p)).collect()
+}
+
+/// Solution `s = k - c * x mod q` to the challenge `c`
+pub fn solve(k: &BigInt, c: &BigInt, x: &BigInt, q: &BigInt) -> BigInt {
+    // Adding `q` keeps the intermediate value non-negative whenever `c * x < k + q`
+    let s = (k + q - c * x) % q;
+
+    // `%` keeps the sign of the dividend, a negative remainder is shifted back into `[0, q)`
+    if s < BigInt::from(0) {
+        s + q
+    } else {
+        s
+    }
+}
+
+/// Inverse of `a` modulo `modulus` by the extended Euclidean algorithm, `None` if `a` and
+/// `modulus` share a factor or `modulus <= 1`. `a` doesn't have to be reduced, the inverse is in
+/// `[1, modulus)`.
+pub fn mod_inverse(a: &BigInt, modulus: &BigInt) -> Option<BigInt> {
+    let (zero, one) = (BigInt::from(0), BigInt::from(1));
+    if *modulus <= one {
+        return None;
+    }
+    let reduce = |v: BigInt| ((v % modulus) + modulus) % modulus;
+
+    // Invariant: `t * a ≡ r mod modulus` for both pairs
+    let (mut old_r, mut r) = (reduce(a.clone()), modulus.clone());
+    let (mut old_t, mut t) = (one.clone(), zero.clone());
+    while r != zero {
+        let quotient = &old_r / &r;
+        let next_r = &old_r - &quotient * &r;
+        old_r = mem::replace(&mut r, next_r);
+        let next_t = &old_t - &quotient * &t;
+        old_t = mem::replace(&mut t, next_t);
+    }
+
+    // `old_r` is the gcd
+    (old_r == one).then(|| reduce(old_t))
+}
+
+/// `g^s * y^c mod p`, the commitment a valid solution `s` to the challenge `c` implies
+pub fn recompute_commitment(g: &BigInt, y: &BigInt, c: &BigInt, s: &BigInt, p: &BigInt) -> BigInt {
+    (g.modpow(s, p) * y.modpow(c, p)) % p
+}
+
+/// `g^s * y^c ≡ r mod p`, holds when `s` solves the challenge `c` for the same `x` as `y`
+pub fn verify_equation(
+    g: &BigInt,
+    y: &BigInt,
+    r: &BigInt,
+    c: &BigInt,
+    s: &BigInt,
+    p: &BigInt,
+) -> bool {
+    recompute_commitment(g, y, c, s, p) == *r
+}
+
+/// `verify_equation` for every generator with its public key and commitment, all three slices
+/// have to be of the same length
+pub fn verify(
+    generators: &[BigInt],
+    keys: &[BigInt],
+    commitments: &[BigInt],
+    c: &BigInt,
+    s: &BigInt,
+    p: &BigInt,
+) -> bool {
+    generators.len() == keys.len()
+        && generators.len() == commitments.len()
+        && generators
+            .iter()
+            .zip(keys)
+            .zip(commitments)
+            .all(|((g, y), r)| verify_equation(g, y, r, c, s, p))
+}
@@ -1,18 +1,37 @@
 use std::sync::Arc;
 
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
 use curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT;
-use curve25519_dalek::RistrettoPoint;
+use curve25519_dalek::ristretto::CompressedRistretto;
 use curve25519_dalek::scalar::Scalar;
-use rand_core::OsRng;
+use curve25519_dalek::traits::Identity;
+use curve25519_dalek::RistrettoPoint;
+use rand_core::{CryptoRng, OsRng, RngCore};
 use sha2::{Digest, Sha512};
-use tokio::try_join;
 
-use crate::ChaumPedersenTrait;
+use crate::hashing;
+use crate::point_cache::{PointCache, DEFAULT_CAPACITY};
+use crate::proof::{NonInteractiveProof, Proof};
+use crate::utils::{decode_base58, encode_base58};
+use crate::{ChaumPedersenTrait, CpError, CpResult, InteractiveVerifier, NonInteractiveVerifier};
+
+const PEM_BEGIN: &str = "-----BEGIN RISTRETTO PUBLIC KEY-----";
+const PEM_END: &str = "-----END RISTRETTO PUBLIC KEY-----";
+
+/// Default domain separation tag absorbed into the Fiat-Shamir challenge
+pub const DEFAULT_CONTEXT: &[u8] = b"chaum-pedersen-v1";
 
 #[derive(Debug, Clone)]
 pub struct EccChaumPedersen {
     g: Arc<RistrettoPoint>,
     h: Arc<RistrettoPoint>,
+    /// Domain separation tag, proofs made under different contexts don't verify against each other
+    context: Arc<Vec<u8>>,
+    /// Time the proofs are bound to, absorbed after the commitment if set
+    timestamp: Option<u64>,
+    /// Decompressed public keys, shared between clones
+    points: Arc<PointCache>,
 }
 
 impl ChaumPedersenTrait for EccChaumPedersen {
@@ -28,58 +47,44 @@ impl ChaumPedersenTrait for EccChaumPedersen {
     ///
     /// Returns:
     /// - A tuple `(y1, y2)` representing the public keys.
+    #[cfg(feature = "std-async")]
     async fn generate_public_keys(
         &self,
         secret_scalar: Self::Scalar,
-    ) -> (Self::Point, Self::Point) {
-        let g = self.g.clone();
-        let h = self.h.clone();
-
-        // Asynchronously calculate the public keys
-        let compute_public_keys = tokio::spawn(async move {
-            let y1 = *g * secret_scalar; // Compute y1 = g * x
-            let y2 = *h * secret_scalar; // Compute y2 = h * x
-            (y1, y2)
-        });
-
-        compute_public_keys
+    ) -> CpResult<(Self::Point, Self::Point)> {
+        let cp = self.clone();
+        tokio::task::spawn_blocking(move || cp.generate_public_keys_sync(secret_scalar))
             .await
-            .expect("failed to compute public keys (y1, y2)")
+            .map_err(|e| CpError::TaskJoin(e.to_string()))?
+    }
+
+    fn generate_public_keys_sync(
+        &self,
+        secret_scalar: Self::Scalar,
+    ) -> CpResult<(Self::Point, Self::Point)> {
+        let y1 = *self.g * secret_scalar; // Compute y1 = g * x
+        let y2 = *self.h * secret_scalar; // Compute y2 = h * x
+        Ok((y1, y2))
     }
 
-    /// Generates the prover's commitment values `r1` and `r2` and the challenge `c`.
+    /// Generates the prover's commitment values `r1` and `r2`.
     /// Here we applied some form of Fiat-Shamir heuristic because prover generates a challenge for himself.
     ///
     /// The function uses the secret random value `k` to calculate `r1` and `r2` using
-    /// the group generators `g` and `h`. The challenge `c` is generated by hashing
-    /// the compressed forms of `r1` and `r2`.
+    /// the group generators `g` and `h`. The challenge `c` is `derive_challenge(r1, r2)`, the hash
+    /// of the context, the generators, the compressed forms of `r1` and `r2` and the timestamp.
     ///
-    /// Returns a tuple containing the challenge `c` and the secret value `k`.
-    async fn prover_commit(&self) -> (Self::Scalar, Option<Self::Scalar>, Option<Self::Scalar>) {
-        let mut csprng = OsRng;
-
-        // Generate a random secret value 'k'
-        let secret_k = Scalar::random(&mut csprng);
-
-        let generator_g = self.g.clone();
-        let generator_h = self.h.clone();
-
-        let (commitment_r1, commitment_r2) = tokio::spawn(async move {
-            let r1 = &*generator_g * secret_k;
-            let r2 = &*generator_h * secret_k;
-            (r1, r2)
-        })
+    /// Returns a tuple containing the secret value `k` and the commitment `(r1, r2)`.
+    #[cfg(feature = "std-async")]
+    async fn prover_commit(&self) -> CpResult<(Self::Scalar, Self::Point, Self::Point)> {
+        let cp = self.clone();
+        tokio::task::spawn_blocking(move || cp.prover_commit_sync())
             .await
-            .expect("Failed to compute prover's commitments");
-
-        let mut challenge_input = Vec::with_capacity(64);
-        challenge_input.extend_from_slice(commitment_r1.compress().as_bytes());
-        challenge_input.extend_from_slice(commitment_r2.compress().as_bytes());
-
-        // Generate the challenge by hashing r1 and r2
-        let challenge_c = Self::hash(challenge_input.as_slice());
+            .map_err(|e| CpError::TaskJoin(e.to_string()))?
+    }
 
-        (secret_k, Some(challenge_c), None)
+    fn prover_commit_sync(&self) -> CpResult<(Self::Scalar, Self::Point, Self::Point)> {
+        self.prover_commit_with(&mut OsRng)
     }
 
     /// Prover solves the challenge `s = k - c * x`
@@ -95,76 +100,291 @@ impl ChaumPedersenTrait for EccChaumPedersen {
     ) -> Self::Scalar {
         random_k - challenge * secret_x
     }
+}
 
-    async fn verify_proof(
+impl InteractiveVerifier for EccChaumPedersen {
+    #[cfg(feature = "std-async")]
+    async fn verify_interactive(
         &self,
-        s: Self::Scalar,
-        c: Self::Scalar,
-        y1: Self::Point,
-        y2: Self::Point,
-        _r1: Option<Self::Scalar>,
-        _r2: Option<Self::Scalar>,
-    ) -> bool {
-        let verify_closure = |base1: Arc<RistrettoPoint>,
-                              exp1: Arc<Scalar>,
-                              base2: Arc<RistrettoPoint>,
-                              exp2: Arc<Scalar>| {
-            tokio::spawn(async move { *base1 * &*exp1 + *base2 * &*exp2 })
-        };
-
-        let s = Arc::new(s);
-        let c = Arc::new(c);
-        let y1 = Arc::new(y1);
-        let y2 = Arc::new(y2);
-
-        let t1 = verify_closure(self.g.clone(), s.clone(), y1.clone(), c.clone());
-        let t2 = verify_closure(self.h.clone(), s.clone(), y2.clone(), c.clone());
-
-        let (t1, t2) = try_join!(t1, t2).unwrap();
-
-        let t1_compressed = t1.compress();
-        let t2_compressed = t2.compress();
-
-        // Now get the bytes
-        let t1_bytes = t1_compressed.as_bytes();
-        let t2_bytes = t2_compressed.as_bytes();
-
-        // Concatenate
-        let mut concatenated = Vec::with_capacity(64);
-        concatenated.extend_from_slice(t1_bytes);
-        concatenated.extend_from_slice(t2_bytes);
-
-        let computed_challenge = Self::hash(&concatenated);
-
-        // Check if the computed challenge matches the given challenge
-        computed_challenge == *c
+        proof: Proof<RistrettoPoint, Scalar>,
+        y1: RistrettoPoint,
+        y2: RistrettoPoint,
+    ) -> CpResult<bool> {
+        let cp = self.clone();
+        tokio::task::spawn_blocking(move || cp.verify_interactive_sync(proof, y1, y2))
+            .await
+            .map_err(|e| CpError::TaskJoin(e.to_string()))?
+    }
+
+    fn verify_interactive_sync(
+        &self,
+        proof: Proof<RistrettoPoint, Scalar>,
+        y1: RistrettoPoint,
+        y2: RistrettoPoint,
+    ) -> CpResult<bool> {
+        Self::validate_public_keys(&y1, &y2)?;
+
+        let t1 = *self.g * proof.s + y1 * proof.c;
+        let t2 = *self.h * proof.s + y2 * proof.c;
+
+        Ok(t1 == proof.r1 && t2 == proof.r2)
+    }
+}
+
+impl NonInteractiveVerifier for EccChaumPedersen {
+    #[cfg(feature = "std-async")]
+    async fn verify_noninteractive(
+        &self,
+        proof: NonInteractiveProof<Scalar>,
+        y1: RistrettoPoint,
+        y2: RistrettoPoint,
+    ) -> CpResult<bool> {
+        let cp = self.clone();
+        tokio::task::spawn_blocking(move || cp.verify_noninteractive_sync(proof, y1, y2))
+            .await
+            .map_err(|e| CpError::TaskJoin(e.to_string()))?
+    }
+
+    fn verify_noninteractive_sync(
+        &self,
+        proof: NonInteractiveProof<Scalar>,
+        y1: RistrettoPoint,
+        y2: RistrettoPoint,
+    ) -> CpResult<bool> {
+        Self::validate_public_keys(&y1, &y2)?;
+
+        // Recompute the commitment, for a valid proof t1 = g * k and t2 = h * k
+        let t1 = *self.g * proof.s + y1 * proof.c;
+        let t2 = *self.h * proof.s + y2 * proof.c;
+
+        // Check if the challenge derived from the recomputed commitment matches the given challenge
+        Ok(self.derive_challenge(&t1, &t2) == proof.c)
     }
 }
 
 impl EccChaumPedersen {
     /// Generate `H` as `H = [hash(G)]G` where `hash` is `sha512`
     pub fn new() -> Self {
+        Self::with_context(DEFAULT_CONTEXT)
+    }
+
+    /// Same as `new` but proofs are bound to `context`, so two deployments can isolate their proofs
+    pub fn with_context(context: &[u8]) -> Self {
         let h =
             RISTRETTO_BASEPOINT_POINT * Self::hash(RISTRETTO_BASEPOINT_POINT.compress().as_bytes());
         Self {
             g: Arc::new(RISTRETTO_BASEPOINT_POINT),
             h: Arc::new(h),
+            context: Arc::new(context.to_vec()),
+            timestamp: None,
+            points: Arc::new(PointCache::new(DEFAULT_CAPACITY)),
+        }
+    }
+
+    /// Same as `self` but proofs are bound to `timestamp` as well, e.g. the seconds since the UNIX
+    /// epoch the proof was made at. A verifier bound to another timestamp rejects them, so it can
+    /// refuse proofs that are too old.
+    pub fn with_timestamp(self, timestamp: u64) -> Self {
+        Self {
+            timestamp: Some(timestamp),
+            ..self
+        }
+    }
+
+    /// Keeps at most `capacity` decompressed public keys instead of `DEFAULT_CAPACITY`
+    pub fn with_cache_capacity(self, capacity: usize) -> Self {
+        Self {
+            points: Arc::new(PointCache::new(capacity)),
+            ..self
         }
     }
 
-    /// Hash function to convert byte slices to `Scalar` values
+    pub fn point_cache(&self) -> &PointCache {
+        &self.points
+    }
+
+    /// Same as `prover_commit_sync` but `k` is drawn from `rng`, tests can pass a seeded rng
+    pub fn prover_commit_with<R: RngCore + CryptoRng>(
+        &self,
+        rng: &mut R,
+    ) -> CpResult<(Scalar, RistrettoPoint, RistrettoPoint)> {
+        // Generate a random secret value 'k'
+        let secret_k = Scalar::random(rng);
+        let (r1, r2, _) = self.commit_with(secret_k);
+
+        Ok((secret_k, r1, r2))
+    }
+
+    /// Checks a non-interactive proof against the commitment `(r1, r2)` the prover sent, `c` has to
+    /// be the challenge derived from it and `s` has to solve `c`. A tampered commitment changes
+    /// the derived challenge and fails both checks.
+    #[cfg(feature = "std-async")]
+    pub async fn verify_with_commitment(
+        &self,
+        proof: Proof<RistrettoPoint, Scalar>,
+        y1: RistrettoPoint,
+        y2: RistrettoPoint,
+    ) -> CpResult<bool> {
+        let cp = self.clone();
+        tokio::task::spawn_blocking(move || cp.verify_with_commitment_sync(proof, y1, y2))
+            .await
+            .map_err(|e| CpError::TaskJoin(e.to_string()))?
+    }
+
+    /// Same as `verify_with_commitment` but computed on the calling thread, no runtime required
+    pub fn verify_with_commitment_sync(
+        &self,
+        proof: Proof<RistrettoPoint, Scalar>,
+        y1: RistrettoPoint,
+        y2: RistrettoPoint,
+    ) -> CpResult<bool> {
+        let derived = self.derive_challenge(&proof.r1, &proof.r2) == proof.c;
+        let solved = self.verify_interactive_sync(proof, y1, y2)?;
+        Ok(derived && solved)
+    }
+
+    /// Commitment `r1 = g * k`, `r2 = h * k` for a caller supplied `k` and the challenge derived from it
+    pub fn commit_with(&self, k: Scalar) -> (RistrettoPoint, RistrettoPoint, Scalar) {
+        let r1 = *self.g * k;
+        let r2 = *self.h * k;
+
+        // Generate the challenge by hashing the transcript
+        let c = self.derive_challenge(&r1, &r2);
+        (r1, r2, c)
+    }
+
+    /// The identity would make `y * c` vanish from the verification equation
+    fn validate_public_keys(y1: &RistrettoPoint, y2: &RistrettoPoint) -> CpResult<()> {
+        if *y1 == RistrettoPoint::identity() || *y2 == RistrettoPoint::identity() {
+            return Err(CpError::InvalidPublicKey);
+        }
+        Ok(())
+    }
+
+    /// Fiat-Shamir challenge `c = H(context || g || h || r1 || r2 || timestamp)` where every part is
+    /// length-prefixed, hashed in the transcript domain. `timestamp` is left out if it isn't set.
+    pub fn derive_challenge(&self, r1: &RistrettoPoint, r2: &RistrettoPoint) -> Scalar {
+        Self::hash(&hashing::hash_transcript(&self.transcript_bytes(r1, r2)))
+    }
+
+    /// Input of the Fiat-Shamir hash for the commitment `(r1, r2)`, `derive_challenge` hashes
+    /// exactly these bytes. Every part is an 8 byte big-endian length followed by the part, the
+    /// context, then the compressed `g`, `h`, `r1` and `r2` and the big-endian `timestamp` if set.
+    pub fn transcript_bytes(&self, r1: &RistrettoPoint, r2: &RistrettoPoint) -> Vec<u8> {
+        let points = [
+            self.g.compress(),
+            self.h.compress(),
+            r1.compress(),
+            r2.compress(),
+        ];
+        let timestamp = self.timestamp.map(u64::to_be_bytes);
+        let parts = std::iter::once(self.context.as_slice())
+            .chain(points.iter().map(|point| point.as_bytes().as_slice()))
+            .chain(timestamp.as_ref().map(|timestamp| timestamp.as_slice()));
+
+        let mut transcript = Vec::with_capacity(self.context.len() + 4 * 32 + 8 + 6 * 8);
+        for part in parts {
+            transcript.extend_from_slice(&(part.len() as u64).to_be_bytes());
+            transcript.extend_from_slice(part);
+        }
+        transcript
+    }
+
+    /// Hash function to convert byte slices to `Scalar` values, the wide 64-byte digest is reduced mod `l`.
+    /// A zero scalar is not a valid secret so the digest is re-hashed until it's non-zero.
     pub fn hash(input: &[u8]) -> Scalar {
-        let mut hasher = Sha512::new();
-        hasher.update(input);
-        let result = hasher.finalize();
-        Scalar::hash_from_bytes::<Sha512>(result.as_slice())
+        let mut result = Sha512::digest(input);
+        loop {
+            result = Sha512::digest(result);
+            let mut wide = [0u8; 64];
+            wide.copy_from_slice(&result);
+
+            let x = Scalar::from_bytes_mod_order_wide(&wide);
+            if x != Scalar::ZERO {
+                return x;
+            }
+        }
+    }
+
+    /// Deserializes a JSON encoded `Scalar`, only canonical encodings (`< l`) are accepted
+    pub fn scalar_from_json(input: &str) -> CpResult<Scalar> {
+        let bytes: [u8; 32] =
+            serde_json::from_str(input).map_err(|e| CpError::Deserialize(e.to_string()))?;
+
+        Option::from(Scalar::from_canonical_bytes(bytes)).ok_or(CpError::OutOfRange)
+    }
+
+    /// Encodes a public key as the base64 of its 32-byte compressed form
+    pub fn to_base64(point: &RistrettoPoint) -> String {
+        STANDARD.encode(point.compress().as_bytes())
+    }
+
+    /// Decodes a public key from the base64 of its 32-byte compressed form,
+    /// non-canonical encodings are rejected by the decompression.
+    pub fn from_base64(input: &str) -> CpResult<RistrettoPoint> {
+        Self::compressed_from_base64(input)?
+            .decompress()
+            .ok_or(CpError::InvalidPublicKey)
+    }
+
+    /// Same as `from_base64` but the decompressed point is memoized, verifying the same user
+    /// again skips the decompression.
+    pub fn point_from_base64(&self, input: &str) -> CpResult<RistrettoPoint> {
+        let compressed = Self::compressed_from_base64(input)?;
+        self.points
+            .decompress(&compressed)
+            .ok_or(CpError::InvalidPublicKey)
+    }
+
+    /// Encodes a public key as the base58 of its 32-byte compressed form, see `utils::encode_base58`
+    pub fn to_base58(point: &RistrettoPoint) -> String {
+        encode_base58(point.compress().as_bytes())
+    }
+
+    /// Decodes a public key from the base58 of its 32-byte compressed form, same checks as
+    /// `from_base64`
+    pub fn from_base58(input: &str) -> CpResult<RistrettoPoint> {
+        let bytes = decode_base58(input)?;
+        CompressedRistretto::from_slice(&bytes)
+            .map_err(|_| CpError::Deserialize(format!("expected 32 bytes, got {}", bytes.len())))?
+            .decompress()
+            .ok_or(CpError::InvalidPublicKey)
+    }
+
+    fn compressed_from_base64(input: &str) -> CpResult<CompressedRistretto> {
+        let bytes = STANDARD
+            .decode(input.trim())
+            .map_err(|e| CpError::Deserialize(e.to_string()))?;
+        CompressedRistretto::from_slice(&bytes)
+            .map_err(|_| CpError::Deserialize(format!("expected 32 bytes, got {}", bytes.len())))
+    }
+
+    /// Wraps the base64 encoded public key in PEM armor
+    pub fn to_pem(point: &RistrettoPoint) -> String {
+        format!("{}\n{}\n{}\n", PEM_BEGIN, Self::to_base64(point), PEM_END)
+    }
+
+    pub fn from_pem(input: &str) -> CpResult<RistrettoPoint> {
+        let body = input
+            .trim()
+            .strip_prefix(PEM_BEGIN)
+            .and_then(|rest| rest.strip_suffix(PEM_END))
+            .ok_or_else(|| CpError::Deserialize("missing PEM armor".to_string()))?;
+
+        Self::from_base64(body)
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use rand_chacha::rand_core::SeedableRng;
+    use rand_chacha::ChaCha8Rng;
+
+    use crate::utils::deserialize;
+
     use super::*;
 
+    #[cfg(feature = "std-async")]
     #[tokio::test]
     async fn proof() {
         let password = "my_secret_password";
@@ -172,17 +392,18 @@ mod tests {
 
         let ecc_cp = EccChaumPedersen::new();
 
-        let pk = ecc_cp.generate_public_keys(x).await;
+        let pk = ecc_cp.generate_public_keys(x).await.unwrap();
 
         // Prover's commitment
-        let (k, c, _) = ecc_cp.prover_commit().await;
+        let (k, r1, r2) = ecc_cp.prover_commit().await.unwrap();
+        let c = ecc_cp.derive_challenge(&r1, &r2);
 
         // Prover solves the challenge
-        let s = ecc_cp.prover_solve_challenge(k, c.clone().unwrap(), x);
+        let s = ecc_cp.prover_solve_challenge(k, c, x);
 
         // serialize and deserialize
         let (str_challenge, str_solution, y1_str, y2_str) = (
-            serde_json::to_string(&c.unwrap()).unwrap(),
+            serde_json::to_string(&c).unwrap(),
             serde_json::to_string(&s).unwrap(),
             serde_json::to_string(&pk.0).unwrap(),
             serde_json::to_string(&pk.1).unwrap(),
@@ -196,20 +417,361 @@ mod tests {
         // Verification
         assert_eq!(
             ecc_cp
-                .verify_proof(solution, challenge, y1.clone(), y2.clone(), None, None)
+                .verify_noninteractive(NonInteractiveProof::new(challenge, solution), y1, y2)
                 .await,
-            true
+            Ok(true)
         );
 
         let invalid_pw = "my_password";
         let invalid_x = EccChaumPedersen::hash(invalid_pw.as_bytes());
-        let (invalid_y1, invalid_y2) = ecc_cp.generate_public_keys(invalid_x).await;
+        let (invalid_y1, invalid_y2) = ecc_cp.generate_public_keys(invalid_x).await.unwrap();
+
+        assert_eq!(
+            ecc_cp
+                .verify_noninteractive(NonInteractiveProof::new(c, s), invalid_y1, invalid_y2)
+                .await,
+            Ok(false),
+        );
+    }
+
+    #[test]
+    fn tampered_commitment() {
+        let ecc_cp = EccChaumPedersen::new();
+        let x = EccChaumPedersen::hash(b"my_secret_password");
+        let (y1, y2) = ecc_cp.generate_public_keys_sync(x).unwrap();
+        let (k, r1, r2) = ecc_cp.prover_commit_sync().unwrap();
+        let c = ecc_cp.derive_challenge(&r1, &r2);
+        let s = ecc_cp.prover_solve_challenge(k, c, x);
+
+        let proof = Proof::new(r1, r2, c, s);
+        assert_eq!(ecc_cp.verify_with_commitment_sync(proof, y1, y2), Ok(true));
+
+        // Swapped, shifted and replayed-elsewhere commitments all fail
+        let other = r1 + RISTRETTO_BASEPOINT_POINT;
+        for (r1, r2, c) in [
+            (r2, r1, c),
+            (other, r2, c),
+            (other, r2, ecc_cp.derive_challenge(&other, &r2)),
+        ] {
+            let proof = Proof::new(r1, r2, c, s);
+            assert_eq!(ecc_cp.verify_with_commitment_sync(proof, y1, y2), Ok(false));
+        }
+    }
+
+    #[cfg(feature = "std-async")]
+    #[tokio::test]
+    async fn identity_public_key() {
+        let ecc_cp = EccChaumPedersen::new();
+        let x = EccChaumPedersen::hash(b"my_secret_password");
+        let (y1, _) = ecc_cp.generate_public_keys(x).await.unwrap();
+        let (k, r1, r2) = ecc_cp.prover_commit().await.unwrap();
+        let c = ecc_cp.derive_challenge(&r1, &r2);
+        let s = ecc_cp.prover_solve_challenge(k, c, x);
 
         assert_eq!(
             ecc_cp
-                .verify_proof(s, c.unwrap(), invalid_y1, invalid_y2, None, None)
+                .verify_noninteractive(
+                    NonInteractiveProof::new(c, s),
+                    y1,
+                    RistrettoPoint::identity()
+                )
+                .await,
+            Err(CpError::InvalidPublicKey),
+        );
+    }
+
+    #[cfg(feature = "std-async")]
+    #[tokio::test]
+    async fn public_key_encoding() {
+        let ecc_cp = EccChaumPedersen::new();
+        let x = EccChaumPedersen::hash(b"my_secret_password");
+        let (y1, y2) = ecc_cp.generate_public_keys(x).await.unwrap();
+
+        let encoded = EccChaumPedersen::to_base64(&y1);
+        assert_eq!(encoded.len(), 44);
+        assert_eq!(EccChaumPedersen::from_base64(&encoded), Ok(y1));
+
+        let pem = EccChaumPedersen::to_pem(&y2);
+        assert!(pem.starts_with(PEM_BEGIN));
+        assert_eq!(EccChaumPedersen::from_pem(&pem), Ok(y2));
+
+        let encoded = EccChaumPedersen::to_base58(&y2);
+        assert!(encoded.chars().all(|c| c.is_ascii_alphanumeric()));
+        assert_eq!(EccChaumPedersen::from_base58(&encoded), Ok(y2));
+    }
+
+    #[test]
+    fn public_key_encoding_rejected() {
+        assert!(matches!(
+            EccChaumPedersen::from_base58(&encode_base58(&[1u8; 31])),
+            Err(CpError::Deserialize(_))
+        ));
+        assert_eq!(
+            EccChaumPedersen::from_base58(&encode_base58(&[0xffu8; 32])),
+            Err(CpError::InvalidPublicKey)
+        );
+        let base58 = EccChaumPedersen::to_base58(&RISTRETTO_BASEPOINT_POINT);
+        assert!(matches!(
+            EccChaumPedersen::from_base58(&format!("0{}", base58)),
+            Err(CpError::Deserialize(_))
+        ));
+
+        let short = STANDARD.encode([1u8; 31]);
+        assert!(matches!(
+            EccChaumPedersen::from_base64(&short),
+            Err(CpError::Deserialize(_))
+        ));
+
+        // 0xff..ff is not the canonical encoding of any point
+        let non_canonical = STANDARD.encode([0xffu8; 32]);
+        assert_eq!(
+            EccChaumPedersen::from_base64(&non_canonical),
+            Err(CpError::InvalidPublicKey)
+        );
+
+        assert!(matches!(
+            EccChaumPedersen::from_pem(&short),
+            Err(CpError::Deserialize(_))
+        ));
+    }
+
+    #[cfg(feature = "std-async")]
+    #[tokio::test]
+    async fn sync_matches_async() {
+        let ecc_cp = EccChaumPedersen::new();
+        let x = EccChaumPedersen::hash(b"my_secret_password");
+
+        let keys = ecc_cp.generate_public_keys(x).await.unwrap();
+        assert_eq!(keys, ecc_cp.generate_public_keys_sync(x).unwrap());
+        let (y1, y2) = keys;
+
+        let (k, r1, r2) = ecc_cp.prover_commit_sync().unwrap();
+        let c = ecc_cp.derive_challenge(&r1, &r2);
+        let s = ecc_cp.prover_solve_challenge(k, c, x);
+
+        for (s, c) in [(s, c), (s, s)] {
+            let proof = NonInteractiveProof::new(c, s);
+            assert_eq!(
+                ecc_cp.verify_noninteractive(proof.clone(), y1, y2).await,
+                ecc_cp.verify_noninteractive_sync(proof, y1, y2)
+            );
+        }
+    }
+
+    #[cfg(feature = "std-async")]
+    #[tokio::test]
+    async fn context_separation() {
+        let x = EccChaumPedersen::hash(b"my_secret_password");
+        let deployment_a = EccChaumPedersen::with_context(b"deployment-a");
+        let deployment_b = EccChaumPedersen::with_context(b"deployment-b");
+
+        let (y1, y2) = deployment_a.generate_public_keys(x).await.unwrap();
+        let (k, r1, r2) = deployment_a.prover_commit().await.unwrap();
+        let c = deployment_a.derive_challenge(&r1, &r2);
+        let s = deployment_a.prover_solve_challenge(k, c, x);
+        let proof = NonInteractiveProof::new(c, s);
+
+        assert_eq!(
+            deployment_a
+                .verify_noninteractive(proof.clone(), y1, y2)
+                .await,
+            Ok(true)
+        );
+        assert_eq!(
+            deployment_b
+                .verify_noninteractive(proof.clone(), y1, y2)
+                .await,
+            Ok(false)
+        );
+        assert_eq!(
+            EccChaumPedersen::new()
+                .verify_noninteractive(proof, y1, y2)
                 .await,
-            false,
+            Ok(false)
         );
     }
+
+    #[test]
+    fn timestamp_separation() {
+        let x = EccChaumPedersen::hash(b"my_secret_password");
+        let ecc = EccChaumPedersen::new().with_timestamp(1700000000);
+        let (y1, y2) = ecc.generate_public_keys_sync(x).unwrap();
+        let (k, r1, r2) = ecc.prover_commit_sync().unwrap();
+        let c = ecc.derive_challenge(&r1, &r2);
+        let proof = NonInteractiveProof::new(c, ecc.prover_solve_challenge(k, c, x));
+
+        assert_eq!(
+            ecc.verify_noninteractive_sync(proof.clone(), y1, y2),
+            Ok(true)
+        );
+        assert_eq!(
+            EccChaumPedersen::new()
+                .with_timestamp(1700000001)
+                .verify_noninteractive_sync(proof.clone(), y1, y2),
+            Ok(false)
+        );
+        assert_eq!(
+            EccChaumPedersen::new().verify_noninteractive_sync(proof, y1, y2),
+            Ok(false)
+        );
+    }
+
+    #[test]
+    fn cached_public_keys() {
+        let ecc = EccChaumPedersen::new();
+        let x = EccChaumPedersen::hash(b"my_secret_password");
+        let (y1, y2) = ecc.generate_public_keys_sync(x).unwrap();
+        let (y1, y2) = (
+            EccChaumPedersen::to_base64(&y1),
+            EccChaumPedersen::to_base64(&y2),
+        );
+
+        // Clones share the cache, the server keeps one instance for every login
+        let verifier = ecc.clone();
+        for _ in 0..3 {
+            let (k, r1, r2) = ecc.prover_commit_sync().unwrap();
+            let c = ecc.derive_challenge(&r1, &r2);
+            let s = ecc.prover_solve_challenge(k, c, x);
+
+            let keys = (
+                verifier.point_from_base64(&y1).unwrap(),
+                verifier.point_from_base64(&y2).unwrap(),
+            );
+            assert_eq!(keys.0, EccChaumPedersen::from_base64(&y1).unwrap());
+            assert_eq!(keys.1, EccChaumPedersen::from_base64(&y2).unwrap());
+            assert_eq!(
+                verifier.verify_noninteractive_sync(NonInteractiveProof::new(c, s), keys.0, keys.1),
+                Ok(true)
+            );
+        }
+
+        let cache = ecc.point_cache();
+        assert_eq!((cache.hits(), cache.misses()), (4, 2));
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[test]
+    fn noninteractive_recomputes_challenge() {
+        let ecc = EccChaumPedersen::new();
+        let x = EccChaumPedersen::hash(b"my_secret_password");
+        let (y1, y2) = ecc.generate_public_keys_sync(x).unwrap();
+
+        // A valid interactive transcript with a challenge that is not derived from the commitment
+        let k = EccChaumPedersen::hash(b"nonce");
+        let (r1, r2, _) = ecc.commit_with(k);
+        let c = EccChaumPedersen::hash(b"chosen by the prover");
+        let s = ecc.prover_solve_challenge(k, c, x);
+
+        assert_eq!(
+            ecc.verify_interactive_sync(Proof::new(r1, r2, c, s), y1, y2),
+            Ok(true)
+        );
+        assert_eq!(
+            ecc.verify_noninteractive_sync(NonInteractiveProof::new(c, s), y1, y2),
+            Ok(false)
+        );
+
+        // The same nonce with the derived challenge verifies
+        let (_, _, c) = ecc.commit_with(k);
+        let s = ecc.prover_solve_challenge(k, c, x);
+        assert_eq!(
+            ecc.verify_noninteractive_sync(NonInteractiveProof::new(c, s), y1, y2),
+            Ok(true)
+        );
+    }
+
+    #[test]
+    fn transcript() {
+        let ecc = EccChaumPedersen::with_context(b"nyan");
+        let x = EccChaumPedersen::hash(b"my_secret_password");
+        let (y1, y2) = ecc.generate_public_keys_sync(x).unwrap();
+        let (k, r1, r2) = ecc.prover_commit_sync().unwrap();
+        let transcript = ecc.transcript_bytes(&r1, &r2);
+
+        let mut expected = [0, 0, 0, 0, 0, 0, 0, 4].to_vec();
+        expected.extend_from_slice(b"nyan");
+        for point in [*ecc.g, *ecc.h, r1, r2] {
+            expected.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 32]);
+            expected.extend_from_slice(point.compress().as_bytes());
+        }
+        assert_eq!(transcript, expected);
+
+        // The verifier recomputes the commitment from `(c, s)` and hashes the same bytes
+        let c = EccChaumPedersen::hash(&hashing::hash_transcript(&transcript));
+        let s = ecc.prover_solve_challenge(k, c, x);
+        let t1 = *ecc.g * s + y1 * c;
+        let t2 = *ecc.h * s + y2 * c;
+        assert_eq!(ecc.transcript_bytes(&t1, &t2), transcript);
+        assert_eq!(
+            ecc.verify_noninteractive_sync(NonInteractiveProof::new(c, s), y1, y2),
+            Ok(true)
+        );
+
+        // The timestamp is the last part
+        expected.extend_from_slice(&[0, 0, 0, 0, 0, 0, 0, 8, 0, 0, 0, 0, 0, 0, 0, 42]);
+        assert_eq!(ecc.with_timestamp(42).transcript_bytes(&r1, &r2), expected);
+    }
+
+    #[test]
+    fn seeded_commit() {
+        let ecc = EccChaumPedersen::new();
+
+        let (k1, r1, _) = ecc
+            .prover_commit_with(&mut ChaCha8Rng::seed_from_u64(42))
+            .unwrap();
+        let (k2, r2, _) = ecc
+            .prover_commit_with(&mut ChaCha8Rng::seed_from_u64(42))
+            .unwrap();
+        let (k3, _, _) = ecc
+            .prover_commit_with(&mut ChaCha8Rng::seed_from_u64(43))
+            .unwrap();
+
+        assert_eq!((k1, r1), (k2, r2));
+        assert_ne!(k1, k3);
+    }
+
+    #[test]
+    fn hash_reduced() {
+        use num_bigint::{BigInt, Sign};
+
+        // SHA-512 is wider than the group order `l`, the digest has to be reduced and not truncated
+        let l = BigInt::parse_bytes(
+            b"7237005577332262213973186563042994240857116359379907606001950938285454250989",
+            10,
+        )
+        .unwrap();
+        let digest = Sha512::digest(Sha512::digest(b"my_secret_password"));
+        let wide = BigInt::from_bytes_le(Sign::Plus, &digest);
+        assert!(wide > l);
+
+        let x = EccChaumPedersen::hash(b"my_secret_password");
+        assert_eq!(BigInt::from_bytes_le(Sign::Plus, &x.to_bytes()), wide % &l);
+        assert_eq!(x, EccChaumPedersen::hash(b"my_secret_password"));
+    }
+
+    #[test]
+    fn scalar_from_json() {
+        let x = EccChaumPedersen::hash(b"my_secret_password");
+        let json = serde_json::to_string(&x).unwrap();
+        assert_eq!(EccChaumPedersen::scalar_from_json(&json), Ok(x));
+
+        assert!(matches!(
+            EccChaumPedersen::scalar_from_json("{not json"),
+            Err(CpError::Deserialize(_))
+        ));
+
+        let non_canonical = serde_json::to_string(&[0xffu8; 32]).unwrap();
+        assert_eq!(
+            EccChaumPedersen::scalar_from_json(&non_canonical),
+            Err(CpError::OutOfRange)
+        );
+    }
+
+    #[test]
+    fn deserialize_error() {
+        assert!(matches!(
+            deserialize::<Scalar>("not a scalar"),
+            Err(CpError::Deserialize(_))
+        ));
+    }
 }
@@ -0,0 +1,286 @@
+//! C ABI over the interactive protocol.
+//!
+//! Ownership: every buffer is allocated and owned by the caller and passed as a pointer plus its
+//! length, nothing returned by these functions has to be freed. `params_json` is a NUL terminated
+//! `CpParams` JSON string. Numbers are unsigned big-endian bytes, outputs are left padded to the
+//! byte length of `p` and proofs use the length-prefixed `Proof::to_bytes` format.
+//!
+//! Negative return values are errors, see the `CP_ERR_*` constants.
+
+use std::ffi::{c_char, c_int, CStr};
+use std::slice;
+
+use num_bigint::{BigInt, Sign};
+
+use crate::chaum_pedersen::{ChaumPedersen, CpParams};
+use crate::proof::Proof;
+use crate::utils::deserialize;
+use crate::{ChaumPedersenTrait, CpError, InteractiveVerifier};
+
+/// A required pointer is null
+pub const CP_ERR_NULL: c_int = -1;
+/// `params_json` is not valid UTF-8, not `CpParams` JSON or the parameters don't validate
+pub const CP_ERR_PARAMS: c_int = -2;
+/// A buffer doesn't hold a well-formed encoding, e.g. a proof of the wrong length
+pub const CP_ERR_ENCODING: c_int = -3;
+/// A value is not a valid group element or out of range
+pub const CP_ERR_INVALID_INPUT: c_int = -4;
+/// An output buffer is shorter than the byte length of `p`, or the proof length for proofs
+pub const CP_ERR_BUFFER_TOO_SMALL: c_int = -5;
+
+/// Computes the public keys `y1 = g^x`, `y2 = h^x` into `y1_out` and `y2_out`, each `out_len`
+/// bytes long. Returns the number of bytes written to each buffer.
+///
+/// # Safety
+/// `params_json` must be a valid NUL terminated string, `secret` must point to `secret_len`
+/// readable bytes and `y1_out`, `y2_out` to `out_len` writable bytes each.
+#[no_mangle]
+pub unsafe extern "C" fn cp_public_keys(
+    params_json: *const c_char,
+    secret: *const u8,
+    secret_len: usize,
+    y1_out: *mut u8,
+    y2_out: *mut u8,
+    out_len: usize,
+) -> c_int {
+    result(|| {
+        let cp = params(params_json)?;
+        let x = bigint(secret, secret_len)?;
+        let (y1, y2) = cp.generate_public_keys_sync(x).map_err(code)?;
+
+        write_padded(&y1, buffer(y1_out, out_len)?, cp.byte_len())?;
+        write_padded(&y2, buffer(y2_out, out_len)?, cp.byte_len())
+    })
+}
+
+/// Commits to a fresh random `k` and answers `challenge` for the secret `x`, the whole transcript
+/// is written to `proof_out`. Returns the length of the proof, `4 * (2 + byte length of p)`.
+///
+/// # Safety
+/// `params_json` must be a valid NUL terminated string, `secret` and `challenge` must point to
+/// `secret_len` and `challenge_len` readable bytes and `proof_out` to `proof_out_len` writable
+/// bytes.
+#[no_mangle]
+pub unsafe extern "C" fn cp_prove(
+    params_json: *const c_char,
+    secret: *const u8,
+    secret_len: usize,
+    challenge: *const u8,
+    challenge_len: usize,
+    proof_out: *mut u8,
+    proof_out_len: usize,
+) -> c_int {
+    result(|| {
+        let cp = params(params_json)?;
+        let x = bigint(secret, secret_len)?;
+        let c = bigint(challenge, challenge_len)?;
+
+        let (k, r1, r2) = cp.prover_commit_sync().map_err(code)?;
+        let s = cp.prover_solve_challenge(k, c.clone(), x);
+
+        let proof = Proof::new(r1, r2, c, s)
+            .to_bytes(cp.byte_len())
+            .map_err(code)?;
+        let out = buffer(proof_out, proof_out_len)?;
+        if out.len() < proof.len() {
+            return Err(CP_ERR_BUFFER_TOO_SMALL);
+        }
+
+        out[..proof.len()].copy_from_slice(&proof);
+        c_int::try_from(proof.len()).map_err(|_| CP_ERR_BUFFER_TOO_SMALL)
+    })
+}
+
+/// Verifies the encoded `proof` against the public keys `y1` and `y2`.
+/// Returns `1` if the proof is valid and `0` if it isn't.
+///
+/// # Safety
+/// `params_json` must be a valid NUL terminated string and `proof`, `y1`, `y2` must point to
+/// `proof_len`, `y1_len` and `y2_len` readable bytes.
+#[no_mangle]
+pub unsafe extern "C" fn cp_verify(
+    params_json: *const c_char,
+    proof: *const u8,
+    proof_len: usize,
+    y1: *const u8,
+    y1_len: usize,
+    y2: *const u8,
+    y2_len: usize,
+) -> c_int {
+    result(|| {
+        let cp = params(params_json)?;
+        let proof = Proof::from_bytes(bytes(proof, proof_len)?, cp.byte_len()).map_err(code)?;
+        let y1 = bigint(y1, y1_len)?;
+        let y2 = bigint(y2, y2_len)?;
+
+        let is_valid = cp.verify_interactive_sync(proof, y1, y2).map_err(code)?;
+        Ok(c_int::from(is_valid))
+    })
+}
+
+fn result<F: FnOnce() -> Result<c_int, c_int>>(f: F) -> c_int {
+    f().unwrap_or_else(|e| e)
+}
+
+fn code(e: CpError) -> c_int {
+    match e {
+        CpError::InvalidParams(_) | CpError::ParamsTooLarge { .. } => CP_ERR_PARAMS,
+        CpError::Deserialize(_) => CP_ERR_ENCODING,
+        _ => CP_ERR_INVALID_INPUT,
+    }
+}
+
+unsafe fn params(params_json: *const c_char) -> Result<ChaumPedersen, c_int> {
+    if params_json.is_null() {
+        return Err(CP_ERR_NULL);
+    }
+    let json = CStr::from_ptr(params_json)
+        .to_str()
+        .map_err(|_| CP_ERR_PARAMS)?;
+    let params: CpParams = deserialize(json).map_err(|_| CP_ERR_PARAMS)?;
+
+    ChaumPedersen::try_from(params).map_err(code)
+}
+
+unsafe fn bytes<'a>(ptr: *const u8, len: usize) -> Result<&'a [u8], c_int> {
+    if ptr.is_null() {
+        return Err(CP_ERR_NULL);
+    }
+    Ok(slice::from_raw_parts(ptr, len))
+}
+
+unsafe fn buffer<'a>(ptr: *mut u8, len: usize) -> Result<&'a mut [u8], c_int> {
+    if ptr.is_null() {
+        return Err(CP_ERR_NULL);
+    }
+    Ok(slice::from_raw_parts_mut(ptr, len))
+}
+
+unsafe fn bigint(ptr: *const u8, len: usize) -> Result<BigInt, c_int> {
+    Ok(BigInt::from_bytes_be(Sign::Plus, bytes(ptr, len)?))
+}
+
+fn write_padded(value: &BigInt, out: &mut [u8], byte_len: usize) -> Result<c_int, c_int> {
+    if out.len() < byte_len {
+        return Err(CP_ERR_BUFFER_TOO_SMALL);
+    }
+
+    let (_, value) = value.to_bytes_be();
+    let padding = byte_len - value.len();
+    out[..padding].fill(0);
+    out[padding..byte_len].copy_from_slice(&value);
+    c_int::try_from(byte_len).map_err(|_| CP_ERR_BUFFER_TOO_SMALL)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::CString;
+    use std::ptr;
+
+    use crate::chaum_pedersen::Group;
+
+    use super::*;
+
+    fn params_json(cp: &ChaumPedersen) -> CString {
+        CString::new(serde_json::to_string(&CpParams::from(cp)).unwrap()).unwrap()
+    }
+
+    /// Public keys and a proof for `password` produced through the C ABI
+    unsafe fn prove(
+        json: &CString,
+        byte_len: usize,
+        password: &[u8],
+    ) -> (Vec<u8>, Vec<u8>, Vec<u8>) {
+        let (_, x) = ChaumPedersen::hash(password).to_bytes_be();
+        let (mut y1, mut y2) = (vec![0u8; byte_len], vec![0u8; byte_len]);
+        let written = cp_public_keys(
+            json.as_ptr(),
+            x.as_ptr(),
+            x.len(),
+            y1.as_mut_ptr(),
+            y2.as_mut_ptr(),
+            byte_len,
+        );
+        assert_eq!(written, byte_len as c_int);
+
+        let (_, c) = BigInt::from(7).to_bytes_be();
+        let mut proof = vec![0u8; 4 * (2 + byte_len)];
+        let written = cp_prove(
+            json.as_ptr(),
+            x.as_ptr(),
+            x.len(),
+            c.as_ptr(),
+            c.len(),
+            proof.as_mut_ptr(),
+            proof.len(),
+        );
+        assert_eq!(written, proof.len() as c_int);
+
+        (y1, y2, proof)
+    }
+
+    unsafe fn verify(json: &CString, proof: &[u8], y1: &[u8], y2: &[u8]) -> c_int {
+        cp_verify(
+            json.as_ptr(),
+            proof.as_ptr(),
+            proof.len(),
+            y1.as_ptr(),
+            y1.len(),
+            y2.as_ptr(),
+            y2.len(),
+        )
+    }
+
+    #[test]
+    fn round_trip() {
+        let cp = Group::Modp2048.protocol();
+        let json = params_json(&cp);
+
+        unsafe {
+            let (y1, y2, proof) = prove(&json, cp.byte_len(), b"cat");
+            assert_eq!(verify(&json, &proof, &y1, &y2), 1);
+
+            let (other_y1, other_y2, _) = prove(&json, cp.byte_len(), b"dog");
+            assert_eq!(verify(&json, &proof, &other_y1, &other_y2), 0);
+        }
+    }
+
+    #[test]
+    fn error_codes() {
+        let cp = Group::Modp2048.protocol();
+        let json = params_json(&cp);
+        let garbage = CString::new("{\"p\": 23}").unwrap();
+
+        unsafe {
+            let (y1, y2, proof) = prove(&json, cp.byte_len(), b"cat");
+
+            assert_eq!(verify(&garbage, &proof, &y1, &y2), CP_ERR_PARAMS);
+            assert_eq!(verify(&json, &proof[1..], &y1, &y2), CP_ERR_ENCODING);
+            assert_eq!(verify(&json, &proof, &[0], &y2), CP_ERR_INVALID_INPUT);
+            assert_eq!(
+                cp_verify(
+                    ptr::null(),
+                    proof.as_ptr(),
+                    proof.len(),
+                    y1.as_ptr(),
+                    y1.len(),
+                    y2.as_ptr(),
+                    y2.len()
+                ),
+                CP_ERR_NULL
+            );
+
+            let x = [1u8];
+            let (mut y1, mut y2) = (vec![0u8; cp.byte_len() - 1], vec![0u8; cp.byte_len() - 1]);
+            let written = cp_public_keys(
+                json.as_ptr(),
+                x.as_ptr(),
+                x.len(),
+                y1.as_mut_ptr(),
+                y2.as_mut_ptr(),
+                y1.len(),
+            );
+            assert_eq!(written, CP_ERR_BUFFER_TOO_SMALL);
+        }
+    }
+}
@@ -0,0 +1,61 @@
+//! Hash domains of the protocols. Every digest is over the length-prefixed domain tag followed by
+//! the input, so the same bytes hashed as a session or a transcript never give the same digest.
+//!
+//! Passwords have no domain, the secret `x` stays `ChaumPedersen::hash(password)` or
+//! `EccChaumPedersen::hash(password)` so the keys registered before the domains keep verifying.
+
+use sha2::{Digest, Sha256, Sha512};
+
+pub const SESSION_DOMAIN: &[u8] = b"chaum-pedersen/session";
+pub const TRANSCRIPT_DOMAIN: &[u8] = b"chaum-pedersen/transcript";
+pub const CHALLENGE_SEEDS_DOMAIN: &[u8] = b"chaum-pedersen/challenge-seeds";
+
+fn tagged<D: Digest>(domain: &[u8], input: &[u8]) -> D {
+    let mut hasher = D::new();
+    hasher.update((domain.len() as u64).to_be_bytes());
+    hasher.update(domain);
+    hasher.update(input);
+    hasher
+}
+
+/// SHA-256 of the fields a session id is signed over
+pub fn hash_session(input: &[u8]) -> [u8; 32] {
+    tagged::<Sha256>(SESSION_DOMAIN, input).finalize().into()
+}
+
+/// SHA-512 of a challenge transcript, the Fiat-Shamir input of the non-interactive protocol or
+/// the commitment a server challenge is bound to
+pub fn hash_transcript(transcript: &[u8]) -> [u8; 64] {
+    tagged::<Sha512>(TRANSCRIPT_DOMAIN, transcript)
+        .finalize()
+        .into()
+}
+
+/// SHA-512 of the challenge seeds of several verifiers. The seeds are sorted first, so the order
+/// they're collected in doesn't matter, and unlike a XOR equal seeds don't cancel out.
+pub fn hash_challenge_seeds(seeds: &[[u8; 32]]) -> [u8; 64] {
+    let mut sorted = seeds.to_vec();
+    sorted.sort_unstable();
+    tagged::<Sha512>(CHALLENGE_SEEDS_DOMAIN, &sorted.concat())
+        .finalize()
+        .into()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn separated_domains() {
+        let input = b"nyancat";
+        let transcript = hash_transcript(input);
+        let session = hash_session(input);
+
+        assert_ne!(transcript[..32], session);
+        assert_ne!(transcript[..], Sha512::digest(input)[..]);
+        assert_ne!(session[..], Sha256::digest(input)[..]);
+
+        assert_eq!(transcript, hash_transcript(input));
+        assert_ne!(transcript, hash_transcript(b"nyandog"));
+    }
+}
@@ -1,28 +1,120 @@
-#![feature(async_fn_in_trait)]
+#![cfg_attr(not(feature = "std"), no_std)]
+#![cfg_attr(feature = "std-async", feature(async_fn_in_trait))]
 
+extern crate alloc;
 extern crate core;
+#[cfg(feature = "std")]
 #[macro_use]
 extern crate lazy_static;
 
+use alloc::string::String;
+use core::fmt;
+
+#[cfg(feature = "std")]
+use crate::proof::{NonInteractiveProof, Proof};
+
+#[cfg(feature = "std")]
 pub mod chaum_pedersen;
+pub mod core_math;
+#[cfg(feature = "std")]
 pub mod ecc_chaum_pedersen;
+#[cfg(feature = "ffi")]
+pub mod ffi;
+#[cfg(feature = "std")]
+pub mod hashing;
+#[cfg(feature = "std")]
+pub mod point_cache;
+#[cfg(feature = "std")]
+pub mod proof;
+#[cfg(feature = "std")]
+pub mod prover;
+#[cfg(feature = "std-async")]
+pub mod scheme;
+#[cfg(feature = "std")]
+pub mod test_vectors;
+#[cfg(feature = "std")]
 pub mod utils;
+#[cfg(feature = "std")]
+pub mod verifier;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CpError {
+    TaskJoin(String),
+    InvalidPublicKey,
+    OutOfRange,
+    Deserialize(String),
+    InvalidParams(String),
+    MissingCommitment,
+    NonceReused,
+    /// No challenge is outstanding under the `auth_id`, it was never issued or already answered
+    UnknownChallenge,
+    /// Verification did not finish within the allowed time
+    Timeout,
+    /// `p` is longer than the configured maximum, exponentiations in the group would take too long
+    ParamsTooLarge {
+        bits: u64,
+        max_bits: u64,
+    },
+    /// The verification was given up because its `CancelFlag` was set
+    Cancelled,
+}
+
+impl fmt::Display for CpError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CpError::TaskJoin(s) => write!(f, "Failed to join computation task: {}", s),
+            CpError::InvalidPublicKey => write!(f, "Public key is not a valid group element"),
+            CpError::OutOfRange => write!(f, "Value is out of range"),
+            CpError::Deserialize(s) => write!(f, "Failed to deserialize value: {}", s),
+            CpError::InvalidParams(s) => write!(f, "Invalid group parameters: {}", s),
+            CpError::MissingCommitment => write!(f, "Prover has not committed to a random k"),
+            CpError::NonceReused => {
+                write!(f, "Random k has already been used to solve a challenge")
+            }
+            CpError::UnknownChallenge => write!(f, "No challenge was issued under the auth id"),
+            CpError::Timeout => write!(f, "Verification timed out"),
+            CpError::Cancelled => write!(f, "Verification was cancelled"),
+            CpError::ParamsTooLarge { bits, max_bits } => {
+                write!(
+                    f,
+                    "p has {} bits, more than the maximum of {}",
+                    bits, max_bits
+                )
+            }
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl std::error::Error for CpError {}
 
+pub type CpResult<T> = Result<T, CpError>;
+
+#[cfg(feature = "std")]
 pub trait ChaumPedersenTrait {
     type Point;
     type Scalar;
 
-    async fn generate_public_keys(&self, secret_scalar: Self::Scalar)
-                                  -> (Self::Point, Self::Point);
+    #[cfg(feature = "std-async")]
+    async fn generate_public_keys(
+        &self,
+        secret_scalar: Self::Scalar,
+    ) -> CpResult<(Self::Point, Self::Point)>;
+
+    /// Same as `generate_public_keys` but computed on the calling thread, no runtime required
+    fn generate_public_keys_sync(
+        &self,
+        secret_scalar: Self::Scalar,
+    ) -> CpResult<(Self::Point, Self::Point)>;
+
+    /// Returns the random `k` and the commitment `(r1, r2)` to it. The commitment is sent to the
+    /// verifier, for the ECC implementation the challenge is derived from it, see
+    /// `EccChaumPedersen::derive_challenge`.
+    #[cfg(feature = "std-async")]
+    async fn prover_commit(&self) -> CpResult<(Self::Scalar, Self::Point, Self::Point)>;
 
-    /// This function returns a tuple containing three elements:
-    ///
-    /// - `t.0`: A randomly generated point `k`.
-    ///
-    /// - `t.1`: An `Option` wrapping a point. For the ECC implementation, this contains a challenge; otherwise, it contains the value `r1`.
-    ///
-    /// - `t.2`: An `Option` wrapping a point. For the ECC implementation, this is `None`; otherwise, it contains the value `r2`.
-    async fn prover_commit(&self) -> (Self::Scalar, Option<Self::Scalar>, Option<Self::Scalar>);
+    /// Same as `prover_commit` but computed on the calling thread, no runtime required
+    fn prover_commit_sync(&self) -> CpResult<(Self::Scalar, Self::Point, Self::Point)>;
 
     fn prover_solve_challenge(
         &self,
@@ -30,14 +122,46 @@ pub trait ChaumPedersenTrait {
         challenge: Self::Scalar,
         secret_x: Self::Scalar,
     ) -> Self::Scalar;
+}
+
+/// Verifier of the interactive protocol, the solution is checked against the commitment `(r1, r2)`
+/// the prover sent before the challenge was issued.
+#[cfg(feature = "std")]
+pub trait InteractiveVerifier: ChaumPedersenTrait {
+    #[cfg(feature = "std-async")]
+    async fn verify_interactive(
+        &self,
+        proof: Proof<Self::Point, Self::Scalar>,
+        y1: Self::Point,
+        y2: Self::Point,
+    ) -> CpResult<bool>;
+
+    /// Same as `verify_interactive` but computed on the calling thread, no runtime required
+    fn verify_interactive_sync(
+        &self,
+        proof: Proof<Self::Point, Self::Scalar>,
+        y1: Self::Point,
+        y2: Self::Point,
+    ) -> CpResult<bool>;
+}
+
+/// Verifier of the non-interactive protocol, only `(c, s)` comes from the prover,
+/// the commitment is recomputed from them and the challenge re-derived from the transcript.
+#[cfg(feature = "std")]
+pub trait NonInteractiveVerifier: ChaumPedersenTrait {
+    #[cfg(feature = "std-async")]
+    async fn verify_noninteractive(
+        &self,
+        proof: NonInteractiveProof<Self::Scalar>,
+        y1: Self::Point,
+        y2: Self::Point,
+    ) -> CpResult<bool>;
 
-    async fn verify_proof(
+    /// Same as `verify_noninteractive` but computed on the calling thread, no runtime required
+    fn verify_noninteractive_sync(
         &self,
-        s: Self::Scalar,
-        c: Self::Scalar,
+        proof: NonInteractiveProof<Self::Scalar>,
         y1: Self::Point,
         y2: Self::Point,
-        r1: Option<Self::Scalar>,
-        r2: Option<Self::Scalar>,
-    ) -> bool;
+    ) -> CpResult<bool>;
 }
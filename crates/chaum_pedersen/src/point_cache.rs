@@ -0,0 +1,152 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use curve25519_dalek::ristretto::CompressedRistretto;
+use curve25519_dalek::RistrettoPoint;
+
+/// Points kept by `EccChaumPedersen` unless configured otherwise
+pub const DEFAULT_CAPACITY: usize = 1024;
+
+/// Decompressed points keyed by their compressed bytes, the least recently used one is evicted
+/// once `capacity` is reached.
+pub struct PointCache {
+    points: RwLock<HashMap<[u8; 32], Entry>>,
+    capacity: usize,
+    /// Source of the `last_used` stamps, a hit only needs the read lock to bump its stamp
+    stamps: AtomicU64,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+struct Entry {
+    point: RistrettoPoint,
+    last_used: AtomicU64,
+}
+
+impl PointCache {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            points: RwLock::new(HashMap::new()),
+            capacity: capacity.max(1),
+            stamps: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Decompresses `compressed` or returns the cached point, `None` for a non-canonical encoding.
+    /// Invalid encodings are not cached.
+    pub fn decompress(&self, compressed: &CompressedRistretto) -> Option<RistrettoPoint> {
+        let key = compressed.to_bytes();
+        if let Some(entry) = self.read().get(&key) {
+            entry.last_used.store(self.stamp(), Ordering::Relaxed);
+            self.hits.fetch_add(1, Ordering::Relaxed);
+            return Some(entry.point);
+        }
+
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let point = compressed.decompress()?;
+
+        let mut points = self.write();
+        if points.len() >= self.capacity && !points.contains_key(&key) {
+            let oldest = points
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used.load(Ordering::Relaxed))
+                .map(|(key, _)| *key);
+            if let Some(oldest) = oldest {
+                points.remove(&oldest);
+            }
+        }
+        points.insert(
+            key,
+            Entry {
+                point,
+                last_used: AtomicU64::new(self.stamp()),
+            },
+        );
+
+        Some(point)
+    }
+
+    pub fn len(&self) -> usize {
+        self.read().len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Lookups answered from the cache
+    pub fn hits(&self) -> u64 {
+        self.hits.load(Ordering::Relaxed)
+    }
+
+    /// Lookups that had to decompress
+    pub fn misses(&self) -> u64 {
+        self.misses.load(Ordering::Relaxed)
+    }
+
+    fn stamp(&self) -> u64 {
+        self.stamps.fetch_add(1, Ordering::Relaxed)
+    }
+
+    // A panic while holding the lock can't leave the map half updated, the poison is ignored
+    fn read(&self) -> RwLockReadGuard<'_, HashMap<[u8; 32], Entry>> {
+        self.points.read().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn write(&self) -> RwLockWriteGuard<'_, HashMap<[u8; 32], Entry>> {
+        self.points.write().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl fmt::Debug for PointCache {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PointCache")
+            .field("capacity", &self.capacity)
+            .field("len", &self.len())
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT;
+    use curve25519_dalek::Scalar;
+
+    use super::*;
+
+    fn compressed(n: u64) -> CompressedRistretto {
+        (RISTRETTO_BASEPOINT_POINT * Scalar::from(n)).compress()
+    }
+
+    #[test]
+    fn lru_eviction() {
+        let cache = PointCache::new(2);
+        let (a, b, c) = (compressed(1), compressed(2), compressed(3));
+
+        assert_eq!(cache.decompress(&a), a.decompress());
+        cache.decompress(&b);
+        cache.decompress(&a);
+        assert_eq!((cache.hits(), cache.misses()), (1, 2));
+
+        // `b` is the least recently used
+        cache.decompress(&c);
+        assert_eq!(cache.len(), 2);
+        cache.decompress(&a);
+        assert_eq!((cache.hits(), cache.misses()), (2, 3));
+        cache.decompress(&b);
+        assert_eq!((cache.hits(), cache.misses()), (2, 4));
+    }
+
+    #[test]
+    fn invalid_encoding() {
+        let cache = PointCache::new(2);
+        let invalid = CompressedRistretto([0xff; 32]);
+
+        assert_eq!(cache.decompress(&invalid), None);
+        assert!(cache.is_empty());
+    }
+}
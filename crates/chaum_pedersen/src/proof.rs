@@ -0,0 +1,232 @@
+use num_bigint::{BigInt, Sign};
+use serde::{Deserialize, Serialize};
+
+use crate::{CpError, CpResult};
+
+/// Transcript of an interactive proof, commitment `(r1, r2)`, challenge `c` and solution `s`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Proof<P = BigInt, S = BigInt> {
+    pub r1: P,
+    pub r2: P,
+    pub c: S,
+    pub s: S,
+}
+
+/// Non-interactive proof, challenge `c` and solution `s`. There is no commitment, the verifier
+/// recomputes it from `c` and `s`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct NonInteractiveProof<S> {
+    pub c: S,
+    pub s: S,
+}
+
+/// Transcript of a proof that the discrete logs of every public key to its generator are equal,
+/// one commitment per generator and a single challenge `c` and solution `s`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct MultiProof<P = BigInt, S = BigInt> {
+    pub commitments: Vec<P>,
+    pub c: S,
+    pub s: S,
+}
+
+impl<P, S> Proof<P, S> {
+    pub fn new(r1: P, r2: P, c: S, s: S) -> Self {
+        Self { r1, r2, c, s }
+    }
+}
+
+impl<P, S> MultiProof<P, S> {
+    pub fn new(commitments: Vec<P>, c: S, s: S) -> Self {
+        Self { commitments, c, s }
+    }
+}
+
+impl<P, S> From<Proof<P, S>> for MultiProof<P, S> {
+    fn from(proof: Proof<P, S>) -> Self {
+        Self::new(vec![proof.r1, proof.r2], proof.c, proof.s)
+    }
+}
+
+impl<S> NonInteractiveProof<S> {
+    pub fn new(c: S, s: S) -> Self {
+        Self { c, s }
+    }
+}
+
+impl Proof {
+    /// Encodes every field as `u16` big-endian length prefix followed by the big-endian value
+    /// left padded to `byte_len`, the byte length of `p`, so the blob has a fixed size.
+    pub fn to_bytes(&self, byte_len: usize) -> CpResult<Vec<u8>> {
+        let prefix = u16::try_from(byte_len).map_err(|_| CpError::OutOfRange)?;
+        let mut bytes = Vec::with_capacity(4 * (2 + byte_len));
+
+        for field in [&self.r1, &self.r2, &self.c, &self.s] {
+            let (sign, value) = field.to_bytes_be();
+            // Zero comes back as `[0]` but needs no bytes, it fits even a zero length field
+            let value = if sign == Sign::NoSign {
+                &[][..]
+            } else {
+                &value[..]
+            };
+            if sign == Sign::Minus || value.len() > byte_len {
+                return Err(CpError::OutOfRange);
+            }
+
+            bytes.extend_from_slice(&prefix.to_be_bytes());
+            bytes.resize(bytes.len() + byte_len - value.len(), 0);
+            bytes.extend_from_slice(value);
+        }
+
+        Ok(bytes)
+    }
+
+    pub fn from_bytes(bytes: &[u8], byte_len: usize) -> CpResult<Self> {
+        if bytes.len() != 4 * (2 + byte_len) {
+            return Err(CpError::Deserialize(format!(
+                "expected {} bytes, got {}",
+                4 * (2 + byte_len),
+                bytes.len()
+            )));
+        }
+
+        let fields = bytes
+            .chunks_exact(2 + byte_len)
+            .map(|chunk| {
+                let prefix = u16::from_be_bytes([chunk[0], chunk[1]]) as usize;
+                if prefix != byte_len {
+                    return Err(CpError::Deserialize(format!(
+                        "expected field length {}, got {}",
+                        byte_len, prefix
+                    )));
+                }
+                Ok(BigInt::from_bytes_be(Sign::Plus, &chunk[2..]))
+            })
+            .collect::<CpResult<Vec<BigInt>>>()?;
+
+        // `chunks_exact` yields exactly four fields since the length was checked above
+        let [r1, r2, c, s]: [BigInt; 4] = fields
+            .try_into()
+            .map_err(|_| CpError::Deserialize("expected 4 fields".to_string()))?;
+
+        Ok(Self { r1, r2, c, s })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use curve25519_dalek::{RistrettoPoint, Scalar};
+
+    use crate::chaum_pedersen::{ChaumPedersen, G, H, P};
+    use crate::ecc_chaum_pedersen::EccChaumPedersen;
+
+    use super::*;
+
+    fn assert_round_trip(proof: Proof, byte_len: usize) {
+        let bytes = proof.to_bytes(byte_len).unwrap();
+        assert_eq!(bytes.len(), 4 * (2 + byte_len));
+
+        let decoded = Proof::from_bytes(&bytes, byte_len).unwrap();
+        assert_eq!(decoded, proof);
+    }
+
+    #[test]
+    fn equality() {
+        let proof = Proof::new(
+            BigInt::from(2),
+            BigInt::from(3),
+            BigInt::from(5),
+            BigInt::from(7),
+        );
+        assert_eq!(proof, proof.clone());
+        assert_ne!(
+            proof,
+            Proof {
+                s: BigInt::from(8),
+                ..proof.clone()
+            }
+        );
+
+        let bytes = bincode::serialize(&proof).unwrap();
+        assert_eq!(bincode::deserialize::<Proof>(&bytes).unwrap(), proof);
+
+        // Ristretto proofs go through serde the same way
+        let ecc = EccChaumPedersen::new();
+        let (_, r1, r2) = ecc.prover_commit_with(&mut rand::thread_rng()).unwrap();
+        let c = ecc.derive_challenge(&r1, &r2);
+        let proof = Proof::new(r1, r2, c, EccChaumPedersen::hash(b"nyancat"));
+        let json = serde_json::to_string(&proof).unwrap();
+        let decoded: Proof<RistrettoPoint, Scalar> = serde_json::from_str(&json).unwrap();
+        assert_eq!(decoded, proof);
+        assert_ne!(decoded, Proof { r1: r2, ..proof });
+
+        let proof = NonInteractiveProof::new(c, c);
+        let bytes = bincode::serialize(&proof).unwrap();
+        assert_eq!(
+            bincode::deserialize::<NonInteractiveProof<Scalar>>(&bytes).unwrap(),
+            proof
+        );
+    }
+
+    #[test]
+    fn round_trip() {
+        let cp = ChaumPedersen::new(P.clone(), G.clone(), H.clone());
+        let byte_len = cp.byte_len();
+        let max = &*P - BigInt::from(1);
+        let zero = BigInt::from(0);
+
+        assert_round_trip(
+            Proof::new(zero.clone(), zero.clone(), zero.clone(), zero.clone()),
+            byte_len,
+        );
+        assert_round_trip(
+            Proof::new(zero.clone(), zero.clone(), zero.clone(), zero),
+            0,
+        );
+        assert_round_trip(
+            Proof::new(max.clone(), max.clone(), max.clone(), max),
+            byte_len,
+        );
+        assert_round_trip(
+            Proof::new(
+                G.clone(),
+                H.clone(),
+                cp.verifier_generate_challenge(),
+                cp.verifier_generate_challenge(),
+            ),
+            byte_len,
+        );
+    }
+
+    #[test]
+    fn rejected() {
+        let byte_len = 256;
+        let one = BigInt::from(1);
+
+        let too_large = Proof::new(P.clone() << 8, one.clone(), one.clone(), one.clone());
+        assert_eq!(
+            too_large.to_bytes(byte_len).unwrap_err(),
+            CpError::OutOfRange
+        );
+
+        let negative = Proof::new(one.clone(), one.clone(), one.clone(), BigInt::from(-1));
+        assert_eq!(
+            negative.to_bytes(byte_len).unwrap_err(),
+            CpError::OutOfRange
+        );
+
+        let bytes = Proof::new(one.clone(), one.clone(), one.clone(), one)
+            .to_bytes(byte_len)
+            .unwrap();
+        assert!(matches!(
+            Proof::from_bytes(&bytes[1..], byte_len),
+            Err(CpError::Deserialize(_))
+        ));
+
+        let mut bad_prefix = bytes.clone();
+        bad_prefix[0] = 0;
+        assert!(matches!(
+            Proof::from_bytes(&bad_prefix, byte_len),
+            Err(CpError::Deserialize(_))
+        ));
+    }
+}
@@ -0,0 +1,159 @@
+use std::mem;
+
+use num_bigint::BigInt;
+use rand_core::{CryptoRng, RngCore};
+
+use crate::chaum_pedersen::ChaumPedersen;
+use crate::{ChaumPedersenTrait, CpError, CpResult};
+
+/// State of the random nonce `k`
+enum Nonce {
+    Empty,
+    Committed(BigInt),
+    Consumed,
+}
+
+/// Stateful prover for the interactive protocol.
+///
+/// The random `k` produced by `commit` is kept internally and handed to `respond`,
+/// so callers never have to hold onto it between the commit and the challenge.
+///
+/// `k` is consumed by the first `respond`, answering two different challenges with the same `k`
+/// leaks the secret since `x = (s1 - s2) / (c2 - c1)`.
+pub struct Prover<'a> {
+    cp: &'a ChaumPedersen,
+    nonce: Nonce,
+}
+
+impl<'a> Prover<'a> {
+    pub fn new(cp: &'a ChaumPedersen) -> Self {
+        Self {
+            cp,
+            nonce: Nonce::Empty,
+        }
+    }
+
+    /// Generates a fresh `k` and returns the commitment `(r1, r2)`, `k` is drawn from the
+    /// `CommitmentRng` of the protocol if it has one, see `ChaumPedersen::with_rng`
+    #[cfg(feature = "std-async")]
+    pub async fn commit(&mut self) -> CpResult<(BigInt, BigInt)> {
+        let (k, r1, r2) = self.cp.prover_commit().await?;
+
+        self.nonce = Nonce::Committed(k);
+        Ok((r1, r2))
+    }
+
+    /// Same as `commit` but computed on the calling thread, no runtime required
+    pub fn commit_sync(&mut self) -> CpResult<(BigInt, BigInt)> {
+        let (k, r1, r2) = self.cp.prover_commit_sync()?;
+
+        self.nonce = Nonce::Committed(k);
+        Ok((r1, r2))
+    }
+
+    /// Same as `commit_sync` but `k` is drawn from `rng`
+    pub fn commit_with<R: RngCore + CryptoRng>(
+        &mut self,
+        rng: &mut R,
+    ) -> CpResult<(BigInt, BigInt)> {
+        let (k, r1, r2) = self.cp.prover_commit_with(rng)?;
+
+        self.nonce = Nonce::Committed(k);
+        Ok((r1, r2))
+    }
+
+    /// Solves the verifier's `challenge`, a new `commit` is required before responding again
+    pub fn respond(&mut self, challenge: BigInt, secret_x: BigInt) -> CpResult<BigInt> {
+        match mem::replace(&mut self.nonce, Nonce::Consumed) {
+            Nonce::Committed(k) => Ok(self.cp.prover_solve_challenge(k, challenge, secret_x)),
+            Nonce::Consumed => Err(CpError::NonceReused),
+            Nonce::Empty => {
+                self.nonce = Nonce::Empty;
+                Err(CpError::MissingCommitment)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::core_math;
+    use crate::proof::Proof;
+    use crate::InteractiveVerifier;
+
+    use super::*;
+
+    /// Recovers `x` from two solutions `s = k - c * x mod q` computed with the same `k`,
+    /// `x = (s1 - s2) * (c2 - c1)^-1 mod q`
+    fn recover_secret(s1: &BigInt, c1: &BigInt, s2: &BigInt, c2: &BigInt, q: &BigInt) -> BigInt {
+        let inverse = core_math::mod_inverse(&(c2 - c1), q).expect("c2 - c1 is invertible mod q");
+        (((s1 - s2) * inverse) % q + q) % q
+    }
+
+    #[cfg(feature = "std-async")]
+    #[tokio::test]
+    async fn commit_and_respond() {
+        let cp = ChaumPedersen::new_test_group();
+        let secret_x = cp.hash_to_scalar(b"nyancat");
+        let (y1, y2) = cp.generate_public_keys(secret_x.clone()).await.unwrap();
+
+        let mut prover = Prover::new(&cp);
+        let (r1, r2) = prover.commit().await.unwrap();
+
+        let challenge = cp.verifier_generate_challenge();
+        let s = prover.respond(challenge.clone(), secret_x).unwrap();
+
+        assert_eq!(
+            cp.verify_interactive(Proof::new(r1, r2, challenge, s), y1, y2)
+                .await,
+            Ok(true)
+        );
+    }
+
+    #[cfg(feature = "std-async")]
+    #[tokio::test]
+    async fn respond_without_commit() {
+        let cp = ChaumPedersen::new_test_group();
+        let secret_x = cp.hash_to_scalar(b"nyancat");
+
+        let mut prover = Prover::new(&cp);
+        assert_eq!(
+            prover.respond(cp.verifier_generate_challenge(), secret_x.clone()),
+            Err(CpError::MissingCommitment)
+        );
+
+        prover.commit().await.unwrap();
+        prover
+            .respond(cp.verifier_generate_challenge(), secret_x.clone())
+            .unwrap();
+
+        // A second challenge requires a new commitment
+        assert_eq!(
+            prover.respond(cp.verifier_generate_challenge(), secret_x),
+            Err(CpError::NonceReused)
+        );
+    }
+
+    #[cfg(feature = "std-async")]
+    #[tokio::test]
+    async fn nonce_reuse_leaks_secret() {
+        let cp = ChaumPedersen::new_test_group();
+        let secret_x = cp.hash_to_scalar(b"nyancat");
+
+        // `c2 - c1 = 1` is always invertible
+        let c1 = cp.verifier_generate_challenge();
+        let c2 = &c1 + BigInt::from(1);
+
+        // Without the guard the same `k` can answer two challenges
+        let (k, _, _) = cp.prover_commit().await.unwrap();
+        let s1 = cp.prover_solve_challenge(k.clone(), c1.clone(), secret_x.clone());
+        let s2 = cp.prover_solve_challenge(k, c2.clone(), secret_x.clone());
+        assert_eq!(recover_secret(&s1, &c1, &s2, &c2, cp.order()), secret_x);
+
+        // The prover refuses to answer the second challenge
+        let mut prover = Prover::new(&cp);
+        prover.commit().await.unwrap();
+        prover.respond(c1, secret_x.clone()).unwrap();
+        assert_eq!(prover.respond(c2, secret_x), Err(CpError::NonceReused));
+    }
+}
@@ -0,0 +1,181 @@
+use async_trait::async_trait;
+use curve25519_dalek::ristretto::CompressedRistretto;
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::RistrettoPoint;
+use num_bigint::{BigInt, Sign};
+
+use crate::chaum_pedersen::ChaumPedersen;
+use crate::ecc_chaum_pedersen::EccChaumPedersen;
+use crate::proof::Proof;
+use crate::{ChaumPedersenTrait, CpError, CpResult, InteractiveVerifier};
+
+/// Object-safe view of a Chaum-Pedersen scheme, points and scalars are passed around in their
+/// byte encoding so schemes with different group types can sit behind `Box<dyn ChaumPedersenScheme>`.
+///
+/// `BigInt`s are unsigned big-endian, ECC scalars are canonical 32-byte little-endian and points
+/// are compressed ristretto points.
+#[async_trait]
+pub trait ChaumPedersenScheme: Send + Sync {
+    /// Hashes a password into the secret `x`
+    fn secret_from_password(&self, password: &[u8]) -> Vec<u8>;
+
+    async fn public_keys(&self, secret_x: &[u8]) -> CpResult<(Vec<u8>, Vec<u8>)>;
+
+    /// Returns the random `k` and the commitment `(r1, r2)`
+    async fn commit(&self) -> CpResult<(Vec<u8>, Vec<u8>, Vec<u8>)>;
+
+    /// Challenge for the commitment, drawn at random by an interactive verifier and derived
+    /// from the transcript by a non-interactive one
+    fn issue_challenge(&self, r1: &[u8], r2: &[u8]) -> CpResult<Vec<u8>>;
+
+    fn solve(&self, k: &[u8], c: &[u8], secret_x: &[u8]) -> CpResult<Vec<u8>>;
+
+    async fn verify(&self, proof: Proof<Vec<u8>, Vec<u8>>, y1: &[u8], y2: &[u8]) -> CpResult<bool>;
+}
+
+#[async_trait]
+impl ChaumPedersenScheme for ChaumPedersen {
+    fn secret_from_password(&self, password: &[u8]) -> Vec<u8> {
+        encode_bigint(&self.hash_to_scalar(password))
+    }
+
+    async fn public_keys(&self, secret_x: &[u8]) -> CpResult<(Vec<u8>, Vec<u8>)> {
+        let (y1, y2) =
+            ChaumPedersenTrait::generate_public_keys(self, decode_bigint(secret_x)).await?;
+        Ok((encode_bigint(&y1), encode_bigint(&y2)))
+    }
+
+    async fn commit(&self) -> CpResult<(Vec<u8>, Vec<u8>, Vec<u8>)> {
+        let (k, r1, r2) = self.prover_commit().await?;
+        Ok((encode_bigint(&k), encode_bigint(&r1), encode_bigint(&r2)))
+    }
+
+    fn issue_challenge(&self, _r1: &[u8], _r2: &[u8]) -> CpResult<Vec<u8>> {
+        Ok(encode_bigint(&self.verifier_generate_challenge()))
+    }
+
+    fn solve(&self, k: &[u8], c: &[u8], secret_x: &[u8]) -> CpResult<Vec<u8>> {
+        let s = self.prover_solve_challenge(
+            decode_bigint(k),
+            decode_bigint(c),
+            decode_bigint(secret_x),
+        );
+        Ok(encode_bigint(&s))
+    }
+
+    async fn verify(&self, proof: Proof<Vec<u8>, Vec<u8>>, y1: &[u8], y2: &[u8]) -> CpResult<bool> {
+        let proof = Proof::new(
+            decode_bigint(&proof.r1),
+            decode_bigint(&proof.r2),
+            decode_bigint(&proof.c),
+            decode_bigint(&proof.s),
+        );
+        self.verify_interactive(proof, decode_bigint(y1), decode_bigint(y2))
+            .await
+    }
+}
+
+#[async_trait]
+impl ChaumPedersenScheme for EccChaumPedersen {
+    fn secret_from_password(&self, password: &[u8]) -> Vec<u8> {
+        EccChaumPedersen::hash(password).to_bytes().to_vec()
+    }
+
+    async fn public_keys(&self, secret_x: &[u8]) -> CpResult<(Vec<u8>, Vec<u8>)> {
+        let (y1, y2) =
+            ChaumPedersenTrait::generate_public_keys(self, decode_scalar(secret_x)?).await?;
+        Ok((encode_point(&y1), encode_point(&y2)))
+    }
+
+    async fn commit(&self) -> CpResult<(Vec<u8>, Vec<u8>, Vec<u8>)> {
+        let (k, r1, r2) = self.prover_commit().await?;
+        Ok((k.to_bytes().to_vec(), encode_point(&r1), encode_point(&r2)))
+    }
+
+    fn issue_challenge(&self, r1: &[u8], r2: &[u8]) -> CpResult<Vec<u8>> {
+        let (r1, r2) = (decode_point(r1)?, decode_point(r2)?);
+        Ok(self.derive_challenge(&r1, &r2).to_bytes().to_vec())
+    }
+
+    fn solve(&self, k: &[u8], c: &[u8], secret_x: &[u8]) -> CpResult<Vec<u8>> {
+        let s = self.prover_solve_challenge(
+            decode_scalar(k)?,
+            decode_scalar(c)?,
+            decode_scalar(secret_x)?,
+        );
+        Ok(s.to_bytes().to_vec())
+    }
+
+    /// Besides the verification equations the challenge has to be the one derived from `(r1, r2)`
+    async fn verify(&self, proof: Proof<Vec<u8>, Vec<u8>>, y1: &[u8], y2: &[u8]) -> CpResult<bool> {
+        let proof = Proof::new(
+            decode_point(&proof.r1)?,
+            decode_point(&proof.r2)?,
+            decode_scalar(&proof.c)?,
+            decode_scalar(&proof.s)?,
+        );
+        if self.derive_challenge(&proof.r1, &proof.r2) != proof.c {
+            return Ok(false);
+        }
+
+        self.verify_interactive(proof, decode_point(y1)?, decode_point(y2)?)
+            .await
+    }
+}
+
+fn encode_bigint(v: &BigInt) -> Vec<u8> {
+    v.to_bytes_be().1
+}
+
+fn decode_bigint(bytes: &[u8]) -> BigInt {
+    BigInt::from_bytes_be(Sign::Plus, bytes)
+}
+
+fn encode_point(point: &RistrettoPoint) -> Vec<u8> {
+    point.compress().to_bytes().to_vec()
+}
+
+fn decode_point(bytes: &[u8]) -> CpResult<RistrettoPoint> {
+    CompressedRistretto::from_slice(bytes)
+        .map_err(|_| CpError::Deserialize(format!("expected 32 bytes, got {}", bytes.len())))?
+        .decompress()
+        .ok_or(CpError::InvalidPublicKey)
+}
+
+fn decode_scalar(bytes: &[u8]) -> CpResult<Scalar> {
+    let bytes: [u8; 32] = bytes
+        .try_into()
+        .map_err(|_| CpError::Deserialize(format!("expected 32 bytes, got {}", bytes.len())))?;
+    Option::from(Scalar::from_canonical_bytes(bytes)).ok_or(CpError::OutOfRange)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn dynamic_dispatch() {
+        let schemes: Vec<Box<dyn ChaumPedersenScheme>> = vec![
+            Box::new(ChaumPedersen::new_test_group()),
+            Box::new(EccChaumPedersen::new()),
+        ];
+
+        for scheme in schemes {
+            let x = scheme.secret_from_password(b"nyancat");
+            let (y1, y2) = scheme.public_keys(&x).await.unwrap();
+
+            let (k, r1, r2) = scheme.commit().await.unwrap();
+            let c = scheme.issue_challenge(&r1, &r2).unwrap();
+            let s = scheme.solve(&k, &c, &x).unwrap();
+            let proof = Proof::new(r1, r2, c, s);
+            assert_eq!(scheme.verify(proof.clone(), &y1, &y2).await, Ok(true));
+
+            let invalid_x = scheme.secret_from_password(b"nyandog");
+            let (invalid_y1, invalid_y2) = scheme.public_keys(&invalid_x).await.unwrap();
+            assert_eq!(
+                scheme.verify(proof, &invalid_y1, &invalid_y2).await,
+                Ok(false)
+            );
+        }
+    }
+}
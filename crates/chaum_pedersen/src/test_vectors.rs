@@ -0,0 +1,171 @@
+//! Golden vectors for both protocols computed with fixed nonces.
+//!
+//! The protocol functions draw `k` (and for the interactive protocol `c`) from the RNG, the
+//! functions here take them explicitly so the arithmetic can be checked against known-good values.
+//! All values are hex encoded, `BigInt`s big-endian and ECC scalars/points in their 32-byte encoding.
+
+use curve25519_dalek::ristretto::CompressedRistretto;
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::RistrettoPoint;
+use num_bigint::BigInt;
+
+use crate::chaum_pedersen::ChaumPedersen;
+use crate::ecc_chaum_pedersen::EccChaumPedersen;
+use crate::proof::Proof;
+use crate::{ChaumPedersenTrait, CpError, CpResult};
+
+/// `(secret_x, k, c, r1, r2, s)` for a single proof
+#[derive(Debug, Clone, Copy)]
+pub struct TestVector {
+    pub secret_x: &'static str,
+    pub k: &'static str,
+    pub c: &'static str,
+    pub r1: &'static str,
+    pub r2: &'static str,
+    pub s: &'static str,
+}
+
+/// Vectors for `ChaumPedersen::new(P, G, H)`
+pub const BIGINT_VECTORS: &[TestVector] = &[
+    // x = hash("cat"), k = hash("nonce"), c = hash("challenge")
+    TestVector {
+        secret_x: "d42c501a50899ce96724e2d05f5cb98782fe93fcc72630252e78aa52a1db2c740e3dcb71b8d8df45bc2292b492b3102bc1494bbc40b8eb45d49195a486b94142",
+        k: "6b4a4047f888432f48326c6d37c0eff2a7c2d8bf608268c82783fbd53551c41bc0e392ec4485f9a352e5105c4ab9005312f83ac10cff105f48616efb69faac0d",
+        c: "43fc90bacc80a092158e240668e350959acb09b1a1c567f23b4578961a541ff062de6138a00791b9fcc9879ccef78119c8a31c9c103b4630d700dcf79565c48d",
+        r1: "62be9a2bac9cb1cc005b205ec9d2c7fa9aac4fa7599ed9f65eec4104e171dda009cd0f033299b5a6753954c323f92be86c1d85879c35675e97d817f8036720feaf0f02b1e5739054324944e55cb85f4b3995ad9e0582e540d68ba45b1a0cf04fc2a1f0ff919114b0fd260b4d07081c5edea7cbee5a7a4ccba934e1d89ddcf9efeef819c1ee7fe2f921e01fb2dcf4fbb3e39de38349014878de105853103798fab935a29f37c225fe3fa06ba058a187775ff2ef0d5ee55b90144df77a9ee382dbe2b72b85d109c9b74b549d6dfcf9119179b9c5790fcb2dfb8e7c36825b288fec906c9a973e9eb33995db4d2296190e4625d553f2c4190796197f5e1e1ed3449b71a763f400a6a1aa1862226d8ccaa53b4dfe88b179536d8f0371ac5a382f7faebe95ed400d396d01efcd68d574232d58622b6a372cb90e44a35bd960f94d9d22a93b6790655718bc2e8dbe1ef5e47719c8e702f283ea6241dd7e74a930a40dc62b0d226e69d5b38e2d8021152f6ada4fda555a8763c07fd02cd8b6bb52c32781",
+        r2: "1763719f127055fee637aa350c5f1e1f4914469ccacee2a0d13911022872a3a61195a491c2878130bc32955246b99dc8b4c58b0ae2e981ea68b13b68d1b835b9019f8d49e3e2ac658e949e7b27751bbccf28ada5a9b83c018c69a650e28d49174da1c8e2e65ded9cb6d1de405a407f33f494d2d17f27999edccb9c7d056a133b09493299a0b4323cd003830c126119a0d09c81af8519ff4d5438253f1826a728a8b5d502d467839446d02da71e5f5b81dea22e8afd261f1567902618e7d7ab267b8d084298849ed73616a0328c2904785147dd4fe955fc4fad544f696c63b27136f9da53fd8a18dbe48499aa8312df0013758add2eb55984b7f66e6eca0712af445c9b0a0fee37fa55c34df975f433fb0a39270edef0d06a4f51cf92fdb967b24c82342703865a4bfb32c21efff4ce5aab613111a125ff43a6a51b76e87f08a357e92b0c37a07e4b3c363aaf4a3af90872caf48a2d4310f3b1d809c38d174179db4e7a74bee0379add0e3a9706f536551e33a021f03d9e5c2d18cb1a8eec2a02",
+        s: "7fffffffffffffffe487ed5110b4611a62633145c06e0e68948127044533e63a0105df531d89cd9128a5043cc71a026ef7ca8cd9e69d218d98158536f92f8a1ba7f09ab6b6a8e122f242dabb312f3f637a262174d31bf6b585ffae5b7a035bf6f71c35fdad44cfd2d74f9208be258ff324943328f6722d9ee1003e5c50b1df82cc6d241b0e2ae9cd348b1fd47e9267afc1b2ae91ee51d6cb0e3179ab1042a95dcf6a9483b84b4b36b3861aa7255e4c0278ba3604650c10be19482f23171b671df1cf3b960c074301cd93c1d17603d147dae2aef837a62964ef15e5fb4aac0b8c1ccaa4be754ab5728ae9130c4c7d02880ab9472d45556216d6998b8682283d199bd1a44c73d4262d33f4c70a28168d1e6ab09fe5e95e25f4897850a3545b2597496cd46674c91b80e93cbd9f8f5d8399e595100e06fb82495f181e0fd23cf1985d64331eaf688607c8560b3bad9a8f14ba20fea44b15064e1dc3f4060fc6ad9347cd3882484866594c1b707144286e2c47b1b0c7395b3052d27a637736f132b2",
+    },
+    // x = 42, k = 7, c = 3
+    TestVector {
+        secret_x: "2a",
+        k: "7",
+        c: "3",
+        r1: "80",
+        r2: "88b",
+        s: "7fffffffffffffffe487ed5110b4611a62633145c06e0e68948127044533e63a0105df531d89cd9128a5043cc71a026ef7ca8cd9e69d218d98158536f92f8a1ba7f09ab6b6a8e122f242dabb312f3f637a262174d31bf6b585ffae5b7a035bf6f71c35fdad44cfd2d74f9208be258ff324943328f6722d9ee1003e5c50b1df82cc6d241b0e2ae9cd348b1fd47e9267afc1b2ae91ee51d6cb0e3179ab1042a95dcf6a9483b84b4b36b3861aa7255e4c0278ba3604650c10be19482f23171b671df1cf3b960c074301cd93c1d17603d147dae2aef837a62964ef15e5fb4aac0b8c1ccaa4be754ab5728ae9130c4c7d02880ab9472d45556216d6998b8682283d19d42a90d5ef8e5d32767dc2822c6df785457538abae83063ed9cb87c2d370f263d5fad7466d8499eb8f464a702512b0cee771e9130d697735f897fd036cc504326c3b01399f643532290f958c0bbd90065df08babbd30aeb63b84c4605d6ca371047127d03a72d598a1edadfe707e884725c16890549d69657fffffffffffff88",
+    },
+];
+
+/// Vectors for `EccChaumPedersen::new()`, `c` is the Fiat-Shamir challenge under `DEFAULT_CONTEXT`
+/// hashed in the transcript domain
+pub const ECC_VECTORS: &[TestVector] = &[
+    // x = hash("cat"), k = hash("nonce")
+    TestVector {
+        secret_x: "59ddba73b06d0c9e579ef6e2376455d7ffd8fac9efbc020bb32e42b9b4cbfa01",
+        k: "6947b38b423307735396d0b081d23e605583fbc92b5d00191fac3b4d025cd402",
+        c: "21fe348db024f9a9e35279a0939087295cee2c965eee1b670e0304ab859d0907",
+        r1: "56a20e12be19c562ce8a5cd8ee8f1675532922bf75b2ac21d4e3393a2da5905f",
+        r2: "c6ce4d14078b11746db8b1d8e294237012526833e2e5d426718cfd473b44141d",
+        s: "9fbd177c3446dea6fa711a2589d9ec672c5b7180a61ff03839edb33a10625a07",
+    },
+    // x = 42, k = 7
+    TestVector {
+        secret_x: "2a00000000000000000000000000000000000000000000000000000000000000",
+        k: "0700000000000000000000000000000000000000000000000000000000000000",
+        c: "b1cdbd543fba1542c0234c43238bd25098a406a7c5ab700c9c3da3394b75e908",
+        r1: "44f53520926ec81fbd5a387845beb7df85a96a24ece18738bdcfa6a7822a176d",
+        r2: "b4bf60bbcd5adc9cf467544117cf12f35869bf51de84ac56d19560b4230cac30",
+        s: "351fe8cf14bc286a8dd6b83c16995cb204ffe89892d183f565e4378ba8c1b209",
+    },
+];
+
+/// Interactive proof of `secret_x` with an explicit `k` and `c` instead of random ones
+pub fn prove_with(cp: &ChaumPedersen, secret_x: &BigInt, k: &BigInt, c: &BigInt) -> Proof {
+    let (r1, r2) = cp.commit_with(k);
+    let s = cp.prover_solve_challenge(k.clone(), c.clone(), secret_x.clone());
+    Proof::new(r1, r2, c.clone(), s)
+}
+
+/// Non-interactive proof of `secret_x` with an explicit `k`, returns `(r1, r2, c, s)`.
+/// The challenge is always derived from the transcript so it can't be supplied.
+pub fn ecc_prove_with(
+    ecc: &EccChaumPedersen,
+    secret_x: Scalar,
+    k: Scalar,
+) -> (RistrettoPoint, RistrettoPoint, Scalar, Scalar) {
+    let (r1, r2, c) = ecc.commit_with(k);
+    let s = ecc.prover_solve_challenge(k, c, secret_x);
+    (r1, r2, c, s)
+}
+
+/// Decodes a canonical 32-byte little-endian scalar
+pub fn scalar_from_hex(input: &str) -> CpResult<Scalar> {
+    let bytes: [u8; 32] = hex::decode(input)
+        .map_err(|e| CpError::Deserialize(e.to_string()))?
+        .try_into()
+        .map_err(|_| CpError::Deserialize("expected 32 bytes".to_string()))?;
+
+    Option::from(Scalar::from_canonical_bytes(bytes)).ok_or(CpError::OutOfRange)
+}
+
+/// Decodes a compressed ristretto point
+pub fn point_from_hex(input: &str) -> CpResult<RistrettoPoint> {
+    let bytes = hex::decode(input).map_err(|e| CpError::Deserialize(e.to_string()))?;
+    CompressedRistretto::from_slice(&bytes)
+        .map_err(|e| CpError::Deserialize(e.to_string()))?
+        .decompress()
+        .ok_or(CpError::InvalidPublicKey)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::chaum_pedersen::{G, H, P};
+    use crate::proof::NonInteractiveProof;
+    use crate::utils::bigint_from_hex;
+    use crate::{InteractiveVerifier, NonInteractiveVerifier};
+
+    use super::*;
+
+    #[test]
+    fn bigint_vectors() {
+        let cp = ChaumPedersen::new(P.clone(), G.clone(), H.clone());
+
+        for vector in BIGINT_VECTORS {
+            let hex = |v: &str| bigint_from_hex(v).unwrap();
+            let x = hex(vector.secret_x);
+            let proof = prove_with(&cp, &x, &hex(vector.k), &hex(vector.c));
+
+            assert_eq!(proof.r1, hex(vector.r1));
+            assert_eq!(proof.r2, hex(vector.r2));
+            assert_eq!(proof.c, hex(vector.c));
+            assert_eq!(proof.s, hex(vector.s));
+
+            let (y1, y2) = cp.generate_public_keys_sync(x).unwrap();
+            assert!(cp.verify_interactive_sync(proof, y1, y2).unwrap());
+        }
+    }
+
+    #[test]
+    fn ecc_vectors() {
+        let ecc = EccChaumPedersen::new();
+
+        for vector in ECC_VECTORS {
+            let x = scalar_from_hex(vector.secret_x).unwrap();
+            let k = scalar_from_hex(vector.k).unwrap();
+            let (r1, r2, c, s) = ecc_prove_with(&ecc, x, k);
+
+            assert_eq!(r1, point_from_hex(vector.r1).unwrap());
+            assert_eq!(r2, point_from_hex(vector.r2).unwrap());
+            assert_eq!(c, scalar_from_hex(vector.c).unwrap());
+            assert_eq!(s, scalar_from_hex(vector.s).unwrap());
+
+            let (y1, y2) = ecc.generate_public_keys_sync(x).unwrap();
+            assert!(ecc
+                .verify_noninteractive_sync(NonInteractiveProof::new(c, s), y1, y2)
+                .unwrap());
+        }
+    }
+
+    #[test]
+    fn hashed_secrets() {
+        assert_eq!(
+            ChaumPedersen::hash(b"cat"),
+            bigint_from_hex(BIGINT_VECTORS[0].secret_x).unwrap()
+        );
+        assert_eq!(
+            EccChaumPedersen::hash(b"cat"),
+            scalar_from_hex(ECC_VECTORS[0].secret_x).unwrap()
+        );
+    }
+}
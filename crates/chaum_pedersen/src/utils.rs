@@ -1,10 +1,20 @@
-use num_bigint::{BigInt, RandBigInt};
+use curve25519_dalek::Scalar;
+use num_bigint::{BigInt, RandBigInt, Sign};
+use rand_core::RngCore;
+use serde::de::DeserializeOwned;
 
-use crate::chaum_pedersen::{ChaumPedersen, G, H, P};
+use crate::chaum_pedersen::{ChaumPedersen, Group};
 use crate::ecc_chaum_pedersen::EccChaumPedersen;
+#[cfg(feature = "std-async")]
+use crate::scheme::ChaumPedersenScheme;
+use crate::{CpError, CpResult};
 
 pub fn generate_random_bigint(bound: &BigInt) -> BigInt {
-    let mut rng = rand::thread_rng();
+    generate_random_bigint_with(&mut rand::thread_rng(), bound)
+}
+
+/// Same as `generate_random_bigint` but draws from `rng`, a seeded rng gives reproducible values
+pub fn generate_random_bigint_with<R: RngCore + ?Sized>(rng: &mut R, bound: &BigInt) -> BigInt {
     rng.gen_bigint_range(&BigInt::from(1), &(bound - BigInt::from(1)))
 }
 
@@ -13,10 +23,254 @@ pub enum ChaumPedersenFactoryType {
     NonInteractive(EccChaumPedersen),
 }
 
-pub fn chaum_pedersen_factory(is_interactive: bool) -> ChaumPedersenFactoryType {
+/// `group` selects the MODP group of the interactive protocol, the elliptic curve variant ignores it
+pub fn chaum_pedersen_factory(is_interactive: bool, group: Group) -> ChaumPedersenFactoryType {
     if is_interactive {
-        ChaumPedersenFactoryType::Interactive(ChaumPedersen::new(P.clone(), G.clone(), H.clone()))
+        ChaumPedersenFactoryType::Interactive(group.protocol())
     } else {
         ChaumPedersenFactoryType::NonInteractive(EccChaumPedersen::new())
     }
 }
+
+/// Same as `chaum_pedersen_factory` but the scheme is type-erased, so callers don't have to match
+#[cfg(feature = "std-async")]
+pub fn chaum_pedersen_scheme(is_interactive: bool, group: Group) -> Box<dyn ChaumPedersenScheme> {
+    match chaum_pedersen_factory(is_interactive, group) {
+        ChaumPedersenFactoryType::Interactive(cp) => Box::new(cp),
+        ChaumPedersenFactoryType::NonInteractive(ecc) => Box::new(ecc),
+    }
+}
+
+/// Deserializes a JSON encoded value such as a Ristretto `Scalar` or `RistrettoPoint`
+pub fn deserialize<T: DeserializeOwned>(input: &str) -> CpResult<T> {
+    serde_json::from_str(input).map_err(|e| CpError::Deserialize(e.to_string()))
+}
+
+/// Parses a base-16 encoded `BigInt`
+pub fn bigint_from_hex(input: &str) -> CpResult<BigInt> {
+    BigInt::parse_bytes(input.as_bytes(), 16)
+        .ok_or_else(|| CpError::Deserialize(format!("invalid hex string {:?}", input)))
+}
+
+/// Same as `bigint_from_hex` but input longer than the hex of `max_bytes` bytes is rejected
+/// before it is parsed
+pub fn bigint_from_hex_bounded(input: &str, max_bytes: usize) -> CpResult<BigInt> {
+    if input.len() > 2 * max_bytes {
+        return Err(CpError::OutOfRange);
+    }
+    bigint_from_hex(input)
+}
+
+/// Big-endian hex of `value` left padded to `byte_len` bytes, `byte_len` being the byte length of
+/// `p` every field of a group encodes to the same width. Values wider than `byte_len` are encoded
+/// in full.
+pub fn encode_field(value: &BigInt, byte_len: usize) -> String {
+    let (_, bytes) = value.to_bytes_be();
+    let padding = byte_len.saturating_sub(bytes.len());
+    let mut padded = vec![0u8; padding];
+    padded.extend_from_slice(&bytes);
+    hex::encode(padded)
+}
+
+/// Inverse of `encode_field`. Input longer than `byte_len` bytes is rejected, shorter input is the
+/// unpadded encoding of older peers and parses to the same value.
+pub fn decode_field(input: &str, byte_len: usize) -> CpResult<BigInt> {
+    bigint_from_hex_bounded(input, byte_len)
+}
+
+/// Base58 of `bytes` in the Bitcoin alphabet, no `0`, `O`, `I` or `l` and nothing a URL has to
+/// escape. Leading zero bytes are kept as leading `1`s.
+pub fn encode_base58(bytes: &[u8]) -> String {
+    bs58::encode(bytes).into_string()
+}
+
+/// Inverse of `encode_base58`, a character outside the alphabet is a `Deserialize` error
+pub fn decode_base58(input: &str) -> CpResult<Vec<u8>> {
+    bs58::decode(input.trim())
+        .into_vec()
+        .map_err(|e| CpError::Deserialize(format!("invalid base58 string: {}", e)))
+}
+
+/// Text encoding of a `BigInt` field, hex is what the protocol messages use. Base58 is shorter for
+/// copy-pasting credentials, e.g. into a QR code or a URL.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FieldEncoding {
+    #[default]
+    Hex,
+    Base58,
+}
+
+impl FieldEncoding {
+    /// `value` in this encoding, hex is left padded like `encode_field`. Base58 drops the padding,
+    /// it would only add a `1` per zero byte.
+    pub fn encode(self, value: &BigInt, byte_len: usize) -> String {
+        match self {
+            FieldEncoding::Hex => encode_field(value, byte_len),
+            FieldEncoding::Base58 => encode_base58(&value.to_bytes_be().1),
+        }
+    }
+
+    /// Inverse of `encode`, values longer than `byte_len` bytes are rejected
+    pub fn decode(self, input: &str, byte_len: usize) -> CpResult<BigInt> {
+        match self {
+            FieldEncoding::Hex => decode_field(input, byte_len),
+            FieldEncoding::Base58 => {
+                let bytes = decode_base58(input)?;
+                let value = BigInt::from_bytes_be(Sign::Plus, &bytes);
+                if value.bits() > 8 * byte_len as u64 {
+                    return Err(CpError::OutOfRange);
+                }
+                Ok(value)
+            }
+        }
+    }
+}
+
+/// Value of `scalar` as a `BigInt`, the inverse of `bigint_to_scalar_mod_order` below the order
+pub fn scalar_to_bigint(scalar: &Scalar) -> BigInt {
+    BigInt::from_bytes_le(Sign::Plus, scalar.as_bytes())
+}
+
+/// Reduces `value` modulo the order of the Ristretto group, `value` has to be non-negative and fit
+/// in 32 bytes
+pub fn bigint_to_scalar_mod_order(value: &BigInt) -> CpResult<Scalar> {
+    let (sign, le) = value.to_bytes_le();
+    if sign == Sign::Minus || le.len() > 32 {
+        return Err(CpError::OutOfRange);
+    }
+
+    let mut bytes = [0u8; 32];
+    bytes[..le.len()].copy_from_slice(&le);
+    Ok(Scalar::from_bytes_mod_order(bytes))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Order `l = 2^252 + 27742317777372353535851937790883648493` of the Ristretto group
+    fn order() -> BigInt {
+        scalar_to_bigint(&-Scalar::ONE) + 1
+    }
+
+    #[test]
+    fn hex_bounded() {
+        assert_eq!(bigint_from_hex_bounded("ffff", 2), Ok(BigInt::from(0xffff)));
+        assert_eq!(
+            bigint_from_hex_bounded("1ffff", 2),
+            Err(CpError::OutOfRange)
+        );
+        for malformed in ["", "cat", "0x1f"] {
+            assert!(matches!(
+                bigint_from_hex_bounded(malformed, 2),
+                Err(CpError::Deserialize(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn fixed_width_field() {
+        let byte_len = Group::Modp2048.protocol().byte_len();
+        let value = BigInt::parse_bytes(b"7fff0001", 16).unwrap();
+        let encoded = encode_field(&value, byte_len);
+        assert_eq!(encoded.len(), 2 * byte_len);
+        assert!(encoded.starts_with("0000") && encoded.ends_with("7fff0001"));
+        assert_eq!(decode_field(&encoded, byte_len), Ok(value.clone()));
+
+        // The unpadded hex of older peers
+        assert_eq!(decode_field("7fff0001", byte_len), Ok(value));
+        assert_eq!(encode_field(&BigInt::from(0), 2), "0000");
+        assert_eq!(
+            decode_field(&format!("00{}", encoded), byte_len),
+            Err(CpError::OutOfRange)
+        );
+    }
+
+    #[test]
+    fn base58_round_trip() {
+        assert_eq!(encode_base58(b"hello world"), "StV1DL6CwTryKyV");
+        assert_eq!(
+            decode_base58("StV1DL6CwTryKyV"),
+            Ok(b"hello world".to_vec())
+        );
+        // Leading zero bytes survive
+        assert_eq!(encode_base58(&[0, 0, 1]), "112");
+        assert_eq!(decode_base58("112"), Ok(vec![0, 0, 1]));
+        assert_eq!(decode_base58(""), Ok(Vec::new()));
+
+        let cp = Group::Modp2048.protocol();
+        let byte_len = cp.byte_len();
+        for value in [BigInt::from(0), BigInt::from(58), (*cp.p).clone() - 1] {
+            for encoding in [FieldEncoding::Hex, FieldEncoding::Base58] {
+                let encoded = encoding.encode(&value, byte_len);
+                assert_eq!(encoding.decode(&encoded, byte_len), Ok(value.clone()));
+            }
+        }
+        let p = FieldEncoding::Base58.encode(&cp.p, byte_len);
+        assert!(p.len() < FieldEncoding::Hex.encode(&cp.p, byte_len).len());
+        assert_eq!(
+            FieldEncoding::Base58.decode(&p, 2),
+            Err(CpError::OutOfRange)
+        );
+    }
+
+    #[test]
+    fn invalid_base58() {
+        // `0`, `O`, `I` and `l` aren't in the alphabet
+        for invalid in ["StV1DL6C0TryKyV", "O", "Il", "Nyan cat", "+/"] {
+            assert!(
+                matches!(decode_base58(invalid), Err(CpError::Deserialize(_))),
+                "{}",
+                invalid
+            );
+            assert!(matches!(
+                FieldEncoding::Base58.decode(invalid, 32),
+                Err(CpError::Deserialize(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn scalar_round_trip() {
+        let l = order();
+        assert_eq!(
+            l,
+            (BigInt::from(1) << 252)
+                + BigInt::parse_bytes(b"27742317777372353535851937790883648493", 10).unwrap()
+        );
+
+        for value in [
+            BigInt::from(0),
+            BigInt::from(7),
+            &l - 1,
+            generate_random_bigint(&l),
+        ] {
+            let scalar = bigint_to_scalar_mod_order(&value).unwrap();
+            assert_eq!(scalar_to_bigint(&scalar), value);
+        }
+
+        let scalar = EccChaumPedersen::hash(b"nyancat");
+        assert_eq!(
+            bigint_to_scalar_mod_order(&scalar_to_bigint(&scalar)),
+            Ok(scalar)
+        );
+    }
+
+    #[test]
+    fn scalar_reduction() {
+        let l = order();
+        for value in [l.clone(), &l + 5, &l * 3 + 11, (BigInt::from(1) << 256) - 1] {
+            let scalar = bigint_to_scalar_mod_order(&value).unwrap();
+            assert_eq!(scalar_to_bigint(&scalar), &value % &l);
+        }
+
+        assert_eq!(
+            bigint_to_scalar_mod_order(&(BigInt::from(1) << 256)),
+            Err(CpError::OutOfRange)
+        );
+        assert_eq!(
+            bigint_to_scalar_mod_order(&BigInt::from(-1)),
+            Err(CpError::OutOfRange)
+        );
+    }
+}
@@ -0,0 +1,157 @@
+use std::collections::HashMap;
+
+use num_bigint::BigInt;
+use rand::RngCore;
+
+use crate::chaum_pedersen::ChaumPedersen;
+use crate::proof::Proof;
+use crate::{CpError, CpResult, InteractiveVerifier};
+
+/// Bytes of the random `auth_id` an exchange is issued under
+pub const AUTH_ID_LEN: usize = 16;
+
+/// Commitment `(r1, r2)` the prover sent and the challenge `c` it was answered with
+struct Exchange {
+    r1: BigInt,
+    r2: BigInt,
+    c: BigInt,
+}
+
+/// Stateful verifier for the interactive protocol, the counterpart of `Prover`.
+///
+/// `issue_challenge` keeps the commitment of the prover next to the challenge under a fresh
+/// `auth_id`, so callers never have to hold onto either until the response arrives. Several
+/// exchanges can be outstanding at once, each one is consumed by its first `verify`.
+pub struct Verifier<'a> {
+    cp: &'a ChaumPedersen,
+    y1: BigInt,
+    y2: BigInt,
+    exchanges: HashMap<String, Exchange>,
+}
+
+impl<'a> Verifier<'a> {
+    /// Verifier of proofs for the public keys `(y1, y2)`
+    pub fn new(cp: &'a ChaumPedersen, y1: BigInt, y2: BigInt) -> Self {
+        Self {
+            cp,
+            y1,
+            y2,
+            exchanges: HashMap::new(),
+        }
+    }
+
+    /// Draws a challenge for the commitment `(r1, r2)`, returns the hex `auth_id` the response is
+    /// verified under and the challenge to send to the prover
+    pub fn issue_challenge(&mut self, r1: BigInt, r2: BigInt) -> (String, BigInt) {
+        let mut id = [0u8; AUTH_ID_LEN];
+        rand::thread_rng().fill_bytes(&mut id);
+        let auth_id = hex::encode(id);
+
+        let c = self.cp.verifier_generate_challenge();
+        self.resume(auth_id.clone(), r1, r2, c.clone());
+        (auth_id, c)
+    }
+
+    /// Picks up an exchange whose commitment and challenge were kept elsewhere, e.g. stored between
+    /// the two requests of a login. Replaces an outstanding exchange under the same `auth_id`.
+    pub fn resume(&mut self, auth_id: String, r1: BigInt, r2: BigInt, c: BigInt) {
+        self.exchanges.insert(auth_id, Exchange { r1, r2, c });
+    }
+
+    /// Whether a challenge issued under `auth_id` is waiting for its response
+    pub fn is_outstanding(&self, auth_id: &str) -> bool {
+        self.exchanges.contains_key(auth_id)
+    }
+
+    /// Checks the solution `s` to the challenge issued under `auth_id`, the exchange is consumed
+    /// whether it verifies or not
+    #[cfg(feature = "std-async")]
+    pub async fn verify(&mut self, auth_id: &str, s: BigInt) -> CpResult<bool> {
+        let proof = self.take(auth_id, s)?;
+        self.cp
+            .verify_interactive(proof, self.y1.clone(), self.y2.clone())
+            .await
+    }
+
+    /// Same as `verify` but computed on the calling thread, no runtime required
+    pub fn verify_sync(&mut self, auth_id: &str, s: BigInt) -> CpResult<bool> {
+        let proof = self.take(auth_id, s)?;
+        self.cp
+            .verify_interactive_sync(proof, self.y1.clone(), self.y2.clone())
+    }
+
+    fn take(&mut self, auth_id: &str, s: BigInt) -> CpResult<Proof> {
+        let Exchange { r1, r2, c } = self
+            .exchanges
+            .remove(auth_id)
+            .ok_or(CpError::UnknownChallenge)?;
+        Ok(Proof::new(r1, r2, c, s))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::prover::Prover;
+    use crate::ChaumPedersenTrait;
+
+    use super::*;
+
+    #[cfg(feature = "std-async")]
+    #[tokio::test]
+    async fn interactive_exchange() {
+        let cp = ChaumPedersen::new_test_group();
+        let secret_x = cp.hash_to_scalar(b"nyancat");
+        let (y1, y2) = cp.generate_public_keys(secret_x.clone()).await.unwrap();
+        let mut verifier = Verifier::new(&cp, y1, y2);
+
+        let mut prover = Prover::new(&cp);
+        let (r1, r2) = prover.commit().await.unwrap();
+        let (auth_id, c) = verifier.issue_challenge(r1, r2);
+        assert_eq!(auth_id.len(), 2 * AUTH_ID_LEN);
+        assert!(verifier.is_outstanding(&auth_id));
+
+        let s = prover.respond(c, secret_x).unwrap();
+        assert_eq!(verifier.verify(&auth_id, s.clone()).await, Ok(true));
+
+        // Consumed by the first response
+        assert!(!verifier.is_outstanding(&auth_id));
+        assert_eq!(
+            verifier.verify(&auth_id, s).await,
+            Err(CpError::UnknownChallenge)
+        );
+    }
+
+    #[test]
+    fn concurrent_exchanges() {
+        let cp = ChaumPedersen::new_test_group();
+        let cat = cp.hash_to_scalar(b"cat");
+        let (y1, y2) = cp.generate_public_keys_sync(cat.clone()).unwrap();
+        let mut verifier = Verifier::new(&cp, y1, y2);
+
+        let mut first = Prover::new(&cp);
+        let (r1, r2) = first.commit_sync().unwrap();
+        let (first_id, first_c) = verifier.issue_challenge(r1, r2);
+        let mut second = Prover::new(&cp);
+        let (r1, r2) = second.commit_sync().unwrap();
+        let (second_id, second_c) = verifier.issue_challenge(r1, r2);
+        assert_ne!(first_id, second_id);
+
+        // Answered out of order, each against its own commitment
+        let s = second.respond(second_c, cat.clone()).unwrap();
+        assert_eq!(verifier.verify_sync(&second_id, s), Ok(true));
+        let s = first.respond(first_c.clone(), cat).unwrap();
+        assert_eq!(verifier.verify_sync(&first_id, s.clone()), Ok(true));
+
+        // Resumed for the wrong secret
+        let dog = cp.hash_to_scalar(b"dog");
+        let mut prover = Prover::new(&cp);
+        let (r1, r2) = prover.commit_sync().unwrap();
+        verifier.resume(first_id.clone(), r1, r2, first_c.clone());
+        let s = prover.respond(first_c, dog).unwrap();
+        assert_eq!(verifier.verify_sync(&first_id, s), Ok(false));
+        assert_eq!(
+            verifier.verify_sync("nyan", BigInt::from(1)),
+            Err(CpError::UnknownChallenge)
+        );
+    }
+}
@@ -0,0 +1,116 @@
+//! Builds `core_math` in a `no_std` crate, the `std` prelude isn't in scope so a stray `std` item
+//! in it fails to compile. The library itself builds without `std` with
+//! `cargo build -p chaum_pedersen --no-default-features`
+#![no_std]
+
+extern crate alloc;
+// The test harness links `std`, `core_math` only sees `core` and `alloc`
+extern crate std;
+
+#[path = "../src/core_math.rs"]
+mod core_math;
+
+use alloc::vec;
+
+use num_bigint::BigInt;
+
+/// `p = 23` is the safe prime `2 * 11 + 1`, `g = 4` and `h = 9` generate the subgroup of order 11
+fn group() -> (BigInt, BigInt, BigInt, BigInt) {
+    (
+        BigInt::from(23),
+        BigInt::from(11),
+        BigInt::from(4),
+        BigInt::from(9),
+    )
+}
+
+#[test]
+fn proof() {
+    let (p, q, g, h) = group();
+    let generators = vec![g.clone(), h.clone()];
+    let x = BigInt::from(3);
+    let keys = vec![
+        core_math::public_key(&g, &x, &p),
+        core_math::public_key(&h, &x, &p),
+    ];
+    assert_eq!(keys, vec![BigInt::from(18), BigInt::from(16)]);
+
+    let k = BigInt::from(5);
+    let commitments = core_math::commit(&generators, &k, &p);
+    let c = BigInt::from(7);
+    let s = core_math::solve(&k, &c, &x, &q);
+    assert_eq!(s, BigInt::from(6));
+
+    assert!(core_math::verify(
+        &generators,
+        &keys,
+        &commitments,
+        &c,
+        &s,
+        &p
+    ));
+    assert!(!core_math::verify(
+        &generators,
+        &keys,
+        &commitments,
+        &c,
+        &(s + 1),
+        &p
+    ));
+    assert!(!core_math::verify(
+        &generators,
+        &keys[..1],
+        &commitments,
+        &c,
+        &BigInt::from(6),
+        &p
+    ));
+}
+
+#[test]
+fn mod_inverse() {
+    let (_, q, _, _) = group();
+    assert_eq!(
+        core_math::mod_inverse(&BigInt::from(3), &q),
+        Some(BigInt::from(4))
+    );
+    assert_eq!(
+        core_math::mod_inverse(&BigInt::from(10), &BigInt::from(17)),
+        Some(BigInt::from(12))
+    );
+    // Negative and unreduced values are reduced first
+    assert_eq!(
+        core_math::mod_inverse(&BigInt::from(-3), &q),
+        Some(BigInt::from(7))
+    );
+    assert_eq!(
+        core_math::mod_inverse(&BigInt::from(14), &q),
+        Some(BigInt::from(4))
+    );
+    // `2^-1 = (m + 1) / 2` for an odd `m`
+    let m = BigInt::from(u64::MAX);
+    assert_eq!(
+        core_math::mod_inverse(&BigInt::from(2), &m),
+        Some((&m + 1) / 2)
+    );
+    for a in 1..11 {
+        let inverse = core_math::mod_inverse(&BigInt::from(a), &q).unwrap();
+        assert_eq!(BigInt::from(a) * inverse % &q, BigInt::from(1));
+    }
+
+    // `15 = 3 * 5` shares a factor with 6 and 10
+    assert_eq!(
+        core_math::mod_inverse(&BigInt::from(6), &BigInt::from(15)),
+        None
+    );
+    assert_eq!(
+        core_math::mod_inverse(&BigInt::from(10), &BigInt::from(15)),
+        None
+    );
+    assert_eq!(core_math::mod_inverse(&BigInt::from(0), &q), None);
+    assert_eq!(core_math::mod_inverse(&q, &q), None);
+    assert_eq!(
+        core_math::mod_inverse(&BigInt::from(1), &BigInt::from(1)),
+        None
+    );
+}
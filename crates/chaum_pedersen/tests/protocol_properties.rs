@@ -0,0 +1,193 @@
+//! Completeness and soundness of both protocols for random secrets, nonces and challenges. An
+//! honest proof has to verify and a proof with any one of `r1`, `r2`, `c` or `s` changed to
+//! another valid value has to be rejected.
+
+use curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT;
+use curve25519_dalek::scalar::Scalar;
+use curve25519_dalek::RistrettoPoint;
+use lazy_static::lazy_static;
+use num_bigint::BigInt;
+use proptest::prelude::*;
+
+use chaum_pedersen::chaum_pedersen::ChaumPedersen;
+use chaum_pedersen::ecc_chaum_pedersen::EccChaumPedersen;
+use chaum_pedersen::proof::{NonInteractiveProof, Proof};
+use chaum_pedersen::{ChaumPedersenTrait, InteractiveVerifier, NonInteractiveVerifier};
+
+lazy_static! {
+    /// Small enough that a case takes microseconds
+    static ref SMALL_GROUP: ChaumPedersen = ChaumPedersen::new_test_group();
+    static ref ECC: EccChaumPedersen = EccChaumPedersen::new();
+}
+
+type EccProof = Proof<RistrettoPoint, Scalar>;
+
+/// Part of a proof that is tampered with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+    R1,
+    R2,
+    C,
+    S,
+}
+
+fn field() -> impl Strategy<Value = Field> {
+    prop_oneof![
+        Just(Field::R1),
+        Just(Field::R2),
+        Just(Field::C),
+        Just(Field::S)
+    ]
+}
+
+/// Scalar in `[0, q)` of the small group
+fn bigint_scalar() -> impl Strategy<Value = BigInt> {
+    any::<u128>().prop_map(|v| BigInt::from(v) % SMALL_GROUP.order())
+}
+
+/// Scalar in `[1, q)` of the small group, a secret of 0 gives the public key 1 which is refused
+fn nonzero_bigint_scalar() -> impl Strategy<Value = BigInt> {
+    any::<u128>().prop_map(|v| BigInt::from(v) % (SMALL_GROUP.order() - 1) + 1)
+}
+
+fn ecc_scalar() -> impl Strategy<Value = Scalar> {
+    any::<[u8; 32]>().prop_map(Scalar::from_bytes_mod_order)
+}
+
+/// A secret of 0 gives the identity as public key which is refused
+fn nonzero_ecc_scalar() -> impl Strategy<Value = Scalar> {
+    ecc_scalar().prop_filter("non-zero", |s| *s != Scalar::ZERO)
+}
+
+/// Honest interactive proof of `x` with the nonce `k` and the challenge `c`
+fn bigint_proof(x: &BigInt, k: &BigInt, c: &BigInt) -> (Proof, BigInt, BigInt) {
+    let cp = &*SMALL_GROUP;
+    let (y1, y2) = cp.generate_public_keys_sync(x.clone()).unwrap();
+    let (r1, r2) = cp.commit_with(k);
+    let s = cp.prover_solve_challenge(k.clone(), c.clone(), x.clone());
+    (Proof::new(r1, r2, c.clone(), s), y1, y2)
+}
+
+/// Moves `field` by `delta`, the commitments stay in the subgroup and the scalars below `q`
+fn tamper_bigint(proof: &Proof, field: Field, delta: &BigInt) -> Proof {
+    let cp = &*SMALL_GROUP;
+    let (p, q) = (&*cp.p, cp.order());
+    let mut tampered = proof.clone();
+    match field {
+        Field::R1 => tampered.r1 = &proof.r1 * cp.g.modpow(delta, p) % p,
+        Field::R2 => tampered.r2 = &proof.r2 * cp.h.modpow(delta, p) % p,
+        Field::C => tampered.c = (&proof.c + delta) % q,
+        Field::S => tampered.s = (&proof.s + delta) % q,
+    }
+    tampered
+}
+
+/// Honest proof of `x` with the nonce `k`, `c` is derived from the commitment
+fn ecc_proof(x: Scalar, k: Scalar) -> (EccProof, RistrettoPoint, RistrettoPoint) {
+    let (y1, y2) = ECC.generate_public_keys_sync(x).unwrap();
+    let (r1, r2, c) = ECC.commit_with(k);
+    let s = ECC.prover_solve_challenge(k, c, x);
+    (Proof::new(r1, r2, c, s), y1, y2)
+}
+
+/// Moves `field` by `delta`, the commitments by `delta` times the base point
+fn tamper_ecc(proof: &EccProof, field: Field, delta: Scalar) -> EccProof {
+    let offset = RISTRETTO_BASEPOINT_POINT * delta;
+    let mut tampered = proof.clone();
+    match field {
+        Field::R1 => tampered.r1 = proof.r1 + offset,
+        Field::R2 => tampered.r2 = proof.r2 + offset,
+        Field::C => tampered.c = proof.c + delta,
+        Field::S => tampered.s = proof.s + delta,
+    }
+    tampered
+}
+
+/// Point multiplications are slow in debug builds, 64 cases unless `PROPTEST_CASES` is set
+fn ecc_config() -> ProptestConfig {
+    match std::env::var("PROPTEST_CASES") {
+        Ok(_) => ProptestConfig::default(),
+        Err(_) => ProptestConfig::with_cases(64),
+    }
+}
+
+proptest! {
+    #[test]
+    fn bigint_complete(
+        x in nonzero_bigint_scalar(),
+        k in bigint_scalar(),
+        c in bigint_scalar(),
+    ) {
+        let (proof, y1, y2) = bigint_proof(&x, &k, &c);
+        prop_assert_eq!(SMALL_GROUP.verify_interactive_sync(proof, y1, y2), Ok(true));
+    }
+
+    #[test]
+    fn bigint_sound(
+        x in nonzero_bigint_scalar(),
+        k in bigint_scalar(),
+        c in bigint_scalar(),
+        field in field(),
+        delta in nonzero_bigint_scalar(),
+    ) {
+        let (proof, y1, y2) = bigint_proof(&x, &k, &c);
+        let tampered = tamper_bigint(&proof, field, &delta);
+        prop_assert_eq!(SMALL_GROUP.verify_interactive_sync(tampered, y1, y2), Ok(false));
+    }
+}
+
+proptest! {
+    #![proptest_config(ecc_config())]
+
+    #[test]
+    fn ecc_interactive_complete(
+        x in nonzero_ecc_scalar(),
+        k in ecc_scalar(),
+        c in ecc_scalar(),
+    ) {
+        let (y1, y2) = ECC.generate_public_keys_sync(x).unwrap();
+        let (r1, r2, _) = ECC.commit_with(k);
+        let s = ECC.prover_solve_challenge(k, c, x);
+        prop_assert_eq!(ECC.verify_interactive_sync(Proof::new(r1, r2, c, s), y1, y2), Ok(true));
+    }
+
+    #[test]
+    fn ecc_interactive_sound(
+        x in nonzero_ecc_scalar(),
+        k in ecc_scalar(),
+        c in ecc_scalar(),
+        field in field(),
+        delta in nonzero_ecc_scalar(),
+    ) {
+        let (y1, y2) = ECC.generate_public_keys_sync(x).unwrap();
+        let (r1, r2, _) = ECC.commit_with(k);
+        let s = ECC.prover_solve_challenge(k, c, x);
+        let tampered = tamper_ecc(&Proof::new(r1, r2, c, s), field, delta);
+        prop_assert_eq!(ECC.verify_interactive_sync(tampered, y1, y2), Ok(false));
+    }
+
+    #[test]
+    fn ecc_noninteractive_complete(x in nonzero_ecc_scalar(), k in ecc_scalar()) {
+        let (proof, y1, y2) = ecc_proof(x, k);
+        let noninteractive = NonInteractiveProof::new(proof.c, proof.s);
+        prop_assert_eq!(ECC.verify_noninteractive_sync(noninteractive, y1, y2), Ok(true));
+        prop_assert_eq!(ECC.verify_with_commitment_sync(proof, y1, y2), Ok(true));
+    }
+
+    #[test]
+    fn ecc_noninteractive_sound(
+        x in nonzero_ecc_scalar(),
+        k in ecc_scalar(),
+        field in field(),
+        delta in nonzero_ecc_scalar(),
+    ) {
+        let (proof, y1, y2) = ecc_proof(x, k);
+        let tampered = tamper_ecc(&proof, field, delta);
+        // Only `(c, s)` is sent in the non-interactive protocol
+        if matches!(field, Field::C | Field::S) {
+            let noninteractive = NonInteractiveProof::new(tampered.c, tampered.s);
+            prop_assert_eq!(ECC.verify_noninteractive_sync(noninteractive, y1, y2), Ok(false));
+        }
+        prop_assert_eq!(ECC.verify_with_commitment_sync(tampered, y1, y2), Ok(false));
+    }
+}
@@ -0,0 +1,44 @@
+//! Smoke test for the runtime-free API, run with
+//! `wasm-pack test --node crates/chaum_pedersen -- --no-default-features --features std`
+#![cfg(target_arch = "wasm32")]
+
+use wasm_bindgen_test::wasm_bindgen_test;
+
+use chaum_pedersen::chaum_pedersen::{ChaumPedersen, G, H, P};
+use chaum_pedersen::ecc_chaum_pedersen::EccChaumPedersen;
+use chaum_pedersen::proof::{NonInteractiveProof, Proof};
+use chaum_pedersen::prover::Prover;
+use chaum_pedersen::{ChaumPedersenTrait, InteractiveVerifier, NonInteractiveVerifier};
+
+#[wasm_bindgen_test]
+fn interactive_proof() {
+    let cp = ChaumPedersen::new(P.clone(), G.clone(), H.clone());
+    let secret_x = ChaumPedersen::hash(b"nyancat");
+    let (y1, y2) = cp.generate_public_keys_sync(secret_x.clone()).unwrap();
+
+    let mut prover = Prover::new(&cp);
+    let (r1, r2) = prover.commit_sync().unwrap();
+    let challenge = cp.verifier_generate_challenge();
+    let s = prover.respond(challenge.clone(), secret_x).unwrap();
+
+    assert_eq!(
+        cp.verify_interactive_sync(Proof::new(r1, r2, challenge, s), y1, y2),
+        Ok(true)
+    );
+}
+
+#[wasm_bindgen_test]
+fn non_interactive_proof() {
+    let ecc_cp = EccChaumPedersen::new();
+    let x = EccChaumPedersen::hash(b"my_secret_password");
+    let (y1, y2) = ecc_cp.generate_public_keys_sync(x).unwrap();
+
+    let (k, r1, r2) = ecc_cp.prover_commit_sync().unwrap();
+    let c = ecc_cp.derive_challenge(&r1, &r2);
+    let s = ecc_cp.prover_solve_challenge(k, c, x);
+
+    assert_eq!(
+        ecc_cp.verify_noninteractive_sync(NonInteractiveProof::new(c, s), y1, y2),
+        Ok(true)
+    );
+}
@@ -1,12 +1,19 @@
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-use sled::{Db, Tree};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sled::transaction::{
+    ConflictableTransactionError, TransactionError, TransactionalTree, UnabortableTransactionError,
+};
+use sled::{Db, Transactional, Tree};
 
+use crate::model::versioned;
 use crate::StorageError::{
-    DeleteFailed, DeserializationFailed, GetFailed, InsertFailed, NotFound, SerializationFailed,
-    TreeNotFound,
+    DeleteFailed, DeserializationFailed, FlushFailed, GetFailed, InsertFailed, NotFound,
+    SerializationFailed, TransactionConflict, TransactionFailed, TreeNotFound, UpdateFailed,
 };
-use crate::StorageResult;
+use crate::{StorageError, StorageResult};
 
 /// The `StorageTree` enum specifies the different collections or trees
 /// that can be used in the storage backend.
@@ -14,33 +21,171 @@ use crate::StorageResult;
 pub enum StorageTree {
     Auth,
     Challenge,
+    Session,
+    /// Outstanding challenge ids per user, oldest first
+    ChallengeIndex,
+    /// Responses to requests sent with an idempotency key
+    Idempotency,
+    /// Hashes of accepted answers to challenges, see `UsedProofModel`
+    UsedProofs,
+}
+
+impl StorageTree {
+    const ALL: [StorageTree; 6] = [
+        StorageTree::Auth,
+        StorageTree::Challenge,
+        StorageTree::Session,
+        StorageTree::ChallengeIndex,
+        StorageTree::Idempotency,
+        StorageTree::UsedProofs,
+    ];
 }
 
 type Key = Vec<u8>;
 
+/// Key/value pairs of one tree as `KeyValueStorage::export` writes them, the values are the
+/// stored bytes so every record keeps its own version
+struct Export(Vec<(Key, Vec<u8>)>);
+
+impl Export {
+    const VERSION: u8 = 1;
+}
+
+impl Serialize for Export {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        versioned::serialize(Self::VERSION, &self.0, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Export {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        versioned::deserialize(Self::VERSION, deserializer).map(Export)
+    }
+}
+
+/// Directory `KeyValueStorage::open` uses
+pub const DEFAULT_PATH: &str = "db";
+
+/// sled settings a store is opened with, the defaults are sled's own
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StorageConfig {
+    pub path: PathBuf,
+    /// Removes the database when it's dropped, `path` is ignored
+    pub temporary: bool,
+    /// zstd compression of the stored pages, can't be changed once a database was created
+    pub use_compression: bool,
+    /// Bytes of the page cache
+    pub cache_capacity: u64,
+    /// Interval of the background flush, `None` only flushes on `flush`
+    pub flush_every_ms: Option<u64>,
+}
+
+impl Default for StorageConfig {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from(DEFAULT_PATH),
+            temporary: false,
+            use_compression: false,
+            cache_capacity: 1024 * 1024 * 1024,
+            flush_every_ms: Some(500),
+        }
+    }
+}
+
+/// Handle to the sled database, `sled::Tree` is safe to use concurrently so clones share the
+/// same trees and can be used from different tasks without any locking.
+///
+/// Writes are buffered by sled and only reach the disk on its periodic background flush, call
+/// `flush` or `flush_async` where losing a write on a crash isn't acceptable. The store is flushed
+/// once the last of the clones is dropped.
+#[derive(Clone)]
 pub struct KeyValueStorage {
     db: Db,
     trees: HashMap<StorageTree, Tree>,
+    _last_handle: Arc<FlushOnDrop>,
+}
+
+/// Shared by all clones of a `KeyValueStorage`, so it's dropped with the last of them
+struct FlushOnDrop(Db);
+
+impl Drop for FlushOnDrop {
+    fn drop(&mut self) {
+        let _ = self.0.flush();
+    }
 }
 
 impl KeyValueStorage {
     pub fn open() -> Self {
-        let db = sled::open("db").expect("failed to open db");
+        Self::open_with(StorageConfig::default())
+    }
+
+    pub fn open_at<P: AsRef<Path>>(path: P) -> Self {
+        Self::open_with(StorageConfig {
+            path: path.as_ref().to_path_buf(),
+            ..StorageConfig::default()
+        })
+    }
+
+    /// Opens a store that lives only for the lifetime of the process, nothing is kept on disk.
+    pub fn temporary() -> Self {
+        Self::open_with(StorageConfig {
+            temporary: true,
+            ..StorageConfig::default()
+        })
+    }
+
+    pub fn open_with(config: StorageConfig) -> Self {
+        let db = Self::sled_config(&config)
+            .open()
+            .expect("failed to open db");
+        Self::from_db(db)
+    }
+
+    fn sled_config(config: &StorageConfig) -> sled::Config {
+        let sled_config = sled::Config::new()
+            .use_compression(config.use_compression)
+            .cache_capacity(config.cache_capacity)
+            .flush_every_ms(config.flush_every_ms);
+        if config.temporary {
+            sled_config.temporary(true)
+        } else {
+            sled_config.path(&config.path)
+        }
+    }
+
+    fn from_db(db: Db) -> Self {
         let trees = [
             (StorageTree::Auth, db.open_tree("auth").unwrap()),
             (StorageTree::Challenge, db.open_tree("challenge").unwrap()),
+            (StorageTree::Session, db.open_tree("session").unwrap()),
+            (
+                StorageTree::ChallengeIndex,
+                db.open_tree("challenge_index").unwrap(),
+            ),
+            (
+                StorageTree::Idempotency,
+                db.open_tree("idempotency").unwrap(),
+            ),
+            (
+                StorageTree::UsedProofs,
+                db.open_tree("used_proofs").unwrap(),
+            ),
         ]
-            .iter()
-            .cloned()
-            .collect();
+        .iter()
+        .cloned()
+        .collect();
 
-        Self { db, trees }
+        Self {
+            _last_handle: Arc::new(FlushOnDrop(db.clone())),
+            db,
+            trees,
+        }
     }
 }
 
 impl KeyValueStorage {
     pub fn insert<T: serde::Serialize>(
-        &mut self,
+        &self,
         collection: StorageTree,
         key: &Key,
         value: T,
@@ -55,6 +200,25 @@ impl KeyValueStorage {
         }
     }
 
+    /// Inserts `value` only if `key` is vacant, atomically with respect to concurrent writers.
+    /// Returns `false` and leaves the stored value untouched if `key` is already taken.
+    pub fn insert_if_absent<T: serde::Serialize>(
+        &self,
+        collection: StorageTree,
+        key: &Key,
+        value: T,
+    ) -> StorageResult<bool> {
+        let tree = self.trees.get(&collection).ok_or(TreeNotFound)?;
+        let serialized_value = bincode::serialize(&value)
+            .map_err(|e| SerializationFailed(format!("Serialization failed: {:?}", e)))?;
+
+        match tree.compare_and_swap(key, None::<&[u8]>, Some(serialized_value)) {
+            Ok(Ok(())) => Ok(true),
+            Ok(Err(_)) => Ok(false),
+            Err(e) => Err(InsertFailed(format!("Insert failed with error: {:?}", e))),
+        }
+    }
+
     pub fn get<T: serde::de::DeserializeOwned>(
         &self,
         collection: StorageTree,
@@ -63,18 +227,79 @@ impl KeyValueStorage {
         let tree = self.trees.get(&collection).ok_or(TreeNotFound)?;
 
         match tree.get(key) {
-            Ok(Some(ivec)) => {
-                let bytes = ivec.to_vec();
-                Ok(bincode::deserialize(&bytes)
-                    .map_err(|e| DeserializationFailed(format!("Deserialization failed: {}", e)))?)
-            }
+            Ok(Some(ivec)) => deserialize(&ivec),
+            Ok(None) => Err(NotFound),
+            Err(e) => Err(GetFailed(format!("Get failed with error {:?}", e))),
+        }
+    }
+
+    /// Stored bytes of `key` without deserializing them
+    pub fn get_raw(&self, collection: StorageTree, key: &Key) -> StorageResult<Vec<u8>> {
+        let tree = self.trees.get(&collection).ok_or(TreeNotFound)?;
+
+        match tree.get(key) {
+            Ok(Some(ivec)) => Ok(ivec.to_vec()),
             Ok(None) => Err(NotFound),
             Err(e) => Err(GetFailed(format!("Get failed with error {:?}", e))),
         }
     }
 
+    /// All keys of `collection` in order
+    pub fn keys(&self, collection: StorageTree) -> StorageResult<Vec<Key>> {
+        let tree = self.trees.get(&collection).ok_or(TreeNotFound)?;
+
+        tree.iter()
+            .keys()
+            .map(|key| {
+                key.map(|key| key.to_vec())
+                    .map_err(|e| GetFailed(format!("Get failed with error {:?}", e)))
+            })
+            .collect()
+    }
+
+    /// Every key/value pair of `collection` as a versioned bincode blob, for `import`
+    pub fn export(&self, collection: StorageTree) -> StorageResult<Vec<u8>> {
+        let tree = self.trees.get(&collection).ok_or(TreeNotFound)?;
+
+        let entries = tree
+            .iter()
+            .map(|entry| {
+                entry
+                    .map(|(key, value)| (key.to_vec(), value.to_vec()))
+                    .map_err(|e| GetFailed(format!("Get failed with error {:?}", e)))
+            })
+            .collect::<StorageResult<_>>()?;
+        bincode::serialize(&Export(entries))
+            .map_err(|e| SerializationFailed(format!("Serialization failed: {:?}", e)))
+    }
+
+    /// Restores the pairs of an `export` blob into `collection` in one transaction. Keys that are
+    /// already stored keep their value unless `overwrite` is set. Returns the number of pairs
+    /// written.
+    pub fn import(
+        &self,
+        collection: StorageTree,
+        bytes: &[u8],
+        overwrite: bool,
+    ) -> StorageResult<usize> {
+        let tree = self.trees.get(&collection).ok_or(TreeNotFound)?;
+        let Export(entries) = deserialize(bytes)?;
+
+        tree.transaction(|tx| {
+            let mut written = 0;
+            for (key, value) in &entries {
+                if overwrite || tx.get(key)?.is_none() {
+                    tx.insert(key.as_slice(), value.as_slice())?;
+                    written += 1;
+                }
+            }
+            Ok(written)
+        })
+        .map_err(|e: TransactionError<()>| TransactionFailed(format!("{:?}", e)))
+    }
+
     pub fn upsert<T: serde::Serialize>(
-        &mut self,
+        &self,
         collection: StorageTree,
         key: &Key,
         value: T,
@@ -82,7 +307,28 @@ impl KeyValueStorage {
         self.insert::<T>(collection, key, value)
     }
 
-    pub fn delete(&mut self, collection: StorageTree, key: &Key) -> StorageResult<()> {
+    /// Atomically replaces `current` with `new`, the old value stays in place if the write fails
+    /// and `UpdateFailed` is returned if the stored value is no longer `current`.
+    pub fn update<T: serde::Serialize>(
+        &self,
+        collection: StorageTree,
+        key: &Key,
+        current: &T,
+        new: T,
+    ) -> StorageResult<()> {
+        let tree = self.trees.get(&collection).ok_or(TreeNotFound)?;
+        let current = bincode::serialize(current)
+            .map_err(|e| SerializationFailed(format!("Serialization failed: {:?}", e)))?;
+        let new = bincode::serialize(&new)
+            .map_err(|e| SerializationFailed(format!("Serialization failed: {:?}", e)))?;
+
+        match tree.compare_and_swap(key, Some(current), Some(new)) {
+            Ok(Ok(())) => Ok(()),
+            Ok(Err(_)) | Err(_) => Err(UpdateFailed),
+        }
+    }
+
+    pub fn delete(&self, collection: StorageTree, key: &Key) -> StorageResult<()> {
         let tree = self.trees.get(&collection).ok_or(TreeNotFound)?;
 
         match tree.remove(key) {
@@ -91,10 +337,388 @@ impl KeyValueStorage {
         }
     }
 
+    /// Deletes every key of `collection` that sorts before `end`, returns the number of keys deleted
+    pub fn delete_before(&self, collection: StorageTree, end: &Key) -> StorageResult<usize> {
+        let tree = self.trees.get(&collection).ok_or(TreeNotFound)?;
+
+        let mut deleted = 0;
+        for key in tree.range(..end.as_slice()).keys() {
+            let key = key.map_err(|e| GetFailed(format!("Get failed with error {:?}", e)))?;
+            tree.remove(key).map_err(|_| DeleteFailed)?;
+            deleted += 1;
+        }
+        Ok(deleted)
+    }
+
+    /// Runs `f` atomically across all trees, either every write made through the `Transaction`
+    /// lands or none does. Returning an error from `f` aborts the transaction with that error,
+    /// `f` may be called more than once if the transaction conflicts with a concurrent write.
+    pub fn transaction<F, R>(&self, f: F) -> StorageResult<R>
+    where
+        F: Fn(&Transaction) -> StorageResult<R>,
+    {
+        let trees = StorageTree::ALL
+            .iter()
+            .map(|collection| self.trees.get(collection).cloned().ok_or(TreeNotFound))
+            .collect::<StorageResult<Vec<Tree>>>()?;
+
+        let result = trees.as_slice().transaction(|view| {
+            let tx = Transaction {
+                trees: StorageTree::ALL.iter().copied().zip(view.iter()).collect(),
+            };
+            f(&tx).map_err(|e| match e {
+                TransactionConflict => ConflictableTransactionError::Conflict,
+                e => ConflictableTransactionError::Abort(e),
+            })
+        });
+
+        result.map_err(|e| match e {
+            TransactionError::Abort(e) => e,
+            TransactionError::Storage(e) => TransactionFailed(format!("{:?}", e)),
+        })
+    }
+
     pub fn exists(&self, collection: StorageTree, key: &Key) -> bool {
         self.trees
             .get(&collection)
             .map(|tree| tree.contains_key(key).unwrap_or(false))
             .unwrap_or(false)
     }
+
+    /// Blocks until every write made so far is durable on disk
+    pub fn flush(&self) -> StorageResult<()> {
+        self.db
+            .flush()
+            .map(|_| ())
+            .map_err(|e| FlushFailed(format!("{:?}", e)))
+    }
+
+    /// Same as `flush` without blocking the thread, for async callers
+    pub async fn flush_async(&self) -> StorageResult<()> {
+        self.db
+            .flush_async()
+            .await
+            .map(|_| ())
+            .map_err(|e| FlushFailed(format!("{:?}", e)))
+    }
+}
+
+/// Errors raised by the models themselves, e.g. an unsupported version, are passed through as is
+fn deserialize<T: serde::de::DeserializeOwned>(bytes: &[u8]) -> StorageResult<T> {
+    bincode::deserialize(bytes).map_err(|e| match *e {
+        bincode::ErrorKind::Custom(message) => DeserializationFailed(message),
+        e => DeserializationFailed(format!("Deserialization failed: {}", e)),
+    })
+}
+
+/// View of the trees inside `KeyValueStorage::transaction`
+pub struct Transaction<'a> {
+    trees: HashMap<StorageTree, &'a TransactionalTree>,
+}
+
+impl<'a> Transaction<'a> {
+    pub fn insert<T: serde::Serialize>(
+        &self,
+        collection: StorageTree,
+        key: &Key,
+        value: T,
+    ) -> StorageResult<()> {
+        let tree = self.trees.get(&collection).ok_or(TreeNotFound)?;
+        let serialized_value = bincode::serialize(&value)
+            .map_err(|e| SerializationFailed(format!("Serialization failed: {:?}", e)))?;
+
+        tree.insert(key.as_slice(), serialized_value)
+            .map_err(Transaction::unabortable)?;
+        Ok(())
+    }
+
+    pub fn get<T: serde::de::DeserializeOwned>(
+        &self,
+        collection: StorageTree,
+        key: &Key,
+    ) -> StorageResult<T> {
+        let tree = self.trees.get(&collection).ok_or(TreeNotFound)?;
+
+        match tree.get(key.as_slice()).map_err(Transaction::unabortable)? {
+            Some(ivec) => deserialize(&ivec),
+            None => Err(NotFound),
+        }
+    }
+
+    pub fn delete(&self, collection: StorageTree, key: &Key) -> StorageResult<()> {
+        let tree = self.trees.get(&collection).ok_or(TreeNotFound)?;
+
+        tree.remove(key.as_slice())
+            .map_err(Transaction::unabortable)?;
+        Ok(())
+    }
+
+    pub fn exists(&self, collection: StorageTree, key: &Key) -> StorageResult<bool> {
+        let tree = self.trees.get(&collection).ok_or(TreeNotFound)?;
+
+        let value = tree.get(key.as_slice()).map_err(Transaction::unabortable)?;
+        Ok(value.is_some())
+    }
+
+    fn unabortable(e: UnabortableTransactionError) -> StorageError {
+        match e {
+            UnabortableTransactionError::Conflict => TransactionConflict,
+            UnabortableTransactionError::Storage(e) => TransactionFailed(format!("{:?}", e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::user_model::{KeyAlgorithm, KeySlot, UserModel};
+
+    use super::*;
+
+    #[test]
+    fn compressed_round_trip() {
+        let path = std::env::temp_dir().join(format!("zkp-compressed-{}", std::process::id()));
+        let config = StorageConfig {
+            path: path.clone(),
+            use_compression: true,
+            cache_capacity: 16 * 1024 * 1024,
+            flush_every_ms: None,
+            ..StorageConfig::default()
+        };
+        let key = b"large".to_vec();
+        let value: String = (0..1 << 20)
+            .map(|i| char::from(b'a' + (i % 26) as u8))
+            .collect();
+
+        let db = KeyValueStorage::open_with(config.clone());
+        db.insert(StorageTree::Auth, &key, &value).unwrap();
+        db.flush().unwrap();
+        drop(db);
+
+        // Read back from disk. sled's background threads can hold the file lock for a moment
+        // after the last handle is dropped.
+        let db = (0..200)
+            .find_map(|_| {
+                let db = KeyValueStorage::sled_config(&config).open().ok();
+                if db.is_none() {
+                    std::thread::sleep(std::time::Duration::from_millis(10));
+                }
+                db
+            })
+            .map(KeyValueStorage::from_db)
+            .expect("db is still locked");
+        let stored = db.get::<String>(StorageTree::Auth, &key);
+        drop(db);
+        std::fs::remove_dir_all(&path).unwrap();
+        assert_eq!(stored.unwrap(), value);
+    }
+
+    #[test]
+    fn transaction_commits() {
+        let db = KeyValueStorage::temporary();
+        let challenge_key = b"challenge".to_vec();
+        let session_key = b"session".to_vec();
+        db.insert(StorageTree::Challenge, &challenge_key, "c".to_string())
+            .unwrap();
+
+        db.transaction(|tx| {
+            tx.delete(StorageTree::Challenge, &challenge_key)?;
+            tx.insert(StorageTree::Session, &session_key, "s".to_string())
+        })
+        .unwrap();
+
+        assert!(!db.exists(StorageTree::Challenge, &challenge_key));
+        assert_eq!(
+            db.get::<String>(StorageTree::Session, &session_key)
+                .unwrap(),
+            "s"
+        );
+    }
+
+    #[test]
+    fn transaction_aborts() {
+        let db = KeyValueStorage::temporary();
+        let challenge_key = b"challenge".to_vec();
+        let session_key = b"session".to_vec();
+        let user_key = b"user".to_vec();
+        db.insert(StorageTree::Challenge, &challenge_key, "c".to_string())
+            .unwrap();
+
+        let result = db.transaction(|tx| {
+            tx.delete(StorageTree::Challenge, &challenge_key)?;
+            tx.insert(StorageTree::Session, &session_key, "s".to_string())?;
+            tx.get::<String>(StorageTree::Auth, &user_key)
+        });
+
+        assert!(matches!(result, Err(NotFound)));
+        assert!(db.exists(StorageTree::Challenge, &challenge_key));
+        assert!(!db.exists(StorageTree::Session, &session_key));
+    }
+
+    #[test]
+    fn insert_if_absent() {
+        let db = KeyValueStorage::temporary();
+        let key = b"user".to_vec();
+
+        assert!(db
+            .insert_if_absent(StorageTree::Auth, &key, "cat".to_string())
+            .unwrap());
+        assert!(!db
+            .insert_if_absent(StorageTree::Auth, &key, "dog".to_string())
+            .unwrap());
+        assert_eq!(db.get::<String>(StorageTree::Auth, &key).unwrap(), "cat");
+    }
+
+    #[test]
+    fn delete_before() {
+        let db = KeyValueStorage::temporary();
+        for key in [[0, 1], [1, 0], [1, 1], [2, 0]] {
+            db.insert(StorageTree::UsedProofs, &key.to_vec(), ())
+                .unwrap();
+        }
+
+        assert_eq!(
+            db.delete_before(StorageTree::UsedProofs, &vec![1, 1])
+                .unwrap(),
+            2
+        );
+        assert_eq!(
+            db.keys(StorageTree::UsedProofs).unwrap(),
+            [vec![1, 1], vec![2, 0]]
+        );
+        assert_eq!(
+            db.delete_before(StorageTree::UsedProofs, &vec![1]).unwrap(),
+            0
+        );
+    }
+
+    #[test]
+    fn flush_survives_reopen() {
+        let path = std::env::temp_dir().join(format!("zkp-db-{}", std::process::id()));
+        let key = b"user".to_vec();
+
+        let db = KeyValueStorage::open_at(&path);
+        db.insert(StorageTree::Auth, &key, "cat".to_string())
+            .unwrap();
+        db.flush().unwrap();
+        drop(db);
+
+        let db = KeyValueStorage::open_at(&path);
+        let value = db.get::<String>(StorageTree::Auth, &key);
+        drop(db);
+        std::fs::remove_dir_all(&path).unwrap();
+
+        assert_eq!(value.unwrap(), "cat");
+    }
+
+    #[test]
+    fn last_handle_flushes() {
+        let db = KeyValueStorage::temporary();
+        let clone = db.clone();
+        assert_eq!(Arc::strong_count(&db._last_handle), 2);
+
+        // Dropping a clone leaves the flush to the remaining handle
+        drop(clone);
+        assert_eq!(Arc::strong_count(&db._last_handle), 1);
+    }
+
+    #[test]
+    fn concurrent_access() {
+        let db = KeyValueStorage::temporary();
+        let keys: Vec<Key> = (0..8u8).map(|i| vec![i]).collect();
+
+        std::thread::scope(|scope| {
+            for writer in 0..16u64 {
+                let db = db.clone();
+                let keys = &keys;
+                scope.spawn(move || {
+                    for round in 0..100 {
+                        let key = &keys[(writer as usize + round) % keys.len()];
+                        db.upsert(StorageTree::Session, key, writer).unwrap();
+                        let value = db.get::<u64>(StorageTree::Session, key).unwrap();
+                        assert!(value < 16);
+                    }
+                });
+            }
+        });
+
+        for key in &keys {
+            assert!(db.get::<u64>(StorageTree::Session, key).unwrap() < 16);
+        }
+    }
+
+    #[test]
+    fn export_import() {
+        let user = |name: &str, y1: &str| UserModel {
+            user: name.to_string(),
+            slots: vec![KeySlot {
+                id: KeySlot::FIRST,
+                y1: y1.to_string(),
+                y2: "3".to_string(),
+                algorithm: KeyAlgorithm::Interactive,
+            }],
+            group: 2048,
+            generators: String::new(),
+            reregister_required: false,
+        };
+        let users: Vec<UserModel> = ["Nyan", "Cat", "Tac"]
+            .iter()
+            .map(|name| user(name, "2"))
+            .collect();
+
+        let db = KeyValueStorage::temporary();
+        for user in &users {
+            db.insert(StorageTree::Auth, &UserModel::user_id(&user.user), user)
+                .unwrap();
+        }
+        db.insert(StorageTree::Session, &b"session".to_vec(), "s".to_string())
+            .unwrap();
+        let backup = db.export(StorageTree::Auth).unwrap();
+
+        let restored = KeyValueStorage::temporary();
+        assert_eq!(
+            restored.import(StorageTree::Auth, &backup, false).unwrap(),
+            3
+        );
+        for user in &users {
+            assert_eq!(
+                restored
+                    .get::<UserModel>(StorageTree::Auth, &UserModel::user_id(&user.user))
+                    .unwrap(),
+                *user
+            );
+        }
+        assert_eq!(
+            restored.keys(StorageTree::Auth).unwrap(),
+            db.keys(StorageTree::Auth).unwrap()
+        );
+        assert!(restored.keys(StorageTree::Session).unwrap().is_empty());
+
+        // Users changed since the backup are kept unless overwritten
+        let changed = user("Nyan", "5");
+        let key = UserModel::user_id(&changed.user);
+        restored.insert(StorageTree::Auth, &key, &changed).unwrap();
+        assert_eq!(
+            restored.import(StorageTree::Auth, &backup, false).unwrap(),
+            0
+        );
+        assert_eq!(
+            restored.get::<UserModel>(StorageTree::Auth, &key).unwrap(),
+            changed
+        );
+        assert_eq!(
+            restored.import(StorageTree::Auth, &backup, true).unwrap(),
+            3
+        );
+        assert_eq!(
+            restored.get::<UserModel>(StorageTree::Auth, &key).unwrap(),
+            users[0]
+        );
+
+        let mut future = backup.clone();
+        future[0] = Export::VERSION + 1;
+        assert!(matches!(
+            restored.import(StorageTree::Auth, &future, true),
+            Err(DeserializationFailed(message)) if message == "unsupported version 2"
+        ));
+    }
 }
@@ -0,0 +1,94 @@
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::db::{KeyValueStorage, StorageTree};
+use crate::StorageError::SerializationFailed;
+use crate::StorageResult;
+
+/// Reads the record at `key` as a `T` and returns it as JSON, for inspecting the database by hand.
+/// The models name their fields in JSON while the stored bincode is a positional tuple.
+pub fn dump_model<T: Serialize + DeserializeOwned>(
+    store: &KeyValueStorage,
+    collection: StorageTree,
+    key: &Vec<u8>,
+) -> StorageResult<serde_json::Value> {
+    let model = store.get::<T>(collection, key)?;
+    serde_json::to_value(&model)
+        .map_err(|e| SerializationFailed(format!("Serialization failed: {}", e)))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use crate::model::challenge_model::ChallengeModel;
+    use crate::model::user_model::{KeyAlgorithm, KeySlot, UserModel};
+    use crate::StorageError::NotFound;
+
+    use super::*;
+
+    fn nyan() -> UserModel {
+        UserModel {
+            user: "Nyan".to_string(),
+            slots: vec![KeySlot {
+                id: KeySlot::FIRST,
+                y1: "2".to_string(),
+                y2: "3".to_string(),
+                algorithm: KeyAlgorithm::Interactive,
+            }],
+            group: 2048,
+            generators: "9e".to_string(),
+            reregister_required: false,
+        }
+    }
+
+    #[test]
+    fn dump_user() {
+        let db = KeyValueStorage::temporary();
+        let key = UserModel::user_id(&"Nyan".to_string());
+        db.insert(StorageTree::Auth, &key, nyan()).unwrap();
+
+        assert_eq!(
+            dump_model::<UserModel>(&db, StorageTree::Auth, &key).unwrap(),
+            json!({
+                "user": "Nyan",
+                "slots": [{"id": 0, "y1": "2", "y2": "3", "algorithm": "interactive"}],
+                "group": 2048,
+                "generators": "9e",
+                "reregister_required": false,
+            })
+        );
+        // The stored record is still the versioned bincode
+        assert_eq!(
+            db.get_raw(StorageTree::Auth, &key).unwrap(),
+            bincode::serialize(&nyan()).unwrap()
+        );
+    }
+
+    #[test]
+    fn dump_challenge() {
+        let db = KeyValueStorage::temporary();
+        let key = b"auth_id".to_vec();
+        let challenge = ChallengeModel::new(
+            "c".to_string(),
+            ("4".to_string(), "5".to_string()),
+            nyan(),
+            KeySlot::FIRST,
+            1_700_000_000,
+            None,
+        );
+        db.insert(StorageTree::Challenge, &key, &challenge).unwrap();
+
+        let dumped = dump_model::<ChallengeModel>(&db, StorageTree::Challenge, &key).unwrap();
+        assert_eq!(dumped["commitment"], json!(["4", "5"]));
+        assert_eq!(dumped["user"]["user"], "Nyan");
+        assert_eq!(dumped["slot"], 0);
+        assert_eq!(dumped["created_at"], 1_700_000_000);
+        assert_eq!(dumped["enrollment_epoch"], json!(null));
+
+        assert!(matches!(
+            dump_model::<ChallengeModel>(&db, StorageTree::Challenge, &b"missing".to_vec()),
+            Err(NotFound)
+        ));
+    }
+}
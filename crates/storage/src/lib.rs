@@ -1,6 +1,8 @@
 use std::fmt;
 
 pub mod db;
+pub mod dump;
+pub mod migration;
 pub mod model;
 
 #[derive(Debug, Clone)]
@@ -13,6 +15,9 @@ pub enum StorageError {
     UpdateFailed,
     DeleteFailed,
     GetFailed(String),
+    TransactionConflict,
+    TransactionFailed(String),
+    FlushFailed(String),
 }
 
 impl fmt::Display for StorageError {
@@ -28,6 +33,9 @@ impl fmt::Display for StorageError {
             StorageError::UpdateFailed => write!(f, "Failed to update item"),
             StorageError::DeleteFailed => write!(f, "Failed to delete item"),
             StorageError::GetFailed(s) => write!(f, "Failed to get item: {}", s),
+            StorageError::TransactionConflict => write!(f, "Transaction conflicted"),
+            StorageError::TransactionFailed(s) => write!(f, "Transaction failed: {}", s),
+            StorageError::FlushFailed(s) => write!(f, "Failed to flush to disk: {}", s),
         }
     }
 }
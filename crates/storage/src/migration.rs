@@ -0,0 +1,417 @@
+use crate::db::{KeyValueStorage, StorageTree};
+use crate::model::user_model::{KeyAlgorithm, UserModel};
+use crate::StorageError::{DeserializationFailed, InsertFailed};
+use crate::{StorageError, StorageResult};
+
+/// Outcome of `migrate_users`
+#[derive(Debug, Default)]
+pub struct MigrationReport {
+    /// Records rewritten to the current layout or moved to their key
+    pub migrated: usize,
+    /// Keys of the records that couldn't be read in any known layout with the reason, e.g. a
+    /// corrupt record or one written by a newer version. They are left as they are.
+    pub skipped: Vec<(Vec<u8>, StorageError)>,
+}
+
+/// Rewrites every `UserModel` in `StorageTree::Auth` written by an earlier version, unversioned
+/// (v0) ones included, to the current layout. Records from before the group get
+/// `UserModel::LEGACY_GROUP`. The keys of the single-key layouts (v0 to v2) go into
+/// `KeySlot::FIRST` with the algorithm `algorithm` tells from `y1`, none of the earlier layouts has
+/// a fingerprint of its generators yet. Records stored under another key than `UserModel::user_id`
+/// of their name, the `DefaultHasher` keys of earlier versions, are moved to it along with their
+/// `StorageTree::ChallengeIndex` entry. Records already in the current layout and under their key
+/// are left untouched so running it again is a no-op.
+///
+/// The reader is picked by the version byte, v0 records have none and are tried when that reader
+/// fails. A record no reader accepts doesn't stop the scan, it ends up in `skipped`.
+pub fn migrate_users(
+    db: &KeyValueStorage,
+    algorithm: impl Fn(&str) -> KeyAlgorithm,
+) -> StorageResult<MigrationReport> {
+    let mut report = MigrationReport::default();
+
+    for key in db.keys(StorageTree::Auth)? {
+        let user = match db.get::<UserModel>(StorageTree::Auth, &key) {
+            Ok(user) if key == UserModel::user_id(&user.user) => continue,
+            Ok(user) => Ok(user),
+            Err(_) => read_legacy_user(db, &key, &algorithm),
+        };
+        match user {
+            Ok(user) => match move_user(db, &key, user) {
+                Ok(()) => report.migrated += 1,
+                Err(e) => report.skipped.push((key, e)),
+            },
+            Err(e) => report.skipped.push((key, e)),
+        }
+    }
+
+    Ok(report)
+}
+
+fn read_legacy_user(
+    db: &KeyValueStorage,
+    key: &Vec<u8>,
+    algorithm: impl Fn(&str) -> KeyAlgorithm,
+) -> StorageResult<UserModel> {
+    db.get_raw(StorageTree::Auth, key).and_then(|raw| {
+        match raw.first() {
+            Some(1) => UserModel::from_v1(&raw, &algorithm),
+            Some(2) => UserModel::from_v2(&raw, &algorithm),
+            Some(3) => UserModel::from_v3(&raw),
+            Some(version) => Err(DeserializationFailed(format!(
+                "unsupported version {}",
+                version
+            ))),
+            None => Err(DeserializationFailed("empty record".to_string())),
+        }
+        .or_else(|e| UserModel::from_v0(&raw, &algorithm).map_err(|_| e))
+    })
+}
+
+/// Writes `user` in the current layout under `UserModel::user_id`, moving it and its challenge
+/// index away from `key` if that's another key. A user already stored under the new key is left
+/// alone, the record under `key` stays too.
+fn move_user(db: &KeyValueStorage, key: &Vec<u8>, user: UserModel) -> StorageResult<()> {
+    let user_key = UserModel::user_id(&user.user);
+    if user_key == *key {
+        return db.insert(StorageTree::Auth, key, user);
+    }
+
+    db.transaction(|tx| {
+        if tx.exists(StorageTree::Auth, &user_key)? {
+            return Err(InsertFailed(format!(
+                "{} is already stored under its key",
+                user.user
+            )));
+        }
+        tx.insert(StorageTree::Auth, &user_key, &user)?;
+        tx.delete(StorageTree::Auth, key)?;
+
+        match tx.get::<Vec<String>>(StorageTree::ChallengeIndex, key) {
+            Ok(index) => {
+                tx.insert(StorageTree::ChallengeIndex, &user_key, index)?;
+                tx.delete(StorageTree::ChallengeIndex, key)
+            }
+            Err(StorageError::NotFound) => Ok(()),
+            Err(e) => Err(e),
+        }
+    })
+}
+
+/// Outcome of `flag_stale_generators`
+#[derive(Debug, Default)]
+pub struct GeneratorReport {
+    /// Users that have to register again
+    pub flagged: usize,
+    /// Keys of the records that couldn't be read with the reason, they weren't checked
+    pub skipped: Vec<(Vec<u8>, StorageError)>,
+}
+
+/// Compares the generators every user with interactive keys registered with to `active`, the
+/// fingerprint of the parameters the user's group runs with now or `None` if it isn't served. A
+/// user whose fingerprint differs gets `reregister_required`, one that matches again has it
+/// cleared. Records without a fingerprint predate it and are assumed to match, they get the active
+/// one. A record that can't be read is skipped like in `migrate_users`.
+pub fn flag_stale_generators(
+    db: &KeyValueStorage,
+    active: impl Fn(u32) -> Option<String>,
+) -> StorageResult<GeneratorReport> {
+    let mut report = GeneratorReport::default();
+
+    for key in db.keys(StorageTree::Auth)? {
+        let user = match db.get::<UserModel>(StorageTree::Auth, &key) {
+            Ok(user) => user,
+            Err(e) => {
+                report.skipped.push((key, e));
+                continue;
+            }
+        };
+        let Some(active) = active(user.group).filter(|_| user.has_interactive_keys()) else {
+            continue;
+        };
+
+        let mut checked = user.clone();
+        if checked.generators.is_empty() {
+            checked.generators = active.clone();
+        }
+        checked.reregister_required = checked.generators != active;
+        if checked.reregister_required {
+            report.flagged += 1;
+        }
+        if checked != user {
+            db.insert(StorageTree::Auth, &key, checked)?;
+        }
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::user_model::KeySlot;
+
+    use super::*;
+
+    #[test]
+    fn migrate_legacy_users() {
+        let db = KeyValueStorage::temporary();
+        let v0 = UserModel::user_id(&"Nyan".to_string());
+        let v1 = UserModel::user_id(&"Tic".to_string());
+        let v2 = UserModel::user_id(&"Cat".to_string());
+        let current = UserModel::user_id(&"Tac".to_string());
+
+        // v0 records are the plain bincode of `(user, y1, y2)`, the versioned ones added the group
+        // in v2
+        db.insert(StorageTree::Auth, &v0, ("Nyan", "2", "3"))
+            .unwrap();
+        db.insert(StorageTree::Auth, &v1, (1u8, ("Tic", "6", "7")))
+            .unwrap();
+        db.insert(
+            StorageTree::Auth,
+            &v2,
+            (2u8, ("Cat", "cGxh", "eXo", 4096u32)),
+        )
+        .unwrap();
+        db.insert(
+            StorageTree::Auth,
+            &current,
+            UserModel {
+                user: "Tac".to_string(),
+                slots: vec![KeySlot {
+                    id: 2,
+                    y1: "4".to_string(),
+                    y2: "5".to_string(),
+                    algorithm: KeyAlgorithm::Interactive,
+                }],
+                group: 2048,
+                generators: "9e".to_string(),
+                reregister_required: false,
+            },
+        )
+        .unwrap();
+        for key in [&v0, &v1, &v2] {
+            assert!(db.get::<UserModel>(StorageTree::Auth, key).is_err());
+        }
+
+        let algorithm = |y1: &str| {
+            if y1.chars().all(|c| c.is_ascii_hexdigit()) {
+                KeyAlgorithm::Interactive
+            } else {
+                KeyAlgorithm::NonInteractive
+            }
+        };
+        assert_eq!(migrate_users(&db, algorithm).unwrap().migrated, 3);
+        assert_eq!(migrate_users(&db, algorithm).unwrap().migrated, 0);
+
+        let user = db.get::<UserModel>(StorageTree::Auth, &v0).unwrap();
+        assert_eq!(user.user, "Nyan");
+        let slot = user.slot(KeySlot::FIRST).unwrap();
+        assert_eq!((slot.y1.as_str(), slot.y2.as_str()), ("2", "3"));
+        assert_eq!(slot.algorithm, KeyAlgorithm::Interactive);
+        assert_eq!(user.group, UserModel::LEGACY_GROUP);
+
+        let user = db.get::<UserModel>(StorageTree::Auth, &v1).unwrap();
+        assert_eq!(user.slot(KeySlot::FIRST).unwrap().y1, "6");
+        assert_eq!(user.group, UserModel::LEGACY_GROUP);
+
+        let user = db.get::<UserModel>(StorageTree::Auth, &v2).unwrap();
+        let slot = user.slot(KeySlot::FIRST).unwrap();
+        assert_eq!((slot.y1.as_str(), slot.y2.as_str()), ("cGxh", "eXo"));
+        assert_eq!(slot.algorithm, KeyAlgorithm::NonInteractive);
+        assert_eq!(user.group, 4096);
+
+        let user = db.get::<UserModel>(StorageTree::Auth, &current).unwrap();
+        assert_eq!(user.group, 2048);
+        assert_eq!(user.slots[0].id, 2);
+        assert_eq!(user.generators, "9e");
+    }
+
+    #[test]
+    fn migrate_slotted_users() {
+        let db = KeyValueStorage::temporary();
+        let key = UserModel::user_id(&"Nyan".to_string());
+        let slot = KeySlot {
+            id: 1,
+            y1: "4".to_string(),
+            y2: "5".to_string(),
+            algorithm: KeyAlgorithm::Interactive,
+        };
+        db.insert(
+            StorageTree::Auth,
+            &key,
+            (3u8, ("Nyan", vec![slot.clone()], 2048u32)),
+        )
+        .unwrap();
+
+        assert_eq!(
+            migrate_users(&db, |_| KeyAlgorithm::Interactive)
+                .unwrap()
+                .migrated,
+            1
+        );
+        let user = db.get::<UserModel>(StorageTree::Auth, &key).unwrap();
+        assert_eq!(user.slots, [slot]);
+        assert_eq!(user.group, 2048);
+        assert!(user.generators.is_empty());
+    }
+
+    #[test]
+    fn move_users_to_their_key() {
+        let db = KeyValueStorage::temporary();
+        let user = |name: &str| UserModel {
+            user: name.to_string(),
+            slots: vec![KeySlot {
+                id: KeySlot::FIRST,
+                y1: "2".to_string(),
+                y2: "3".to_string(),
+                algorithm: KeyAlgorithm::Interactive,
+            }],
+            group: 2048,
+            generators: String::new(),
+            reregister_required: false,
+        };
+        // `DefaultHasher` keys of earlier versions, the decimal digits of a `u64`
+        let nyan = b"5049276521869813412".to_vec();
+        let tic = b"1168405893212349720".to_vec();
+        let cat = b"9381234567890123456".to_vec();
+        db.insert(StorageTree::Auth, &nyan, user("Nyan")).unwrap();
+        db.insert(StorageTree::ChallengeIndex, &nyan, vec!["ab".to_string()])
+            .unwrap();
+        db.insert(StorageTree::Auth, &tic, (1u8, ("Tic", "6", "7")))
+            .unwrap();
+        db.insert(StorageTree::Auth, &cat, user("Cat")).unwrap();
+        let cat_key = UserModel::user_id(&"Cat".to_string());
+        db.insert(StorageTree::Auth, &cat_key, user("Cat")).unwrap();
+
+        let report = migrate_users(&db, |_| KeyAlgorithm::Interactive).unwrap();
+        assert_eq!(report.migrated, 2);
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(report.skipped[0].0, cat);
+
+        let nyan_key = UserModel::user_id(&"Nyan".to_string());
+        assert_eq!(
+            db.get::<UserModel>(StorageTree::Auth, &nyan_key).unwrap(),
+            user("Nyan")
+        );
+        assert_eq!(
+            db.get::<Vec<String>>(StorageTree::ChallengeIndex, &nyan_key)
+                .unwrap(),
+            ["ab"]
+        );
+        assert!(!db.exists(StorageTree::Auth, &nyan));
+        assert!(!db.exists(StorageTree::ChallengeIndex, &nyan));
+        let tic_key = UserModel::user_id(&"Tic".to_string());
+        assert_eq!(
+            db.get::<UserModel>(StorageTree::Auth, &tic_key)
+                .unwrap()
+                .user,
+            "Tic"
+        );
+        assert!(!db.exists(StorageTree::Auth, &tic));
+        assert!(db.exists(StorageTree::Auth, &cat));
+
+        assert_eq!(
+            migrate_users(&db, |_| KeyAlgorithm::Interactive)
+                .unwrap()
+                .migrated,
+            0
+        );
+    }
+
+    #[test]
+    fn skip_unreadable_users() {
+        let db = KeyValueStorage::temporary();
+        let corrupt = UserModel::user_id(&"Nyan".to_string());
+        let newer = UserModel::user_id(&"Cat".to_string());
+        let v0 = UserModel::user_id(&"Tac".to_string());
+        db.insert(StorageTree::Auth, &corrupt, (2u8, "garbage"))
+            .unwrap();
+        db.insert(StorageTree::Auth, &newer, (9u8, ("Cat", "2", "3")))
+            .unwrap();
+        db.insert(StorageTree::Auth, &v0, ("Tac", "2", "3"))
+            .unwrap();
+
+        let report = migrate_users(&db, |_| KeyAlgorithm::Interactive).unwrap();
+        assert_eq!(report.migrated, 1);
+        let skipped: Vec<(Vec<u8>, String)> = report
+            .skipped
+            .into_iter()
+            .map(|(key, e)| (key, e.to_string()))
+            .collect();
+        assert_eq!(skipped.len(), 2);
+        assert!(skipped.iter().any(|(key, _)| *key == corrupt));
+        assert!(skipped
+            .iter()
+            .any(|(key, e)| *key == newer && e.ends_with("unsupported version 9")));
+
+        assert_eq!(
+            db.get::<UserModel>(StorageTree::Auth, &v0).unwrap().user,
+            "Tac"
+        );
+        assert!(db.get_raw(StorageTree::Auth, &newer).is_ok());
+    }
+
+    #[test]
+    fn flag_users_of_changed_generators() {
+        let db = KeyValueStorage::temporary();
+        let user = |name: &str, group: u32, generators: &str, algorithm: KeyAlgorithm| UserModel {
+            user: name.to_string(),
+            slots: vec![KeySlot {
+                id: KeySlot::FIRST,
+                y1: "2".to_string(),
+                y2: "3".to_string(),
+                algorithm,
+            }],
+            group,
+            generators: generators.to_string(),
+            reregister_required: false,
+        };
+        let users = [
+            user("Nyan", 2048, "old", KeyAlgorithm::Interactive),
+            user("Cat", 2048, "new", KeyAlgorithm::Interactive),
+            user("Legacy", 2048, "", KeyAlgorithm::Interactive),
+            // Ristretto keys don't depend on the MODP generators
+            user("Ecc", 2048, "old", KeyAlgorithm::NonInteractive),
+            user("Unserved", 1024, "old", KeyAlgorithm::Interactive),
+        ];
+        for user in &users {
+            db.insert(StorageTree::Auth, &UserModel::user_id(&user.user), user)
+                .unwrap();
+        }
+        let stored = |name: &str| {
+            db.get::<UserModel>(StorageTree::Auth, &UserModel::user_id(&name.to_string()))
+                .unwrap()
+        };
+        let active = |fingerprint: &'static str| {
+            move |group: u32| (group == 2048).then(|| fingerprint.to_string())
+        };
+
+        let report = flag_stale_generators(&db, active("new")).unwrap();
+        assert_eq!((report.flagged, report.skipped.len()), (1, 0));
+        assert!(stored("Nyan").reregister_required);
+        assert!(!stored("Cat").reregister_required);
+        assert_eq!(stored("Legacy").generators, "new");
+        assert!(!stored("Legacy").reregister_required);
+        assert_eq!(stored("Ecc"), users[3]);
+        assert_eq!(stored("Unserved"), users[4]);
+
+        // Back to the old generators
+        assert_eq!(
+            flag_stale_generators(&db, active("old")).unwrap().flagged,
+            2
+        );
+        assert!(!stored("Nyan").reregister_required);
+        assert!(stored("Cat").reregister_required);
+        assert!(stored("Legacy").reregister_required);
+
+        // A corrupt record doesn't stop the others from being checked
+        let corrupt = UserModel::user_id(&"Corrupt".to_string());
+        db.insert(StorageTree::Auth, &corrupt, (9u8, "garbage"))
+            .unwrap();
+        let report = flag_stale_generators(&db, active("new")).unwrap();
+        assert_eq!(report.flagged, 1);
+        assert_eq!(report.skipped.len(), 1);
+        assert_eq!(report.skipped[0].0, corrupt);
+        assert!(!stored("Cat").reregister_required);
+    }
+}
@@ -1,32 +1,131 @@
-use std::collections::hash_map::DefaultHasher;
 use std::fmt;
 use std::fmt::Display;
 use std::hash::{Hash, Hasher};
 
-use serde::{Deserialize, Serialize};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256};
 
 use crate::model::user_model::UserModel;
+use crate::model::versioned;
+use crate::{StorageError, StorageResult};
 
-#[derive(Serialize, Deserialize, Debug)]
+/// Stored as `version || (challenge, commitment, user, slot, created_at, enrollment_epoch)`, see
+/// `versioned`. Human-readable formats get the fields by name instead, see `dump`.
+#[derive(Debug)]
 pub struct ChallengeModel {
     pub challenge: String,
     pub commitment: (String, String),
     pub user: UserModel,
+    /// Id of the key slot of `user` the challenge has to be answered for
+    pub slot: u32,
+    /// Seconds since the UNIX epoch the challenge was issued at
+    pub created_at: u64,
+    /// Seconds since the UNIX epoch the user enrolled at, taken from an enrollment token the
+    /// server signed. `None` if the prover didn't present one.
+    pub enrollment_epoch: Option<u64>,
 }
 
 impl ChallengeModel {
-    pub fn new(challenge: String, commitment: (String, String), user: UserModel) -> Self {
+    /// Challenges are short lived, records of earlier versions are not migrated
+    pub const VERSION: u8 = 4;
+
+    pub fn new(
+        challenge: String,
+        commitment: (String, String),
+        user: UserModel,
+        slot: u32,
+        created_at: u64,
+        enrollment_epoch: Option<u64>,
+    ) -> Self {
         Self {
             challenge,
             commitment,
             user,
+            slot,
+            created_at,
+            enrollment_epoch,
         }
     }
 
+    /// SHA-256 over `user_id || r1 || r2 || challenge`, each prefixed with its length so the
+    /// boundaries are unambiguous. `user_id` is `UserModel::user_id` of the user.
+    pub fn digest(&self) -> [u8; 32] {
+        let mut hasher = Sha256::new();
+        for field in [
+            UserModel::user_id(&self.user.user).as_slice(),
+            self.commitment.0.as_bytes(),
+            self.commitment.1.as_bytes(),
+            self.challenge.as_bytes(),
+        ] {
+            hasher.update((field.len() as u64).to_be_bytes());
+            hasher.update(field);
+        }
+        hasher.finalize().into()
+    }
+
+    /// Hex encoded `digest`, returned to the prover and stored under `key`
     pub fn generate_auth_id(&self) -> String {
-        let mut hasher = DefaultHasher::new();
-        self.hash(&mut hasher);
-        hasher.finish().to_string()
+        hex::encode(self.digest())
+    }
+
+    /// Storage key of the challenge issued under `auth_id`, the raw bytes of its digest. Anything
+    /// that isn't hex maps to the empty key, nothing is ever stored under it.
+    pub fn key(auth_id: &str) -> Vec<u8> {
+        hex::decode(auth_id).unwrap_or_default()
+    }
+
+    /// The versioned bincode the challenge is stored as, for callers keeping it outside the
+    /// database
+    pub fn to_bytes(&self) -> StorageResult<Vec<u8>> {
+        bincode::serialize(self).map_err(|e| StorageError::SerializationFailed(e.to_string()))
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> StorageResult<Self> {
+        bincode::deserialize(bytes).map_err(|e| StorageError::DeserializationFailed(e.to_string()))
+    }
+}
+
+impl Serialize for ChallengeModel {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            let mut state = serializer.serialize_struct("ChallengeModel", 6)?;
+            state.serialize_field("challenge", &self.challenge)?;
+            state.serialize_field("commitment", &self.commitment)?;
+            state.serialize_field("user", &self.user)?;
+            state.serialize_field("slot", &self.slot)?;
+            state.serialize_field("created_at", &self.created_at)?;
+            state.serialize_field("enrollment_epoch", &self.enrollment_epoch)?;
+            return state.end();
+        }
+
+        versioned::serialize(
+            Self::VERSION,
+            &(
+                &self.challenge,
+                &self.commitment,
+                &self.user,
+                self.slot,
+                self.created_at,
+                self.enrollment_epoch,
+            ),
+            serializer,
+        )
+    }
+}
+
+impl<'de> Deserialize<'de> for ChallengeModel {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (challenge, commitment, user, slot, created_at, enrollment_epoch) =
+            versioned::deserialize(Self::VERSION, deserializer)?;
+        Ok(Self {
+            challenge,
+            commitment,
+            user,
+            slot,
+            created_at,
+            enrollment_epoch,
+        })
     }
 }
 
@@ -34,8 +133,8 @@ impl Display for ChallengeModel {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
             f,
-            "Challenge [challenge: {}, user: {}]",
-            self.challenge, self.user,
+            "Challenge [challenge: {}, user: {}, slot: {}]",
+            self.challenge, self.user, self.slot,
         )
     }
 }
@@ -45,5 +144,80 @@ impl Hash for ChallengeModel {
         self.challenge.hash(state);
         self.commitment.hash(state);
         self.user.hash(state);
+        self.slot.hash(state);
+        self.created_at.hash(state);
+        self.enrollment_epoch.hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::model::user_model::{KeyAlgorithm, KeySlot};
+
+    use super::*;
+
+    fn transcript() -> ChallengeModel {
+        let user = UserModel {
+            user: "nyan".to_string(),
+            slots: vec![KeySlot {
+                id: KeySlot::FIRST,
+                y1: "02".to_string(),
+                y2: "03".to_string(),
+                algorithm: KeyAlgorithm::Interactive,
+            }],
+            group: 2048,
+            generators: String::new(),
+            reregister_required: false,
+        };
+        ChallengeModel::new(
+            "0b".to_string(),
+            ("04".to_string(), "05".to_string()),
+            user,
+            KeySlot::FIRST,
+            1_700_000_000,
+            None,
+        )
+    }
+
+    #[test]
+    fn fixed_auth_id() {
+        let model = transcript();
+        let auth_id = model.generate_auth_id();
+        assert_eq!(
+            auth_id,
+            "5d774e45fcafa45a7f0e4f7ed7ed48cc9dce287bcc3847ac4139ec043b1eb8cd"
+        );
+        assert_eq!(ChallengeModel::key(&auth_id), model.digest().to_vec());
+
+        // Only the transcript is hashed
+        let later = ChallengeModel {
+            created_at: 1_700_000_060,
+            ..transcript()
+        };
+        assert_eq!(later.generate_auth_id(), auth_id);
+
+        let other = ChallengeModel {
+            challenge: "0c".to_string(),
+            ..transcript()
+        };
+        assert_ne!(other.generate_auth_id(), auth_id);
+    }
+
+    #[test]
+    fn bytes_round_trip() {
+        let bytes = transcript().to_bytes().unwrap();
+        let model = ChallengeModel::from_bytes(&bytes).unwrap();
+        assert_eq!(model.generate_auth_id(), transcript().generate_auth_id());
+        assert_eq!(
+            (model.slot, model.created_at),
+            (KeySlot::FIRST, 1_700_000_000)
+        );
+
+        assert!(ChallengeModel::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn malformed_key() {
+        assert!(ChallengeModel::key("not hex").is_empty());
     }
 }
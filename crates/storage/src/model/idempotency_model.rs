@@ -0,0 +1,42 @@
+use std::fmt;
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// `IdempotencyModel` keeps the response to a request sent with an idempotency key, a retry with
+/// the same key is answered with it instead of running the request again.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct IdempotencyModel {
+    /// `fingerprint` of the request, a key reused for another request doesn't match it
+    pub request: Vec<u8>,
+    pub response: String,
+    /// Seconds since the UNIX epoch the request was handled at
+    pub created_at: u64,
+}
+
+impl IdempotencyModel {
+    pub fn new(request: Vec<u8>, response: String, created_at: u64) -> Self {
+        Self {
+            request,
+            response,
+            created_at,
+        }
+    }
+
+    /// SHA-256 over the request fields, each prefixed with its length
+    pub fn fingerprint(fields: &[&str]) -> Vec<u8> {
+        let mut hasher = Sha256::new();
+        for field in fields {
+            hasher.update((field.len() as u64).to_be_bytes());
+            hasher.update(field.as_bytes());
+        }
+        hasher.finalize().to_vec()
+    }
+}
+
+impl Display for IdempotencyModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Idempotency [created_at: {}]", self.created_at)
+    }
+}
@@ -1,2 +1,7 @@
 pub mod challenge_model;
+pub mod idempotency_model;
+pub mod redact;
+pub mod session_model;
+pub mod used_proof_model;
 pub mod user_model;
+pub mod versioned;
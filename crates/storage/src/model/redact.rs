@@ -0,0 +1,27 @@
+/// Characters kept at either end of a redacted value
+const VISIBLE: usize = 6;
+
+/// First and last `VISIBLE` characters of `value` around an ellipsis, for logging long keys and
+/// tokens without dumping them. Values too short to hide anything are returned as is.
+pub fn redact(value: &str) -> String {
+    let chars: Vec<char> = value.chars().collect();
+    if chars.len() <= 2 * VISIBLE + 1 {
+        return value.to_string();
+    }
+
+    let head: String = chars[..VISIBLE].iter().collect();
+    let tail: String = chars[chars.len() - VISIBLE..].iter().collect();
+    format!("{}…{}", head, tail)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redacted() {
+        assert_eq!(redact("abc123456789def456"), "abc123…def456");
+        assert_eq!(redact("abc1230def456"), "abc1230def456");
+        assert_eq!(redact(""), "");
+    }
+}
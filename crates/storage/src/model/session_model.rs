@@ -0,0 +1,212 @@
+use std::fmt;
+use std::fmt::Display;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+use crate::model::redact::redact;
+
+/// Bytes of the random nonce of a session id
+pub const SESSION_NONCE_LEN: usize = 16;
+/// Bytes of the HMAC-SHA256 tag of a session id
+pub const SESSION_TAG_LEN: usize = 32;
+
+/// `hex(nonce).iat.hex(tag)`, the id of a session handed out after a login. Parsing only checks
+/// the shape, the tag is checked by the server that issued the id. Stored and sent as its string.
+#[derive(Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(try_from = "String", into = "String")]
+pub struct SessionId {
+    nonce: [u8; SESSION_NONCE_LEN],
+    iat: u64,
+    tag: [u8; SESSION_TAG_LEN],
+}
+
+impl SessionId {
+    pub fn new(nonce: [u8; SESSION_NONCE_LEN], iat: u64, tag: [u8; SESSION_TAG_LEN]) -> Self {
+        Self { nonce, iat, tag }
+    }
+
+    pub fn nonce(&self) -> &[u8; SESSION_NONCE_LEN] {
+        &self.nonce
+    }
+
+    /// Unix time the session was issued at
+    pub fn iat(&self) -> u64 {
+        self.iat
+    }
+
+    pub fn tag(&self) -> &[u8; SESSION_TAG_LEN] {
+        &self.tag
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidSessionId;
+
+impl Display for InvalidSessionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "malformed session id")
+    }
+}
+
+impl std::error::Error for InvalidSessionId {}
+
+impl FromStr for SessionId {
+    type Err = InvalidSessionId;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split('.').collect();
+        let [nonce, iat, tag] = parts[..] else {
+            return Err(InvalidSessionId);
+        };
+        // `u64::from_str` takes a leading `+`, the issued ids never have one
+        if !iat.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(InvalidSessionId);
+        }
+
+        let mut id = SessionId::new([0; SESSION_NONCE_LEN], 0, [0; SESSION_TAG_LEN]);
+        hex::decode_to_slice(nonce, &mut id.nonce).map_err(|_| InvalidSessionId)?;
+        hex::decode_to_slice(tag, &mut id.tag).map_err(|_| InvalidSessionId)?;
+        id.iat = iat.parse().map_err(|_| InvalidSessionId)?;
+        Ok(id)
+    }
+}
+
+impl TryFrom<String> for SessionId {
+    type Error = InvalidSessionId;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<SessionId> for String {
+    fn from(id: SessionId) -> Self {
+        id.to_string()
+    }
+}
+
+impl Display for SessionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}.{}.{}",
+            hex::encode(self.nonce),
+            self.iat,
+            hex::encode(self.tag)
+        )
+    }
+}
+
+/// The id is a bearer credential, only its ends are shown
+impl fmt::Debug for SessionId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "SessionId({})", redact(&self.to_string()))
+    }
+}
+
+/// `SessionModel` binds a session id issued after a successful login to the user that proved
+/// knowledge of the secret. The session id is a bearer credential, `Debug` redacts it and
+/// `Display` leaves it out.
+#[derive(Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct SessionModel {
+    pub session_id: SessionId,
+    pub user: String,
+    /// Seconds since the UNIX epoch after which the session isn't accepted anymore, moved forward
+    /// on use when the server slides the expiry
+    pub expires_at: u64,
+}
+
+impl SessionModel {
+    pub fn new(session_id: SessionId, user: String, expires_at: u64) -> Self {
+        Self {
+            session_id,
+            user,
+            expires_at,
+        }
+    }
+
+    /// Whether the session can't be used anymore at `now`
+    pub fn is_expired(&self, now: u64) -> bool {
+        now > self.expires_at
+    }
+}
+
+impl fmt::Debug for SessionModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SessionModel")
+            .field("session_id", &self.session_id)
+            .field("user", &self.user)
+            .field("expires_at", &self.expires_at)
+            .finish()
+    }
+}
+
+impl Display for SessionModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "Session [user: {}]", self.user)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn session_id() -> SessionId {
+        SessionId::new(
+            [0xab; SESSION_NONCE_LEN],
+            1700000000,
+            [0xcd; SESSION_TAG_LEN],
+        )
+    }
+
+    #[test]
+    fn parse_session_id() {
+        let id = session_id();
+        let text = format!("{}.1700000000.{}", "ab".repeat(16), "cd".repeat(32));
+        assert_eq!(id.to_string(), text);
+        assert_eq!(text.parse::<SessionId>(), Ok(id));
+        assert_eq!(text.to_uppercase().parse::<SessionId>(), Ok(id));
+        assert_eq!(id.iat(), 1700000000);
+
+        // Stored as the string, like the ids before they were typed
+        let bytes = bincode::serialize(&id).unwrap();
+        assert_eq!(bytes, bincode::serialize(&text).unwrap());
+        assert_eq!(bincode::deserialize::<SessionId>(&bytes).unwrap(), id);
+
+        // The id is redacted when debug printed
+        assert!(!format!("{:?}", id).contains(&text));
+    }
+
+    #[test]
+    fn invalid_session_id() {
+        let nonce = "ab".repeat(16);
+        let tag = "cd".repeat(32);
+        for invalid in [
+            String::new(),
+            "forged".to_string(),
+            "Nyan".to_string(),
+            format!("{}.1700000000", nonce),
+            format!("{}.1700000000.{}.{}", nonce, tag, tag),
+            // Wrong lengths
+            format!("{}.1700000000.{}", "ab".repeat(15), tag),
+            format!("{}.1700000000.{}", nonce, "cd".repeat(31)),
+            format!("{}.1700000000.{}c", nonce, tag),
+            // Not hex or not a number
+            format!("{}.1700000000.{}", "zz".repeat(16), tag),
+            format!("{}.nyan.{}", nonce, tag),
+            format!("{}.+1700000000.{}", nonce, tag),
+            format!("{}.-1.{}", nonce, tag),
+            format!("{}..{}", nonce, tag),
+            format!("{}.{}0.{}", nonce, u64::MAX, tag),
+        ] {
+            assert_eq!(
+                invalid.parse::<SessionId>(),
+                Err(InvalidSessionId),
+                "{}",
+                invalid
+            );
+        }
+        assert!(bincode::deserialize::<SessionId>(&bincode::serialize("forged").unwrap()).is_err());
+    }
+}
@@ -0,0 +1,92 @@
+use std::fmt;
+use std::fmt::Display;
+
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+
+/// `UsedProofModel` marks the answer to a challenge, or a non-interactive proof, as accepted. The
+/// same answer is rejected until `expires_at` even if the challenge itself isn't stored anywhere.
+///
+/// Keys start with `expires_at` in big-endian so they sort by expiry, the expired ones are the
+/// keys before `expired_before`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct UsedProofModel {
+    /// Seconds since the UNIX epoch the challenge or the proof expires at, an answer replayed
+    /// later is rejected as expired anyway
+    pub expires_at: u64,
+}
+
+impl UsedProofModel {
+    pub fn new(expires_at: u64) -> Self {
+        Self { expires_at }
+    }
+
+    /// `expires_at` followed by the SHA-256 over the `auth_id` and the big-endian bytes of the
+    /// solution `s`, each prefixed with its length. `s` is passed decoded so every hex encoding of
+    /// it gives the same key.
+    pub fn key(expires_at: u64, auth_id: &str, s: &[u8]) -> Vec<u8> {
+        UsedProofModel::key_of(expires_at, &[auth_id.as_bytes(), s])
+    }
+
+    /// Key of a non-interactive proof of `user` with the challenge `c` and the solution `s`, both
+    /// canonical scalar bytes. `c` is the hash of the commitment so it stands for `(r1, r2)`, with
+    /// or without the commitment sent along the same proof gets the same key.
+    pub fn non_interactive_key(expires_at: u64, user: &str, c: &[u8], s: &[u8]) -> Vec<u8> {
+        UsedProofModel::key_of(expires_at, &[user.as_bytes(), c, s])
+    }
+
+    /// Every key of a proof whose challenge expired before `now` sorts before this one
+    pub fn expired_before(now: u64) -> Vec<u8> {
+        now.to_be_bytes().to_vec()
+    }
+
+    fn key_of(expires_at: u64, fields: &[&[u8]]) -> Vec<u8> {
+        let mut hasher = Sha256::new();
+        for field in fields {
+            hasher.update((field.len() as u64).to_be_bytes());
+            hasher.update(field);
+        }
+
+        let mut key = expires_at.to_be_bytes().to_vec();
+        key.extend_from_slice(&hasher.finalize());
+        key
+    }
+}
+
+impl Display for UsedProofModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "UsedProof [expires_at: {}]", self.expires_at)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key() {
+        let key = UsedProofModel::key(60, "auth", &[1, 2]);
+        assert_eq!(key, UsedProofModel::key(60, "auth", &[1, 2]));
+        assert_ne!(key, UsedProofModel::key(60, "auth", &[1, 3]));
+        assert_ne!(key, UsedProofModel::key(60, "other", &[1, 2]));
+        // The lengths keep the boundary between the fields
+        assert_ne!(
+            UsedProofModel::key(60, "auth\u{1}", &[2]),
+            UsedProofModel::key(60, "auth", &[1, 2])
+        );
+        assert_ne!(
+            UsedProofModel::non_interactive_key(60, "nyan", &[1], &[2]),
+            UsedProofModel::non_interactive_key(60, "cat", &[1], &[2])
+        );
+    }
+
+    #[test]
+    fn keys_sort_by_expiry() {
+        let expired = UsedProofModel::key(59, "auth", &[0xff]);
+        let current = UsedProofModel::key(60, "auth", &[0]);
+        let bound = UsedProofModel::expired_before(60);
+        assert!(expired < bound);
+        assert!(bound <= current);
+        assert!(UsedProofModel::non_interactive_key(59, "nyan", &[1], &[2]) < bound);
+    }
+}
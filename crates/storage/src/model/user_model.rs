@@ -3,26 +3,177 @@ use std::fmt;
 use std::fmt::Display;
 use std::hash::{Hash, Hasher};
 
-use serde::{Deserialize, Serialize};
+use bincode::Options;
+use serde::de::DeserializeOwned;
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use sha2::{Digest, Sha256};
+
+use crate::model::redact::redact;
+use crate::model::versioned;
+use crate::StorageError::DeserializationFailed;
+use crate::StorageResult;
+
+/// Protocol the keys of a slot were generated for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyAlgorithm {
+    Interactive,
+    NonInteractive,
+}
+
+/// One public key pair of a user, e.g. one per device. A login is answered for a single slot.
+///
+/// `Display` and `Debug` redact `y1` and `y2` like `UserModel`.
+#[derive(Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct KeySlot {
+    pub id: u32,
+    pub y1: String,
+    pub y2: String,
+    pub algorithm: KeyAlgorithm,
+}
+
+impl KeySlot {
+    /// Id of the slot registration creates, records from before key slots are migrated into it
+    pub const FIRST: u32 = 0;
+
+    fn display(&self, key: impl Fn(&str) -> String) -> String {
+        format!(
+            "KeySlot [id: {}, y1: {}, y2: {}]",
+            self.id,
+            key(&self.y1),
+            key(&self.y2)
+        )
+    }
+}
+
+impl Display for KeySlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.display(redact))
+    }
+}
+
+impl fmt::Debug for KeySlot {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeySlot")
+            .field("id", &self.id)
+            .field("y1", &redact(&self.y1))
+            .field("y2", &redact(&self.y2))
+            .field("algorithm", &self.algorithm)
+            .finish()
+    }
+}
 
 /// `UserModel` represents the data model for user authentication.
 ///
 /// This model includes necessary fields from the `RegisterRequest` struct,
 /// and may or may not include additional fields specific to the authentication layer.
-#[derive(Serialize, Deserialize, Debug)]
+///
+/// Stored as `version || (user, slots, group, generators, reregister_required)`, see
+/// `versioned`. Human-readable formats get the fields by name instead, see `dump`.
+///
+/// `Display` and `Debug` shorten the keys with `redact` so logging a user doesn't dump them,
+/// `display_full` prints them whole.
+#[derive(Clone, PartialEq, Eq)]
 pub struct UserModel {
     pub user: String,
-    pub y1: String,
-    pub y2: String,
+    /// Public key pairs any of which can log the user in, registration creates `KeySlot::FIRST`
+    pub slots: Vec<KeySlot>,
+    /// Bit length of the MODP group the interactive keys were generated in
+    pub group: u32,
+    /// Fingerprint of the parameters of `group` the interactive keys were generated with, empty
+    /// for records from before it was stored. See `migration::flag_stale_generators`.
+    pub generators: String,
+    /// The generators of `group` changed since the keys were generated, they can't verify a proof
+    /// anymore and the user has to register again before an interactive login
+    pub reregister_required: bool,
 }
 
 impl UserModel {
+    /// Bumped whenever the stored layout changes, `from_vN` reads the records of version `N`
+    pub const VERSION: u8 = 4;
+
+    /// Group of v0 records, they were all registered against the 3072-bit MODP group
+    pub const LEGACY_GROUP: u32 = 3072;
+
+    /// Reads a record written before versioning, the plain bincode of `(user, y1, y2)`. The keys
+    /// go into `KeySlot::FIRST`, `algorithm` tells from `y1` what they were generated for.
+    pub fn from_v0(bytes: &[u8], algorithm: impl Fn(&str) -> KeyAlgorithm) -> StorageResult<Self> {
+        let (user, y1, y2): (String, String, String) = exact(bytes)?;
+        Ok(Self::single_key(
+            user,
+            y1,
+            y2,
+            Self::LEGACY_GROUP,
+            algorithm,
+        ))
+    }
+
+    /// Reads a record of the first versioned layout, `version || (user, y1, y2)`. The group is
+    /// filled in like in `from_v0` and the keys go into `KeySlot::FIRST`.
+    pub fn from_v1(bytes: &[u8], algorithm: impl Fn(&str) -> KeyAlgorithm) -> StorageResult<Self> {
+        let (version, (user, y1, y2)): (u8, (String, String, String)) = exact(bytes)?;
+        check_version(version, 1)?;
+        Ok(Self::single_key(
+            user,
+            y1,
+            y2,
+            Self::LEGACY_GROUP,
+            algorithm,
+        ))
+    }
+
+    /// Reads a record written before key slots, `version || (user, y1, y2, group)`
+    pub fn from_v2(bytes: &[u8], algorithm: impl Fn(&str) -> KeyAlgorithm) -> StorageResult<Self> {
+        let (version, (user, y1, y2, group)): (u8, (String, String, String, u32)) = exact(bytes)?;
+        check_version(version, 2)?;
+        Ok(Self::single_key(user, y1, y2, group, algorithm))
+    }
+
+    /// Reads a record written before the generators were stored, `version || (user, slots,
+    /// group)`
+    pub fn from_v3(bytes: &[u8]) -> StorageResult<Self> {
+        let (version, (user, slots, group)): (u8, (String, Vec<KeySlot>, u32)) = exact(bytes)?;
+        check_version(version, 3)?;
+        Ok(Self {
+            user,
+            slots,
+            group,
+            generators: String::new(),
+            reregister_required: false,
+        })
+    }
+
+    /// Record of the layouts before key slots, the keys go into `KeySlot::FIRST`
+    fn single_key(
+        user: String,
+        y1: String,
+        y2: String,
+        group: u32,
+        algorithm: impl Fn(&str) -> KeyAlgorithm,
+    ) -> Self {
+        Self {
+            user,
+            slots: vec![Self::first_slot(y1, y2, algorithm)],
+            group,
+            generators: String::new(),
+            reregister_required: false,
+        }
+    }
+
+    fn first_slot(y1: String, y2: String, algorithm: impl Fn(&str) -> KeyAlgorithm) -> KeySlot {
+        KeySlot {
+            id: KeySlot::FIRST,
+            algorithm: algorithm(&y1),
+            y1,
+            y2,
+        }
+    }
+
+    /// Storage key of `user`, the SHA-256 of the name. Earlier versions used a `DefaultHasher`
+    /// value, which isn't stable across Rust versions, `migrate_users` moves those records.
     pub fn user_id(user: &String) -> Vec<u8> {
-        let mut hasher = DefaultHasher::new();
-        let _ = user.hash(&mut hasher);
-        let k = hasher.finish();
-        // @todo: fix
-        k.to_string().as_bytes().to_vec()
+        Sha256::digest(user.as_bytes()).to_vec()
     }
 
     pub fn auth_id(&self) -> String {
@@ -30,22 +181,296 @@ impl UserModel {
         self.hash(&mut hasher);
         hasher.finish().to_string()
     }
+
+    pub fn slot(&self, id: u32) -> Option<&KeySlot> {
+        self.slots.iter().find(|slot| slot.id == id)
+    }
+
+    /// One past the highest slot id in use
+    pub fn next_slot_id(&self) -> u32 {
+        self.slots
+            .iter()
+            .map(|slot| slot.id + 1)
+            .max()
+            .unwrap_or(KeySlot::FIRST)
+    }
+
+    /// Whether any slot holds keys of the interactive protocol, only those depend on the generators
+    pub fn has_interactive_keys(&self) -> bool {
+        self.slots
+            .iter()
+            .any(|slot| slot.algorithm == KeyAlgorithm::Interactive)
+    }
+
+    /// `Display` without the redaction
+    pub fn display_full(&self) -> String {
+        self.display(str::to_string)
+    }
+
+    fn display(&self, key: impl Fn(&str) -> String) -> String {
+        let slots: Vec<String> = self.slots.iter().map(|slot| slot.display(&key)).collect();
+        format!(
+            "UserModel [user: {}, slots: [{}], group: {}]",
+            self.user,
+            slots.join(", "),
+            self.group,
+        )
+    }
+}
+
+/// bincode like `bincode::deserialize` but all of `bytes` has to be consumed, so a shorter layout
+/// doesn't read the prefix of a longer one
+fn exact<T: DeserializeOwned>(bytes: &[u8]) -> StorageResult<T> {
+    bincode::DefaultOptions::new()
+        .with_fixint_encoding()
+        .reject_trailing_bytes()
+        .deserialize(bytes)
+        .map_err(|e| DeserializationFailed(format!("Deserialization failed: {}", e)))
+}
+
+fn check_version(version: u8, expected: u8) -> StorageResult<()> {
+    if version != expected {
+        return Err(DeserializationFailed(format!(
+            "unsupported version {}",
+            version
+        )));
+    }
+    Ok(())
+}
+
+impl Serialize for UserModel {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if serializer.is_human_readable() {
+            let mut state = serializer.serialize_struct("UserModel", 5)?;
+            state.serialize_field("user", &self.user)?;
+            state.serialize_field("slots", &self.slots)?;
+            state.serialize_field("group", &self.group)?;
+            state.serialize_field("generators", &self.generators)?;
+            state.serialize_field("reregister_required", &self.reregister_required)?;
+            return state.end();
+        }
+
+        versioned::serialize(
+            Self::VERSION,
+            &(
+                &self.user,
+                &self.slots,
+                self.group,
+                &self.generators,
+                self.reregister_required,
+            ),
+            serializer,
+        )
+    }
+}
+
+impl<'de> Deserialize<'de> for UserModel {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (user, slots, group, generators, reregister_required) =
+            versioned::deserialize(Self::VERSION, deserializer)?;
+        Ok(Self {
+            user,
+            slots,
+            group,
+            generators,
+            reregister_required,
+        })
+    }
 }
 
 impl Hash for UserModel {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.user.hash(state);
-        self.y1.hash(state);
-        self.y2.hash(state);
+        self.slots.hash(state);
+        self.group.hash(state);
+        self.generators.hash(state);
+        self.reregister_required.hash(state);
     }
 }
 
 impl Display for UserModel {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "UserModel [user: {}, y1: {}, y2: {}]",
-            self.user, self.y1, self.y2,
-        )
+        write!(f, "{}", self.display(redact))
+    }
+}
+
+impl fmt::Debug for UserModel {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UserModel")
+            .field("user", &self.user)
+            .field("slots", &self.slots)
+            .field("group", &self.group)
+            .field("generators", &self.generators)
+            .field("reregister_required", &self.reregister_required)
+            .finish()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::db::{KeyValueStorage, StorageTree};
+
+    use super::*;
+
+    fn slot(id: u32, y1: &str, y2: &str) -> KeySlot {
+        KeySlot {
+            id,
+            y1: y1.to_string(),
+            y2: y2.to_string(),
+            algorithm: KeyAlgorithm::Interactive,
+        }
+    }
+
+    fn nyan() -> UserModel {
+        UserModel {
+            user: "Nyan".to_string(),
+            slots: vec![slot(KeySlot::FIRST, "2", "3")],
+            group: UserModel::LEGACY_GROUP,
+            generators: String::new(),
+            reregister_required: false,
+        }
+    }
+
+    fn interactive(_: &str) -> KeyAlgorithm {
+        KeyAlgorithm::Interactive
+    }
+
+    #[test]
+    fn redacted_display() {
+        let user = UserModel {
+            slots: vec![
+                slot(0, "abc1230000000000def456", "AAECAwQFBgcICQoLDA0ODw"),
+                slot(1, "2", "3"),
+            ],
+            group: 2048,
+            ..nyan()
+        };
+        assert_eq!(
+            user.to_string(),
+            "UserModel [user: Nyan, slots: [KeySlot [id: 0, y1: abc123…def456, y2: AAECAw…DA0ODw], \
+             KeySlot [id: 1, y1: 2, y2: 3]], group: 2048]"
+        );
+        assert_eq!(
+            user.display_full(),
+            "UserModel [user: Nyan, slots: [KeySlot [id: 0, y1: abc1230000000000def456, \
+             y2: AAECAwQFBgcICQoLDA0ODw], KeySlot [id: 1, y1: 2, y2: 3]], group: 2048]"
+        );
+
+        let debug = format!("{:?}", user);
+        assert!(debug.contains("abc123…def456"));
+        assert!(!debug.contains(&user.slots[0].y1));
+        assert!(!debug.contains(&user.slots[0].y2));
+    }
+
+    #[test]
+    fn versioned_round_trip() {
+        let bytes = bincode::serialize(&nyan()).unwrap();
+        assert_eq!(bytes[0], UserModel::VERSION);
+        assert_eq!(bincode::deserialize::<UserModel>(&bytes).unwrap(), nyan());
+    }
+
+    #[test]
+    fn unsupported_version() {
+        let db = KeyValueStorage::temporary();
+        let key = UserModel::user_id(&"Nyan".to_string());
+        db.insert(StorageTree::Auth, &key, (7u8, ("Nyan", "2", "3", 2048u32)))
+            .unwrap();
+
+        let result = db.get::<UserModel>(StorageTree::Auth, &key);
+        assert!(matches!(
+            result,
+            Err(DeserializationFailed(message)) if message == "unsupported version 7"
+        ));
+    }
+
+    #[test]
+    fn slots() {
+        let mut user = nyan();
+        assert_eq!(user.slot(0), Some(&slot(0, "2", "3")));
+        assert_eq!(user.slot(1), None);
+        assert_eq!(user.next_slot_id(), 1);
+
+        user.slots.push(slot(3, "4", "5"));
+        assert_eq!(user.next_slot_id(), 4);
+        user.slots.clear();
+        assert_eq!(user.next_slot_id(), KeySlot::FIRST);
+    }
+
+    #[test]
+    fn migrate_v0() {
+        let v0 = bincode::serialize(&("Nyan", "2", "3")).unwrap();
+        assert!(bincode::deserialize::<UserModel>(&v0).is_err());
+        assert!(UserModel::from_v1(&v0, interactive).is_err());
+
+        let migrated = UserModel::from_v0(&v0, interactive).unwrap();
+        assert_eq!(migrated, nyan());
+
+        let db = KeyValueStorage::temporary();
+        let key = UserModel::user_id(&migrated.user);
+        db.insert(StorageTree::Auth, &key, &migrated).unwrap();
+        assert_eq!(
+            db.get::<UserModel>(StorageTree::Auth, &key).unwrap(),
+            nyan()
+        );
+    }
+
+    #[test]
+    fn migrate_v1() {
+        let v1 = bincode::serialize(&(1u8, ("Nyan", "2", "3"))).unwrap();
+        assert!(bincode::deserialize::<UserModel>(&v1).is_err());
+
+        let migrated = UserModel::from_v1(&v1, |y1| match y1 {
+            "2" => KeyAlgorithm::NonInteractive,
+            _ => KeyAlgorithm::Interactive,
+        })
+        .unwrap();
+        assert_eq!(
+            migrated.slots,
+            [KeySlot {
+                algorithm: KeyAlgorithm::NonInteractive,
+                ..slot(KeySlot::FIRST, "2", "3")
+            }]
+        );
+        assert_eq!(migrated.group, UserModel::LEGACY_GROUP);
+
+        // The version 0 record of a 1 byte name starts with a 1 as well
+        let v0 = bincode::serialize(&("N", "2", "3")).unwrap();
+        assert_eq!(v0[0], 1);
+        assert!(UserModel::from_v1(&v0, interactive).is_err());
+        assert_eq!(UserModel::from_v0(&v0, interactive).unwrap().user, "N");
+    }
+
+    #[test]
+    fn migrate_v2() {
+        let v2 = bincode::serialize(&(2u8, ("Nyan", "2", "3", 2048u32))).unwrap();
+        assert!(bincode::deserialize::<UserModel>(&v2).is_err());
+        assert!(UserModel::from_v1(&v2, interactive).is_err());
+
+        let migrated = UserModel::from_v2(&v2, interactive).unwrap();
+        assert_eq!(
+            migrated,
+            UserModel {
+                group: 2048,
+                ..nyan()
+            }
+        );
+    }
+
+    #[test]
+    fn migrate_v3() {
+        let v3 = bincode::serialize(&(3u8, ("Nyan", vec![slot(3, "2", "3")], 2048u32))).unwrap();
+        assert!(bincode::deserialize::<UserModel>(&v3).is_err());
+        assert!(UserModel::from_v2(&v3, interactive).is_err());
+
+        let migrated = UserModel::from_v3(&v3).unwrap();
+        assert_eq!(
+            migrated,
+            UserModel {
+                slots: vec![slot(3, "2", "3")],
+                group: 2048,
+                ..nyan()
+            }
+        );
+        assert!(UserModel::from_v3(&bincode::serialize(&nyan()).unwrap()).is_err());
     }
 }
@@ -0,0 +1,58 @@
+use std::fmt;
+use std::marker::PhantomData;
+
+use serde::de::{self, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// Serializes `value` as `(version, value)` so the layout a record was written with can be
+/// detected when it is read back.
+pub fn serialize<T: Serialize, S: Serializer>(
+    version: u8,
+    value: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    (version, value).serialize(serializer)
+}
+
+/// Reads a record written by `serialize`, fails with `unsupported version N` before touching
+/// the payload if the version byte is not `version`.
+pub fn deserialize<'de, T: Deserialize<'de>, D: Deserializer<'de>>(
+    version: u8,
+    deserializer: D,
+) -> Result<T, D::Error> {
+    deserializer.deserialize_tuple(
+        2,
+        VersionedVisitor {
+            version,
+            value: PhantomData,
+        },
+    )
+}
+
+struct VersionedVisitor<T> {
+    version: u8,
+    value: PhantomData<T>,
+}
+
+impl<'de, T: Deserialize<'de>> Visitor<'de> for VersionedVisitor<T> {
+    type Value = T;
+
+    fn expecting(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "a version {} record", self.version)
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<T, A::Error> {
+        let version: u8 = seq
+            .next_element()?
+            .ok_or_else(|| de::Error::invalid_length(0, &self))?;
+        if version != self.version {
+            return Err(de::Error::custom(format!(
+                "unsupported version {}",
+                version
+            )));
+        }
+
+        seq.next_element()?
+            .ok_or_else(|| de::Error::invalid_length(1, &self))
+    }
+}
@@ -0,0 +1,158 @@
+//! Complete logins against an in-process `LocalAuth`, the interactive protocol in every group and
+//! the non-interactive one. Storage is temporary and never flushed in the background, so the
+//! numbers are dominated by the protocol itself. The allocations of a single login are printed
+//! before each benchmark, criterion only measures time.
+//!
+//! `cargo bench -p zkp --bench login`
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use num_bigint::BigInt;
+use tokio::runtime::Runtime;
+
+use chaum_pedersen::chaum_pedersen::{ChaumPedersen, Group};
+use chaum_pedersen::ecc_chaum_pedersen::EccChaumPedersen;
+use chaum_pedersen::prover::Prover;
+use chaum_pedersen::utils::bigint_from_hex;
+use chaum_pedersen::ChaumPedersenTrait;
+use curve25519_dalek::Scalar;
+use storage::db::{KeyValueStorage, StorageConfig};
+use storage::model::user_model::KeySlot;
+use zkp::clock::{Clock, SystemClock};
+use zkp::local_auth::{Answer, LocalAuth};
+
+/// Counts every allocation of the process, including the blocking tasks the proofs run on
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+fn local_auth() -> LocalAuth {
+    LocalAuth::with_storage(KeyValueStorage::open_with(StorageConfig {
+        temporary: true,
+        flush_every_ms: None,
+        ..StorageConfig::default()
+    }))
+}
+
+/// Commitment, challenge and answer, the three messages of the interactive protocol
+async fn interactive_login(auth: &LocalAuth, group: Group, x: &BigInt) {
+    let mut prover = Prover::new(auth.protocol(group));
+    let (r1, r2) = prover.commit().await.unwrap();
+    let challenge = auth
+        .create_challenge("Nyan", (r1.to_str_radix(16), r2.to_str_radix(16)), group)
+        .await
+        .unwrap();
+    let s = prover
+        .respond(bigint_from_hex(&challenge.c).unwrap(), x.clone())
+        .unwrap();
+    auth.verify(&challenge.auth_id, Answer::Solution(s.to_str_radix(16)))
+        .await
+        .unwrap();
+}
+
+/// The proof with its commitment in a single message
+async fn non_interactive_login(auth: &LocalAuth, ecc: &EccChaumPedersen, x: Scalar) {
+    let created_at = SystemClock.now_unix();
+    let ecc = ecc.clone().with_timestamp(created_at);
+    let (k, r1, r2) = ecc.prover_commit().await.unwrap();
+    let c = ecc.derive_challenge(&r1, &r2);
+    let s = ecc.prover_solve_challenge(k, c, x);
+    let (r1, r2) = (
+        EccChaumPedersen::to_base64(&r1),
+        EccChaumPedersen::to_base64(&r2),
+    );
+    auth.verify_non_interactive_with_commitment(
+        "Nyan",
+        KeySlot::FIRST,
+        created_at,
+        Some((&r1, &r2)),
+        &serde_json::to_string(&s).unwrap(),
+        &serde_json::to_string(&c).unwrap(),
+    )
+    .await
+    .unwrap();
+}
+
+/// Allocations of a single run of `login`
+fn allocations<F: std::future::Future>(runtime: &Runtime, login: F) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    runtime.block_on(login);
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+fn login(c: &mut Criterion) {
+    let runtime = Runtime::new().unwrap();
+    let mut bench = c.benchmark_group("login");
+
+    for group in Group::ALL {
+        let auth = local_auth();
+        let x = ChaumPedersen::hash(b"cat");
+        runtime.block_on(async {
+            let (y1, y2) = auth
+                .protocol(group)
+                .generate_public_keys(x.clone())
+                .await
+                .unwrap();
+            auth.register("Nyan", y1.to_str_radix(16), y2.to_str_radix(16), group)
+                .await
+                .unwrap();
+        });
+
+        let allocated = allocations(&runtime, interactive_login(&auth, group, &x));
+        println!("login/interactive/{}: {} allocations", group, allocated);
+        bench.bench_with_input(
+            BenchmarkId::new("interactive", group),
+            &group,
+            |b, &group| {
+                b.to_async(&runtime)
+                    .iter(|| interactive_login(&auth, group, &x))
+            },
+        );
+    }
+
+    let auth = local_auth();
+    let ecc = EccChaumPedersen::new();
+    let x = EccChaumPedersen::hash(b"cat");
+    runtime.block_on(async {
+        let (y1, y2) = ecc.generate_public_keys(x).await.unwrap();
+        auth.register(
+            "Nyan",
+            EccChaumPedersen::to_base64(&y1),
+            EccChaumPedersen::to_base64(&y2),
+            Group::default(),
+        )
+        .await
+        .unwrap();
+    });
+
+    let allocated = allocations(&runtime, non_interactive_login(&auth, &ecc, x));
+    println!(
+        "login/non_interactive/ristretto255: {} allocations",
+        allocated
+    );
+    bench.bench_function(BenchmarkId::new("non_interactive", "ristretto255"), |b| {
+        b.to_async(&runtime)
+            .iter(|| non_interactive_login(&auth, &ecc, x))
+    });
+
+    bench.finish();
+}
+
+criterion_group!(benches, login);
+criterion_main!(benches);
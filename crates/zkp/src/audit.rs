@@ -0,0 +1,99 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::Mutex;
+
+use serde::Serialize;
+use tracing::warn;
+
+use crate::local_auth::Protocol;
+
+/// Audit log of the server, appended to in its working directory
+pub const AUDIT_LOG_PATH: &str = "audit.log";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Success,
+    Failure,
+}
+
+/// Decision on a login attempt
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct AuthEvent {
+    pub user: String,
+    /// Challenge the attempt answered, `None` for non-interactive logins
+    pub auth_id: Option<String>,
+    pub algorithm: Protocol,
+    pub outcome: Outcome,
+    /// Seconds since the UNIX epoch
+    pub timestamp: u64,
+}
+
+pub trait AuditSink: Send + Sync {
+    fn record(&self, event: AuthEvent);
+}
+
+/// Appends every event as a line of JSON
+pub struct JsonLinesSink {
+    file: Mutex<File>,
+}
+
+impl JsonLinesSink {
+    /// Opens `path` for appending, creating it if it doesn't exist
+    pub fn open(path: &Path) -> io::Result<Self> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self {
+            file: Mutex::new(file),
+        })
+    }
+}
+
+impl AuditSink for JsonLinesSink {
+    fn record(&self, event: AuthEvent) {
+        let line = serde_json::to_string(&event).expect("AuthEvent serializes to JSON");
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        // One write per line, so concurrent appenders never interleave within a line
+        if let Err(e) = file.write_all(format!("{}\n", line).as_bytes()) {
+            warn!(error = %e, "failed to write audit event");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn json_lines() {
+        let path = std::env::temp_dir().join(format!("zkp-audit-{}.log", std::process::id()));
+        let sink = JsonLinesSink::open(&path).unwrap();
+
+        let event = AuthEvent {
+            user: "Nyan".to_string(),
+            auth_id: Some("42".to_string()),
+            algorithm: Protocol::Interactive,
+            outcome: Outcome::Success,
+            timestamp: 1_700_000_000,
+        };
+        sink.record(event.clone());
+        sink.record(AuthEvent {
+            auth_id: None,
+            algorithm: Protocol::NonInteractive,
+            outcome: Outcome::Failure,
+            ..event
+        });
+
+        let log = fs::read_to_string(&path);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            log.unwrap(),
+            "{\"user\":\"Nyan\",\"auth_id\":\"42\",\"algorithm\":\"interactive\",\
+             \"outcome\":\"success\",\"timestamp\":1700000000}\n\
+             {\"user\":\"Nyan\",\"auth_id\":null,\"algorithm\":\"non_interactive\",\
+             \"outcome\":\"failure\",\"timestamp\":1700000000}\n"
+        );
+    }
+}
@@ -0,0 +1,104 @@
+use std::fmt;
+
+use tonic::transport::{Channel, Endpoint};
+
+pub const DEFAULT_SERVER_URL: &str = "http://0.0.0.0:50051";
+pub const SERVER_URL_ENV: &str = "ZKP_SERVER_URL";
+
+#[derive(Debug, Clone)]
+pub enum ClientError {
+    InvalidUrl(String),
+    ConnectionFailed(String),
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ClientError::InvalidUrl(s) => write!(f, "Invalid server url: {}", s),
+            ClientError::ConnectionFailed(s) => {
+                write!(
+                    f,
+                    "Could not connect to the zkp server at {}, is it running?",
+                    s
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for ClientError {}
+
+/// Resolves the server url, the `--server` flag takes precedence over the `ZKP_SERVER_URL` env var,
+/// if neither is set `DEFAULT_SERVER_URL` is used.
+pub fn server_url(flag: Option<&String>, env: Option<String>) -> String {
+    flag.cloned()
+        .or(env)
+        .unwrap_or_else(|| DEFAULT_SERVER_URL.to_string())
+}
+
+/// Builds the endpoint for `url`, only absolute `http` and `https` urls are accepted.
+pub fn endpoint(url: &str) -> Result<Endpoint, ClientError> {
+    let endpoint = Endpoint::from_shared(url.to_string())
+        .map_err(|e| ClientError::InvalidUrl(format!("{} ({})", url, e)))?;
+
+    let uri = endpoint.uri();
+    match (uri.scheme_str(), uri.host()) {
+        (Some("http") | Some("https"), Some(_)) => Ok(endpoint),
+        _ => Err(ClientError::InvalidUrl(format!(
+            "{} (expected http://host:port or https://host:port)",
+            url
+        ))),
+    }
+}
+
+pub async fn connect(url: &str) -> Result<Channel, ClientError> {
+    endpoint(url)?
+        .connect()
+        .await
+        .map_err(|_| ClientError::ConnectionFailed(url.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_url() {
+        let url = server_url(None, None);
+        assert_eq!(url, DEFAULT_SERVER_URL);
+
+        let endpoint = endpoint(&url).unwrap();
+        assert_eq!(endpoint.uri().host(), Some("0.0.0.0"));
+        assert_eq!(endpoint.uri().port_u16(), Some(50051));
+    }
+
+    #[test]
+    fn overridden_url() {
+        let env = Some("http://env.example:50051".to_string());
+        assert_eq!(server_url(None, env.clone()), "http://env.example:50051");
+
+        let flag = "https://zkp.example:443".to_string();
+        let url = server_url(Some(&flag), env);
+        assert_eq!(url, flag);
+
+        let endpoint = endpoint(&url).unwrap();
+        assert_eq!(endpoint.uri().scheme_str(), Some("https"));
+        assert_eq!(endpoint.uri().host(), Some("zkp.example"));
+    }
+
+    #[test]
+    fn invalid_url() {
+        assert!(matches!(
+            endpoint("not a url"),
+            Err(ClientError::InvalidUrl(_))
+        ));
+        assert!(matches!(
+            endpoint("ftp://zkp.example:21"),
+            Err(ClientError::InvalidUrl(_))
+        ));
+        assert!(matches!(
+            endpoint("0.0.0.0:50051"),
+            Err(ClientError::InvalidUrl(_))
+        ));
+    }
+}
@@ -0,0 +1,481 @@
+//! The subcommands of the client as functions, run once from the command line or line by line
+//! from the `repl` over the same connection.
+
+use std::collections::BTreeMap;
+
+use clap::ArgMatches;
+use ed25519_dalek::VerifyingKey;
+use tonic::transport::Channel;
+
+use chaum_pedersen::chaum_pedersen::{ChaumPedersen, Group};
+use chaum_pedersen::ecc_chaum_pedersen::EccChaumPedersen;
+use chaum_pedersen::proof::Proof;
+use chaum_pedersen::prover::Prover;
+use chaum_pedersen::utils::{
+    chaum_pedersen_factory, decode_field, encode_field, ChaumPedersenFactoryType,
+};
+use chaum_pedersen::ChaumPedersenTrait;
+use zkp::clock::{Clock, SystemClock};
+
+use crate::retry::{retry, RetryPolicy};
+use crate::signature::{nonce, verify};
+use crate::utils::{mismatched_keys, self_check};
+use crate::zkp::auth_client::AuthClient;
+use crate::zkp::{
+    Algorithm, AuthenticationAnswerRequest, AuthenticationChallengeRequest,
+    NonInteractiveAuthenticationRequest, PublicKeysRequest, RegisterRequest, UnregisterRequest,
+};
+use crate::{idempotency_key, retry_policy, selected_group, selected_server_key};
+
+pub type CommandResult<T = ()> = Result<T, Box<dyn std::error::Error>>;
+
+/// Connection and state shared by the commands of one invocation, or of a whole `repl`
+pub struct Session {
+    client: AuthClient<Channel>,
+    /// Session id of the last login of every user
+    pub session_ids: BTreeMap<String, String>,
+}
+
+impl Session {
+    pub fn new(channel: Channel) -> Self {
+        Self {
+            client: AuthClient::new(channel),
+            session_ids: BTreeMap::new(),
+        }
+    }
+}
+
+/// Global flags, given once on the command line or with every command in the `repl`
+#[derive(Debug, Clone, Copy)]
+struct Options {
+    group: Group,
+    slot_id: u32,
+    policy: RetryPolicy,
+    /// Key the enrollments and sessions have to be signed with, unchecked if `None`
+    server_key: Option<VerifyingKey>,
+}
+
+/// Runs the subcommand of `matches` on the connection of `session`, `repl` is handled by the caller
+pub async fn execute(session: &mut Session, matches: &ArgMatches) -> CommandResult {
+    let options = Options {
+        group: selected_group(matches),
+        slot_id: matches.get_one::<u32>("slot").copied().unwrap_or_default(),
+        policy: retry_policy(matches),
+        server_key: selected_server_key(matches)?,
+    };
+
+    match matches.subcommand() {
+        Some(("register", sub)) => register(&mut session.client, options, sub).await,
+        Some(("login", sub)) => {
+            let (user, session_id) = login(&mut session.client, options, sub).await?;
+            session.session_ids.insert(user, session_id);
+            Ok(())
+        }
+        Some(("unregister", sub)) => {
+            let user = unregister(&mut session.client, options, sub).await?;
+            session.session_ids.remove(&user);
+            Ok(())
+        }
+        Some(("inspect", sub)) => inspect(&mut session.client, options, sub).await,
+        _ => unreachable!(),
+    }
+}
+
+async fn register(
+    client: &mut AuthClient<Channel>,
+    options: Options,
+    sub: &ArgMatches,
+) -> CommandResult {
+    let Options {
+        group,
+        slot_id,
+        policy,
+        server_key,
+    } = options;
+    let user_name = sub.get_one::<String>("name").expect("name is required");
+    let is_interactive = sub
+        .get_one::<String>("algorithm")
+        .map(|schema| schema != "non-interactive")
+        .unwrap_or(true);
+
+    match chaum_pedersen_factory(is_interactive, group) {
+        ChaumPedersenFactoryType::Interactive(schema) => {
+            info!("Interactive protocol, {}-bit group", group);
+            let secret_x = sub
+                .get_one::<String>("password")
+                .map(|pw| ChaumPedersen::hash(pw.as_bytes()))
+                .expect("password is required");
+            let (y1, y2) = schema.generate_public_keys(secret_x).await?;
+
+            // Re-registering answers a challenge with the secret of the registered keys
+            let (auth_id, s) = match sub.get_one::<String>("old-password") {
+                Some(old_password) => {
+                    let mut prover = Prover::new(&schema);
+                    let (r1, r2) = prover.commit().await?;
+                    let request = AuthenticationChallengeRequest {
+                        user: user_name.clone(),
+                        r1: encode_field(&r1, schema.byte_len()),
+                        r2: encode_field(&r2, schema.byte_len()),
+                        group: group.bits(),
+                        enrollment: String::new(),
+                        slot_id,
+                    };
+                    let challenge = retry(&policy, || {
+                        let (mut client, request) = (client.clone(), request.clone());
+                        async move {
+                            client
+                                .create_authentication_challenge(tonic::Request::new(request))
+                                .await
+                        }
+                    })
+                    .await?
+                    .into_inner();
+                    let c = decode_field(&challenge.c, schema.byte_len())?;
+                    let old_x = ChaumPedersen::hash(old_password.as_bytes());
+                    let s = prover.respond(c, old_x)?;
+                    (challenge.auth_id, encode_field(&s, schema.byte_len()))
+                }
+                None => (String::new(), String::new()),
+            };
+
+            let request = RegisterRequest {
+                user: user_name.clone(),
+                y1: encode_field(&y1, schema.byte_len()),
+                y2: encode_field(&y2, schema.byte_len()),
+                group: group.bits(),
+                auth_id,
+                s,
+                idempotency_key: idempotency_key(sub, &policy),
+                nonce: nonce(),
+            };
+            // Replacing the keys consumes the challenge, like a login it's sent once
+            let response = if request.auth_id.is_empty() {
+                retry(&policy, || {
+                    let (mut client, request) = (client.clone(), request.clone());
+                    async move { client.register(tonic::Request::new(request)).await }
+                })
+                .await?
+            } else {
+                client
+                    .register(tonic::Request::new(request.clone()))
+                    .await?
+            };
+            verify(server_key.as_ref(), &request.nonce, response.get_ref())?;
+            info!("Enrollment token {}", response.get_ref().enrollment);
+        }
+        ChaumPedersenFactoryType::NonInteractive(ecc_schema) => {
+            info!("Non interactive protocol");
+            let secret_x = sub
+                .get_one::<String>("password")
+                .map(|pw| EccChaumPedersen::hash(pw.as_bytes()))
+                .expect("password is required");
+            let (pk_y1, pk_y2) = ecc_schema.generate_public_keys(secret_x).await?;
+
+            let request = RegisterRequest {
+                user: user_name.clone(),
+                y1: EccChaumPedersen::to_base64(&pk_y1),
+                y2: EccChaumPedersen::to_base64(&pk_y2),
+                group: group.bits(),
+                auth_id: String::new(),
+                s: String::new(),
+                idempotency_key: idempotency_key(sub, &policy),
+                nonce: nonce(),
+            };
+            let response = retry(&policy, || {
+                let (mut client, request) = (client.clone(), request.clone());
+                async move { client.register(tonic::Request::new(request)).await }
+            })
+            .await?;
+            verify(server_key.as_ref(), &request.nonce, response.get_ref())?;
+        }
+    }
+    info!("Successfully registered {}", user_name);
+    Ok(())
+}
+
+/// Logs in and returns the user with its session id
+async fn login(
+    client: &mut AuthClient<Channel>,
+    options: Options,
+    sub: &ArgMatches,
+) -> CommandResult<(String, String)> {
+    let Options {
+        group,
+        slot_id,
+        policy,
+        server_key,
+    } = options;
+    let user_name = sub.get_one::<String>("name").expect("name is required");
+    let is_interactive = sub
+        .get_one::<String>("algorithm")
+        .map(|schema| schema != "non-interactive")
+        .unwrap_or(true);
+
+    let session_id = match chaum_pedersen_factory(is_interactive, group) {
+        ChaumPedersenFactoryType::Interactive(schema) => {
+            info!("Interactive protocol, {}-bit group", group);
+            let secret_x = sub
+                .get_one::<String>("password")
+                .map(|pw| ChaumPedersen::hash(pw.as_bytes()))
+                .expect("password is required");
+
+            // === Commitment === //
+            let mut prover = Prover::new(&schema);
+            let (r1, r2) = prover.commit().await?;
+            let request = AuthenticationChallengeRequest {
+                user: user_name.clone(),
+                r1: encode_field(&r1, schema.byte_len()),
+                r2: encode_field(&r2, schema.byte_len()),
+                group: group.bits(),
+                enrollment: sub
+                    .get_one::<String>("enrollment")
+                    .cloned()
+                    .unwrap_or_default(),
+                slot_id,
+            };
+            // A retried challenge the server already stored just expires unanswered
+            let auth_challenge_response = retry(&policy, || {
+                let (mut client, request) = (client.clone(), request.clone());
+                async move {
+                    client
+                        .create_authentication_challenge(tonic::Request::new(request))
+                        .await
+                }
+            })
+            .await?;
+
+            // === Verifier sent the challenge, let's solve it === //
+            let auth_id = &auth_challenge_response.get_ref().auth_id;
+            info!("Commit phase is successful auth_id {}", auth_id);
+            let challenge = decode_field(&auth_challenge_response.get_ref().c, schema.byte_len())?;
+            let solution = prover.respond(challenge.clone(), secret_x.clone())?;
+            let nonce = nonce();
+
+            let answer = if sub.get_flag("binary") {
+                let proof = Proof::new(r1, r2, challenge, solution);
+                AuthenticationAnswerRequest {
+                    auth_id: auth_id.clone(),
+                    s: String::new(),
+                    proof: proof.to_bytes(schema.byte_len())?,
+                    nonce: nonce.clone(),
+                }
+            } else {
+                AuthenticationAnswerRequest {
+                    auth_id: auth_id.clone(),
+                    s: encode_field(&solution, schema.byte_len()),
+                    proof: Vec::new(),
+                    nonce: nonce.clone(),
+                }
+            };
+            if sub.get_flag("self-check") {
+                // Decoded from the messages, so a field encoded wrong is caught as well
+                let len = schema.byte_len();
+                let sent = if answer.proof.is_empty() {
+                    Proof::new(
+                        decode_field(&request.r1, len)?,
+                        decode_field(&request.r2, len)?,
+                        decode_field(&auth_challenge_response.get_ref().c, len)?,
+                        decode_field(&answer.s, len)?,
+                    )
+                } else {
+                    Proof::from_bytes(&answer.proof, len)?
+                };
+                self_check(&schema, secret_x, sent)?;
+            }
+
+            // Send for verification
+            let verify_response = client
+                .verify_authentication(tonic::Request::new(answer))
+                .await?;
+            verify(server_key.as_ref(), &nonce, verify_response.get_ref())?;
+            info!(
+                "Received session id {} for {} with auth_id {}",
+                verify_response.get_ref().session_id,
+                user_name,
+                auth_id,
+            );
+            verify_response.into_inner().session_id
+        }
+        ChaumPedersenFactoryType::NonInteractive(ecc_schema) => {
+            info!("Non interactive protocol");
+            let secret_x = sub
+                .get_one::<String>("password")
+                .map(|pw| EccChaumPedersen::hash(pw.as_bytes()))
+                .expect("password is required");
+
+            // The challenge is bound to the time the proof is made at, the server only accepts it
+            // for a few minutes around that
+            let created_at = SystemClock.now_unix();
+            let ecc_schema = ecc_schema.with_timestamp(created_at);
+
+            // === Commitment === //
+            let (k, r1, r2) = ecc_schema.prover_commit().await?;
+            let challenge = ecc_schema.derive_challenge(&r1, &r2);
+
+            // === Solution === //
+            let solution = ecc_schema.prover_solve_challenge(k, challenge, secret_x);
+
+            // Send for verification once, the server remembers an accepted proof and rejects it
+            // with ALREADY_EXISTS when it's sent again
+            let request = NonInteractiveAuthenticationRequest {
+                user: user_name.to_string(),
+                c: serde_json::to_string(&challenge).unwrap(), // we want to error if something is wrong
+                s: serde_json::to_string(&solution).unwrap(),
+                r1: EccChaumPedersen::to_base64(&r1),
+                r2: EccChaumPedersen::to_base64(&r2),
+                slot_id,
+                nonce: nonce(),
+                created_at: Some(prost_types::Timestamp {
+                    seconds: created_at as i64,
+                    nanos: 0,
+                }),
+            };
+            if sub.get_flag("self-check") {
+                let sent = Proof::new(
+                    EccChaumPedersen::from_base64(&request.r1)?,
+                    EccChaumPedersen::from_base64(&request.r2)?,
+                    EccChaumPedersen::scalar_from_json(&request.c)?,
+                    EccChaumPedersen::scalar_from_json(&request.s)?,
+                );
+                self_check(&ecc_schema, secret_x, sent)?;
+            }
+            let verify_response = client
+                .non_interactive_authentication(tonic::Request::new(request.clone()))
+                .await?;
+            verify(
+                server_key.as_ref(),
+                &request.nonce,
+                verify_response.get_ref(),
+            )?;
+            info!(
+                "Received session id {} for {}",
+                verify_response.get_ref().session_id,
+                user_name,
+            );
+            verify_response.into_inner().session_id
+        }
+    };
+
+    Ok((user_name.clone(), session_id))
+}
+
+/// Deletes the user and returns its name
+async fn unregister(
+    client: &mut AuthClient<Channel>,
+    options: Options,
+    sub: &ArgMatches,
+) -> CommandResult<String> {
+    let Options {
+        group,
+        slot_id,
+        policy,
+        ..
+    } = options;
+    let user_name = sub.get_one::<String>("name").expect("name is required");
+    let secret_x = sub
+        .get_one::<String>("password")
+        .map(|pw| ChaumPedersen::hash(pw.as_bytes()))
+        .expect("password is required");
+    let schema = group.protocol();
+
+    let mut prover = Prover::new(&schema);
+    let (r1, r2) = prover.commit().await?;
+    let request = AuthenticationChallengeRequest {
+        user: user_name.clone(),
+        r1: encode_field(&r1, schema.byte_len()),
+        r2: encode_field(&r2, schema.byte_len()),
+        group: group.bits(),
+        enrollment: String::new(),
+        slot_id,
+    };
+    let challenge = retry(&policy, || {
+        let (mut client, request) = (client.clone(), request.clone());
+        async move {
+            client
+                .create_authentication_challenge(tonic::Request::new(request))
+                .await
+        }
+    })
+    .await?
+    .into_inner();
+    let c = decode_field(&challenge.c, schema.byte_len())?;
+    let s = prover.respond(c, secret_x)?;
+
+    // Consumes the challenge like a login, sent once
+    client
+        .unregister(tonic::Request::new(UnregisterRequest {
+            user: user_name.clone(),
+            auth_id: challenge.auth_id,
+            s: encode_field(&s, schema.byte_len()),
+        }))
+        .await?;
+    info!("Successfully unregistered {}", user_name);
+    Ok(user_name.clone())
+}
+
+async fn inspect(
+    client: &mut AuthClient<Channel>,
+    options: Options,
+    sub: &ArgMatches,
+) -> CommandResult {
+    // The keys are derived in the group they're stored for
+    let Options {
+        slot_id, policy, ..
+    } = options;
+    let user_name = sub.get_one::<String>("name").expect("name is required");
+    let request = PublicKeysRequest {
+        user: user_name.clone(),
+        slot_id,
+    };
+    let keys = retry(&policy, || {
+        let (mut client, request) = (client.clone(), request.clone());
+        async move { client.get_public_keys(tonic::Request::new(request)).await }
+    })
+    .await?
+    .into_inner();
+    info!(
+        "{} registered {:?} keys y1 {} y2 {} in slot {} of the {}-bit group",
+        user_name,
+        keys.algorithm(),
+        keys.y1,
+        keys.y2,
+        slot_id,
+        keys.group,
+    );
+
+    if let Some(password) = sub.get_one::<String>("password") {
+        // Derived the way `register` derives them, in the same encoding
+        let interactive = keys.algorithm() == Algorithm::Interactive;
+        let (y1, y2) = if interactive {
+            let schema = Group::from_bits(keys.group)?.protocol();
+            let (y1, y2) = schema
+                .generate_public_keys(ChaumPedersen::hash(password.as_bytes()))
+                .await?;
+            (
+                encode_field(&y1, schema.byte_len()),
+                encode_field(&y2, schema.byte_len()),
+            )
+        } else {
+            let (y1, y2) = EccChaumPedersen::new()
+                .generate_public_keys(EccChaumPedersen::hash(password.as_bytes()))
+                .await?;
+            (
+                EccChaumPedersen::to_base64(&y1),
+                EccChaumPedersen::to_base64(&y2),
+            )
+        };
+
+        let mismatched = mismatched_keys((&keys.y1, &keys.y2), (&y1, &y2), interactive);
+        if mismatched.is_empty() {
+            info!("The password derives the stored keys");
+        } else {
+            info!(
+                "The password doesn't derive the stored {}, derived y1 {} y2 {}",
+                mismatched.join(" and "),
+                y1,
+                y2,
+            );
+        }
+    }
+    Ok(())
+}
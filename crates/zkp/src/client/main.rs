@@ -2,23 +2,26 @@
 extern crate log;
 extern crate pretty_env_logger;
 
+use std::time::Duration;
+
 use clap::{arg, Command};
-use num_bigint::BigInt;
-use num_traits::Num;
+use ed25519_dalek::VerifyingKey;
 use pretty_env_logger::init;
+use rand::RngCore;
 
-use chaum_pedersen::chaum_pedersen::ChaumPedersen;
-use chaum_pedersen::ChaumPedersenTrait;
-use chaum_pedersen::ecc_chaum_pedersen::EccChaumPedersen;
-use chaum_pedersen::utils::{chaum_pedersen_factory, ChaumPedersenFactoryType};
-use zkp::auth_client::AuthClient;
+use ::zkp::signing::{SignatureError, SERVER_PUBLIC_KEY_ENV};
+use chaum_pedersen::chaum_pedersen::Group;
 
-use crate::utils::bigint_to_hex_string;
-use crate::zkp::{
-    AuthenticationAnswerRequest, AuthenticationChallengeRequest,
-    NonInteractiveAuthenticationRequest, RegisterRequest,
-};
+use crate::channel::{connect, server_url, SERVER_URL_ENV};
+use crate::commands::{execute, Session};
+use crate::retry::RetryPolicy;
+use crate::signature::server_key;
 
+mod channel;
+mod commands;
+mod repl;
+mod retry;
+mod signature;
 mod utils;
 
 pub mod zkp {
@@ -29,6 +32,40 @@ fn cli() -> Command {
     Command::new("zkp")
         .about("zkp")
         .subcommand_required(true)
+        .arg(
+            arg!(--server <URL> "Server url, defaults to $ZKP_SERVER_URL or http://0.0.0.0:50051")
+                .required(false)
+                .global(true),
+        )
+        .arg(
+            arg!(--"server-key" <KEY> "Public key of the server its responses have to be signed with, hex or base64, defaults to $ZKP_SERVER_PUBLIC_KEY")
+                .required(false)
+                .global(true),
+        )
+        .arg(
+            arg!(--group <BITS> "MODP group size of the interactive protocol: 2048, 3072 or 4096")
+                .value_parser(clap::value_parser!(Group))
+                .default_value("2048")
+                .global(true),
+        )
+        .arg(
+            arg!(--slot <ID> "Key slot the password is proven for, 0 is the one registration creates")
+                .value_parser(clap::value_parser!(u32))
+                .default_value("0")
+                .global(true),
+        )
+        .arg(
+            arg!(--retries <N> "Times an RPC is sent again after the server was unreachable, verifying a challenge is never retried")
+                .value_parser(clap::value_parser!(u32))
+                .default_value("3")
+                .global(true),
+        )
+        .arg(
+            arg!(--"retry-base-ms" <MS> "Delay before the first retry, doubled for every further one")
+                .value_parser(clap::value_parser!(u64))
+                .default_value("100")
+                .global(true),
+        )
         .subcommand(
             Command::new("register")
                 .about("Register or update user")
@@ -37,6 +74,8 @@ fn cli() -> Command {
                     arg!(--password <PASSWORD> "Password").required(true),
                     arg!(--algorithm <ALGORITHM> "Choose an algorithm, default algorithm is interactive")
                         .value_parser(["interactive", "non-interactive"]).default_missing_value("default").required(false).num_args(0..=1),
+                    arg!(--"old-password" <PASSWORD> "Current password of an existing user, proves it to replace the interactive keys").required(false),
+                    arg!(--"idempotency-key" <KEY> "Retrying with the same key returns the first response instead of registering again").required(false),
                 ]),
         )
         .subcommand(Command::new("login").about("login").args(&[
@@ -44,145 +83,129 @@ fn cli() -> Command {
             arg!(--password <PASSWORD> "Password").required(true),
             arg!(--algorithm <ALGORITHM> "Choose an algorithm, default algorithm is interactive")
                 .value_parser(["interactive", "non-interactive"]).default_missing_value("default").required(false).num_args(0..=1),
+            arg!(--binary "Send the interactive proof in the binary wire format instead of hex"),
+            arg!(--"self-check" "Verify the proof locally before sending it, one that doesn't verify isn't sent"),
+            arg!(--enrollment <TOKEN> "Enrollment token printed at registration, limits the interactive login to the server's enrollment window"),
         ]))
+        .subcommand(
+            Command::new("unregister")
+                .about("Delete an interactive user, proving its password")
+                .args(&[
+                    arg!(--name <NAME> "Username").required(true),
+                    arg!(--password <PASSWORD> "Password").required(true),
+                ]),
+        )
+        .subcommand(
+            Command::new("inspect")
+                .about("Print the stored public keys of a user and whether a password derives them")
+                .args(&[
+                    arg!(--name <NAME> "Username").required(true),
+                    arg!(--password <PASSWORD> "Password to derive the public keys from").required(false),
+                ]),
+        )
+        .subcommand(Command::new("repl").about(
+            "Prompt for commands and run them over one connection, `sessions` lists the session ids of the logins, `exit` quits",
+        ))
+}
+
+/// Without `--idempotency-key` a random key is used when retries are enabled, so a retry of a
+/// registration the server already stored is replayed instead of failing with `AlreadyExists`
+fn idempotency_key(matches: &clap::ArgMatches, policy: &RetryPolicy) -> String {
+    match matches.get_one::<String>("idempotency-key") {
+        Some(key) => key.clone(),
+        None if policy.retries() > 0 => {
+            let mut key = [0u8; 16];
+            rand::thread_rng().fill_bytes(&mut key);
+            hex::encode(key)
+        }
+        None => String::new(),
+    }
+}
+
+fn retry_policy(matches: &clap::ArgMatches) -> RetryPolicy {
+    RetryPolicy::new(
+        matches
+            .get_one::<u32>("retries")
+            .copied()
+            .unwrap_or_default(),
+        Duration::from_millis(
+            matches
+                .get_one::<u64>("retry-base-ms")
+                .copied()
+                .unwrap_or_default(),
+        ),
+    )
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     init();
-    let channel = tonic::transport::Channel::from_static("http://0.0.0.0:50051")
-        .connect()
-        .await?;
-
-    let mut client = AuthClient::new(channel);
     let matches = cli().get_matches();
 
-    match matches.subcommand() {
-        Some(("register", sub)) => {
-            let user_name = sub.get_one::<String>("name").expect("name is required");
-            let is_interactive = sub
-                .get_one::<String>("algorithm")
-                .map(|schema| schema != "non-interactive")
-                .unwrap_or(true);
-
-            match chaum_pedersen_factory(is_interactive) {
-                ChaumPedersenFactoryType::Interactive(schema) => {
-                    info!("Interactive protocol");
-                    let secret_x = sub
-                        .get_one::<String>("password")
-                        .map(|pw| ChaumPedersen::hash(pw.as_bytes()))
-                        .expect("password is required");
-                    let (y1, y2) = schema.generate_public_keys(secret_x).await;
-
-                    client
-                        .register(tonic::Request::new(RegisterRequest {
-                            user: user_name.clone(),
-                            y1: bigint_to_hex_string(y1),
-                            y2: bigint_to_hex_string(y2),
-                        }))
-                        .await?;
-                }
-                ChaumPedersenFactoryType::NonInteractive(ecc_schema) => {
-                    info!("Non interactive protocol");
-                    let secret_x = sub
-                        .get_one::<String>("password")
-                        .map(|pw| EccChaumPedersen::hash(pw.as_bytes()))
-                        .expect("password is required");
-                    let (pk_y1, pk_y2) = ecc_schema.generate_public_keys(secret_x).await;
-
-                    client
-                        .register(tonic::Request::new(RegisterRequest {
-                            user: user_name.clone(),
-                            y1: serde_json::to_string(&pk_y1).unwrap(),
-                            y2: serde_json::to_string(&pk_y2).unwrap(),
-                        }))
-                        .await?;
-                }
-            }
-            info!("Successfully registered {}", user_name);
-        }
-        Some(("login", sub)) => {
-            let user_name = sub.get_one::<String>("name").expect("name is required");
-            let is_interactive = sub
-                .get_one::<String>("algorithm")
-                .map(|schema| schema != "non-interactive")
-                .unwrap_or(true);
-
-            match chaum_pedersen_factory(is_interactive) {
-                ChaumPedersenFactoryType::Interactive(schema) => {
-                    info!("Interactive protocol");
-                    let secret_x = sub
-                        .get_one::<String>("password")
-                        .map(|pw| ChaumPedersen::hash(pw.as_bytes()))
-                        .expect("password is required");
-
-                    // === Commitment === //
-                    let (k, r1, r2) = schema.prover_commit().await;
-                    let auth_challenge_response = client
-                        .create_authentication_challenge(tonic::Request::new(
-                            AuthenticationChallengeRequest {
-                                user: user_name.clone(),
-                                r1: bigint_to_hex_string(r1.unwrap()), // unwrap because we want to fail if it's None
-                                r2: bigint_to_hex_string(r2.unwrap()),
-                            },
-                        ))
-                        .await?;
-
-                    // === Verifier sent the challenge, let's solve it === //
-                    let auth_id = &auth_challenge_response.get_ref().auth_id;
-                    info!("Commit phase is successful auth_id {}", auth_id);
-                    let challenge =
-                        BigInt::from_str_radix(&auth_challenge_response.get_ref().c, 16)?;
-                    let solution = schema.prover_solve_challenge(k, challenge, secret_x);
-
-                    // Send for verification
-                    let verify_response = client
-                        .verify_authentication(tonic::Request::new(AuthenticationAnswerRequest {
-                            auth_id: auth_id.clone(),
-                            s: bigint_to_hex_string(solution),
-                        }))
-                        .await?;
-                    info!(
-                        "Received session id {} for {} with auth_id {}",
-                        verify_response.get_ref().session_id,
-                        user_name,
-                        auth_id,
-                    );
-                }
-                ChaumPedersenFactoryType::NonInteractive(ecc_schema) => {
-                    info!("Non interactive protocol");
-                    let secret_x = sub
-                        .get_one::<String>("password")
-                        .map(|pw| EccChaumPedersen::hash(pw.as_bytes()))
-                        .expect("password is required");
-
-                    // === Commitment === //
-                    let (k, challenge, _) = ecc_schema.prover_commit().await;
-
-                    // === Solution === //
-                    let solution =
-                        ecc_schema.prover_solve_challenge(k, challenge.unwrap(), secret_x);
-
-                    // Send for verification
-                    let verify_response = client
-                        .non_interactive_authentication(tonic::Request::new(
-                            NonInteractiveAuthenticationRequest {
-                                user: user_name.to_string(),
-                                c: serde_json::to_string(&challenge.unwrap()).unwrap(), // we want to error if something is wrong
-                                s: serde_json::to_string(&solution).unwrap(),
-                            },
-                        ))
-                        .await?;
-                    info!(
-                        "Received session id {} for {}",
-                        verify_response.get_ref().session_id,
-                        user_name,
-                    );
-                }
-            }
-        }
-        _ => unreachable!(),
+    let url = server_url(
+        matches.get_one::<String>("server"),
+        std::env::var(SERVER_URL_ENV).ok(),
+    );
+    let channel = connect(&url).await?;
+    info!("Connected to {}", url);
+    if selected_server_key(&matches)?.is_none() {
+        warn!(
+            "{} is not set, the responses of the server aren't verified",
+            SERVER_PUBLIC_KEY_ENV
+        );
+    }
+
+    let mut session = Session::new(channel);
+    match matches.subcommand_name() {
+        Some("repl") => repl::run(&mut session).await,
+        _ => execute(&mut session, &matches).await,
+    }
+}
+
+fn selected_server_key(matches: &clap::ArgMatches) -> Result<Option<VerifyingKey>, SignatureError> {
+    server_key(
+        matches.get_one::<String>("server-key"),
+        std::env::var(SERVER_PUBLIC_KEY_ENV).ok(),
+    )
+}
+
+fn selected_group(matches: &clap::ArgMatches) -> Group {
+    matches
+        .get_one::<Group>("group")
+        .copied()
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Group, clap::Error> {
+        let base = ["zkp", "login", "--name", "Nyan", "--password", "cat"];
+        cli()
+            .try_get_matches_from(base.iter().chain(args))
+            .map(|matches| selected_group(&matches))
     }
 
-    Ok(())
+    #[test]
+    fn group_flag() {
+        assert_eq!(parse(&[]).unwrap(), Group::Modp2048);
+        assert_eq!(parse(&["--group", "3072"]).unwrap(), Group::Modp3072);
+        assert_eq!(parse(&["--group", "4096"]).unwrap(), Group::Modp4096);
+        assert!(parse(&["--group", "1024"]).is_err());
+        assert!(parse(&["--group", "modp"]).is_err());
+    }
+
+    #[test]
+    fn retry_flags() {
+        let policy = |args: &[&str]| {
+            let base = ["zkp", "login", "--name", "Nyan", "--password", "cat"];
+            retry_policy(&cli().try_get_matches_from(base.iter().chain(args)).unwrap())
+        };
+        assert_eq!(policy(&[]), RetryPolicy::new(3, Duration::from_millis(100)));
+        assert_eq!(
+            policy(&["--retries", "0", "--retry-base-ms", "50"]),
+            RetryPolicy::new(0, Duration::from_millis(50))
+        );
+    }
 }
@@ -0,0 +1,102 @@
+use std::io::Write;
+
+use clap::error::ErrorKind;
+use clap::ArgMatches;
+use tokio::io::{AsyncBufReadExt, BufReader};
+
+use crate::cli;
+use crate::commands::{execute, CommandResult, Session};
+
+/// One line typed at the prompt
+#[derive(Debug)]
+pub enum Line {
+    Empty,
+    Exit,
+    /// Lists the session id of the last login of every user
+    Sessions,
+    /// A subcommand with its flags, `--server` is ignored since the connection is kept
+    Command(ArgMatches),
+}
+
+/// Parses `line` like the arguments of the client, words are split on whitespace and can't be
+/// quoted
+pub fn dispatch(line: &str) -> Result<Line, clap::Error> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    match words.as_slice() {
+        [] => Ok(Line::Empty),
+        ["exit"] | ["quit"] => Ok(Line::Exit),
+        ["sessions"] => Ok(Line::Sessions),
+        _ => {
+            let matches = cli().try_get_matches_from(std::iter::once("zkp").chain(words))?;
+            if matches.subcommand_name() == Some("repl") {
+                return Err(
+                    cli().error(ErrorKind::InvalidSubcommand, "the repl is already running")
+                );
+            }
+            Ok(Line::Command(matches))
+        }
+    }
+}
+
+/// Reads commands from stdin until `exit` or the end of input, a failed command is printed and
+/// the next one is read
+pub async fn run(session: &mut Session) -> CommandResult {
+    let mut lines = BufReader::new(tokio::io::stdin()).lines();
+    loop {
+        print!("zkp> ");
+        std::io::stdout().flush()?;
+        let Some(line) = lines.next_line().await? else {
+            break;
+        };
+
+        match dispatch(&line) {
+            Ok(Line::Empty) => {}
+            Ok(Line::Exit) => break,
+            Ok(Line::Sessions) => {
+                for (user, session_id) in &session.session_ids {
+                    println!("{} {}", user, session_id);
+                }
+            }
+            Ok(Line::Command(matches)) => {
+                if let Err(e) = execute(session, &matches).await {
+                    eprintln!("error: {}", e);
+                }
+            }
+            Err(e) => e.print()?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use chaum_pedersen::chaum_pedersen::Group;
+
+    use crate::selected_group;
+
+    use super::*;
+
+    #[test]
+    fn dispatch_line() {
+        assert!(matches!(dispatch(""), Ok(Line::Empty)));
+        assert!(matches!(dispatch("   "), Ok(Line::Empty)));
+        assert!(matches!(dispatch("exit"), Ok(Line::Exit)));
+        assert!(matches!(dispatch(" quit "), Ok(Line::Exit)));
+        assert!(matches!(dispatch("sessions"), Ok(Line::Sessions)));
+
+        let Ok(Line::Command(matches)) = dispatch("login --name Nyan --password cat --group 4096")
+        else {
+            panic!("login is a command");
+        };
+        let (name, sub) = matches.subcommand().unwrap();
+        assert_eq!(name, "login");
+        assert_eq!(sub.get_one::<String>("name").unwrap(), "Nyan");
+        assert_eq!(selected_group(&matches), Group::Modp4096);
+
+        let e = dispatch("login --name Nyan").unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::MissingRequiredArgument);
+        let e = dispatch("repl").unwrap_err();
+        assert_eq!(e.kind(), ErrorKind::InvalidSubcommand);
+        assert!(dispatch("nyan").is_err());
+    }
+}
@@ -0,0 +1,134 @@
+use std::future::Future;
+use std::time::Duration;
+
+use rand::Rng;
+use tonic::{Code, Status};
+
+/// Upper bound of a single delay, the doubling stops here
+const MAX_DELAY: Duration = Duration::from_secs(10);
+
+/// How often and how long to wait before an RPC is sent again after a transient failure.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    retries: u32,
+    base: Duration,
+}
+
+impl RetryPolicy {
+    pub fn new(retries: u32, base: Duration) -> Self {
+        Self { retries, base }
+    }
+
+    pub fn retries(&self) -> u32 {
+        self.retries
+    }
+
+    /// Delay before retry `attempt`, counting from 0, without jitter: `base * 2^attempt` capped
+    /// at `MAX_DELAY`
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        self.base
+            .checked_mul(2u32.saturating_pow(attempt))
+            .map_or(MAX_DELAY, |delay| delay.min(MAX_DELAY))
+    }
+
+    /// `backoff` with the upper half randomized, so clients that failed together don't retry
+    /// together
+    pub fn delay(&self, attempt: u32) -> Duration {
+        let backoff = self.backoff(attempt);
+        backoff / 2 + rand::thread_rng().gen_range(Duration::ZERO..=backoff / 2)
+    }
+}
+
+/// Only failures where the request didn't reach the server, or the server couldn't take it, are
+/// worth sending again. Everything else fails the same way on a retry.
+fn transient(status: &Status) -> bool {
+    status.code() == Code::Unavailable
+}
+
+/// Calls `rpc` until it succeeds, fails with a non transient status or runs out of retries.
+/// Only for RPCs that can be sent twice, a `VerifyAuthentication` consumes its challenge and a
+/// `NonInteractiveAuthentication` its proof, they have to be sent once.
+pub async fn retry<T, F, Fut>(policy: &RetryPolicy, mut rpc: F) -> Result<T, Status>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, Status>>,
+{
+    let mut attempt = 0;
+    loop {
+        match rpc().await {
+            Err(status) if attempt < policy.retries && transient(&status) => {
+                let delay = policy.delay(attempt);
+                warn!(
+                    "{}, retrying in {}ms ({}/{})",
+                    status.message(),
+                    delay.as_millis(),
+                    attempt + 1,
+                    policy.retries
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+            result => return result,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    use super::*;
+
+    /// Fails with `status` for the first `failures` calls
+    async fn flaky(calls: &AtomicU32, failures: u32, status: Status) -> Result<u32, Status> {
+        let call = calls.fetch_add(1, Ordering::SeqCst);
+        if call < failures {
+            Err(status)
+        } else {
+            Ok(call)
+        }
+    }
+
+    #[test]
+    fn backoff_schedule() {
+        let policy = RetryPolicy::new(5, Duration::from_millis(100));
+        let schedule: Vec<_> = (0..5).map(|attempt| policy.backoff(attempt)).collect();
+        assert_eq!(
+            schedule,
+            [100, 200, 400, 800, 1600].map(Duration::from_millis)
+        );
+        assert_eq!(policy.backoff(7), MAX_DELAY);
+        assert_eq!(policy.backoff(u32::MAX), MAX_DELAY);
+
+        for attempt in 0..5 {
+            let delay = policy.delay(attempt);
+            assert!(delay >= policy.backoff(attempt) / 2);
+            assert!(delay <= policy.backoff(attempt));
+        }
+    }
+
+    #[tokio::test]
+    async fn flaky_rpc() {
+        let policy = RetryPolicy::new(3, Duration::from_millis(1));
+
+        let calls = AtomicU32::new(0);
+        let result = retry(&policy, || flaky(&calls, 3, Status::unavailable("down"))).await;
+        assert_eq!(result.unwrap(), 3);
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+
+        // Out of retries
+        let calls = AtomicU32::new(0);
+        let result = retry(&policy, || flaky(&calls, 4, Status::unavailable("down"))).await;
+        assert_eq!(result.unwrap_err().code(), Code::Unavailable);
+        assert_eq!(calls.load(Ordering::SeqCst), 4);
+
+        // A rejected request isn't sent again
+        let calls = AtomicU32::new(0);
+        let result = retry(&policy, || {
+            flaky(&calls, 1, Status::invalid_argument("invalid proof"))
+        })
+        .await;
+        assert_eq!(result.unwrap_err().code(), Code::InvalidArgument);
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
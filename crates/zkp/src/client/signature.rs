@@ -0,0 +1,168 @@
+//! Checks the signature of the server on the responses handing out enrollments and sessions, see
+//! `zkp::signing`.
+
+use ed25519_dalek::VerifyingKey;
+use prost::Message;
+use rand::RngCore;
+
+use zkp::signing::{self, SignatureError, SignedResponse};
+
+use crate::zkp::{AuthenticationAnswerResponse, RegisterResponse};
+
+/// Resolves the server key, the `--server-key` flag takes precedence over the
+/// `ZKP_SERVER_PUBLIC_KEY` env var. If neither is set the responses aren't checked.
+pub fn server_key(
+    flag: Option<&String>,
+    env: Option<String>,
+) -> Result<Option<VerifyingKey>, SignatureError> {
+    flag.cloned()
+        .or(env)
+        .map(|key| signing::parse_verifying_key(&key))
+        .transpose()
+}
+
+/// Fresh nonce for a request, the response has to be signed with it
+pub fn nonce() -> Vec<u8> {
+    let mut nonce = vec![0u8; 32];
+    rand::thread_rng().fill_bytes(&mut nonce);
+    nonce
+}
+
+/// A response carrying the signature of the server
+pub trait Signed: Message + Clone {
+    const KIND: SignedResponse;
+
+    fn signature(&self) -> &[u8];
+
+    /// The response as it was signed
+    fn unsigned(&self) -> Self;
+}
+
+impl Signed for RegisterResponse {
+    const KIND: SignedResponse = SignedResponse::Register;
+
+    fn signature(&self) -> &[u8] {
+        &self.signature
+    }
+
+    fn unsigned(&self) -> Self {
+        Self {
+            signature: Vec::new(),
+            ..self.clone()
+        }
+    }
+}
+
+impl Signed for AuthenticationAnswerResponse {
+    const KIND: SignedResponse = SignedResponse::AuthenticationAnswer;
+
+    fn signature(&self) -> &[u8] {
+        &self.signature
+    }
+
+    fn unsigned(&self) -> Self {
+        Self {
+            signature: Vec::new(),
+            ..self.clone()
+        }
+    }
+}
+
+/// Checks `response` to the request sent with `nonce`, without a server key every response passes
+pub fn verify<R: Signed>(
+    key: Option<&VerifyingKey>,
+    nonce: &[u8],
+    response: &R,
+) -> Result<(), SignatureError> {
+    match key {
+        Some(key) => signing::verify(
+            key,
+            R::KIND,
+            nonce,
+            &response.unsigned().encode_to_vec(),
+            response.signature(),
+        ),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ed25519_dalek::SigningKey;
+
+    use super::*;
+
+    /// Signs `response` the way the server does
+    fn sign<R: Signed>(key: &SigningKey, nonce: &[u8], response: &R) -> Vec<u8> {
+        signing::sign(key, R::KIND, nonce, &response.encode_to_vec())
+    }
+
+    #[test]
+    fn valid_signature() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let nonce = nonce();
+        let mut response = AuthenticationAnswerResponse {
+            session_id: "6f1c".to_string(),
+            signature: Vec::new(),
+            created_at: None,
+            expires_at: None,
+        };
+        response.signature = sign(&key, &nonce, &response);
+
+        assert!(verify(Some(&key.verifying_key()), &nonce, &response).is_ok());
+        // Nothing is checked without a key
+        assert!(verify(None, &nonce, &response).is_ok());
+    }
+
+    #[test]
+    fn tampered_response() {
+        let key = SigningKey::from_bytes(&[7u8; 32]);
+        let public = key.verifying_key();
+        let nonce = nonce();
+        let mut response = RegisterResponse {
+            enrollment: "6f1c".to_string(),
+            signature: Vec::new(),
+        };
+        response.signature = sign(&key, &nonce, &response);
+        assert!(verify(Some(&public), &nonce, &response).is_ok());
+
+        let tampered = RegisterResponse {
+            enrollment: "forged".to_string(),
+            ..response.clone()
+        };
+        assert_eq!(
+            verify(Some(&public), &nonce, &tampered),
+            Err(SignatureError::Mismatch)
+        );
+        // Replayed for another request
+        assert_eq!(
+            verify(Some(&public), &super::nonce(), &response),
+            Err(SignatureError::Mismatch)
+        );
+        // Signed by someone else
+        let impostor = SigningKey::from_bytes(&[8u8; 32]);
+        assert_eq!(
+            verify(Some(&impostor.verifying_key()), &nonce, &response),
+            Err(SignatureError::Mismatch)
+        );
+        // The same fields signed as the other kind of response
+        let answer = AuthenticationAnswerResponse {
+            session_id: response.enrollment.clone(),
+            signature: response.signature.clone(),
+            created_at: None,
+            expires_at: None,
+        };
+        assert_eq!(
+            verify(Some(&public), &nonce, &answer),
+            Err(SignatureError::Mismatch)
+        );
+        let unsigned = RegisterResponse {
+            signature: Vec::new(),
+            ..response
+        };
+        assert_eq!(
+            verify(Some(&public), &nonce, &unsigned),
+            Err(SignatureError::Mismatch)
+        );
+    }
+}
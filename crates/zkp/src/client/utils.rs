@@ -1,6 +1,132 @@
+use std::fmt;
+
 use num_bigint::BigInt;
 
-pub fn bigint_to_hex_string(input: BigInt) -> String {
-    let bytes = input.to_bytes_be().1;
-    hex::encode(bytes)
+use chaum_pedersen::proof::Proof;
+use chaum_pedersen::InteractiveVerifier;
+
+/// Names of the stored public keys that differ from the ones derived from the password, empty if
+/// they all match. Keys of the interactive protocol are hex and compared by value, so leading
+/// zeros and case don't count as a difference. Keys of the non-interactive protocol are compared
+/// as is.
+pub fn mismatched_keys(
+    stored: (&str, &str),
+    derived: (&str, &str),
+    interactive: bool,
+) -> Vec<&'static str> {
+    let equal = |stored: &str, derived: &str| {
+        if !interactive {
+            return stored == derived;
+        }
+        match (
+            BigInt::parse_bytes(stored.as_bytes(), 16),
+            BigInt::parse_bytes(derived.as_bytes(), 16),
+        ) {
+            (Some(stored), Some(derived)) => stored == derived,
+            _ => false,
+        }
+    };
+
+    [("y1", stored.0, derived.0), ("y2", stored.1, derived.1)]
+        .into_iter()
+        .filter(|(_, stored, derived)| !equal(stored, derived))
+        .map(|(name, _, _)| name)
+        .collect()
+}
+
+/// The proof about to be sent doesn't verify against the keys derived from the password
+#[derive(Debug, PartialEq, Eq)]
+pub struct SelfCheckError(String);
+
+impl fmt::Display for SelfCheckError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "self-check failed, the proof wasn't sent: {}", self.0)
+    }
+}
+
+impl std::error::Error for SelfCheckError {}
+
+/// Verifies `proof` against the public keys of `secret_x` before it's sent, so a proof the client
+/// computed or encoded wrong fails with a reason instead of an opaque rejection by the server.
+/// A non-interactive proof satisfies the same equations, its challenge is derived from the
+/// commitment.
+pub fn self_check<V: InteractiveVerifier>(
+    protocol: &V,
+    secret_x: V::Scalar,
+    proof: Proof<V::Point, V::Scalar>,
+) -> Result<(), SelfCheckError> {
+    let (y1, y2) = protocol
+        .generate_public_keys_sync(secret_x)
+        .map_err(|e| SelfCheckError(e.to_string()))?;
+    match protocol.verify_interactive_sync(proof, y1, y2) {
+        Ok(true) => Ok(()),
+        Ok(false) => Err(SelfCheckError(
+            "it doesn't verify against the keys of the password".to_string(),
+        )),
+        Err(e) => Err(SelfCheckError(e.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chaum_pedersen::chaum_pedersen::ChaumPedersen;
+    use chaum_pedersen::ecc_chaum_pedersen::EccChaumPedersen;
+    use chaum_pedersen::ChaumPedersenTrait;
+
+    use super::*;
+
+    #[test]
+    fn matching_keys() {
+        assert!(mismatched_keys(("1f", "2a"), ("1f", "2a"), true).is_empty());
+        assert!(mismatched_keys(("001F", "2A"), ("1f", "02a"), true).is_empty());
+        assert!(mismatched_keys(("AAEC", "AAED"), ("AAEC", "AAED"), false).is_empty());
+    }
+
+    #[test]
+    fn mismatching_keys() {
+        assert_eq!(mismatched_keys(("1f", "2a"), ("1f", "2b"), true), ["y2"]);
+        assert_eq!(
+            mismatched_keys(("1f", "2a"), ("2a", "1f"), true),
+            ["y1", "y2"]
+        );
+        // Garbage stored by another client never matches
+        assert_eq!(mismatched_keys(("nyan", "2a"), ("1f", "2a"), true), ["y1"]);
+        // Base64 is case sensitive
+        assert_eq!(
+            mismatched_keys(("AAEC", "aaed"), ("AAEC", "AAED"), false),
+            ["y2"]
+        );
+    }
+
+    #[test]
+    fn self_check_interactive() {
+        let cp = ChaumPedersen::new_test_group();
+        let x = cp.hash_to_scalar(b"nyancat");
+        let (k, r1, r2) = cp.prover_commit_sync().unwrap();
+        let c = cp.verifier_generate_challenge();
+        let s = cp.prover_solve_challenge(k, c.clone(), x.clone());
+        let proof = Proof::new(r1, r2, c, s);
+        assert_eq!(self_check(&cp, x.clone(), proof.clone()), Ok(()));
+
+        let corrupted = Proof {
+            s: &proof.s + 1,
+            ..proof.clone()
+        };
+        assert!(self_check(&cp, x.clone(), corrupted).is_err());
+        // Proven for another password
+        assert!(self_check(&cp, x + 1, proof).is_err());
+    }
+
+    #[test]
+    fn self_check_non_interactive() {
+        let ecc = EccChaumPedersen::new();
+        let x = EccChaumPedersen::hash(b"nyancat");
+        let (k, r1, r2) = ecc.prover_commit_sync().unwrap();
+        let c = ecc.derive_challenge(&r1, &r2);
+        let s = ecc.prover_solve_challenge(k, c, x);
+        assert_eq!(self_check(&ecc, x, Proof::new(r1, r2, c, s)), Ok(()));
+
+        let corrupted = Proof::new(r1, r2, c, s + s);
+        assert!(self_check(&ecc, x, corrupted).is_err());
+    }
 }
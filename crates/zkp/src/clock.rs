@@ -0,0 +1,48 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Source of the current time, swapped for a `MockClock` in tests
+pub trait Clock: Send + Sync {
+    /// Seconds since the UNIX epoch
+    fn now_unix(&self) -> u64;
+}
+
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    fn now_unix(&self) -> u64 {
+        // Could happen
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .expect("SystemTime set before UNIX EPOCH")
+            .as_secs()
+    }
+}
+
+#[cfg(test)]
+pub use mock::MockClock;
+
+#[cfg(test)]
+mod mock {
+    use std::sync::atomic::{AtomicU64, Ordering};
+
+    use super::Clock;
+
+    /// Clock that only moves when told to
+    pub struct MockClock(AtomicU64);
+
+    impl MockClock {
+        pub fn new(now: u64) -> Self {
+            Self(AtomicU64::new(now))
+        }
+
+        pub fn advance(&self, secs: u64) {
+            self.0.fetch_add(secs, Ordering::SeqCst);
+        }
+    }
+
+    impl Clock for MockClock {
+        fn now_unix(&self) -> u64 {
+            self.0.load(Ordering::SeqCst)
+        }
+    }
+}
@@ -0,0 +1,63 @@
+use std::process::ExitCode;
+
+use clap::{arg, Command};
+
+use storage::db::{KeyValueStorage, StorageTree};
+use storage::dump::dump_model;
+use storage::model::challenge_model::ChallengeModel;
+use storage::model::user_model::UserModel;
+
+fn cli() -> Command {
+    Command::new("zkp-dump")
+        .about("Prints a stored record as pretty JSON, stop the server first, it locks the db")
+        .subcommand_required(true)
+        .arg(
+            arg!(--db <PATH> "Path of the sled database")
+                .default_value("db")
+                .global(true),
+        )
+        .subcommand(
+            Command::new("user")
+                .about("Registered user")
+                .arg(arg!(<NAME> "Username")),
+        )
+        .subcommand(
+            Command::new("challenge")
+                .about("Outstanding challenge")
+                .arg(arg!(<AUTH_ID> "auth_id the challenge was issued under")),
+        )
+}
+
+fn main() -> ExitCode {
+    let matches = cli().get_matches();
+    let db = KeyValueStorage::open_at(matches.get_one::<String>("db").expect("db has a default"));
+
+    let dumped = match matches.subcommand() {
+        Some(("user", sub)) => {
+            let name = sub.get_one::<String>("NAME").expect("NAME is required");
+            dump_model::<UserModel>(&db, StorageTree::Auth, &UserModel::user_id(name))
+        }
+        Some(("challenge", sub)) => {
+            let auth_id = sub
+                .get_one::<String>("AUTH_ID")
+                .expect("AUTH_ID is required");
+            // Challenges are keyed by the digest their auth_id is the hex of
+            dump_model::<ChallengeModel>(&db, StorageTree::Challenge, &ChallengeModel::key(auth_id))
+        }
+        _ => unreachable!("a subcommand is required"),
+    };
+
+    match dumped {
+        Ok(json) => {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&json).expect("a JSON value always serializes")
+            );
+            ExitCode::SUCCESS
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
+}
@@ -0,0 +1,7 @@
+pub mod audit;
+pub mod clock;
+pub mod local_auth;
+pub mod metrics;
+pub mod offline;
+pub mod secret;
+pub mod signing;
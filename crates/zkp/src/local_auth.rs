@@ -0,0 +1,3215 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::future::Future;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use curve25519_dalek::{RistrettoPoint, Scalar};
+use hmac::{Hmac, Mac};
+use metrics::{counter, histogram, increment_counter};
+use num_bigint::BigInt;
+use prost::Message;
+use rand::RngCore;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use tracing::{error, info, info_span, warn, Instrument, Span};
+
+use chaum_pedersen::chaum_pedersen::{
+    AuditReport, ChaumPedersen, CpParams, Group, DEFAULT_MAX_P_BITS,
+};
+use chaum_pedersen::ecc_chaum_pedersen::EccChaumPedersen;
+use chaum_pedersen::hashing;
+use chaum_pedersen::proof::{NonInteractiveProof, Proof};
+use chaum_pedersen::prover::Prover;
+use chaum_pedersen::utils::{bigint_from_hex, decode_field, deserialize, encode_field};
+use chaum_pedersen::verifier::Verifier;
+use chaum_pedersen::{
+    ChaumPedersenTrait, CpError, CpResult, InteractiveVerifier, NonInteractiveVerifier,
+};
+use storage::db::{KeyValueStorage, StorageTree, Transaction};
+use storage::migration::{flag_stale_generators, migrate_users};
+use storage::model::challenge_model::ChallengeModel;
+use storage::model::idempotency_model::IdempotencyModel;
+use storage::model::session_model::{SessionId, SessionModel, SESSION_NONCE_LEN};
+use storage::model::used_proof_model::UsedProofModel;
+use storage::model::user_model::{KeyAlgorithm, KeySlot, UserModel};
+use storage::{StorageError, StorageResult};
+
+use crate::audit::{AuditSink, AuthEvent, JsonLinesSink, Outcome, AUDIT_LOG_PATH};
+use crate::clock::{Clock, SystemClock};
+use crate::metrics::{LOGIN_FAILURE_TOTAL, LOGIN_SUCCESS_TOTAL, REGISTER_TOTAL, VERIFY_SECONDS};
+use crate::secret::{self, ServerSecret};
+
+/// Custom group parameters in the `CpParams` JSON format, read at startup if the file exists
+pub const PARAMS_PATH: &str = "params.json";
+/// Longest `p` in bits the parameters may have, overrides `DEFAULT_MAX_P_BITS`
+pub const MAX_P_BITS_ENV: &str = "ZKP_MAX_P_BITS";
+/// Seconds a challenge can be answered for
+pub const CHALLENGE_TTL_SECS: u64 = 300;
+/// Outstanding challenges per user, issuing another one evicts the oldest
+pub const MAX_OUTSTANDING_CHALLENGES: usize = 5;
+/// Time a proof verification is allowed to take before the login is rejected
+pub const VERIFY_TIMEOUT: Duration = Duration::from_secs(10);
+/// Seconds after registration an enrollment token is accepted for
+pub const ENROLLMENT_WINDOW_SECS: u64 = 365 * 24 * 60 * 60;
+/// Seconds a register response is replayed for to retries with the same idempotency key
+pub const IDEMPOTENCY_TTL_SECS: u64 = 24 * 60 * 60;
+/// Seconds a session is accepted for after the login, or after its last use with sliding expiry
+pub const SESSION_TTL_SECS: u64 = 24 * 60 * 60;
+/// Keeps enrollment tags apart from session tags signed with the same secret
+const ENROLLMENT_DOMAIN: &[u8] = b"zkp-enrollment";
+/// Keeps challenge tags apart from the other tags signed with the same secret
+const CHALLENGE_DOMAIN: &[u8] = b"zkp-challenge";
+
+type HmacSha256 = Hmac<Sha256>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuthError {
+    AlreadyExists,
+    UserNotFound,
+    KeySlotNotFound,
+    ChallengeNotFound,
+    ChallengeExpired,
+    VerificationTimeout,
+    /// The enrollment epoch the challenge was issued with is outside the allowed window
+    EnrollmentOutOfWindow,
+    /// The timestamp a non-interactive proof was made at is outside the allowed window
+    ProofOutOfWindow,
+    /// The user's keys were generated with generators the server no longer uses
+    ReregistrationRequired,
+    InvalidArgument(String),
+    InvalidProof,
+    /// The answer to the challenge was already accepted once
+    ProofReplayed,
+    InvalidSession,
+    /// The session outlived its TTL, it's deleted and the user has to log in again
+    SessionExpired,
+    SessionMismatch,
+    UpdateFailed(String),
+    Internal(String),
+}
+
+impl fmt::Display for AuthError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AuthError::AlreadyExists => write!(f, "user already exists"),
+            AuthError::UserNotFound => write!(f, "user does not exist"),
+            AuthError::KeySlotNotFound => write!(f, "key slot does not exist"),
+            AuthError::ChallengeNotFound => write!(f, "challenge does not exist"),
+            AuthError::ChallengeExpired => write!(f, "challenge expired"),
+            AuthError::VerificationTimeout => write!(f, "proof verification timed out"),
+            AuthError::EnrollmentOutOfWindow => {
+                write!(f, "enrollment is outside the allowed window")
+            }
+            AuthError::ProofOutOfWindow => write!(f, "proof is outside the allowed window"),
+            AuthError::ReregistrationRequired => write!(f, "re-registration required"),
+            AuthError::InvalidArgument(s) => write!(f, "{}", s),
+            AuthError::InvalidProof => write!(f, "Proof is not valid!"),
+            AuthError::ProofReplayed => write!(f, "proof was already accepted"),
+            AuthError::InvalidSession => write!(f, "invalid session"),
+            AuthError::SessionExpired => write!(f, "session expired"),
+            AuthError::SessionMismatch => write!(f, "session does not belong to the user"),
+            AuthError::UpdateFailed(s) => write!(f, "failed to update credentials {}", s),
+            AuthError::Internal(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+impl std::error::Error for AuthError {}
+
+impl From<CpError> for AuthError {
+    fn from(e: CpError) -> Self {
+        match e {
+            CpError::TaskJoin(_) | CpError::Cancelled => AuthError::Internal(e.to_string()),
+            CpError::Timeout => AuthError::VerificationTimeout,
+            _ => AuthError::InvalidArgument(e.to_string()),
+        }
+    }
+}
+
+pub type AuthResult<T> = Result<T, AuthError>;
+
+/// Challenge issued for a commitment, answered under `auth_id`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Challenge {
+    /// Hex encoded
+    pub c: String,
+    pub auth_id: String,
+    /// Unix seconds the challenge was issued at
+    pub created_at: u64,
+}
+
+impl Challenge {
+    /// Unix seconds after which the challenge can't be answered anymore
+    pub fn expires_at(&self) -> u64 {
+        self.created_at + CHALLENGE_TTL_SECS
+    }
+}
+
+/// Answer to an interactive challenge
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Answer {
+    /// Hex encoded solution `s`
+    Solution(String),
+    /// The whole transcript in the `Proof::to_bytes` format
+    Proof(Vec<u8>),
+}
+
+/// User registered by `LocalAuth::register_batch`, the arguments of `LocalAuth::register`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewUser {
+    pub user: String,
+    pub y1: String,
+    pub y2: String,
+    pub group: Group,
+}
+
+/// Complete interactive transcript checked by `LocalAuth::verify_transcript`, every field hex
+/// encoded like in a login
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transcript {
+    /// The `auth_id` the challenge was issued under by a server with stateless challenges, see
+    /// `with_stateless_challenges`
+    pub auth_id: String,
+    pub group: Group,
+    pub y1: String,
+    pub y2: String,
+    pub r1: String,
+    pub r2: String,
+    pub c: String,
+    pub s: String,
+}
+
+/// Protocol the keys of a user were registered for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Protocol {
+    Interactive,
+    NonInteractive,
+}
+
+impl From<KeyAlgorithm> for Protocol {
+    fn from(algorithm: KeyAlgorithm) -> Self {
+        match algorithm {
+            KeyAlgorithm::Interactive => Protocol::Interactive,
+            KeyAlgorithm::NonInteractive => Protocol::NonInteractive,
+        }
+    }
+}
+
+/// Registration and login as in-process calls, the gRPC `AuthService` is an adapter over it.
+pub struct LocalAuth {
+    db: KeyValueStorage,
+    cp_protocols: HashMap<Group, ChaumPedersen>,
+    /// Non-interactive verifier, memoizes the decompressed public keys across logins
+    ecc: EccChaumPedersen,
+    /// Key session ids are signed with
+    secret: ServerSecret,
+    clock: Arc<dyn Clock>,
+    max_challenges: usize,
+    verify_timeout: Duration,
+    /// Seconds after enrollment a challenge carrying the enrollment epoch can be answered
+    enrollment_window: u64,
+    /// Receives every login decision, `None` keeps no audit log
+    audit: Option<Arc<dyn AuditSink>>,
+    /// Challenges are signed into their `auth_id` instead of stored, see `with_stateless_challenges`
+    stateless_challenges: bool,
+    /// Seconds a session is accepted for
+    session_ttl: u64,
+    /// Every accepted use of a session moves its expiry `session_ttl` past it, see
+    /// `with_sliding_sessions`
+    sliding_sessions: bool,
+    /// Seconds since the UNIX epoch `sweep_expired_challenges` last ran at
+    last_sweep: AtomicU64,
+}
+
+impl LocalAuth {
+    /// Opens the storage and reads the configuration of the server from the environment, a
+    /// configuration the server can't start with is an `AuthError::Internal`.
+    pub fn new(clock: Arc<dyn Clock>) -> AuthResult<Self> {
+        let storage = KeyValueStorage::open();
+        match migrate_users(&storage, LocalAuth::key_algorithm) {
+            Ok(report) => {
+                if report.migrated > 0 {
+                    info!(migrated = report.migrated, "migrated legacy user records");
+                }
+                for (key, e) in &report.skipped {
+                    warn!(key = %String::from_utf8_lossy(key), %e, "skipped unreadable user record");
+                }
+            }
+            Err(e) => error!(%e, "failed to migrate user records"),
+        }
+
+        let max_bits = LocalAuth::max_p_bits(std::env::var(MAX_P_BITS_ENV).ok())?;
+        let params = LocalAuth::load_params(Path::new(PARAMS_PATH), max_bits)
+            .map_err(|e| AuthError::Internal(format!("failed to load {}: {}", PARAMS_PATH, e)))?;
+        let auth = match params {
+            Some((group, cp)) => {
+                info!(
+                    path = PARAMS_PATH,
+                    bits = cp.p.bits(),
+                    "loaded group parameters"
+                );
+                Self::with_params(storage, group, cp)
+            }
+            None => {
+                let bits = Group::ALL.map(|group| group.bits());
+                info!(?bits, "using built-in RFC 3526 groups");
+                Self::with_storage(storage)
+            }
+        };
+
+        match auth.flag_stale_generators() {
+            Ok(0) => {}
+            Ok(flagged) => warn!(
+                flagged,
+                "users registered with other generators have to register again"
+            ),
+            Err(e) => error!(%e, "failed to check the generators of user records"),
+        }
+
+        let secret = secret::from_env().map_err(|e| AuthError::Internal(e.to_string()))?;
+        let audit = JsonLinesSink::open(Path::new(AUDIT_LOG_PATH)).map_err(|e| {
+            AuthError::Internal(format!("failed to open {}: {}", AUDIT_LOG_PATH, e))
+        })?;
+        Ok(auth
+            .with_secret(secret)
+            .with_clock(clock)
+            .with_audit_sink(Arc::new(audit)))
+    }
+
+    pub fn with_storage(storage: KeyValueStorage) -> Self {
+        Self {
+            db: storage,
+            cp_protocols: Group::ALL
+                .into_iter()
+                .map(|group| (group, group.protocol()))
+                .collect(),
+            ecc: EccChaumPedersen::new(),
+            secret: secret::random(),
+            clock: Arc::new(SystemClock),
+            max_challenges: MAX_OUTSTANDING_CHALLENGES,
+            verify_timeout: VERIFY_TIMEOUT,
+            enrollment_window: ENROLLMENT_WINDOW_SECS,
+            audit: None,
+            stateless_challenges: false,
+            session_ttl: SESSION_TTL_SECS,
+            sliding_sessions: false,
+            last_sweep: AtomicU64::new(0),
+        }
+    }
+
+    pub fn with_secret(self, secret: ServerSecret) -> Self {
+        Self { secret, ..self }
+    }
+
+    pub fn with_clock(self, clock: Arc<dyn Clock>) -> Self {
+        Self { clock, ..self }
+    }
+
+    /// Limits the outstanding challenges per user to `max`, at least one
+    pub fn with_challenge_limit(self, max: usize) -> Self {
+        Self {
+            max_challenges: max.max(1),
+            ..self
+        }
+    }
+
+    pub fn with_verify_timeout(self, verify_timeout: Duration) -> Self {
+        Self {
+            verify_timeout,
+            ..self
+        }
+    }
+
+    pub fn with_enrollment_window(self, enrollment_window: u64) -> Self {
+        Self {
+            enrollment_window,
+            ..self
+        }
+    }
+
+    pub fn with_audit_sink(self, audit: Arc<dyn AuditSink>) -> Self {
+        Self {
+            audit: Some(audit),
+            ..self
+        }
+    }
+
+    /// Issues the challenge state as the `auth_id` itself, signed with the server secret, instead of
+    /// storing it until it's answered. Nothing is left to clean up after abandoned logins, in
+    /// exchange a challenge can be answered more than once until it expires and there's no limit
+    /// on the outstanding challenges of a user.
+    pub fn with_stateless_challenges(self, stateless_challenges: bool) -> Self {
+        Self {
+            stateless_challenges,
+            ..self
+        }
+    }
+
+    /// Sessions expire `session_ttl` seconds after the login instead of `SESSION_TTL_SECS`
+    pub fn with_session_ttl(self, session_ttl: u64) -> Self {
+        Self {
+            session_ttl,
+            ..self
+        }
+    }
+
+    /// Moves the expiry of a session `session_ttl` past every accepted use, a session in use
+    /// doesn't expire while an idle one still does
+    pub fn with_sliding_sessions(self, sliding_sessions: bool) -> Self {
+        Self {
+            sliding_sessions,
+            ..self
+        }
+    }
+
+    /// Seconds a session is accepted for after the login
+    pub fn session_ttl(&self) -> u64 {
+        self.session_ttl
+    }
+
+    /// Runs `group` with the custom parameters `cp` instead of the built-in RFC 3526 prime
+    pub fn with_params(storage: KeyValueStorage, group: Group, cp: ChaumPedersen) -> Self {
+        let mut auth = Self::with_storage(storage);
+        auth.cp_protocols.insert(group, cp);
+        auth
+    }
+
+    /// Parameters `group` runs with, provers have to use the same
+    pub fn protocol(&self, group: Group) -> &ChaumPedersen {
+        &self.cp_protocols[&group]
+    }
+
+    /// Hex SHA-256 of `p`, `g` and `h` of `group`, each prefixed with its length. Stored with the
+    /// keys on registration, keys generated with other parameters can't verify a proof.
+    pub fn generators(&self, group: Group) -> String {
+        let cp = self.protocol(group);
+        let mut hasher = Sha256::new();
+        for n in [&*cp.p, &*cp.g, &*cp.h] {
+            let (_, bytes) = n.to_bytes_be();
+            hasher.update((bytes.len() as u64).to_be_bytes());
+            hasher.update(bytes);
+        }
+        hex::encode(hasher.finalize())
+    }
+
+    /// Sets `reregister_required` on the users whose interactive keys were generated with other
+    /// parameters than their group runs with now, e.g. after `g` and `h` were replaced, and clears
+    /// it where they match again. Flagged users can't complete an interactive login, answering the
+    /// challenge fails with `ReregistrationRequired` instead of verifying against keys that can't
+    /// hold. `new` runs it on startup, returns the number of flagged users. Records that can't be
+    /// read are logged and left unchecked.
+    pub fn flag_stale_generators(&self) -> AuthResult<usize> {
+        let report = flag_stale_generators(&self.db, |bits| {
+            Group::from_bits(bits)
+                .ok()
+                .map(|group| self.generators(group))
+        })
+        .map_err(|e| AuthError::Internal(format!("failed to flag users {}", e)))?;
+        for (key, e) in &report.skipped {
+            warn!(key = %String::from_utf8_lossy(key), %e, "skipped unreadable user record");
+        }
+        Ok(report.flagged)
+    }
+
+    /// Returns the enrollment token of `user`, the registration time signed with the server secret.
+    /// Presenting it with a challenge restricts the login to the enrollment window.
+    pub async fn register(
+        &self,
+        user: &str,
+        y1: String,
+        y2: String,
+        group: Group,
+    ) -> AuthResult<String> {
+        let data = UserModel {
+            user: user.to_string(),
+            slots: vec![LocalAuth::key_slot(KeySlot::FIRST, y1, y2)],
+            group: group.bits(),
+            generators: self.generators(group),
+            reregister_required: false,
+        };
+
+        self.insert_user(&UserModel::user_id(&data.user), data)
+            .await?;
+        increment_counter!(REGISTER_TOTAL);
+        info!("registration successful");
+        Ok(self.enrollment_token(user, self.clock.now_unix()))
+    }
+
+    /// Same as `register`, but a retry with the same `idempotency_key` within
+    /// `IDEMPOTENCY_TTL_SECS` gets the response of the first call instead of `AlreadyExists`.
+    /// Reusing the key for a different request is rejected.
+    pub async fn register_idempotent(
+        &self,
+        idempotency_key: &str,
+        user: &str,
+        y1: String,
+        y2: String,
+        group: Group,
+    ) -> AuthResult<String> {
+        let now = self.clock.now_unix();
+        let request = IdempotencyModel::fingerprint(&[user, &y1, &y2, &group.bits().to_string()]);
+        let fresh = IdempotencyModel::new(request.clone(), self.enrollment_token(user, now), now);
+        let cache_key = idempotency_key.as_bytes().to_vec();
+        let user_key = UserModel::user_id(&user.to_string());
+        let data = UserModel {
+            user: user.to_string(),
+            slots: vec![LocalAuth::key_slot(KeySlot::FIRST, y1, y2)],
+            group: group.bits(),
+            generators: self.generators(group),
+            reregister_required: false,
+        };
+
+        // The user and the response are written together, a concurrent retry either sees both or
+        // registers itself
+        let (stored, replayed) = self
+            .db
+            .transaction(|tx| {
+                match tx.get::<IdempotencyModel>(StorageTree::Idempotency, &cache_key) {
+                    Ok(cached) if now <= cached.created_at + IDEMPOTENCY_TTL_SECS => {
+                        return Ok(Ok((cached, true)));
+                    }
+                    Ok(_) | Err(StorageError::NotFound) => {}
+                    Err(e) => return Err(e),
+                }
+                if tx.exists(StorageTree::Auth, &user_key)? {
+                    return Ok(Err(AuthError::AlreadyExists));
+                }
+                tx.insert(StorageTree::Auth, &user_key, &data)?;
+                tx.insert(StorageTree::Idempotency, &cache_key, &fresh)?;
+                Ok(Ok((fresh.clone(), false)))
+            })
+            .map_err(|e| AuthError::Internal(format!("failed to insert {}", e)))??;
+        if stored.request != request {
+            return Err(AuthError::InvalidArgument(
+                "idempotency key was used for another request".to_string(),
+            ));
+        }
+        if replayed {
+            info!("registration replayed");
+            return Ok(stored.response);
+        }
+
+        self.flush().await?;
+        increment_counter!(REGISTER_TOTAL);
+        info!("registration successful");
+        Ok(stored.response)
+    }
+
+    /// Registers every user of `users` in one transaction and returns the enrollment token of
+    /// each, in order. A user that already exists, or came earlier in the batch, is
+    /// `AlreadyExists` and the others are registered anyway. Fails as a whole only if the
+    /// transaction does, then nobody is registered.
+    pub async fn register_batch(&self, users: Vec<NewUser>) -> AuthResult<Vec<AuthResult<String>>> {
+        let records: Vec<(Vec<u8>, UserModel)> = users
+            .into_iter()
+            .map(|new| {
+                let data = UserModel {
+                    user: new.user,
+                    slots: vec![LocalAuth::key_slot(KeySlot::FIRST, new.y1, new.y2)],
+                    group: new.group.bits(),
+                    generators: self.generators(new.group),
+                    reregister_required: false,
+                };
+                (UserModel::user_id(&data.user), data)
+            })
+            .collect();
+
+        // Reads inside the transaction see its own writes, so a repeated user finds the first one
+        let inserted = self
+            .db
+            .transaction(|tx| {
+                records
+                    .iter()
+                    .map(|(user_key, data)| {
+                        if tx.exists(StorageTree::Auth, user_key)? {
+                            return Ok(false);
+                        }
+                        tx.insert(StorageTree::Auth, user_key, data)?;
+                        Ok(true)
+                    })
+                    .collect::<StorageResult<Vec<bool>>>()
+            })
+            .map_err(|e| AuthError::Internal(format!("failed to insert {}", e)))?;
+        self.flush().await?;
+
+        let now = self.clock.now_unix();
+        let registered = inserted.iter().filter(|inserted| **inserted).count();
+        counter!(REGISTER_TOTAL, registered as u64);
+        info!(
+            registered,
+            existing = inserted.len() - registered,
+            "batch registration successful"
+        );
+        Ok(records
+            .iter()
+            .zip(inserted)
+            .map(|((_, data), inserted)| {
+                if inserted {
+                    Ok(self.enrollment_token(&data.user, now))
+                } else {
+                    Err(AuthError::AlreadyExists)
+                }
+            })
+            .collect())
+    }
+
+    /// Issues a challenge for the commitment `(r1, r2)`, both hex encoded, answered with the keys
+    /// registration put in `KeySlot::FIRST`
+    pub async fn create_challenge(
+        &self,
+        user: &str,
+        commitment: (String, String),
+        group: Group,
+    ) -> AuthResult<Challenge> {
+        self.create_challenge_with_enrollment(user, KeySlot::FIRST, commitment, group, None)
+            .await
+    }
+
+    /// Same as `create_challenge` but answered with the keys in `slot`, and only accepted within
+    /// the enrollment window of the epoch signed in `enrollment`, the token `register` returned
+    pub async fn create_challenge_with_enrollment(
+        &self,
+        user: &str,
+        slot: u32,
+        commitment: (String, String),
+        group: Group,
+        enrollment: Option<&str>,
+    ) -> AuthResult<Challenge> {
+        let enrollment_epoch = enrollment
+            .map(|token| {
+                self.enrollment_epoch(token, user).ok_or_else(|| {
+                    AuthError::InvalidArgument("invalid enrollment token".to_string())
+                })
+            })
+            .transpose()?;
+        let cp = self.protocol(group);
+        LocalAuth::hex_field(cp, "r1", &commitment.0)?;
+        LocalAuth::hex_field(cp, "r2", &commitment.1)?;
+        let user_key = UserModel::user_id(&user.to_string());
+
+        // Unknown users get a challenge against a placeholder record, so the response and the work
+        // done don't reveal whether the user exists. The placeholder never verifies, neither does a
+        // slot the user doesn't have. A challenge in another group than the user registered in
+        // can't verify either, it gets the placeholder too so the group isn't revealed.
+        let record = match self.get_user(&user_key).await {
+            Ok(record) if group.bits() != record.group => {
+                warn!(
+                    registered = record.group,
+                    "challenge in another group than the user registered in"
+                );
+                LocalAuth::placeholder_user(user, group)
+            }
+            Ok(record) => record,
+            Err(AuthError::UserNotFound) => LocalAuth::placeholder_user(user, group),
+            Err(e) => return Err(e),
+        };
+
+        let challenge = if self.stateless_challenges {
+            let challenge_model = self.new_challenge(commitment, record, slot, enrollment_epoch)?;
+            Challenge {
+                c: challenge_model.challenge.clone(),
+                auth_id: self.challenge_token(&challenge_model)?,
+                created_at: challenge_model.created_at,
+            }
+        } else {
+            self.upsert_challenge(commitment, record, slot, enrollment_epoch)
+                .await?
+        };
+
+        Span::current().record("auth_id", challenge.auth_id.as_str());
+        info!("challenge issued");
+        Ok(challenge)
+    }
+
+    /// Answers the challenge issued under `auth_id`, returns the session id if the proof is valid
+    pub async fn verify(&self, auth_id: &str, answer: Answer) -> AuthResult<SessionId> {
+        let (is_valid, challenge_model) = self.verify_answer(auth_id, answer).await?;
+
+        let user = &challenge_model.user;
+        self.login_response(is_valid, user, Protocol::Interactive, Some(auth_id))
+            .await
+    }
+
+    /// Registers new keys for an existing `user`, `answer` has to prove knowledge of the secret of
+    /// the keys in one of its slots to the challenge issued for `user` under `auth_id`. The new keys
+    /// replace every slot like a fresh registration, the outstanding challenges of the user can't
+    /// be answered any more, stateless ones included. Returns the enrollment token of the new keys.
+    pub async fn rotate_keys(
+        &self,
+        user: &str,
+        y1: String,
+        y2: String,
+        group: Group,
+        auth_id: &str,
+        answer: Answer,
+    ) -> AuthResult<String> {
+        let current = self.prove_current_keys(user, auth_id, answer).await?;
+
+        let user_key = UserModel::user_id(&current.user);
+        let challenge_key = ChallengeModel::key(auth_id);
+        let data = UserModel {
+            user: current.user.clone(),
+            slots: vec![LocalAuth::key_slot(KeySlot::FIRST, y1, y2)],
+            group: group.bits(),
+            generators: self.generators(group),
+            reregister_required: false,
+        };
+
+        // The keys the proof was checked against have to still be the stored ones, the challenges
+        // are deleted with the update so the proof can't be replayed. Stateless challenges hold the
+        // old keys, `verify_answer` rejects them once they aren't stored any more.
+        self.db
+            .transaction(|tx| {
+                if tx.get::<UserModel>(StorageTree::Auth, &user_key)? != current {
+                    return Err(StorageError::UpdateFailed);
+                }
+                for id in LocalAuth::outstanding_challenges(tx, &user_key)? {
+                    tx.delete(StorageTree::Challenge, &ChallengeModel::key(&id))?;
+                }
+                tx.delete(StorageTree::ChallengeIndex, &user_key)?;
+                tx.delete(StorageTree::Challenge, &challenge_key)?;
+                tx.insert(StorageTree::Auth, &user_key, &data)
+            })
+            .map_err(|e| AuthError::UpdateFailed(e.to_string()))?;
+        self.flush().await?;
+
+        self.audit(
+            &current,
+            Some(auth_id),
+            Protocol::Interactive,
+            Outcome::Success,
+        );
+        info!("keys rotated");
+        Ok(self.enrollment_token(user, self.clock.now_unix()))
+    }
+
+    /// Deletes `user`, `answer` has to prove knowledge of the secret of the keys in one of its slots
+    /// to the challenge issued for `user` under `auth_id`. Outstanding challenges of the user go with it,
+    /// sessions expire on their own.
+    pub async fn unregister(&self, user: &str, auth_id: &str, answer: Answer) -> AuthResult<()> {
+        let current = self.prove_current_keys(user, auth_id, answer).await?;
+
+        let user_key = UserModel::user_id(&current.user);
+        let challenge_key = ChallengeModel::key(auth_id);
+        self.db
+            .transaction(|tx| {
+                // Keys rotated since the challenge was issued weren't proven
+                if tx.get::<UserModel>(StorageTree::Auth, &user_key)? != current {
+                    return Err(StorageError::UpdateFailed);
+                }
+                for id in LocalAuth::outstanding_challenges(tx, &user_key)? {
+                    tx.delete(StorageTree::Challenge, &ChallengeModel::key(&id))?;
+                }
+                tx.delete(StorageTree::ChallengeIndex, &user_key)?;
+                tx.delete(StorageTree::Challenge, &challenge_key)?;
+                tx.delete(StorageTree::Auth, &user_key)
+            })
+            .map_err(|e| AuthError::UpdateFailed(e.to_string()))?;
+        self.flush().await?;
+
+        self.audit(
+            &current,
+            Some(auth_id),
+            Protocol::Interactive,
+            Outcome::Success,
+        );
+        info!("user unregistered");
+        Ok(())
+    }
+
+    /// Checks `answer` to the challenge issued for `user` under `auth_id`, returns the record the
+    /// proof was verified against. A proof for a placeholder of an unknown user never verifies.
+    async fn prove_current_keys(
+        &self,
+        user: &str,
+        auth_id: &str,
+        answer: Answer,
+    ) -> AuthResult<UserModel> {
+        let (is_valid, challenge_model) = self.verify_answer(auth_id, answer).await?;
+        let current = challenge_model.user;
+        if current.user != user {
+            return Err(AuthError::InvalidArgument(
+                "challenge was issued for another user".to_string(),
+            ));
+        }
+        info!(is_valid, "proof verified");
+        if !is_valid {
+            self.audit(
+                &current,
+                Some(auth_id),
+                Protocol::Interactive,
+                Outcome::Failure,
+            );
+            return Err(AuthError::InvalidProof);
+        }
+        Ok(current)
+    }
+
+    /// Checks `answer` to the challenge issued under `auth_id`, returns whether the proof is valid
+    /// and the challenge it answers with the user as stored now
+    async fn verify_answer(
+        &self,
+        auth_id: &str,
+        answer: Answer,
+    ) -> AuthResult<(bool, ChallengeModel)> {
+        let challenge_key = ChallengeModel::key(auth_id);
+        let mut challenge_model = if self.stateless_challenges {
+            self.challenge_from_token(auth_id)
+                .ok_or(AuthError::ChallengeNotFound)?
+        } else {
+            self.get_challenge_data(&challenge_key).await?
+        };
+        Span::current().record("user", challenge_model.user.user.as_str());
+        if self.clock.now_unix() > challenge_model.created_at + CHALLENGE_TTL_SECS {
+            if !self.stateless_challenges {
+                self.db
+                    .delete(StorageTree::Challenge, &challenge_key)
+                    .map_err(|e| AuthError::Internal(format!("failed to delete {}", e)))?;
+            }
+            return Err(AuthError::ChallengeExpired);
+        }
+        if let Some(epoch) = challenge_model.enrollment_epoch {
+            self.check_enrollment_window(epoch)?;
+        }
+        // The challenge holds the keys it was issued for, once they were rotated, replaced or
+        // removed it's gone. Placeholders of unknown users have no slots and are left as they are.
+        if let Some(issued) = challenge_model.user.slot(challenge_model.slot) {
+            let current = self
+                .get_user(&UserModel::user_id(&challenge_model.user.user))
+                .await
+                .map_err(|_| AuthError::ChallengeNotFound)?;
+            if current.group != challenge_model.user.group
+                || current.slot(challenge_model.slot) != Some(issued)
+            {
+                return Err(AuthError::ChallengeNotFound);
+            }
+            // Flagged users get their challenge like everyone else, so issuing one doesn't tell
+            // whether the user exists. Answering it does, whatever the answer is.
+            if current.reregister_required {
+                return Err(AuthError::ReregistrationRequired);
+            }
+            challenge_model.user = current;
+        }
+        let cp_protocol = self.cp_protocol(challenge_model.user.group)?;
+
+        // == Params for verification ==
+        let challenge = bigint_from_hex(&challenge_model.challenge)?;
+
+        let r1 = bigint_from_hex(&challenge_model.commitment.0)?;
+        let r2 = bigint_from_hex(&challenge_model.commitment.1)?;
+
+        let solution = match answer {
+            Answer::Solution(s) => LocalAuth::hex_field(cp_protocol, "s", &s)?,
+            Answer::Proof(bytes) => {
+                let proof = Proof::from_bytes(&bytes, cp_protocol.byte_len())?;
+
+                if proof.r1 != r1 || proof.r2 != r2 || proof.c != challenge {
+                    increment_counter!(LOGIN_FAILURE_TOTAL);
+                    self.audit(
+                        &challenge_model.user,
+                        Some(auth_id),
+                        Protocol::Interactive,
+                        Outcome::Failure,
+                    );
+                    return Err(AuthError::InvalidArgument(
+                        "proof does not match the issued challenge".to_string(),
+                    ));
+                }
+                proof.s
+            }
+        };
+
+        // Stateless challenges aren't deleted once answered, an accepted answer is remembered
+        // until its challenge expires instead
+        let expires_at = challenge_model.created_at + CHALLENGE_TTL_SECS;
+        let used_key = UsedProofModel::key(expires_at, auth_id, &solution.to_bytes_be().1);
+        if self.proof_used(&used_key)? {
+            return Err(AuthError::ProofReplayed);
+        }
+
+        // Placeholders of unknown users have no slots
+        let Some(slot) = challenge_model.user.slot(challenge_model.slot) else {
+            return Ok((false, challenge_model));
+        };
+        let y1 = bigint_from_hex(&slot.y1)?;
+        let y2 = bigint_from_hex(&slot.y2)?;
+
+        // The exchange was kept in storage or in the token between the two requests of the login
+        let mut verifier = Verifier::new(cp_protocol, y1, y2);
+        verifier.resume(auth_id.to_string(), r1, r2, challenge);
+
+        let started = Instant::now();
+        let verification = verifier
+            .verify(auth_id, solution)
+            .instrument(info_span!("verify_proof", protocol = "interactive"));
+        let is_valid = self.within_timeout(verification).await?;
+        histogram!(VERIFY_SECONDS, started.elapsed().as_secs_f64());
+
+        // Lost to a concurrent request with the same answer
+        if is_valid && !self.record_used_proof(&used_key, expires_at)? {
+            return Err(AuthError::ProofReplayed);
+        }
+
+        Ok((is_valid, challenge_model))
+    }
+
+    /// Remembers an accepted answer under `key` until `expires_at`, returns `false` if it was
+    /// accepted before. Answers to challenges that have expired by now are pruned on the way.
+    fn record_used_proof(&self, key: &Vec<u8>, expires_at: u64) -> AuthResult<bool> {
+        let expired = UsedProofModel::expired_before(self.clock.now_unix());
+        self.db
+            .delete_before(StorageTree::UsedProofs, &expired)
+            .and_then(|_| {
+                self.db.insert_if_absent(
+                    StorageTree::UsedProofs,
+                    key,
+                    UsedProofModel::new(expires_at),
+                )
+            })
+            .map_err(|e| AuthError::Internal(format!("failed to store used proof {}", e)))
+    }
+
+    /// Whether an answer with `key` was accepted for a challenge that hasn't expired yet
+    fn proof_used(&self, key: &Vec<u8>) -> AuthResult<bool> {
+        match self.db.get::<UsedProofModel>(StorageTree::UsedProofs, key) {
+            Ok(used) => Ok(self.clock.now_unix() <= used.expires_at),
+            Err(StorageError::NotFound) => Ok(false),
+            Err(e) => Err(AuthError::Internal(format!(
+                "failed to read used proofs {}",
+                e
+            ))),
+        }
+    }
+
+    /// Verifies a non-interactive proof for the keys in `KeySlot::FIRST`, `s` and `c` are JSON
+    /// encoded scalars and `c` is bound to `created_at`, the seconds since the UNIX epoch the proof
+    /// was made at. Returns the session id if the proof is valid.
+    pub async fn verify_non_interactive(
+        &self,
+        user: &str,
+        created_at: u64,
+        s: &str,
+        c: &str,
+    ) -> AuthResult<SessionId> {
+        self.verify_non_interactive_with_commitment(user, KeySlot::FIRST, created_at, None, s, c)
+            .await
+    }
+
+    /// Same as `verify_non_interactive` but for the keys in `slot`, and checked against the
+    /// commitment `(r1, r2)` the prover sent, base64 encoded like the public keys. `c` has to be the
+    /// challenge derived from it.
+    ///
+    /// A proof is accepted within `CHALLENGE_TTL_SECS` of `created_at` like the answer to a
+    /// challenge, and only once.
+    pub async fn verify_non_interactive_with_commitment(
+        &self,
+        user: &str,
+        slot: u32,
+        created_at: u64,
+        commitment: Option<(&str, &str)>,
+        s: &str,
+        c: &str,
+    ) -> AuthResult<SessionId> {
+        let now = self.clock.now_unix();
+        if now > created_at.saturating_add(CHALLENGE_TTL_SECS)
+            || created_at > now + CHALLENGE_TTL_SECS
+        {
+            return Err(AuthError::ProofOutOfWindow);
+        }
+        let commitment = match commitment {
+            Some((r1, r2)) => Some((
+                LocalAuth::commitment_point("r1", r1)?,
+                LocalAuth::commitment_point("r2", r2)?,
+            )),
+            None => None,
+        };
+        let (solution, challenge, y1, y2, record) = self
+            .non_interactive_verification_params(user, slot, s, c)
+            .await?;
+
+        let ecc = self.ecc.clone().with_timestamp(created_at);
+        let started = Instant::now();
+        let span = info_span!("verify_proof", protocol = "non_interactive");
+        let is_valid = match commitment {
+            Some((r1, r2)) => {
+                let proof = Proof::new(r1, r2, challenge, solution);
+                let verification = ecc.verify_with_commitment(proof, y1, y2);
+                self.within_timeout(verification.instrument(span)).await?
+            }
+            None => {
+                let proof = NonInteractiveProof::new(challenge, solution);
+                let verification = ecc.verify_noninteractive(proof, y1, y2);
+                self.within_timeout(verification.instrument(span)).await?
+            }
+        };
+        histogram!(VERIFY_SECONDS, started.elapsed().as_secs_f64());
+
+        // Nothing is consumed by a non-interactive login, an accepted proof is remembered until
+        // it's out of the window instead
+        let expires_at = created_at + CHALLENGE_TTL_SECS;
+        let used_key = UsedProofModel::non_interactive_key(
+            expires_at,
+            &record.user,
+            challenge.as_bytes(),
+            solution.as_bytes(),
+        );
+        if is_valid && !self.record_used_proof(&used_key, expires_at)? {
+            return Err(AuthError::ProofReplayed);
+        }
+
+        self.login_response(is_valid, &record, Protocol::NonInteractive, None)
+            .await
+    }
+
+    /// Verifies an interactive `transcript` whose challenge was issued elsewhere, e.g. by another
+    /// host of a federation. The challenge has to be signed in `auth_id` under the same server
+    /// secret, a prover picking its own challenge can answer it without the secret `x`. The
+    /// transcript verifies only for the commitment, challenge and keys it was issued for.
+    /// Nothing is read or stored, so the challenge isn't consumed and the same transcript verifies
+    /// again, even once the challenge expired.
+    pub async fn verify_transcript(&self, transcript: &Transcript) -> AuthResult<bool> {
+        let issued = self
+            .challenge_from_token(&transcript.auth_id)
+            .ok_or_else(|| AuthError::InvalidArgument("invalid challenge token".to_string()))?;
+        let cp = self.protocol(transcript.group);
+        let field = |name, value| LocalAuth::hex_field(cp, name, value);
+        let proof = Proof::new(
+            field("r1", &transcript.r1)?,
+            field("r2", &transcript.r2)?,
+            field("c", &transcript.c)?,
+            field("s", &transcript.s)?,
+        );
+        let (y1, y2) = (field("y1", &transcript.y1)?, field("y2", &transcript.y2)?);
+
+        // Placeholders of unknown users have no slots
+        let issued_for = match issued.user.slot(issued.slot) {
+            Some(keys) => {
+                issued.user.group == transcript.group.bits()
+                    && bigint_from_hex(&issued.commitment.0)? == proof.r1
+                    && bigint_from_hex(&issued.commitment.1)? == proof.r2
+                    && bigint_from_hex(&issued.challenge)? == proof.c
+                    && bigint_from_hex(&keys.y1)? == y1
+                    && bigint_from_hex(&keys.y2)? == y2
+            }
+            None => false,
+        };
+
+        let started = Instant::now();
+        let verification = cp
+            .verify_interactive(proof, y1, y2)
+            .instrument(info_span!("verify_proof", protocol = "transcript"));
+        let is_valid = self.within_timeout(verification).await?;
+        histogram!(VERIFY_SECONDS, started.elapsed().as_secs_f64());
+        Ok(issued_for && is_valid)
+    }
+
+    /// Replaces the public keys in `slot` of `user`, `session_id` has to be a session of that user
+    pub async fn update_credentials(
+        &self,
+        session_id: &SessionId,
+        user: &str,
+        slot: u32,
+        y1: String,
+        y2: String,
+    ) -> AuthResult<()> {
+        let current = self.session_user(session_id, user).await?;
+        let mut data = current.clone();
+        let Some(keys) = data.slots.iter_mut().find(|keys| keys.id == slot) else {
+            return Err(AuthError::KeySlotNotFound);
+        };
+        *keys = self.new_key_slot(&current, slot, y1, y2)?;
+
+        let db = &self.db;
+        db.update::<UserModel>(
+            StorageTree::Auth,
+            &UserModel::user_id(&current.user),
+            &current,
+            data,
+        )
+        .map_err(|e| AuthError::UpdateFailed(e.to_string()))?;
+        self.flush().await?;
+
+        info!(slot, "credentials updated");
+        Ok(())
+    }
+
+    /// Adds `(y1, y2)` as another key slot `user` can log in with, `session_id` has to be a session
+    /// of that user. Returns the id of the new slot.
+    pub async fn add_key_slot(
+        &self,
+        session_id: &SessionId,
+        user: &str,
+        y1: String,
+        y2: String,
+    ) -> AuthResult<u32> {
+        let current = self.session_user(session_id, user).await?;
+        let keys = self.new_key_slot(&current, current.next_slot_id(), y1, y2)?;
+        let slot = keys.id;
+        let mut data = current.clone();
+        data.slots.push(keys);
+
+        let db = &self.db;
+        db.update::<UserModel>(
+            StorageTree::Auth,
+            &UserModel::user_id(&current.user),
+            &current,
+            data,
+        )
+        .map_err(|e| AuthError::UpdateFailed(e.to_string()))?;
+        self.flush().await?;
+
+        info!(slot, "key slot added");
+        Ok(slot)
+    }
+
+    /// Removes `slot` of `user`, `session_id` has to be a session of that user. The last slot can't
+    /// be removed, `unregister` deletes the user instead. Outstanding challenges for the slot go
+    /// with it, stateless ones can't be answered any more either.
+    pub async fn remove_key_slot(
+        &self,
+        session_id: &SessionId,
+        user: &str,
+        slot: u32,
+    ) -> AuthResult<()> {
+        let current = self.session_user(session_id, user).await?;
+        if current.slot(slot).is_none() {
+            return Err(AuthError::KeySlotNotFound);
+        }
+        if current.slots.len() == 1 {
+            return Err(AuthError::InvalidArgument(
+                "the last key slot can't be removed".to_string(),
+            ));
+        }
+
+        let user_key = UserModel::user_id(&current.user);
+        let mut data = current.clone();
+        data.slots.retain(|keys| keys.id != slot);
+        self.db
+            .transaction(|tx| {
+                if tx.get::<UserModel>(StorageTree::Auth, &user_key)? != current {
+                    return Err(StorageError::UpdateFailed);
+                }
+                for id in LocalAuth::outstanding_challenges(tx, &user_key)? {
+                    let challenge_key = ChallengeModel::key(&id);
+                    match tx.get::<ChallengeModel>(StorageTree::Challenge, &challenge_key) {
+                        Ok(challenge) if challenge.slot == slot => {
+                            tx.delete(StorageTree::Challenge, &challenge_key)?
+                        }
+                        Ok(_) | Err(StorageError::NotFound) => {}
+                        Err(e) => return Err(e),
+                    }
+                }
+                tx.insert(StorageTree::Auth, &user_key, &data)
+            })
+            .map_err(|e| AuthError::UpdateFailed(e.to_string()))?;
+        self.flush().await?;
+
+        info!(slot, "key slot removed");
+        Ok(())
+    }
+
+    /// The protocol of the first key slot of `user`, `None` if there is no such user
+    pub async fn user_exists(&self, user: &str) -> AuthResult<Option<Protocol>> {
+        let user_key = UserModel::user_id(&user.to_string());
+        if !self.db.exists(StorageTree::Auth, &user_key) {
+            return Ok(None);
+        }
+
+        let record = self.get_user(&user_key).await?;
+        let first = record.slots.first().ok_or(AuthError::KeySlotNotFound)?;
+        Ok(Some(first.algorithm.into()))
+    }
+
+    /// Stored record of `user` and its key slot `slot`, to tell a wrong password from wrong stored
+    /// keys when logins keep failing
+    pub async fn public_keys(&self, user: &str, slot: u32) -> AuthResult<(UserModel, KeySlot)> {
+        let record = self
+            .get_user(&UserModel::user_id(&user.to_string()))
+            .await?;
+        let keys = record
+            .slot(slot)
+            .cloned()
+            .ok_or(AuthError::KeySlotNotFound)?;
+        Ok((record, keys))
+    }
+
+    /// Recomputes the commitment of a captured interactive `proof` from the keys in `slot` of
+    /// `user` in the group it's registered in, see `ChaumPedersen::audit`
+    pub fn audit_keys(
+        &self,
+        user: &UserModel,
+        slot: u32,
+        proof: &Proof,
+    ) -> AuthResult<AuditReport> {
+        let cp = self.cp_protocol(user.group)?;
+        let keys = user.slot(slot).ok_or(AuthError::KeySlotNotFound)?;
+        let y1 = bigint_from_hex(&keys.y1)?;
+        let y2 = bigint_from_hex(&keys.y2)?;
+        Ok(cp.audit(&y1, &y2, proof))
+    }
+
+    /// Registers a throwaway user and logs it in with every group and the non-interactive protocol,
+    /// on temporary storage but with the parameters this instance runs with. A login with the
+    /// wrong secret has to fail too, parameters that accept anything don't pass.
+    pub async fn self_test(&self) -> AuthResult<()> {
+        let probe = Self {
+            cp_protocols: self.cp_protocols.clone(),
+            ..Self::with_storage(KeyValueStorage::temporary())
+        };
+
+        for group in Group::ALL {
+            let failed = |e: AuthError| AuthError::Internal(format!("{}-bit group: {}", group, e));
+            let cp = probe.protocol(group);
+            let user = format!("self-test-{}", group.bits());
+            let x = cp.hash_to_scalar(b"self-test");
+            let (y1, y2) = cp.generate_public_keys(x.clone()).await?;
+            probe
+                .register(
+                    &user,
+                    encode_field(&y1, cp.byte_len()),
+                    encode_field(&y2, cp.byte_len()),
+                    group,
+                )
+                .await
+                .map_err(failed)?;
+
+            probe
+                .self_test_login(&user, group, x)
+                .await
+                .map_err(failed)?;
+            match probe
+                .self_test_login(&user, group, cp.hash_to_scalar(b"wrong"))
+                .await
+            {
+                Err(AuthError::InvalidProof) => {}
+                Ok(_) => return Err(failed(AuthError::Internal("wrong secret accepted".into()))),
+                Err(e) => return Err(failed(e)),
+            }
+        }
+
+        let failed = |e: AuthError| AuthError::Internal(format!("non-interactive: {}", e));
+        let ecc = &probe.ecc;
+        let x = EccChaumPedersen::hash(b"self-test");
+        let (y1, y2) = ecc.generate_public_keys(x).await?;
+        probe
+            .register(
+                "self-test-ecc",
+                EccChaumPedersen::to_base64(&y1),
+                EccChaumPedersen::to_base64(&y2),
+                Group::default(),
+            )
+            .await
+            .map_err(failed)?;
+        let created_at = probe.clock.now_unix();
+        let ecc = ecc.clone().with_timestamp(created_at);
+        let (k, r1, r2) = ecc.prover_commit().await?;
+        let c = ecc.derive_challenge(&r1, &r2);
+        let s = ecc.prover_solve_challenge(k, c, x);
+        let (r1, r2) = (
+            EccChaumPedersen::to_base64(&r1),
+            EccChaumPedersen::to_base64(&r2),
+        );
+        let json = |scalar: &Scalar| serde_json::to_string(scalar).expect("scalars serialize");
+        probe
+            .verify_non_interactive_with_commitment(
+                "self-test-ecc",
+                KeySlot::FIRST,
+                created_at,
+                Some((&r1, &r2)),
+                &json(&s),
+                &json(&c),
+            )
+            .await
+            .map_err(failed)?;
+
+        Ok(())
+    }
+
+    /// Runs the interactive protocol for `user` with the secret `x`
+    async fn self_test_login(&self, user: &str, group: Group, x: BigInt) -> AuthResult<SessionId> {
+        let cp = self.protocol(group);
+        let byte_len = cp.byte_len();
+        let mut prover = Prover::new(cp);
+        let (r1, r2) = prover.commit().await?;
+        let commitment = (encode_field(&r1, byte_len), encode_field(&r2, byte_len));
+        let challenge = self.create_challenge(user, commitment, group).await?;
+        let s = prover.respond(decode_field(&challenge.c, byte_len)?, x)?;
+        self.verify(
+            &challenge.auth_id,
+            Answer::Solution(encode_field(&s, byte_len)),
+        )
+        .await
+    }
+
+    /// Largest prime accepted from the parameters file, `value` is `MAX_P_BITS_ENV` if it is set
+    fn max_p_bits(value: Option<String>) -> AuthResult<u64> {
+        match value {
+            Some(bits) => bits
+                .parse()
+                .map_err(|e| AuthError::Internal(format!("invalid {}: {}", MAX_P_BITS_ENV, e))),
+            None => Ok(DEFAULT_MAX_P_BITS),
+        }
+    }
+
+    /// Reads and validates the parameters at `path`, `None` if there is no such file.
+    /// The prime has to be one of the supported group sizes and at most `max_bits` long.
+    fn load_params(path: &Path, max_bits: u64) -> CpResult<Option<(Group, ChaumPedersen)>> {
+        let json = match fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(CpError::InvalidParams(e.to_string())),
+        };
+
+        let cp = ChaumPedersen::from_params(deserialize::<CpParams>(&json)?, max_bits)?;
+        let group = Group::from_bits(cp.p.bits() as u32)?;
+        Ok(Some((group, cp)))
+    }
+
+    /// Hex encoded field of a request, at most as long as the hex of `p`
+    fn hex_field(cp: &ChaumPedersen, name: &str, value: &str) -> AuthResult<BigInt> {
+        decode_field(value, cp.byte_len())
+            .map_err(|_| AuthError::InvalidArgument(format!("malformed hex field {}", name)))
+    }
+
+    fn cp_protocol(&self, group: u32) -> AuthResult<&ChaumPedersen> {
+        Ok(self.protocol(Group::from_bits(group)?))
+    }
+
+    /// Registered keys are never overwritten, rotating them goes through `update_credentials` or
+    /// `rotate_keys`.
+    /// Of concurrent registrations of the same user exactly one wins.
+    async fn insert_user(&self, user_key: &Vec<u8>, data: UserModel) -> AuthResult<()> {
+        let inserted = self
+            .db
+            .insert_if_absent::<UserModel>(StorageTree::Auth, user_key, data)
+            .map_err(|e| AuthError::Internal(format!("failed to insert {}", e)))?;
+        if !inserted {
+            return Err(AuthError::AlreadyExists);
+        }
+        self.flush().await
+    }
+
+    /// Persists pending writes, user and session writes are flushed before answering while
+    /// challenges are short lived and left to sled's background flush.
+    pub async fn flush(&self) -> AuthResult<()> {
+        self.db
+            .flush_async()
+            .await
+            .map_err(|e| AuthError::Internal(format!("failed to persist {}", e)))
+    }
+
+    async fn get_user(&self, user_key: &Vec<u8>) -> AuthResult<UserModel> {
+        let db = &self.db;
+        if !db.exists(StorageTree::Auth, &user_key) {
+            return Err(AuthError::UserNotFound);
+        }
+
+        db.get::<UserModel>(StorageTree::Auth, &user_key)
+            .map_err(|_| AuthError::UserNotFound)
+    }
+
+    /// Stand-in for a user that doesn't exist, without key slots nothing verifies against it
+    fn placeholder_user(user: &str, group: Group) -> UserModel {
+        UserModel {
+            user: user.to_string(),
+            slots: Vec::new(),
+            group: group.bits(),
+            generators: String::new(),
+            reregister_required: false,
+        }
+    }
+
+    /// The stored session of `session_id` if this server issued it and it hasn't expired. An
+    /// expired session is deleted, with sliding expiry an accepted one gets `session_ttl` from now.
+    pub async fn verify_session(&self, session_id: &SessionId) -> AuthResult<SessionModel> {
+        let mut session = self.get_session(session_id).await?;
+        if !self.verify_session_id(session_id, &session.user) {
+            return Err(AuthError::InvalidSession);
+        }
+
+        let db = &self.db;
+        let session_key = session_id.to_string().encode_to_vec();
+        let now = self.clock.now_unix();
+        if session.is_expired(now) {
+            db.delete(StorageTree::Session, &session_key)
+                .map_err(|e| AuthError::Internal(format!("failed to delete session {}", e)))?;
+            return Err(AuthError::SessionExpired);
+        }
+
+        if self.sliding_sessions {
+            session.expires_at = now + self.session_ttl;
+            // Not flushed, losing the renewal in a crash only expires the session earlier
+            db.insert::<&SessionModel>(StorageTree::Session, &session_key, &session)
+                .map_err(|e| AuthError::Internal(format!("failed to renew session {}", e)))?;
+        }
+        Ok(session)
+    }
+
+    /// The record of `user` if `session_id` is a session of that user
+    async fn session_user(&self, session_id: &SessionId, user: &str) -> AuthResult<UserModel> {
+        let session = self.verify_session(session_id).await?;
+        if session.user != user {
+            return Err(AuthError::SessionMismatch);
+        }
+
+        self.get_user(&UserModel::user_id(&session.user)).await
+    }
+
+    /// Key slot `id` of `user` holding `(y1, y2)`, interactive keys have to fit the group of the
+    /// user
+    fn new_key_slot(
+        &self,
+        user: &UserModel,
+        id: u32,
+        y1: String,
+        y2: String,
+    ) -> AuthResult<KeySlot> {
+        let keys = LocalAuth::key_slot(id, y1, y2);
+        if keys.algorithm == KeyAlgorithm::Interactive {
+            let cp = self.cp_protocol(user.group)?;
+            LocalAuth::hex_field(cp, "y1", &keys.y1)?;
+            LocalAuth::hex_field(cp, "y2", &keys.y2)?;
+        }
+        Ok(keys)
+    }
+
+    async fn get_session(&self, session_id: &SessionId) -> AuthResult<SessionModel> {
+        let db = &self.db;
+        db.get::<SessionModel>(
+            StorageTree::Session,
+            &session_id.to_string().encode_to_vec(),
+        )
+        .map_err(|_| AuthError::InvalidSession)
+    }
+
+    async fn get_challenge_data(&self, challenge_key: &Vec<u8>) -> AuthResult<ChallengeModel> {
+        let db = &self.db;
+        if !db.exists(StorageTree::Challenge, challenge_key) {
+            return Err(AuthError::ChallengeNotFound);
+        }
+
+        db.get::<ChallengeModel>(StorageTree::Challenge, challenge_key)
+            .map_err(|_| AuthError::ChallengeNotFound)
+    }
+
+    /// Draws a random challenge bound to `commitment` and the user name, see
+    /// `ChaumPedersen::verifier_bind_challenge`
+    fn new_challenge(
+        &self,
+        commitment: (String, String),
+        user: UserModel,
+        slot: u32,
+        enrollment_epoch: Option<u64>,
+    ) -> AuthResult<ChallengeModel> {
+        let cp_protocol = self.cp_protocol(user.group)?;
+        let r1 = LocalAuth::hex_field(cp_protocol, "r1", &commitment.0)?;
+        let r2 = LocalAuth::hex_field(cp_protocol, "r2", &commitment.1)?;
+        let challenge = info_span!("generate_challenge")
+            .in_scope(|| cp_protocol.verifier_bind_challenge(&r1, &r2, user.user.as_bytes()));
+
+        Ok(ChallengeModel::new(
+            encode_field(&challenge, cp_protocol.byte_len()),
+            commitment,
+            user,
+            slot,
+            self.clock.now_unix(),
+            enrollment_epoch,
+        ))
+    }
+
+    async fn upsert_challenge(
+        &self,
+        commitment: (String, String),
+        user: UserModel,
+        slot: u32,
+        enrollment_epoch: Option<u64>,
+    ) -> AuthResult<Challenge> {
+        let index_key = UserModel::user_id(&user.user);
+        let challenge_model = self.new_challenge(commitment, user, slot, enrollment_epoch)?;
+        let challenge_hex = &challenge_model.challenge;
+
+        let auth_id = challenge_model.generate_auth_id();
+        let challenge_model_key = ChallengeModel::key(&auth_id);
+
+        // Evicting instead of rejecting, otherwise anyone could lock a user out by requesting
+        // challenges on their behalf.
+        let evicted = self
+            .db
+            .transaction(|tx| {
+                let mut outstanding = Vec::new();
+                for id in LocalAuth::outstanding_challenges(tx, &index_key)? {
+                    // Answered and expired challenges are deleted without touching the index
+                    if tx.exists(StorageTree::Challenge, &ChallengeModel::key(&id))? {
+                        outstanding.push(id);
+                    }
+                }
+
+                let excess = (outstanding.len() + 1).saturating_sub(self.max_challenges);
+                for id in outstanding.drain(..excess) {
+                    tx.delete(StorageTree::Challenge, &ChallengeModel::key(&id))?;
+                }
+                outstanding.push(auth_id.clone());
+
+                tx.insert(
+                    StorageTree::Challenge,
+                    &challenge_model_key,
+                    &challenge_model,
+                )?;
+                tx.insert(StorageTree::ChallengeIndex, &index_key, &outstanding)?;
+                Ok(excess)
+            })
+            .map_err(|e| AuthError::Internal(format!("failed to upsert {}", e)))?;
+        if evicted > 0 {
+            info!(evicted, "evicted oldest challenges");
+        }
+
+        // Placeholders of unknown users are never answered, without the sweep every name anyone
+        // asked a challenge for would stay in storage
+        let now = self.clock.now_unix();
+        let last_sweep = self.last_sweep.load(Ordering::Relaxed);
+        if now >= last_sweep + CHALLENGE_TTL_SECS
+            && self
+                .last_sweep
+                .compare_exchange(last_sweep, now, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        {
+            match self.sweep_expired_challenges() {
+                Ok(0) => {}
+                Ok(swept) => info!(swept, "swept expired challenges"),
+                Err(e) => error!(%e, "failed to sweep expired challenges"),
+            }
+        }
+
+        Ok(Challenge {
+            c: challenge_hex.clone(),
+            auth_id,
+            created_at: challenge_model.created_at,
+        })
+    }
+
+    /// Deletes the stored challenges that have expired and drops them from the index of their
+    /// user, records that can't be read any more go too. `upsert_challenge` runs it at most once
+    /// per `CHALLENGE_TTL_SECS`. Returns the number of challenges deleted.
+    pub fn sweep_expired_challenges(&self) -> AuthResult<usize> {
+        let now = self.clock.now_unix();
+        let keys = self
+            .db
+            .keys(StorageTree::Challenge)
+            .map_err(|e| AuthError::Internal(format!("failed to read challenges {}", e)))?;
+
+        let mut swept = 0;
+        for challenge_key in keys {
+            let index_key = match self
+                .db
+                .get::<ChallengeModel>(StorageTree::Challenge, &challenge_key)
+            {
+                Ok(challenge) if now <= challenge.created_at + CHALLENGE_TTL_SECS => continue,
+                Ok(challenge) => Some(UserModel::user_id(&challenge.user.user)),
+                Err(_) => None,
+            };
+            let auth_id = hex::encode(&challenge_key);
+            self.db
+                .transaction(|tx| {
+                    tx.delete(StorageTree::Challenge, &challenge_key)?;
+                    let Some(index_key) = &index_key else {
+                        return Ok(());
+                    };
+                    let mut outstanding = LocalAuth::outstanding_challenges(tx, index_key)?;
+                    outstanding.retain(|id| *id != auth_id);
+                    if outstanding.is_empty() {
+                        tx.delete(StorageTree::ChallengeIndex, index_key)
+                    } else {
+                        tx.insert(StorageTree::ChallengeIndex, index_key, &outstanding)
+                    }
+                })
+                .map_err(|e| AuthError::Internal(format!("failed to sweep {}", e)))?;
+            swept += 1;
+        }
+        Ok(swept)
+    }
+
+    fn outstanding_challenges(tx: &Transaction, index_key: &Vec<u8>) -> StorageResult<Vec<String>> {
+        match tx.get::<Vec<String>>(StorageTree::ChallengeIndex, index_key) {
+            Err(StorageError::NotFound) => Ok(Vec::new()),
+            result => result,
+        }
+    }
+
+    async fn non_interactive_verification_params(
+        &self,
+        user: &str,
+        slot: u32,
+        s: &str,
+        c: &str,
+    ) -> AuthResult<(Scalar, Scalar, RistrettoPoint, RistrettoPoint, UserModel)> {
+        let record = self
+            .get_user(&UserModel::user_id(&user.to_string()))
+            .await?;
+
+        // == Params for verification ==
+        let (solution, challenge) = LocalAuth::parse_non_interactive_proof(s, c)?;
+        let keys = record.slot(slot).ok_or(AuthError::KeySlotNotFound)?;
+        let y1 = self.ecc.point_from_base64(&keys.y1)?;
+        let y2 = self.ecc.point_from_base64(&keys.y2)?;
+
+        Ok((solution, challenge, y1, y2, record))
+    }
+
+    fn commitment_point(name: &str, value: &str) -> AuthResult<RistrettoPoint> {
+        EccChaumPedersen::from_base64(value)
+            .map_err(|e| AuthError::InvalidArgument(format!("invalid {}: {}", name, e)))
+    }
+
+    /// Malformed or non-canonical scalars are rejected before they reach the verifier
+    fn parse_non_interactive_proof(s: &str, c: &str) -> AuthResult<(Scalar, Scalar)> {
+        let solution = EccChaumPedersen::scalar_from_json(s)
+            .map_err(|e| AuthError::InvalidArgument(format!("invalid solution: {}", e)))?;
+        let challenge = EccChaumPedersen::scalar_from_json(c)
+            .map_err(|e| AuthError::InvalidArgument(format!("invalid challenge: {}", e)))?;
+
+        Ok((solution, challenge))
+    }
+
+    /// Issues and stores a session for `user` if the proof is valid, the challenge answered under
+    /// `auth_id` is deleted in the same transaction so it can't be replayed.
+    async fn login_response(
+        &self,
+        is_valid: bool,
+        user: &UserModel,
+        protocol: Protocol,
+        auth_id: Option<&str>,
+    ) -> AuthResult<SessionId> {
+        info!(is_valid, "proof verified");
+        if is_valid {
+            let challenge_key = auth_id.map(ChallengeModel::key);
+            let session_id = self.generate_session_id(user);
+            let session = SessionModel::new(
+                session_id,
+                user.user.clone(),
+                session_id.iat() + self.session_ttl,
+            );
+
+            let session_key = session_id.to_string().encode_to_vec();
+
+            let db = &self.db;
+            db.transaction(|tx| {
+                if let Some(challenge_key) = &challenge_key {
+                    tx.delete(StorageTree::Challenge, challenge_key)?;
+                }
+                tx.insert::<&SessionModel>(StorageTree::Session, &session_key, &session)
+            })
+            .map_err(|e| AuthError::Internal(format!("failed to store session {}", e)))?;
+            self.flush().await?;
+
+            increment_counter!(LOGIN_SUCCESS_TOTAL);
+            self.audit(user, auth_id, protocol, Outcome::Success);
+            return Ok(session_id);
+        }
+
+        increment_counter!(LOGIN_FAILURE_TOTAL);
+        self.audit(user, auth_id, protocol, Outcome::Failure);
+        Err(AuthError::InvalidProof)
+    }
+
+    /// Gives up on `verification` after `verify_timeout`. Dropping an interactive verification
+    /// cancels its blocking task before the next exponentiation, a non-interactive one is detached
+    /// and its result dropped.
+    async fn within_timeout(
+        &self,
+        verification: impl Future<Output = CpResult<bool>>,
+    ) -> AuthResult<bool> {
+        match tokio::time::timeout(self.verify_timeout, verification).await {
+            Ok(result) => Ok(result?),
+            Err(_) => {
+                warn!(timeout = ?self.verify_timeout, "proof verification timed out");
+                Err(CpError::Timeout.into())
+            }
+        }
+    }
+
+    fn audit(&self, user: &UserModel, auth_id: Option<&str>, protocol: Protocol, outcome: Outcome) {
+        if let Some(audit) = &self.audit {
+            audit.record(AuthEvent {
+                user: user.user.clone(),
+                auth_id: auth_id.map(str::to_string),
+                algorithm: protocol,
+                outcome,
+                timestamp: self.clock.now_unix(),
+            });
+        }
+    }
+
+    /// Registration doesn't say which protocol the keys are for, non-interactive keys are base64
+    /// encoded Ristretto points which a hex encoded `BigInt` never decodes to.
+    fn key_algorithm(y1: &str) -> KeyAlgorithm {
+        if EccChaumPedersen::from_base64(y1).is_ok() {
+            KeyAlgorithm::NonInteractive
+        } else {
+            KeyAlgorithm::Interactive
+        }
+    }
+
+    fn key_slot(id: u32, y1: String, y2: String) -> KeySlot {
+        KeySlot {
+            id,
+            algorithm: LocalAuth::key_algorithm(&y1),
+            y1,
+            y2,
+        }
+    }
+
+    /// `hex(nonce).iat.hex(tag)` where `tag` is the HMAC of `(user, nonce, iat)` under the server
+    /// secret, the random nonce keeps ids of logins within the same second apart.
+    fn generate_session_id(&self, user: &UserModel) -> SessionId {
+        let iat = self.clock.now_unix();
+
+        let mut nonce = [0u8; SESSION_NONCE_LEN];
+        rand::thread_rng().fill_bytes(&mut nonce);
+        let tag = self.session_mac(&user.user, &nonce, iat).finalize();
+
+        SessionId::new(nonce, iat, tag.into_bytes().into())
+    }
+
+    /// Checks that `session_id` was issued by this server for `user` without a storage lookup
+    fn verify_session_id(&self, session_id: &SessionId, user: &str) -> bool {
+        self.session_mac(user, session_id.nonce(), session_id.iat())
+            .verify_slice(session_id.tag())
+            .is_ok()
+    }
+
+    /// HMAC of the session-domain digest of `(user, nonce, iat)`
+    fn session_mac(&self, user: &str, nonce: &[u8], iat: u64) -> HmacSha256 {
+        let mut fields = (user.len() as u64).to_be_bytes().to_vec();
+        fields.extend_from_slice(user.as_bytes());
+        fields.extend_from_slice(nonce);
+        fields.extend_from_slice(&iat.to_be_bytes());
+
+        let mut mac =
+            HmacSha256::new_from_slice(&self.secret[..]).expect("HMAC accepts keys of any length");
+        mac.update(&hashing::hash_session(&fields));
+        mac
+    }
+
+    /// `epoch.hex(tag)` where `tag` is the HMAC of `(user, epoch)` under the server secret
+    fn enrollment_token(&self, user: &str, epoch: u64) -> String {
+        let tag = self.enrollment_mac(user, epoch).finalize();
+        format!("{}.{}", epoch, hex::encode(tag.into_bytes()))
+    }
+
+    /// The epoch signed in `token` if this server issued it for `user`
+    fn enrollment_epoch(&self, token: &str, user: &str) -> Option<u64> {
+        let (epoch, tag) = token.split_once('.')?;
+        let (Ok(epoch), Ok(tag)) = (epoch.parse(), hex::decode(tag)) else {
+            return None;
+        };
+
+        self.enrollment_mac(user, epoch)
+            .verify_slice(&tag)
+            .ok()
+            .map(|_| epoch)
+    }
+
+    /// `base64url(record).hex(tag)` where `record` is the stored form of the challenge and `tag` its
+    /// HMAC under the server secret
+    fn challenge_token(&self, challenge_model: &ChallengeModel) -> AuthResult<String> {
+        let record = challenge_model
+            .to_bytes()
+            .map_err(|e| AuthError::Internal(format!("failed to encode challenge {}", e)))?;
+        let tag = self.challenge_mac(&record).finalize();
+        Ok(format!(
+            "{}.{}",
+            URL_SAFE_NO_PAD.encode(&record),
+            hex::encode(tag.into_bytes())
+        ))
+    }
+
+    /// The challenge signed in `token` if this server issued it, expiry is up to the caller
+    fn challenge_from_token(&self, token: &str) -> Option<ChallengeModel> {
+        let (record, tag) = token.split_once('.')?;
+        let (Ok(record), Ok(tag)) = (URL_SAFE_NO_PAD.decode(record), hex::decode(tag)) else {
+            return None;
+        };
+
+        self.challenge_mac(&record).verify_slice(&tag).ok()?;
+        ChallengeModel::from_bytes(&record).ok()
+    }
+
+    fn challenge_mac(&self, record: &[u8]) -> HmacSha256 {
+        let mut mac =
+            HmacSha256::new_from_slice(&self.secret[..]).expect("HMAC accepts keys of any length");
+        mac.update(CHALLENGE_DOMAIN);
+        mac.update(record);
+        mac
+    }
+
+    /// Enrollments from the future or older than `enrollment_window` are rejected
+    fn check_enrollment_window(&self, epoch: u64) -> AuthResult<()> {
+        let now = self.clock.now_unix();
+        if epoch > now || now - epoch > self.enrollment_window {
+            return Err(AuthError::EnrollmentOutOfWindow);
+        }
+        Ok(())
+    }
+
+    fn enrollment_mac(&self, user: &str, epoch: u64) -> HmacSha256 {
+        let mut mac =
+            HmacSha256::new_from_slice(&self.secret[..]).expect("HMAC accepts keys of any length");
+        mac.update(ENROLLMENT_DOMAIN);
+        mac.update(&(user.len() as u64).to_be_bytes());
+        mac.update(user.as_bytes());
+        mac.update(&epoch.to_be_bytes());
+        mac
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+
+    use crate::clock::MockClock;
+
+    use super::*;
+
+    #[test]
+    fn non_interactive_proof_valid() {
+        let s = EccChaumPedersen::hash(b"solution");
+        let c = EccChaumPedersen::hash(b"challenge");
+
+        assert_eq!(
+            LocalAuth::parse_non_interactive_proof(
+                &serde_json::to_string(&s).unwrap(),
+                &serde_json::to_string(&c).unwrap(),
+            )
+            .unwrap(),
+            (s, c)
+        );
+    }
+
+    #[test]
+    fn non_interactive_proof_garbage() {
+        let c = serde_json::to_string(&EccChaumPedersen::hash(b"challenge")).unwrap();
+
+        for garbage in ["", "nyancat", "[1, 2, 3]", "{\"s\": 1}"] {
+            let e = LocalAuth::parse_non_interactive_proof(garbage, &c).unwrap_err();
+            assert!(matches!(e, AuthError::InvalidArgument(_)));
+        }
+    }
+
+    #[test]
+    fn non_interactive_proof_non_canonical() {
+        let s = serde_json::to_string(&EccChaumPedersen::hash(b"solution")).unwrap();
+        let non_canonical = serde_json::to_string(&[0xffu8; 32]).unwrap();
+
+        let e = LocalAuth::parse_non_interactive_proof(&s, &non_canonical).unwrap_err();
+        assert!(matches!(e, AuthError::InvalidArgument(_)));
+    }
+
+    #[test]
+    fn non_canonical_public_key() {
+        let non_canonical = STANDARD.encode([0xffu8; 32]);
+        let e = AuthError::from(EccChaumPedersen::from_base64(&non_canonical).unwrap_err());
+        assert!(matches!(e, AuthError::InvalidArgument(_)));
+    }
+
+    /// Writes `contents` to a file unique to the test and process, removed by the caller
+    fn params_file(name: &str, contents: &str) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!("{}-{}.json", name, std::process::id()));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn load_params() {
+        let missing = std::env::temp_dir().join("zkp-params-missing.json");
+        assert!(LocalAuth::load_params(&missing, DEFAULT_MAX_P_BITS)
+            .unwrap()
+            .is_none());
+
+        // `g = 4 = 2^2` is a quadratic residue, it generates the same subgroup as the built-in `g`
+        let mut params = CpParams::from(&Group::Modp2048.protocol());
+        params.g = BigInt::from(4);
+        let path = params_file("zkp-params-valid", &serde_json::to_string(&params).unwrap());
+        let loaded = LocalAuth::load_params(&path, DEFAULT_MAX_P_BITS);
+        fs::remove_file(&path).unwrap();
+
+        let (group, cp) = loaded.unwrap().unwrap();
+        assert_eq!(group, Group::Modp2048);
+        let auth = LocalAuth::with_params(KeyValueStorage::temporary(), group, cp);
+        assert_eq!(*auth.cp_protocol(2048).unwrap().g, BigInt::from(4));
+        assert_eq!(*auth.cp_protocol(3072).unwrap().g, BigInt::from(2));
+    }
+
+    #[tokio::test]
+    async fn audit_keys() {
+        let auth = LocalAuth::with_storage(KeyValueStorage::temporary());
+        let cat = ChaumPedersen::hash(b"cat");
+        register_user(&auth, "Nyan", &cat).await.unwrap();
+        let (user, _) = auth.public_keys("Nyan", KeySlot::FIRST).await.unwrap();
+
+        let cp = auth.protocol(Group::Modp2048);
+        let (k, r1, r2) = cp.prover_commit().await.unwrap();
+        let c = cp.verifier_generate_challenge();
+        let s = cp.prover_solve_challenge(k, c.clone(), cat);
+        let proof = Proof::new(r1, r2, c, s);
+        assert!(auth
+            .audit_keys(&user, KeySlot::FIRST, &proof)
+            .unwrap()
+            .holds());
+
+        // `y1` overwritten with the key of another password
+        let (dog, _) = cp
+            .generate_public_keys(ChaumPedersen::hash(b"dog"))
+            .await
+            .unwrap();
+        let mut tampered = user.clone();
+        tampered.slots[0].y1 = dog.to_str_radix(16);
+        let report = auth.audit_keys(&tampered, KeySlot::FIRST, &proof).unwrap();
+        assert!(!report.r1_holds);
+        assert!(report.r2_holds);
+
+        let unsupported = UserModel {
+            group: 1024,
+            ..user.clone()
+        };
+        assert!(auth
+            .audit_keys(&unsupported, KeySlot::FIRST, &proof)
+            .is_err());
+        assert_eq!(
+            auth.audit_keys(&user, 1, &proof),
+            Err(AuthError::KeySlotNotFound)
+        );
+    }
+
+    #[tokio::test]
+    async fn self_test() {
+        let auth = LocalAuth::with_storage(KeyValueStorage::temporary());
+        assert_eq!(auth.self_test().await, Ok(()));
+        // Nothing is left behind in the instance's own storage
+        assert!(auth.db.keys(StorageTree::Auth).unwrap().is_empty());
+
+        // A modulus that no longer matches the group order
+        let mut cp = Group::Modp2048.protocol();
+        cp.p = Arc::new(&*cp.p + 2);
+        let tampered = LocalAuth::with_params(KeyValueStorage::temporary(), Group::Modp2048, cp);
+        assert!(matches!(
+            tampered.self_test().await,
+            Err(AuthError::Internal(e)) if e.starts_with("2048-bit group")
+        ));
+    }
+
+    #[test]
+    fn load_invalid_params() {
+        let mut params = CpParams::from(&Group::Modp2048.protocol());
+        params.q = BigInt::from(11);
+        let path = params_file(
+            "zkp-params-invalid",
+            &serde_json::to_string(&params).unwrap(),
+        );
+        let loaded = LocalAuth::load_params(&path, DEFAULT_MAX_P_BITS);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            loaded.unwrap_err().to_string(),
+            "Invalid group parameters: q does not match p"
+        );
+
+        // Refused by its size before the generators are checked
+        let params = CpParams::from(&Group::Modp2048.protocol());
+        let path = params_file(
+            "zkp-params-too-large",
+            &serde_json::to_string(&params).unwrap(),
+        );
+        let loaded = LocalAuth::load_params(&path, 1024);
+        fs::remove_file(&path).unwrap();
+        assert_eq!(
+            loaded.unwrap_err().to_string(),
+            "p has 2048 bits, more than the maximum of 1024"
+        );
+
+        assert_eq!(LocalAuth::max_p_bits(None).unwrap(), DEFAULT_MAX_P_BITS);
+        assert_eq!(LocalAuth::max_p_bits(Some("1024".into())).unwrap(), 1024);
+        assert_eq!(
+            LocalAuth::max_p_bits(Some("nyan".into())).unwrap_err(),
+            AuthError::Internal(format!(
+                "invalid {}: invalid digit found in string",
+                MAX_P_BITS_ENV
+            ))
+        );
+
+        let path = params_file("zkp-params-garbage", "{\"p\": 23}");
+        let loaded = LocalAuth::load_params(&path, DEFAULT_MAX_P_BITS);
+        fs::remove_file(&path).unwrap();
+        assert!(matches!(loaded, Err(CpError::Deserialize(_))));
+    }
+
+    async fn register_user(auth: &LocalAuth, user: &str, x: &BigInt) -> AuthResult<String> {
+        let (y1, y2) = auth
+            .protocol(Group::Modp2048)
+            .generate_public_keys(x.clone())
+            .await
+            .unwrap();
+        auth.register(
+            user,
+            y1.to_str_radix(16),
+            y2.to_str_radix(16),
+            Group::Modp2048,
+        )
+        .await
+    }
+
+    /// Well-formed session id this server never issued
+    fn forged_session() -> SessionId {
+        SessionId::new([7; SESSION_NONCE_LEN], 1700000000, [7; 32])
+    }
+
+    /// Runs the interactive protocol for `user` and returns the issued session id
+    async fn login(auth: &LocalAuth, user: &str, x: &BigInt) -> AuthResult<SessionId> {
+        login_with(auth, user, KeySlot::FIRST, x, None).await
+    }
+
+    /// Same as `login` but answered with the keys in `slot`, for a challenge issued for the
+    /// `enrollment` token
+    async fn login_with(
+        auth: &LocalAuth,
+        user: &str,
+        slot: u32,
+        x: &BigInt,
+        enrollment: Option<&str>,
+    ) -> AuthResult<SessionId> {
+        let mut prover = Prover::new(auth.protocol(Group::Modp2048));
+        let (r1, r2) = prover.commit().await.unwrap();
+        let challenge = auth
+            .create_challenge_with_enrollment(
+                user,
+                slot,
+                (r1.to_str_radix(16), r2.to_str_radix(16)),
+                Group::Modp2048,
+                enrollment,
+            )
+            .await?;
+
+        let c = bigint_from_hex(&challenge.c).unwrap();
+        let s = prover.respond(c, x.clone()).unwrap();
+        auth.verify(&challenge.auth_id, Answer::Solution(s.to_str_radix(16)))
+            .await
+    }
+
+    async fn challenge(auth: &LocalAuth, user: &str) -> Challenge {
+        let cp = auth.protocol(Group::Modp2048);
+        let (_, r1, r2) = cp.prover_commit().await.unwrap();
+        auth.create_challenge(
+            user,
+            (r1.to_str_radix(16), r2.to_str_radix(16)),
+            Group::Modp2048,
+        )
+        .await
+        .unwrap()
+    }
+
+    async fn answer(auth: &LocalAuth, auth_id: &str) -> AuthError {
+        auth.verify(auth_id, Answer::Solution("1".to_string()))
+            .await
+            .unwrap_err()
+    }
+
+    #[tokio::test]
+    async fn interactive_login() {
+        let auth = LocalAuth::with_storage(KeyValueStorage::temporary());
+        let cat = ChaumPedersen::hash(b"cat");
+        register_user(&auth, "Nyan", &cat).await.unwrap();
+        assert_eq!(
+            auth.user_exists("Nyan").await.unwrap(),
+            Some(Protocol::Interactive)
+        );
+
+        let session_id = login(&auth, "Nyan", &cat).await.unwrap();
+        assert!(auth.verify_session_id(&session_id, "Nyan"));
+        assert_eq!(
+            login(&auth, "Nyan", &ChaumPedersen::hash(b"dog")).await,
+            Err(AuthError::InvalidProof)
+        );
+
+        // The same login with the whole transcript as a binary proof
+        let cp = auth.protocol(Group::Modp2048);
+        let mut prover = Prover::new(cp);
+        let (r1, r2) = prover.commit().await.unwrap();
+        let challenge = auth
+            .create_challenge(
+                "Nyan",
+                (r1.to_str_radix(16), r2.to_str_radix(16)),
+                Group::Modp2048,
+            )
+            .await
+            .unwrap();
+        let c = bigint_from_hex(&challenge.c).unwrap();
+        let s = prover.respond(c.clone(), cat).unwrap();
+        let proof = Proof::new(r1, r2, c, s).to_bytes(cp.byte_len()).unwrap();
+
+        let session_id = auth
+            .verify(&challenge.auth_id, Answer::Proof(proof.clone()))
+            .await
+            .unwrap();
+        assert!(auth.verify_session_id(&session_id, "Nyan"));
+        assert_eq!(
+            auth.verify(&challenge.auth_id, Answer::Proof(proof)).await,
+            Err(AuthError::ChallengeNotFound)
+        );
+    }
+
+    /// Keeps the recorded events in memory
+    #[derive(Default)]
+    struct CapturingSink(std::sync::Mutex<Vec<AuthEvent>>);
+
+    impl AuditSink for CapturingSink {
+        fn record(&self, event: AuthEvent) {
+            self.0.lock().unwrap().push(event);
+        }
+    }
+
+    #[tokio::test]
+    async fn audit_events() {
+        let clock = Arc::new(MockClock::new(1_700_000_000));
+        let sink = Arc::new(CapturingSink::default());
+        let auth = LocalAuth::with_storage(KeyValueStorage::temporary())
+            .with_clock(clock)
+            .with_audit_sink(sink.clone());
+        let cat = ChaumPedersen::hash(b"cat");
+        register_user(&auth, "Nyan", &cat).await.unwrap();
+
+        login(&auth, "Nyan", &cat).await.unwrap();
+        login(&auth, "Nyan", &ChaumPedersen::hash(b"dog"))
+            .await
+            .unwrap_err();
+
+        let events = sink.0.lock().unwrap();
+        let outcomes: Vec<_> = events.iter().map(|event| event.outcome).collect();
+        assert_eq!(outcomes, [Outcome::Success, Outcome::Failure]);
+        for event in events.iter() {
+            assert_eq!(event.user, "Nyan");
+            assert_eq!(event.algorithm, Protocol::Interactive);
+            assert_eq!(event.timestamp, 1_700_000_000);
+            assert!(event.auth_id.is_some());
+        }
+        assert_ne!(events[0].auth_id, events[1].auth_id);
+    }
+
+    #[tokio::test]
+    async fn non_interactive_login() {
+        let auth = LocalAuth::with_storage(KeyValueStorage::temporary());
+        let ecc = EccChaumPedersen::new();
+        let x = EccChaumPedersen::hash(b"cat");
+        let (y1, y2) = ecc.generate_public_keys(x).await.unwrap();
+        auth.register(
+            "Nyan",
+            EccChaumPedersen::to_base64(&y1),
+            EccChaumPedersen::to_base64(&y2),
+            Group::Modp2048,
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            auth.user_exists("Nyan").await.unwrap(),
+            Some(Protocol::NonInteractive)
+        );
+        assert_eq!(auth.user_exists("Tac").await.unwrap(), None);
+
+        let now = auth.clock.now_unix();
+        let ecc = ecc.with_timestamp(now);
+        let (k, r1, r2) = ecc.prover_commit().await.unwrap();
+        let c = ecc.derive_challenge(&r1, &r2);
+        let s = ecc.prover_solve_challenge(k, c, x);
+        let session_id = auth
+            .verify_non_interactive(
+                "Nyan",
+                now,
+                &serde_json::to_string(&s).unwrap(),
+                &serde_json::to_string(&c).unwrap(),
+            )
+            .await
+            .unwrap();
+        assert!(auth.verify_session_id(&session_id, "Nyan"));
+    }
+
+    /// Registers `user` for the non-interactive protocol, returns the public keys
+    async fn register_ecc(
+        auth: &LocalAuth,
+        user: &str,
+        x: Scalar,
+    ) -> (RistrettoPoint, RistrettoPoint) {
+        let (y1, y2) = auth.ecc.generate_public_keys(x).await.unwrap();
+        auth.register(
+            user,
+            EccChaumPedersen::to_base64(&y1),
+            EccChaumPedersen::to_base64(&y2),
+            Group::Modp2048,
+        )
+        .await
+        .unwrap();
+        (y1, y2)
+    }
+
+    #[tokio::test]
+    async fn non_interactive_commitment() {
+        let auth = LocalAuth::with_storage(KeyValueStorage::temporary());
+        let now = auth.clock.now_unix();
+        let ecc = EccChaumPedersen::new().with_timestamp(now);
+        let x = EccChaumPedersen::hash(b"cat");
+        let (y1, _) = register_ecc(&auth, "Nyan", x).await;
+
+        let (k, r1, r2) = ecc.prover_commit().await.unwrap();
+        let c = ecc.derive_challenge(&r1, &r2);
+        let s = serde_json::to_string(&ecc.prover_solve_challenge(k, c, x)).unwrap();
+        let c = serde_json::to_string(&c).unwrap();
+        let auth = &auth;
+        let login = |r1: &RistrettoPoint, r2: &RistrettoPoint| {
+            let (r1, r2) = (
+                EccChaumPedersen::to_base64(r1),
+                EccChaumPedersen::to_base64(r2),
+            );
+            let (s, c) = (s.clone(), c.clone());
+            async move {
+                auth.verify_non_interactive_with_commitment(
+                    "Nyan",
+                    KeySlot::FIRST,
+                    now,
+                    Some((&r1, &r2)),
+                    &s,
+                    &c,
+                )
+                .await
+            }
+        };
+
+        assert!(login(&r1, &r2).await.is_ok());
+        assert_eq!(login(&r2, &r1).await, Err(AuthError::InvalidProof));
+        let tampered = r1 + y1;
+        assert_eq!(login(&tampered, &r2).await, Err(AuthError::InvalidProof));
+
+        let e = auth
+            .verify_non_interactive_with_commitment(
+                "Nyan",
+                KeySlot::FIRST,
+                now,
+                Some(("nyan", "")),
+                &s,
+                &c,
+            )
+            .await
+            .unwrap_err();
+        assert!(matches!(e, AuthError::InvalidArgument(_)));
+    }
+
+    #[tokio::test]
+    async fn replayed_non_interactive_proof() {
+        let clock = Arc::new(MockClock::new(1_700_000_000));
+        let storage = KeyValueStorage::temporary();
+        let auth = LocalAuth::with_storage(storage.clone()).with_clock(clock.clone());
+        let x = EccChaumPedersen::hash(b"cat");
+        register_ecc(&auth, "Nyan", x).await;
+
+        let created_at = clock.now_unix();
+        let ecc = EccChaumPedersen::new().with_timestamp(created_at);
+        let (k, r1, r2) = ecc.prover_commit().await.unwrap();
+        let c = ecc.derive_challenge(&r1, &r2);
+        let s = serde_json::to_string(&ecc.prover_solve_challenge(k, c, x)).unwrap();
+        let c = serde_json::to_string(&c).unwrap();
+        let (r1, r2) = (
+            EccChaumPedersen::to_base64(&r1),
+            EccChaumPedersen::to_base64(&r2),
+        );
+
+        // The timestamp is part of the challenge, it can't be moved to get another proof
+        assert_eq!(
+            auth.verify_non_interactive("Nyan", created_at + 1, &s, &c)
+                .await,
+            Err(AuthError::InvalidProof)
+        );
+        auth.verify_non_interactive("Nyan", created_at, &s, &c)
+            .await
+            .unwrap();
+        assert_eq!(
+            auth.verify_non_interactive("Nyan", created_at, &s, &c)
+                .await,
+            Err(AuthError::ProofReplayed)
+        );
+        // Sending the commitment along doesn't make it another proof
+        assert_eq!(
+            auth.verify_non_interactive_with_commitment(
+                "Nyan",
+                KeySlot::FIRST,
+                created_at,
+                Some((&r1, &r2)),
+                &s,
+                &c
+            )
+            .await,
+            Err(AuthError::ProofReplayed)
+        );
+
+        // Out of the window the proof is rejected without being looked up, and its record is
+        // pruned with the next accepted one
+        clock.advance(CHALLENGE_TTL_SECS + 1);
+        assert_eq!(
+            auth.verify_non_interactive("Nyan", created_at, &s, &c)
+                .await,
+            Err(AuthError::ProofOutOfWindow)
+        );
+        let now = clock.now_unix();
+        assert_eq!(
+            auth.verify_non_interactive("Nyan", now + CHALLENGE_TTL_SECS + 1, &s, &c)
+                .await,
+            Err(AuthError::ProofOutOfWindow)
+        );
+        let ecc = ecc.with_timestamp(now);
+        let (k, r1, r2) = ecc.prover_commit().await.unwrap();
+        let c = ecc.derive_challenge(&r1, &r2);
+        let s = ecc.prover_solve_challenge(k, c, x);
+        auth.verify_non_interactive(
+            "Nyan",
+            now,
+            &serde_json::to_string(&s).unwrap(),
+            &serde_json::to_string(&c).unwrap(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(storage.keys(StorageTree::UsedProofs).unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn chosen_challenge() {
+        let auth = LocalAuth::with_storage(KeyValueStorage::temporary());
+        let now = auth.clock.now_unix();
+        let ecc = EccChaumPedersen::new().with_timestamp(now);
+        let (y1, y2) = register_ecc(&auth, "Nyan", EccChaumPedersen::hash(b"cat")).await;
+
+        // Without `x` a prover can still pick `c` and `s` and solve the verification equations
+        // for the commitment, only the challenge not being the hash of it gives the forgery away
+        let c = EccChaumPedersen::hash(b"chosen challenge");
+        let s = EccChaumPedersen::hash(b"chosen solution");
+        let (gs, hs, _) = ecc.commit_with(s);
+        let (r1, r2) = (gs + y1 * c, hs + y2 * c);
+        assert_eq!(
+            ecc.verify_interactive_sync(Proof::new(r1, r2, c, s), y1, y2),
+            Ok(true)
+        );
+
+        let (s, c) = (
+            serde_json::to_string(&s).unwrap(),
+            serde_json::to_string(&c).unwrap(),
+        );
+        assert_eq!(
+            auth.verify_non_interactive("Nyan", now, &s, &c).await,
+            Err(AuthError::InvalidProof)
+        );
+        let (r1, r2) = (
+            EccChaumPedersen::to_base64(&r1),
+            EccChaumPedersen::to_base64(&r2),
+        );
+        assert_eq!(
+            auth.verify_non_interactive_with_commitment(
+                "Nyan",
+                KeySlot::FIRST,
+                now,
+                Some((&r1, &r2)),
+                &s,
+                &c
+            )
+            .await,
+            Err(AuthError::InvalidProof)
+        );
+    }
+
+    #[tokio::test]
+    async fn malformed_hex_fields() {
+        let auth = LocalAuth::with_storage(KeyValueStorage::temporary());
+        register_user(&auth, "Nyan", &ChaumPedersen::hash(b"cat"))
+            .await
+            .unwrap();
+        let valid = challenge(&auth, "Nyan").await;
+        let commitment = |r1: &str, r2: &str| (r1.to_string(), r2.to_string());
+
+        let too_long = "f".repeat(2 * auth.protocol(Group::Modp2048).byte_len() + 1);
+        for malformed in ["", "nyancat", too_long.as_str()] {
+            let e = auth
+                .create_challenge("Nyan", commitment(malformed, "2"), Group::Modp2048)
+                .await
+                .unwrap_err();
+            assert_eq!(e.to_string(), "malformed hex field r1");
+            let e = auth
+                .create_challenge("Nyan", commitment("2", malformed), Group::Modp2048)
+                .await
+                .unwrap_err();
+            assert_eq!(e.to_string(), "malformed hex field r2");
+
+            let e = auth
+                .verify(&valid.auth_id, Answer::Solution(malformed.to_string()))
+                .await
+                .unwrap_err();
+            assert_eq!(
+                e,
+                AuthError::InvalidArgument("malformed hex field s".to_string())
+            );
+        }
+
+        // None of the rejected answers consumed the challenge
+        assert_eq!(answer(&auth, &valid.auth_id).await, AuthError::InvalidProof);
+    }
+
+    #[tokio::test]
+    async fn register_existing_user() {
+        let auth = LocalAuth::with_storage(KeyValueStorage::temporary());
+        let cat = ChaumPedersen::hash(b"cat");
+        let dog = ChaumPedersen::hash(b"dog");
+
+        register_user(&auth, "Nyan", &cat).await.unwrap();
+        let user_key = UserModel::user_id(&"Nyan".to_string());
+        let user = auth.get_user(&user_key).await.unwrap();
+
+        let e = register_user(&auth, "Nyan", &dog).await.unwrap_err();
+        assert_eq!(e, AuthError::AlreadyExists);
+
+        assert_eq!(auth.get_user(&user_key).await.unwrap(), user);
+        assert!(login(&auth, "Nyan", &cat).await.is_ok());
+        assert!(login(&auth, "Nyan", &dog).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn register_idempotent() {
+        let clock = Arc::new(MockClock::new(1_700_000_000));
+        let db = KeyValueStorage::temporary();
+        let auth = LocalAuth::with_storage(db.clone()).with_clock(clock.clone());
+        let cat = ChaumPedersen::hash(b"cat");
+        let (y1, y2) = auth
+            .protocol(Group::Modp2048)
+            .generate_public_keys(cat.clone())
+            .await
+            .unwrap();
+        let (y1, y2) = (y1.to_str_radix(16), y2.to_str_radix(16));
+        let register = |key: &'static str, y2: String| {
+            auth.register_idempotent(key, "Nyan", y1.clone(), y2, Group::Modp2048)
+        };
+
+        let enrollment = register("retry", y2.clone()).await.unwrap();
+        clock.advance(60);
+        assert_eq!(register("retry", y2.clone()).await.unwrap(), enrollment);
+        assert_eq!(db.keys(StorageTree::Auth).unwrap().len(), 1);
+        assert!(login(&auth, "Nyan", &cat).await.is_ok());
+
+        assert!(matches!(
+            register("retry", "3".to_string()).await,
+            Err(AuthError::InvalidArgument(_))
+        ));
+        assert_eq!(
+            register("other", y2.clone()).await,
+            Err(AuthError::AlreadyExists)
+        );
+
+        // Past the TTL the key is no longer remembered
+        clock.advance(IDEMPOTENCY_TTL_SECS);
+        assert_eq!(register("retry", y2).await, Err(AuthError::AlreadyExists));
+    }
+
+    #[tokio::test]
+    async fn unknown_user_challenge() {
+        let auth = LocalAuth::with_storage(KeyValueStorage::temporary());
+        register_user(&auth, "Nyan", &ChaumPedersen::hash(b"cat"))
+            .await
+            .unwrap();
+
+        let known = challenge(&auth, "Nyan").await;
+        let unknown = challenge(&auth, "Tac").await;
+
+        for challenge in [&known, &unknown] {
+            let key = ChallengeModel::key(&challenge.auth_id);
+            assert!(auth.db.exists(StorageTree::Challenge, &key));
+            assert!(bigint_from_hex(&challenge.c).is_ok());
+        }
+
+        assert_eq!(
+            answer(&auth, &unknown.auth_id).await,
+            AuthError::InvalidProof
+        );
+    }
+
+    #[tokio::test]
+    async fn sweep_expired_challenges() {
+        let clock = Arc::new(MockClock::new(1_700_000_000));
+        let auth = LocalAuth::with_storage(KeyValueStorage::temporary()).with_clock(clock.clone());
+        register_user(&auth, "Nyan", &ChaumPedersen::hash(b"cat"))
+            .await
+            .unwrap();
+        let challenges = || auth.db.keys(StorageTree::Challenge).unwrap();
+        let indexes = || auth.db.keys(StorageTree::ChallengeIndex).unwrap();
+
+        challenge(&auth, "Nyan").await;
+        for user in ["Tac", "Cat", "Dog"] {
+            challenge(&auth, user).await;
+        }
+        clock.advance(CHALLENGE_TTL_SECS);
+        let current = challenge(&auth, "Nyan").await;
+        assert_eq!((challenges().len(), indexes().len()), (5, 4));
+
+        // Only the challenges of the unknown users and the first one of Nyan have expired
+        clock.advance(1);
+        assert_eq!(auth.sweep_expired_challenges(), Ok(4));
+        assert_eq!(challenges(), [ChallengeModel::key(&current.auth_id)]);
+        let nyan = UserModel::user_id(&"Nyan".to_string());
+        assert_eq!(indexes(), std::slice::from_ref(&nyan));
+        assert_eq!(
+            auth.db
+                .get::<Vec<String>>(StorageTree::ChallengeIndex, &nyan)
+                .unwrap(),
+            std::slice::from_ref(&current.auth_id)
+        );
+
+        // Issuing a challenge sweeps once per challenge lifetime
+        clock.advance(CHALLENGE_TTL_SECS);
+        let next = challenge(&auth, "Tac").await;
+        assert_eq!(challenges(), [ChallengeModel::key(&next.auth_id)]);
+        assert_eq!(indexes(), [UserModel::user_id(&"Tac".to_string())]);
+    }
+
+    #[tokio::test]
+    async fn other_group_challenge() {
+        let auth = LocalAuth::with_storage(KeyValueStorage::temporary());
+        let cat = ChaumPedersen::hash(b"cat");
+        register_user(&auth, "Nyan", &cat).await.unwrap();
+
+        // Issued like for an unknown user, so it tells neither that Nyan exists nor the group
+        let mut prover = Prover::new(auth.protocol(Group::Modp3072));
+        let (r1, r2) = prover.commit().await.unwrap();
+        let challenge = auth
+            .create_challenge(
+                "Nyan",
+                (r1.to_str_radix(16), r2.to_str_radix(16)),
+                Group::Modp3072,
+            )
+            .await
+            .unwrap();
+        let c = bigint_from_hex(&challenge.c).unwrap();
+        let s = prover.respond(c, cat).unwrap();
+        assert_eq!(
+            auth.verify(&challenge.auth_id, Answer::Solution(s.to_str_radix(16)))
+                .await,
+            Err(AuthError::InvalidProof)
+        );
+    }
+
+    #[tokio::test]
+    async fn challenge_expiry() {
+        let clock = Arc::new(MockClock::new(1_700_000_000));
+        let auth = LocalAuth::with_storage(KeyValueStorage::temporary()).with_clock(clock.clone());
+        register_user(&auth, "Nyan", &ChaumPedersen::hash(b"cat"))
+            .await
+            .unwrap();
+
+        let fresh = challenge(&auth, "Nyan").await;
+        let expired = challenge(&auth, "Nyan").await;
+
+        clock.advance(CHALLENGE_TTL_SECS);
+        assert_eq!(answer(&auth, &fresh.auth_id).await, AuthError::InvalidProof);
+
+        clock.advance(1);
+        assert_eq!(
+            answer(&auth, &expired.auth_id).await,
+            AuthError::ChallengeExpired
+        );
+        let key = ChallengeModel::key(&expired.auth_id);
+        assert!(!auth.db.exists(StorageTree::Challenge, &key));
+        assert_eq!(
+            answer(&auth, &expired.auth_id).await,
+            AuthError::ChallengeNotFound
+        );
+    }
+
+    #[tokio::test]
+    async fn stateless_challenge() {
+        let auth = LocalAuth::with_storage(KeyValueStorage::temporary())
+            .with_stateless_challenges(true)
+            .with_challenge_limit(1);
+        let cat = ChaumPedersen::hash(b"cat");
+        register_user(&auth, "Nyan", &cat).await.unwrap();
+
+        let session_id = login(&auth, "Nyan", &cat).await.unwrap();
+        assert!(auth.verify_session_id(&session_id, "Nyan"));
+        assert_eq!(
+            login(&auth, "Nyan", &ChaumPedersen::hash(b"dog")).await,
+            Err(AuthError::InvalidProof)
+        );
+
+        // Nothing is stored, so nothing is evicted either
+        let first = challenge(&auth, "Nyan").await;
+        challenge(&auth, "Nyan").await;
+        assert!(auth.db.keys(StorageTree::Challenge).unwrap().is_empty());
+        assert!(auth
+            .db
+            .keys(StorageTree::ChallengeIndex)
+            .unwrap()
+            .is_empty());
+        assert_eq!(answer(&auth, &first.auth_id).await, AuthError::InvalidProof);
+    }
+
+    #[tokio::test]
+    async fn replayed_proof() {
+        let storage = KeyValueStorage::temporary();
+        let auth = LocalAuth::with_storage(storage.clone()).with_stateless_challenges(true);
+        let secret = auth.secret.clone();
+        let cat = ChaumPedersen::hash(b"cat");
+        register_user(&auth, "Nyan", &cat).await.unwrap();
+
+        let cp = auth.protocol(Group::Modp2048);
+        let mut prover = Prover::new(cp);
+        let (r1, r2) = prover.commit().await.unwrap();
+        let challenge = auth
+            .create_challenge(
+                "Nyan",
+                (r1.to_str_radix(16), r2.to_str_radix(16)),
+                Group::Modp2048,
+            )
+            .await
+            .unwrap();
+        let c = bigint_from_hex(&challenge.c).unwrap();
+        let s = prover.respond(c, cat.clone()).unwrap();
+
+        auth.verify(&challenge.auth_id, Answer::Solution(s.to_str_radix(16)))
+            .await
+            .unwrap();
+        // In any encoding of the solution
+        let padded = encode_field(&s, cp.byte_len());
+        assert_eq!(
+            auth.verify(&challenge.auth_id, Answer::Solution(padded.clone()))
+                .await,
+            Err(AuthError::ProofReplayed)
+        );
+
+        // Nothing but the storage and the secret survive a restart
+        drop(auth);
+        let restarted = LocalAuth::with_storage(storage)
+            .with_secret(secret)
+            .with_stateless_challenges(true);
+        assert_eq!(
+            restarted
+                .verify(&challenge.auth_id, Answer::Solution(padded))
+                .await,
+            Err(AuthError::ProofReplayed)
+        );
+        login(&restarted, "Nyan", &cat).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn prune_used_proofs() {
+        let clock = Arc::new(MockClock::new(1_700_000_000));
+        let storage = KeyValueStorage::temporary();
+        let auth = LocalAuth::with_storage(storage.clone()).with_clock(clock.clone());
+        let cat = ChaumPedersen::hash(b"cat");
+        register_user(&auth, "Nyan", &cat).await.unwrap();
+        let used_proofs = || storage.keys(StorageTree::UsedProofs).unwrap().len();
+
+        login(&auth, "Nyan", &cat).await.unwrap();
+        clock.advance(CHALLENGE_TTL_SECS);
+        login(&auth, "Nyan", &cat).await.unwrap();
+        assert_eq!(used_proofs(), 2);
+
+        // The first challenge has expired now, its answer is pruned when the next one is stored
+        clock.advance(1);
+        login(&auth, "Nyan", &cat).await.unwrap();
+        assert_eq!(used_proofs(), 2);
+    }
+
+    #[tokio::test]
+    async fn stateless_challenge_expiry() {
+        let clock = Arc::new(MockClock::new(1_700_000_000));
+        let auth = LocalAuth::with_storage(KeyValueStorage::temporary())
+            .with_clock(clock.clone())
+            .with_stateless_challenges(true);
+        register_user(&auth, "Nyan", &ChaumPedersen::hash(b"cat"))
+            .await
+            .unwrap();
+
+        let challenge = challenge(&auth, "Nyan").await;
+        clock.advance(CHALLENGE_TTL_SECS);
+        assert_eq!(
+            answer(&auth, &challenge.auth_id).await,
+            AuthError::InvalidProof
+        );
+
+        clock.advance(1);
+        assert_eq!(
+            answer(&auth, &challenge.auth_id).await,
+            AuthError::ChallengeExpired
+        );
+    }
+
+    #[tokio::test]
+    async fn stateless_challenge_tampered() {
+        let auth =
+            LocalAuth::with_storage(KeyValueStorage::temporary()).with_stateless_challenges(true);
+        let cat = ChaumPedersen::hash(b"cat");
+        register_user(&auth, "Nyan", &cat).await.unwrap();
+        register_user(&auth, "Cat", &ChaumPedersen::hash(b"dog"))
+            .await
+            .unwrap();
+
+        let nyan = challenge(&auth, "Nyan").await.auth_id;
+        let cat_token = challenge(&auth, "Cat").await.auth_id;
+        let (record, tag) = nyan.split_once('.').unwrap();
+
+        // The record of one challenge under the tag of another
+        let (other, _) = cat_token.split_once('.').unwrap();
+        let swapped = format!("{}.{}", other, tag);
+        // A flipped bit in the record
+        let mut bytes = URL_SAFE_NO_PAD.decode(record).unwrap();
+        *bytes.last_mut().unwrap() ^= 1;
+        let flipped = format!("{}.{}", URL_SAFE_NO_PAD.encode(bytes), tag);
+        // Signed by another server
+        let foreign =
+            LocalAuth::with_storage(KeyValueStorage::temporary()).with_stateless_challenges(true);
+        register_user(&foreign, "Nyan", &cat).await.unwrap();
+        let foreign = challenge(&foreign, "Nyan").await.auth_id;
+
+        for auth_id in [&swapped, &flipped, &foreign, record, "", "not.a token"] {
+            assert_eq!(answer(&auth, auth_id).await, AuthError::ChallengeNotFound);
+        }
+    }
+
+    /// Proves knowledge of `old` to a fresh challenge for `user` and rotates its keys to those of `new`
+    async fn rotate(
+        auth: &LocalAuth,
+        user: &str,
+        old: &BigInt,
+        new: &BigInt,
+    ) -> AuthResult<String> {
+        let cp = auth.protocol(Group::Modp2048);
+        let mut prover = Prover::new(cp);
+        let (r1, r2) = prover.commit().await.unwrap();
+        let challenge = auth
+            .create_challenge(
+                user,
+                (r1.to_str_radix(16), r2.to_str_radix(16)),
+                Group::Modp2048,
+            )
+            .await?;
+        let c = bigint_from_hex(&challenge.c).unwrap();
+        let s = prover.respond(c, old.clone()).unwrap();
+
+        let (y1, y2) = cp.generate_public_keys(new.clone()).await.unwrap();
+        auth.rotate_keys(
+            user,
+            y1.to_str_radix(16),
+            y2.to_str_radix(16),
+            Group::Modp2048,
+            &challenge.auth_id,
+            Answer::Solution(s.to_str_radix(16)),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn rotate_keys() {
+        let auth = LocalAuth::with_storage(KeyValueStorage::temporary());
+        let cat = ChaumPedersen::hash(b"cat");
+        let dog = ChaumPedersen::hash(b"dog");
+        register_user(&auth, "Nyan", &cat).await.unwrap();
+        assert_eq!(
+            register_user(&auth, "Nyan", &dog).await,
+            Err(AuthError::AlreadyExists)
+        );
+
+        // Proving a secret that isn't the registered one leaves the keys alone
+        assert_eq!(
+            rotate(&auth, "Nyan", &dog, &dog).await,
+            Err(AuthError::InvalidProof)
+        );
+        assert!(login(&auth, "Nyan", &cat).await.is_ok());
+
+        rotate(&auth, "Nyan", &cat, &dog).await.unwrap();
+        assert!(login(&auth, "Nyan", &dog).await.is_ok());
+        assert_eq!(
+            login(&auth, "Nyan", &cat).await,
+            Err(AuthError::InvalidProof)
+        );
+
+        // A proof for one user doesn't rotate the keys of another
+        register_user(&auth, "Tac", &cat).await.unwrap();
+        let challenge = challenge(&auth, "Tac").await;
+        let e = auth
+            .rotate_keys(
+                "Nyan",
+                "2".to_string(),
+                "3".to_string(),
+                Group::Modp2048,
+                &challenge.auth_id,
+                Answer::Solution("1".to_string()),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(
+            e,
+            AuthError::InvalidArgument("challenge was issued for another user".to_string())
+        );
+    }
+
+    #[tokio::test]
+    async fn rotation_ends_challenges() {
+        for stateless in [false, true] {
+            let auth = LocalAuth::with_storage(KeyValueStorage::temporary())
+                .with_stateless_challenges(stateless);
+            let cat = ChaumPedersen::hash(b"cat");
+            let dog = ChaumPedersen::hash(b"dog");
+            register_user(&auth, "Nyan", &cat).await.unwrap();
+
+            // Issued before the rotation and answered with the old password after it
+            let cp = auth.protocol(Group::Modp2048);
+            let mut prover = Prover::new(cp);
+            let (r1, r2) = prover.commit().await.unwrap();
+            let challenge = auth
+                .create_challenge(
+                    "Nyan",
+                    (r1.to_str_radix(16), r2.to_str_radix(16)),
+                    Group::Modp2048,
+                )
+                .await
+                .unwrap();
+            rotate(&auth, "Nyan", &cat, &dog).await.unwrap();
+            assert!(auth.db.keys(StorageTree::Challenge).unwrap().is_empty());
+            assert!(auth
+                .db
+                .keys(StorageTree::ChallengeIndex)
+                .unwrap()
+                .is_empty());
+
+            let c = bigint_from_hex(&challenge.c).unwrap();
+            let s = prover.respond(c, cat.clone()).unwrap();
+            assert_eq!(
+                auth.verify(&challenge.auth_id, Answer::Solution(s.to_str_radix(16)))
+                    .await,
+                Err(AuthError::ChallengeNotFound)
+            );
+            assert!(login(&auth, "Nyan", &dog).await.is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn changed_generators() {
+        let storage = KeyValueStorage::temporary();
+        let auth = LocalAuth::with_storage(storage.clone());
+        let cat = ChaumPedersen::hash(b"cat");
+        register_user(&auth, "Nyan", &cat).await.unwrap();
+        assert_eq!(auth.flag_stale_generators(), Ok(0));
+
+        // Restarted with other generators of the group Nyan registered in
+        let (g, h) = (BigInt::from(4), BigInt::from(9));
+        let rotated = ChaumPedersen::new(Group::Modp2048.prime().clone(), g, h);
+        let restarted = LocalAuth::with_params(storage, Group::Modp2048, rotated);
+        assert_ne!(
+            restarted.generators(Group::Modp2048),
+            auth.generators(Group::Modp2048)
+        );
+        assert_eq!(restarted.flag_stale_generators(), Ok(1));
+        // The challenge is issued like to any other user, only the answer is refused
+        let cp = restarted.protocol(Group::Modp2048);
+        let (_, r1, r2) = cp.prover_commit().await.unwrap();
+        let commitment = (r1.to_str_radix(16), r2.to_str_radix(16));
+        let challenge = restarted
+            .create_challenge("Nyan", commitment, Group::Modp2048)
+            .await
+            .unwrap();
+        assert_eq!(
+            restarted
+                .verify(&challenge.auth_id, Answer::Solution("1".to_string()))
+                .await,
+            Err(AuthError::ReregistrationRequired)
+        );
+        assert_eq!(
+            login(&restarted, "Nyan", &cat).await,
+            Err(AuthError::ReregistrationRequired)
+        );
+
+        register_user(&restarted, "Cat", &cat).await.unwrap();
+        assert!(login(&restarted, "Cat", &cat).await.is_ok());
+        assert_eq!(restarted.flag_stale_generators(), Ok(1));
+
+        // Back to the generators Nyan registered with
+        assert_eq!(auth.flag_stale_generators(), Ok(1));
+        assert!(login(&auth, "Nyan", &cat).await.is_ok());
+        assert_eq!(
+            login(&auth, "Cat", &cat).await,
+            Err(AuthError::ReregistrationRequired)
+        );
+    }
+
+    #[tokio::test]
+    async fn enrollment_window() {
+        let clock = Arc::new(MockClock::new(1_700_000_000));
+        let auth = LocalAuth::with_storage(KeyValueStorage::temporary())
+            .with_clock(clock.clone())
+            .with_enrollment_window(1000);
+        let cat = ChaumPedersen::hash(b"cat");
+        let enrollment = register_user(&auth, "Nyan", &cat).await.unwrap();
+        assert!(enrollment.starts_with("1700000000."));
+
+        clock.advance(1000);
+        let session_id = login_with(&auth, "Nyan", KeySlot::FIRST, &cat, Some(&enrollment))
+            .await
+            .unwrap();
+        assert!(auth.verify_session_id(&session_id, "Nyan"));
+
+        clock.advance(1);
+        assert_eq!(
+            login_with(&auth, "Nyan", KeySlot::FIRST, &cat, Some(&enrollment)).await,
+            Err(AuthError::EnrollmentOutOfWindow)
+        );
+        // Without the token the window doesn't apply
+        assert!(login(&auth, "Nyan", &cat).await.is_ok());
+
+        let tac = register_user(&auth, "Tac", &cat).await.unwrap();
+        let backdated = format!("1699999999.{}", tac.split_once('.').unwrap().1);
+        for forged in [tac.as_str(), backdated.as_str(), "1700000000", "nyancat"] {
+            assert_eq!(
+                login_with(&auth, "Nyan", KeySlot::FIRST, &cat, Some(forged)).await,
+                Err(AuthError::InvalidArgument(
+                    "invalid enrollment token".to_string()
+                ))
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn verify_timeout() {
+        let auth = LocalAuth::with_storage(KeyValueStorage::temporary())
+            .with_verify_timeout(Duration::from_millis(10));
+
+        let slow = async {
+            tokio::task::spawn_blocking(|| std::thread::sleep(Duration::from_millis(200)))
+                .await
+                .map_err(|e| CpError::TaskJoin(e.to_string()))?;
+            Ok(true)
+        };
+        assert_eq!(
+            auth.within_timeout(slow).await,
+            Err(AuthError::VerificationTimeout)
+        );
+        assert_eq!(auth.within_timeout(async { Ok(true) }).await, Ok(true));
+    }
+
+    #[tokio::test]
+    async fn session_issued_at() {
+        let clock = Arc::new(MockClock::new(1_700_000_000));
+        let auth = LocalAuth::with_storage(KeyValueStorage::temporary()).with_clock(clock.clone());
+        let cat = ChaumPedersen::hash(b"cat");
+        register_user(&auth, "Nyan", &cat).await.unwrap();
+
+        clock.advance(42);
+        let session_id = login(&auth, "Nyan", &cat).await.unwrap();
+        assert_eq!(session_id.iat(), 1700000042);
+    }
+
+    #[tokio::test]
+    async fn session_expiry() {
+        let clock = Arc::new(MockClock::new(1_700_000_000));
+        let storage = KeyValueStorage::temporary();
+        let auth = LocalAuth::with_storage(storage.clone())
+            .with_clock(clock.clone())
+            .with_session_ttl(60);
+        let cat = ChaumPedersen::hash(b"cat");
+        register_user(&auth, "Nyan", &cat).await.unwrap();
+
+        let session_id = login(&auth, "Nyan", &cat).await.unwrap();
+        let session = auth.verify_session(&session_id).await.unwrap();
+        assert_eq!(session.expires_at, 1700000060);
+
+        // Using it doesn't extend a fixed expiry
+        clock.advance(60);
+        assert!(auth.verify_session(&session_id).await.is_ok());
+        clock.advance(1);
+        assert_eq!(
+            auth.verify_session(&session_id).await,
+            Err(AuthError::SessionExpired)
+        );
+
+        // Deleted on the lookup that found it expired
+        let session_key = session_id.to_string().encode_to_vec();
+        assert!(!storage.exists(StorageTree::Session, &session_key));
+        assert_eq!(
+            auth.verify_session(&session_id).await,
+            Err(AuthError::InvalidSession)
+        );
+    }
+
+    #[tokio::test]
+    async fn sliding_session_expiry() {
+        let clock = Arc::new(MockClock::new(1_700_000_000));
+        let auth = LocalAuth::with_storage(KeyValueStorage::temporary())
+            .with_clock(clock.clone())
+            .with_session_ttl(60)
+            .with_sliding_sessions(true);
+        let cat = ChaumPedersen::hash(b"cat");
+        register_user(&auth, "Nyan", &cat).await.unwrap();
+        let session_id = login(&auth, "Nyan", &cat).await.unwrap();
+
+        // Used within the TTL it outlives the TTL of the login
+        for _ in 0..3 {
+            clock.advance(50);
+            let session = auth.verify_session(&session_id).await.unwrap();
+            assert_eq!(session.expires_at, clock.now_unix() + 60);
+        }
+        assert!(clock.now_unix() > session_id.iat() + 60);
+
+        // Idle for longer than the TTL it expires
+        clock.advance(61);
+        assert_eq!(
+            auth.verify_session(&session_id).await,
+            Err(AuthError::SessionExpired)
+        );
+        let (y1, y2) = auth
+            .protocol(Group::Modp2048)
+            .generate_public_keys(cat)
+            .await
+            .unwrap();
+        assert_eq!(
+            auth.add_key_slot(
+                &session_id,
+                "Nyan",
+                y1.to_str_radix(16),
+                y2.to_str_radix(16)
+            )
+            .await,
+            Err(AuthError::InvalidSession)
+        );
+    }
+
+    #[tokio::test]
+    async fn challenge_limit() {
+        let auth = LocalAuth::with_storage(KeyValueStorage::temporary()).with_challenge_limit(3);
+        register_user(&auth, "Nyan", &ChaumPedersen::hash(b"cat"))
+            .await
+            .unwrap();
+
+        let mut issued = Vec::new();
+        for _ in 0..4 {
+            issued.push(challenge(&auth, "Nyan").await.auth_id);
+        }
+
+        let outstanding = |auth_id: &String| {
+            auth.db
+                .exists(StorageTree::Challenge, &ChallengeModel::key(auth_id))
+        };
+        assert!(!outstanding(&issued[0]));
+        assert!(issued[1..].iter().all(outstanding));
+        let index = auth
+            .db
+            .get::<Vec<String>>(
+                StorageTree::ChallengeIndex,
+                &UserModel::user_id(&"Nyan".to_string()),
+            )
+            .unwrap();
+        assert_eq!(index, issued[1..]);
+
+        assert_eq!(
+            answer(&auth, &issued[0]).await,
+            AuthError::ChallengeNotFound
+        );
+    }
+
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn concurrent_registrations() {
+        let auth = Arc::new(LocalAuth::with_storage(KeyValueStorage::temporary()));
+
+        let registrations: Vec<_> = (0..32)
+            .map(|i| {
+                let auth = auth.clone();
+                tokio::spawn(async move {
+                    let x = ChaumPedersen::hash(format!("cat{}", i).as_bytes());
+                    register_user(&auth, "Nyan", &x).await.map(|_| x)
+                })
+            })
+            .collect();
+
+        let mut winners = Vec::new();
+        for registration in registrations {
+            match registration.await.unwrap() {
+                Ok(x) => winners.push(x),
+                Err(e) => assert_eq!(e, AuthError::AlreadyExists),
+            }
+        }
+
+        assert_eq!(winners.len(), 1);
+        assert!(login(&auth, "Nyan", &winners[0]).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn session_ids() {
+        let auth = LocalAuth::with_storage(KeyValueStorage::temporary());
+        let cat = ChaumPedersen::hash(b"cat");
+        register_user(&auth, "Nyan", &cat).await.unwrap();
+
+        let first = login(&auth, "Nyan", &cat).await.unwrap();
+        let second = login(&auth, "Nyan", &cat).await.unwrap();
+        assert_ne!(first, second);
+        assert!(auth.verify_session_id(&first, "Nyan"));
+        assert!(auth.verify_session_id(&second, "Nyan"));
+        assert!(!auth.verify_session_id(&first, "Tac"));
+
+        let tampered_iat = SessionId::new(*first.nonce(), first.iat() + 1, *first.tag());
+        assert!(!auth.verify_session_id(&tampered_iat, "Nyan"));
+        let tampered_tag = SessionId::new(*first.nonce(), first.iat(), [0; 32]);
+        assert!(!auth.verify_session_id(&tampered_tag, "Nyan"));
+        assert!(!auth.verify_session_id(&forged_session(), "Nyan"));
+
+        let restarted = LocalAuth::with_storage(KeyValueStorage::temporary());
+        assert!(!restarted.verify_session_id(&first, "Nyan"));
+        let restarted = restarted.with_secret(auth.secret.clone());
+        assert!(restarted.verify_session_id(&first, "Nyan"));
+    }
+
+    #[tokio::test]
+    async fn update_credentials() {
+        let auth = LocalAuth::with_storage(KeyValueStorage::temporary());
+        let cat = ChaumPedersen::hash(b"cat");
+        let dog = ChaumPedersen::hash(b"dog");
+        let (y1, y2) = auth
+            .protocol(Group::Modp2048)
+            .generate_public_keys(dog.clone())
+            .await
+            .unwrap();
+        let (y1, y2) = (y1.to_str_radix(16), y2.to_str_radix(16));
+
+        register_user(&auth, "Nyan", &cat).await.unwrap();
+        register_user(&auth, "Tac", &dog).await.unwrap();
+
+        let e = auth
+            .update_credentials(
+                &forged_session(),
+                "Nyan",
+                KeySlot::FIRST,
+                y1.clone(),
+                y2.clone(),
+            )
+            .await
+            .unwrap_err();
+        assert_eq!(e, AuthError::InvalidSession);
+
+        let tac_session = login(&auth, "Tac", &dog).await.unwrap();
+        let e = auth
+            .update_credentials(&tac_session, "Nyan", KeySlot::FIRST, y1.clone(), y2.clone())
+            .await
+            .unwrap_err();
+        assert_eq!(e, AuthError::SessionMismatch);
+
+        let session = login(&auth, "Nyan", &cat).await.unwrap();
+        assert_eq!(
+            auth.update_credentials(&session, "Nyan", 1, y1.clone(), y2.clone())
+                .await,
+            Err(AuthError::KeySlotNotFound)
+        );
+        auth.update_credentials(&session, "Nyan", KeySlot::FIRST, y1, y2)
+            .await
+            .unwrap();
+
+        assert!(login(&auth, "Nyan", &cat).await.is_err());
+        assert!(login(&auth, "Nyan", &dog).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn key_slots() {
+        let auth = LocalAuth::with_storage(KeyValueStorage::temporary());
+        let cat = ChaumPedersen::hash(b"cat");
+        let dog = ChaumPedersen::hash(b"dog");
+        let (y1, y2) = auth
+            .protocol(Group::Modp2048)
+            .generate_public_keys(dog.clone())
+            .await
+            .unwrap();
+        let (y1, y2) = (y1.to_str_radix(16), y2.to_str_radix(16));
+
+        register_user(&auth, "Nyan", &cat).await.unwrap();
+        assert_eq!(
+            auth.add_key_slot(&forged_session(), "Nyan", y1.clone(), y2.clone())
+                .await,
+            Err(AuthError::InvalidSession)
+        );
+
+        // Keys of another group than the user's
+        let session = login(&auth, "Nyan", &cat).await.unwrap();
+        let e = auth
+            .add_key_slot(&session, "Nyan", "1".repeat(2 * 512), y2.clone())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            e,
+            AuthError::InvalidArgument("malformed hex field y1".to_string())
+        );
+
+        let slot = auth.add_key_slot(&session, "Nyan", y1, y2).await.unwrap();
+        assert_eq!(slot, 1);
+        let (user, keys) = auth.public_keys("Nyan", slot).await.unwrap();
+        assert_eq!(user.slots.len(), 2);
+        assert_eq!(keys.algorithm, KeyAlgorithm::Interactive);
+
+        // Either slot logs in, each with its own secret
+        assert!(login_with(&auth, "Nyan", slot, &dog, None).await.is_ok());
+        assert!(login(&auth, "Nyan", &cat).await.is_ok());
+        assert_eq!(
+            login_with(&auth, "Nyan", slot, &cat, None).await,
+            Err(AuthError::InvalidProof)
+        );
+        assert_eq!(
+            login(&auth, "Nyan", &dog).await,
+            Err(AuthError::InvalidProof)
+        );
+        // A slot the user doesn't have is challenged like an unknown user
+        assert_eq!(
+            login_with(&auth, "Nyan", 7, &cat, None).await,
+            Err(AuthError::InvalidProof)
+        );
+
+        // Removing a slot drops its outstanding challenges
+        let outstanding = challenge(&auth, "Nyan").await;
+        auth.remove_key_slot(&session, "Nyan", KeySlot::FIRST)
+            .await
+            .unwrap();
+        assert_eq!(
+            answer(&auth, &outstanding.auth_id).await,
+            AuthError::ChallengeNotFound
+        );
+        assert_eq!(
+            login(&auth, "Nyan", &cat).await,
+            Err(AuthError::InvalidProof)
+        );
+        assert!(login_with(&auth, "Nyan", slot, &dog, None).await.is_ok());
+        assert_eq!(
+            auth.user_exists("Nyan").await.unwrap(),
+            Some(Protocol::Interactive)
+        );
+
+        assert_eq!(
+            auth.remove_key_slot(&session, "Nyan", KeySlot::FIRST).await,
+            Err(AuthError::KeySlotNotFound)
+        );
+        assert_eq!(
+            auth.remove_key_slot(&session, "Nyan", slot).await,
+            Err(AuthError::InvalidArgument(
+                "the last key slot can't be removed".to_string()
+            ))
+        );
+        // The next slot doesn't reuse the id of the remaining one
+        let (y1, y2) = auth
+            .protocol(Group::Modp2048)
+            .generate_public_keys(cat.clone())
+            .await
+            .unwrap();
+        let next = auth
+            .add_key_slot(&session, "Nyan", y1.to_str_radix(16), y2.to_str_radix(16))
+            .await
+            .unwrap();
+        assert_eq!(next, 2);
+    }
+}
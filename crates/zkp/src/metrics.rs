@@ -0,0 +1,16 @@
+use std::net::SocketAddr;
+
+use metrics_exporter_prometheus::{BuildError, PrometheusBuilder};
+
+pub const REGISTER_TOTAL: &str = "zkp_register_total";
+pub const LOGIN_SUCCESS_TOTAL: &str = "zkp_login_success_total";
+pub const LOGIN_FAILURE_TOTAL: &str = "zkp_login_failure_total";
+pub const VERIFY_SECONDS: &str = "zkp_verify_seconds";
+
+pub const METRICS_ADDR: &str = "0.0.0.0:9000";
+
+/// Installs the global Prometheus recorder and serves the scrape endpoint at `addr`/metrics,
+/// must be called from within the tokio runtime.
+pub fn install(addr: SocketAddr) -> Result<(), BuildError> {
+    PrometheusBuilder::new().with_http_listener(addr).install()
+}
@@ -0,0 +1,138 @@
+//! Verification of a single proof without network or storage, see the `zkp-verify` binary.
+//!
+//! The input is a JSON statement tagged with its `type`:
+//! - `bigint`: an interactive transcript in the MODP group `params` (`CpParams`), hex encoded public
+//!   keys `y1`, `y2` and a `proof` that is either the hex of `Proof::to_bytes` or an object of hex
+//!   encoded `r1`, `r2`, `c`, `s`.
+//! - `ecc`: a non-interactive proof on Ristretto, base64 encoded public keys `y1`, `y2`, an optional
+//!   `context`, the `created_at` seconds the proof is bound to if any and a `proof` that is either
+//!   the hex of `c || s` or an object of the JSON encoded scalars `c` and `s`.
+
+use curve25519_dalek::Scalar;
+use serde::Deserialize;
+
+use chaum_pedersen::chaum_pedersen::{ChaumPedersen, CpParams};
+use chaum_pedersen::ecc_chaum_pedersen::{EccChaumPedersen, DEFAULT_CONTEXT};
+use chaum_pedersen::proof::{NonInteractiveProof, Proof};
+use chaum_pedersen::utils::bigint_from_hex;
+use chaum_pedersen::{CpError, CpResult, InteractiveVerifier, NonInteractiveVerifier};
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Statement {
+    Bigint {
+        params: CpParams,
+        y1: String,
+        y2: String,
+        proof: Encoded<BigIntProof>,
+    },
+    Ecc {
+        context: Option<String>,
+        created_at: Option<u64>,
+        y1: String,
+        y2: String,
+        proof: Encoded<EccProof>,
+    },
+}
+
+/// A proof either in its binary encoding, hex encoded, or as JSON fields
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Encoded<T> {
+    Hex(String),
+    Json(T),
+}
+
+#[derive(Deserialize)]
+struct BigIntProof {
+    r1: String,
+    r2: String,
+    c: String,
+    s: String,
+}
+
+#[derive(Deserialize)]
+struct EccProof {
+    c: [u8; 32],
+    s: [u8; 32],
+}
+
+/// Parses the statement in `input` and verifies its proof.
+/// Malformed input is an error, a proof that doesn't verify is `Ok(false)`.
+pub fn verify(input: &str) -> CpResult<bool> {
+    let statement: Statement =
+        serde_json::from_str(input).map_err(|e| CpError::Deserialize(e.to_string()))?;
+
+    match statement {
+        Statement::Bigint {
+            params,
+            y1,
+            y2,
+            proof,
+        } => {
+            let cp = ChaumPedersen::try_from(params)?;
+            let proof = match proof {
+                Encoded::Hex(proof) => Proof::from_bytes(&hex_bytes(&proof)?, cp.byte_len())?,
+                Encoded::Json(proof) => Proof::new(
+                    bigint_from_hex(&proof.r1)?,
+                    bigint_from_hex(&proof.r2)?,
+                    bigint_from_hex(&proof.c)?,
+                    bigint_from_hex(&proof.s)?,
+                ),
+            };
+
+            cp.verify_interactive_sync(proof, bigint_from_hex(&y1)?, bigint_from_hex(&y2)?)
+        }
+        Statement::Ecc {
+            context,
+            created_at,
+            y1,
+            y2,
+            proof,
+        } => {
+            let ecc = match context {
+                Some(context) => EccChaumPedersen::with_context(context.as_bytes()),
+                None => EccChaumPedersen::with_context(DEFAULT_CONTEXT),
+            };
+            let ecc = match created_at {
+                Some(created_at) => ecc.with_timestamp(created_at),
+                None => ecc,
+            };
+            let (c, s) = match proof {
+                Encoded::Hex(proof) => ecc_proof_bytes(&proof)?,
+                Encoded::Json(proof) => (proof.c, proof.s),
+            };
+            let proof = NonInteractiveProof::new(canonical_scalar(c)?, canonical_scalar(s)?);
+
+            ecc.verify_noninteractive_sync(
+                proof,
+                EccChaumPedersen::from_base64(&y1)?,
+                EccChaumPedersen::from_base64(&y2)?,
+            )
+        }
+    }
+}
+
+fn hex_bytes(input: &str) -> CpResult<Vec<u8>> {
+    hex::decode(input.trim()).map_err(|e| CpError::Deserialize(e.to_string()))
+}
+
+/// `c || s`, 32 bytes each
+fn ecc_proof_bytes(input: &str) -> CpResult<([u8; 32], [u8; 32])> {
+    let bytes = hex_bytes(input)?;
+    if bytes.len() != 64 {
+        return Err(CpError::Deserialize(format!(
+            "expected 64 bytes, got {}",
+            bytes.len()
+        )));
+    }
+
+    // Both halves are 32 bytes since the length was checked above
+    let (c, s) = bytes.split_at(32);
+    Ok((c.try_into().unwrap(), s.try_into().unwrap()))
+}
+
+/// Only canonical encodings (`< l`) are accepted, same as `EccChaumPedersen::scalar_from_json`
+fn canonical_scalar(bytes: [u8; 32]) -> CpResult<Scalar> {
+    Option::from(Scalar::from_canonical_bytes(bytes)).ok_or(CpError::OutOfRange)
+}
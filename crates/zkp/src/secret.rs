@@ -0,0 +1,94 @@
+use std::fmt;
+
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use rand::RngCore;
+use tracing::warn;
+use zeroize::Zeroizing;
+
+/// 32 byte key, hex or base64 encoded
+pub const SERVER_SECRET_ENV: &str = "ZKP_SERVER_SECRET";
+
+/// Server-wide key, wiped from memory on drop
+pub type ServerSecret = Zeroizing<[u8; 32]>;
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SecretError {
+    Encoding,
+    Length(usize),
+}
+
+impl fmt::Display for SecretError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SecretError::Encoding => write!(f, "{} is neither hex nor base64", SERVER_SECRET_ENV),
+            SecretError::Length(len) => {
+                write!(f, "{} has to be 32 bytes, got {}", SERVER_SECRET_ENV, len)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SecretError {}
+
+/// Reads the key from `SERVER_SECRET_ENV`, if it is unset an ephemeral key is generated and
+/// everything signed with it is lost on restart.
+pub fn from_env() -> Result<ServerSecret, SecretError> {
+    match std::env::var(SERVER_SECRET_ENV) {
+        Ok(value) => parse(&value),
+        Err(_) => {
+            warn!("{} is not set, using an ephemeral key", SERVER_SECRET_ENV);
+            Ok(random())
+        }
+    }
+}
+
+pub fn parse(value: &str) -> Result<ServerSecret, SecretError> {
+    let value = value.trim();
+    let bytes = Zeroizing::new(
+        hex::decode(value)
+            .or_else(|_| STANDARD.decode(value))
+            .map_err(|_| SecretError::Encoding)?,
+    );
+
+    let mut secret = Zeroizing::new([0u8; 32]);
+    if bytes.len() != secret.len() {
+        return Err(SecretError::Length(bytes.len()));
+    }
+    secret.copy_from_slice(&bytes);
+    Ok(secret)
+}
+
+pub fn random() -> ServerSecret {
+    let mut secret = Zeroizing::new([0u8; 32]);
+    rand::thread_rng().fill_bytes(&mut secret[..]);
+    secret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_secret() {
+        let key: Vec<u8> = (0..32).collect();
+
+        assert_eq!(*parse(&hex::encode(&key)).unwrap(), key[..]);
+        assert_eq!(*parse(&STANDARD.encode(&key)).unwrap(), key[..]);
+        assert_eq!(
+            *parse(&format!(" {}\n", hex::encode(&key))).unwrap(),
+            key[..]
+        );
+    }
+
+    #[test]
+    fn invalid_secret() {
+        assert_eq!(parse(&hex::encode([7u8; 16])), Err(SecretError::Length(16)));
+        assert_eq!(
+            parse(&STANDARD.encode([7u8; 33])),
+            Err(SecretError::Length(33))
+        );
+        assert_eq!(parse("nyan cat"), Err(SecretError::Encoding));
+        assert_eq!(parse(""), Err(SecretError::Length(0)));
+    }
+}
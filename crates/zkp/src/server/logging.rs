@@ -0,0 +1,54 @@
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, EnvFilter};
+
+/// `json` for one JSON object per event, `pretty` or unset for human readable lines
+pub const LOG_FORMAT_ENV: &str = "ZKP_LOG_FORMAT";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    Pretty,
+    Json,
+}
+
+impl LogFormat {
+    /// Format selected by `ZKP_LOG_FORMAT`, anything but `json` keeps the readable one
+    pub fn from_env() -> Self {
+        match std::env::var(LOG_FORMAT_ENV).as_deref() {
+            Ok(format) if format.eq_ignore_ascii_case("json") => LogFormat::Json,
+            _ => LogFormat::Pretty,
+        }
+    }
+}
+
+/// Installs the global subscriber, events are filtered by `RUST_LOG` and written in `format`.
+/// With the `otel` feature the spans are exported over OTLP as well.
+pub fn init(format: LogFormat) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let subscriber = tracing_subscriber::registry()
+        .with(EnvFilter::from_default_env())
+        .with((format == LogFormat::Pretty).then(fmt::layer))
+        .with((format == LogFormat::Json).then(|| fmt::layer().json()));
+    #[cfg(feature = "otel")]
+    let subscriber = subscriber.with(crate::otel::layer()?);
+    subscriber.try_init()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_from_env() {
+        std::env::remove_var(LOG_FORMAT_ENV);
+        assert_eq!(LogFormat::from_env(), LogFormat::Pretty);
+
+        std::env::set_var(LOG_FORMAT_ENV, "json");
+        assert_eq!(LogFormat::from_env(), LogFormat::Json);
+
+        std::env::set_var(LOG_FORMAT_ENV, "pretty");
+        assert_eq!(LogFormat::from_env(), LogFormat::Pretty);
+
+        std::env::remove_var(LOG_FORMAT_ENV);
+    }
+}
@@ -1,27 +1,104 @@
-#[macro_use]
-extern crate log;
-extern crate pretty_env_logger;
+use std::net::SocketAddr;
+use std::sync::Arc;
 
-use tonic::transport::Server;
+use tokio::net::TcpListener;
+use tracing::{error, info};
 
-use crate::service::auth_service::AuthService;
-use crate::service::zkp::auth_server::AuthServer;
+use zkp::clock::SystemClock;
+use zkp::local_auth::SESSION_TTL_SECS;
+use zkp::signing;
 
+use crate::service::auth_service::{
+    AuthService, DEFAULT_MAX_MESSAGE_SIZE, MAX_MESSAGE_SIZE_ENV, SESSION_TTL_ENV,
+    SLIDING_SESSIONS_ENV, STATELESS_CHALLENGES_ENV,
+};
+
+mod logging;
+#[cfg(feature = "otel")]
+mod otel;
 mod service;
+mod shutdown;
+
+/// Runs `AuthService::self_test` before taking traffic and exits if it fails
+const SELF_TEST_FLAG: &str = "--self-test";
 
 #[tokio::main]
-async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    pretty_env_logger::init();
-    let addr = "0.0.0.0:50051".parse().expect("invalid address");
+async fn main() -> shutdown::ServeResult {
+    logging::init(logging::LogFormat::from_env())?;
+    let addr: SocketAddr = "0.0.0.0:50051".parse().expect("invalid address");
+    let metrics_addr = zkp::metrics::METRICS_ADDR
+        .parse()
+        .expect("invalid metrics address");
+
+    let stateless_challenges = matches!(
+        std::env::var(STATELESS_CHALLENGES_ENV).as_deref(),
+        Ok("1") | Ok("true")
+    );
+    let max_message_size = match std::env::var(MAX_MESSAGE_SIZE_ENV) {
+        Ok(bytes) => bytes
+            .parse()
+            .map_err(|e| format!("invalid {}: {}", MAX_MESSAGE_SIZE_ENV, e))?,
+        Err(_) => DEFAULT_MAX_MESSAGE_SIZE,
+    };
+    let session_ttl = match std::env::var(SESSION_TTL_ENV) {
+        Ok(secs) => secs
+            .parse()
+            .map_err(|e| format!("invalid {}: {}", SESSION_TTL_ENV, e))?,
+        Err(_) => SESSION_TTL_SECS,
+    };
+    let sliding_sessions = matches!(
+        std::env::var(SLIDING_SESSIONS_ENV).as_deref(),
+        Ok("1") | Ok("true")
+    );
+    let auth_service = match AuthService::new(Arc::new(SystemClock)) {
+        Ok(auth_service) => auth_service,
+        Err(e) => {
+            error!(error = %e, "failed to start");
+            return Err(e.into());
+        }
+    };
+    let auth_service = auth_service
+        .with_stateless_challenges(stateless_challenges)
+        .with_session_ttl(session_ttl)
+        .with_sliding_sessions(sliding_sessions)
+        .with_max_message_size(max_message_size)
+        .with_signing_key(signing::signing_key_from_env()?);
+    if stateless_challenges {
+        info!("challenges are signed instead of stored");
+    }
+    info!(
+        public_key = %hex::encode(auth_service.verifying_key().as_bytes()),
+        "responses are signed, pass the public key to the clients in {}",
+        signing::SERVER_PUBLIC_KEY_ENV
+    );
+
+    // Before the metrics are installed, the probe logins don't count
+    if std::env::args().any(|arg| arg == SELF_TEST_FLAG) {
+        match auth_service.self_test().await {
+            Ok(()) => info!("self-test PASS"),
+            Err(e) => {
+                error!(error = %e, "self-test FAIL");
+                return Err(e.into());
+            }
+        }
+    }
 
-    let auth_service = AuthService::new();
+    zkp::metrics::install(metrics_addr)?;
+    info!(%metrics_addr, "metrics endpoint started");
 
-    info!("gRPC server started at {}", addr);
+    let listener = TcpListener::bind(addr).await?;
+    info!(%addr, "gRPC server started");
 
-    Server::builder()
-        .add_service(AuthServer::new(auth_service))
-        .serve(addr)
-        .await?;
+    shutdown::serve(
+        listener,
+        Arc::new(auth_service),
+        shutdown::signal(),
+        shutdown::DRAIN_TIMEOUT,
+    )
+    .await?;
 
+    info!("shutdown complete");
+    #[cfg(feature = "otel")]
+    otel::shutdown();
     Ok(())
 }
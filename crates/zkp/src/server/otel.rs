@@ -0,0 +1,96 @@
+//! OpenTelemetry export of the RPC spans. Every request gets a `grpc` span continuing the W3C
+//! trace context of the caller, the spans of the `Auth` handlers are its children. The exporter is
+//! configured with the usual `OTEL_EXPORTER_OTLP_ENDPOINT` and `OTEL_SERVICE_NAME`.
+
+use opentelemetry::propagation::{Extractor, TextMapPropagator};
+use opentelemetry::sdk::propagation::TraceContextPropagator;
+use opentelemetry::trace::TraceError;
+use opentelemetry::Context;
+use tonic::codegen::http::{HeaderMap, Request};
+use tracing::{info_span, Span, Subscriber};
+use tracing_opentelemetry::{OpenTelemetryLayer, OpenTelemetrySpanExt};
+use tracing_subscriber::registry::LookupSpan;
+
+/// Reads the gRPC metadata of a request as propagation fields
+struct MetadataExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for MetadataExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|key| key.as_str()).collect()
+    }
+}
+
+/// Layer exporting the spans to the OTLP collector in batches, needs a Tokio runtime
+pub fn layer<S>() -> Result<OpenTelemetryLayer<S, opentelemetry::sdk::trace::Tracer>, TraceError>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let tracer = opentelemetry_otlp::new_pipeline()
+        .tracing()
+        .with_exporter(opentelemetry_otlp::new_exporter().tonic())
+        .install_batch(opentelemetry::runtime::Tokio)?;
+    Ok(tracing_opentelemetry::layer().with_tracer(tracer))
+}
+
+/// Exports the spans that are still buffered
+pub fn shutdown() {
+    opentelemetry::global::shutdown_tracer_provider();
+}
+
+/// Trace context of the caller from the `traceparent` metadata, empty if there is none
+pub fn extract(metadata: &HeaderMap) -> Context {
+    TraceContextPropagator::new().extract(&MetadataExtractor(metadata))
+}
+
+/// Span of one RPC, see `Server::trace_fn`
+pub fn request_span(request: &Request<()>) -> Span {
+    let span = info_span!(
+        "grpc",
+        otel.name = %request.uri().path(),
+        otel.kind = "server"
+    );
+    span.set_parent(extract(request.headers()));
+    span
+}
+
+#[cfg(test)]
+mod tests {
+    use opentelemetry::trace::{SpanId, TraceContextExt, TraceId};
+    use tonic::metadata::MetadataMap;
+
+    use super::*;
+
+    #[test]
+    fn extract_injected_context() {
+        let mut metadata = MetadataMap::new();
+        metadata.insert(
+            "traceparent",
+            "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"
+                .parse()
+                .unwrap(),
+        );
+
+        let cx = extract(&metadata.into_headers());
+        let span = cx.span();
+        let parent = span.span_context();
+        assert!(parent.is_valid());
+        assert!(parent.is_remote());
+        assert!(parent.is_sampled());
+        assert_eq!(
+            parent.trace_id(),
+            TraceId::from_hex("4bf92f3577b34da6a3ce929d0e0e4736").unwrap()
+        );
+        assert_eq!(
+            parent.span_id(),
+            SpanId::from_hex("00f067aa0ba902b7").unwrap()
+        );
+
+        // A request without a trace starts a new one
+        let cx = extract(&MetadataMap::new().into_headers());
+        assert!(!cx.span().span_context().is_valid());
+    }
+}
@@ -1,225 +1,1212 @@
-use std::time::{SystemTime, UNIX_EPOCH};
+use std::sync::Arc;
 
-use curve25519_dalek::{RistrettoPoint, Scalar};
-use num_bigint::BigInt;
-use num_traits::Num;
+use ed25519_dalek::{SigningKey, VerifyingKey};
 use prost::Message;
-use sha2::{Digest, Sha256};
-use tokio::sync::RwLock;
-use tonic::{Request, Response, Status};
-
-use chaum_pedersen::chaum_pedersen::{ChaumPedersen, G, H, P};
-use chaum_pedersen::ecc_chaum_pedersen::EccChaumPedersen;
-use chaum_pedersen::ChaumPedersenTrait;
-use storage::db::{KeyValueStorage, StorageTree};
-use storage::model::challenge_model::ChallengeModel;
-use storage::model::user_model::UserModel;
-
-use crate::service::zkp::auth_server::Auth;
+use tonic::{Code, Request, Response, Status};
+
+use chaum_pedersen::chaum_pedersen::Group;
+use storage::model::session_model::SessionId;
+use zkp::clock::Clock;
+use zkp::local_auth::{AuthError, AuthResult, LocalAuth, NewUser, Protocol, Transcript};
+use zkp::signing::{self, SignedResponse};
+
+use crate::service::convert::{
+    field_len, field_size, session_id, timestamp, validate_username, AnswerRequest,
+    ChallengeRequest, NewKeySlot, NonInteractiveAnswer, Registration, RegistrationKind,
+    Unregistration,
+};
+use crate::service::zkp::auth_server::{Auth, AuthServer};
 use crate::service::zkp::{
-    AuthenticationAnswerRequest, AuthenticationAnswerResponse, AuthenticationChallengeRequest,
-    AuthenticationChallengeResponse, NonInteractiveAuthenticationRequest, RegisterRequest,
-    RegisterResponse,
+    AddKeySlotRequest, AddKeySlotResponse, Algorithm, AuthenticationAnswerRequest,
+    AuthenticationAnswerResponse, AuthenticationChallengeRequest, AuthenticationChallengeResponse,
+    BulkRegisterRequest, BulkRegisterResponse, BulkRegisterResult,
+    NonInteractiveAuthenticationRequest, PublicKeysRequest, PublicKeysResponse, RegisterRequest,
+    RegisterResponse, RemoveKeySlotRequest, RemoveKeySlotResponse, UnregisterRequest,
+    UnregisterResponse, UpdateCredentialsRequest, UpdateCredentialsResponse, UserExistsRequest,
+    UserExistsResponse, VerifyTranscriptRequest, VerifyTranscriptResponse,
 };
 
+/// Set to `1` or `true` to sign challenges into the `auth_id` instead of storing them
+pub const STATELESS_CHALLENGES_ENV: &str = "ZKP_STATELESS_CHALLENGES";
+
+/// Seconds a session is accepted for, overrides `SESSION_TTL_SECS`
+pub const SESSION_TTL_ENV: &str = "ZKP_SESSION_TTL";
+
+/// Set to `1` or `true` to move the expiry of a session forward on every use
+pub const SLIDING_SESSIONS_ENV: &str = "ZKP_SLIDING_SESSIONS";
+
+/// Largest request or response in bytes, overrides `DEFAULT_MAX_MESSAGE_SIZE`
+pub const MAX_MESSAGE_SIZE_ENV: &str = "ZKP_MAX_MESSAGE_SIZE";
+
+/// Every field of the largest group fits several times over, a request of this size is refused
+/// before it's buffered
+pub const DEFAULT_MAX_MESSAGE_SIZE: usize = 64 * 1024;
+
+/// gRPC adapter over `LocalAuth`
 pub struct AuthService {
-    db: RwLock<KeyValueStorage>,
-    cp_protocol: ChaumPedersen,
+    auth: LocalAuth,
+    max_message_size: usize,
+    /// Signs the responses handing out enrollments and sessions
+    signing_key: SigningKey,
+}
+
+impl From<LocalAuth> for AuthService {
+    fn from(auth: LocalAuth) -> Self {
+        Self {
+            auth,
+            max_message_size: DEFAULT_MAX_MESSAGE_SIZE,
+            signing_key: signing::random_signing_key(),
+        }
+    }
+}
+
+/// gRPC status of a `LocalAuth` error. Both types are foreign to this binary, so this can't be a
+/// `From` impl.
+///
+/// Requests that don't parse or are out of range are `INVALID_ARGUMENT`, a well-formed proof that
+/// doesn't verify is `UNAUTHENTICATED`.
+fn status(e: AuthError) -> Status {
+    let message = e.to_string();
+    match e {
+        AuthError::AlreadyExists | AuthError::ProofReplayed => Status::already_exists(message),
+        AuthError::UserNotFound | AuthError::KeySlotNotFound | AuthError::ChallengeNotFound => {
+            Status::not_found(message)
+        }
+        AuthError::EnrollmentOutOfWindow
+        | AuthError::ProofOutOfWindow
+        | AuthError::ReregistrationRequired => Status::failed_precondition(message),
+        AuthError::ChallengeExpired | AuthError::VerificationTimeout => {
+            Status::deadline_exceeded(message)
+        }
+        AuthError::InvalidArgument(_) => Status::invalid_argument(message),
+        AuthError::InvalidProof | AuthError::InvalidSession | AuthError::SessionExpired => {
+            Status::unauthenticated(message)
+        }
+        AuthError::SessionMismatch => Status::permission_denied(message),
+        AuthError::UpdateFailed(_) => Status::aborted(message),
+        AuthError::Internal(_) => Status::internal(message),
+    }
 }
 
 #[tonic::async_trait]
 impl Auth for AuthService {
+    #[tracing::instrument(skip_all, fields(user = %request.get_ref().user))]
     async fn register(
         &self,
         request: Request<RegisterRequest>,
     ) -> Result<Response<RegisterResponse>, Status> {
-        let register_request = request.get_ref();
-        let user_key = UserModel::user_id(&register_request.user);
-        let data = UserModel {
-            user: register_request.user.clone(),
-            y1: register_request.y1.clone(),
-            y2: register_request.y2.clone(),
+        let Registration {
+            user,
+            y1,
+            y2,
+            group,
+            kind,
+            nonce,
+        } = request.into_inner().try_into().map_err(status)?;
+
+        // Existing users can only re-register with a proof for their current keys
+        let enrollment = match kind {
+            RegistrationKind::Rotation { auth_id, answer } => {
+                self.auth
+                    .rotate_keys(&user, y1, y2, group, &auth_id, answer)
+                    .await
+            }
+            RegistrationKind::Idempotent(key) => {
+                self.auth
+                    .register_idempotent(&key, &user, y1, y2, group)
+                    .await
+            }
+            RegistrationKind::New => self.auth.register(&user, y1, y2, group).await,
+        }
+        .map_err(status)?;
+        let mut response = RegisterResponse {
+            enrollment,
+            signature: Vec::new(),
         };
+        response.signature = self.sign(SignedResponse::Register, &nonce, &response);
+        Ok(Response::new(response))
+    }
+
+    /// Entries that don't convert are reported without reaching `LocalAuth`, the others are
+    /// registered together
+    #[tracing::instrument(skip_all, fields(users = request.get_ref().users.len()))]
+    async fn bulk_register(
+        &self,
+        request: Request<BulkRegisterRequest>,
+    ) -> Result<Response<BulkRegisterResponse>, Status> {
+        let entries: Vec<(String, AuthResult<NewUser>)> = request
+            .into_inner()
+            .users
+            .into_iter()
+            .map(|request| {
+                let user = request.user.clone();
+                let new_user =
+                    Registration::try_from(request).and_then(Registration::into_new_user);
+                (user, new_user)
+            })
+            .collect();
+        let new_users = entries
+            .iter()
+            .filter_map(|(_, new_user)| new_user.as_ref().ok().cloned())
+            .collect();
+        let mut registered = self
+            .auth
+            .register_batch(new_users)
+            .await
+            .map_err(status)?
+            .into_iter();
 
-        self.upsert_user(&user_key, data).await?;
-        AuthService::log_success("Registration successful", &register_request.user);
-        Ok(Response::new(RegisterResponse {}))
+        let results = entries
+            .into_iter()
+            .map(|(user, new_user)| {
+                let outcome = new_user
+                    .and_then(|_| registered.next().expect("one result per converted entry"));
+                match outcome {
+                    Ok(enrollment) => BulkRegisterResult {
+                        user,
+                        enrollment,
+                        code: Code::Ok as i32,
+                        message: String::new(),
+                    },
+                    Err(e) => {
+                        let status = status(e);
+                        BulkRegisterResult {
+                            user,
+                            enrollment: String::new(),
+                            code: status.code() as i32,
+                            message: status.message().to_string(),
+                        }
+                    }
+                }
+            })
+            .collect();
+        Ok(Response::new(BulkRegisterResponse { results }))
     }
 
+    #[tracing::instrument(
+        skip_all,
+        fields(user = %request.get_ref().user, auth_id = tracing::field::Empty)
+    )]
     async fn create_authentication_challenge(
         &self,
         request: Request<AuthenticationChallengeRequest>,
     ) -> Result<Response<AuthenticationChallengeResponse>, Status> {
-        let challenge_request = request.get_ref();
-        let user_key = UserModel::user_id(&challenge_request.user);
+        let request = ChallengeRequest::try_from(request.into_inner()).map_err(status)?;
 
-        let user = self.get_user(&user_key).await?;
-        let (c, auth_id) = self
-            .upsert_challenge(challenge_request.clone(), user)
-            .await?;
-
-        AuthService::log_success("Challenge issued to the prover auth_id", &auth_id);
-
-        Ok(Response::new(AuthenticationChallengeResponse {
-            c,
-            auth_id,
-        }))
+        let challenge = self
+            .auth
+            .create_challenge_with_enrollment(
+                &request.user,
+                request.slot,
+                request.commitment,
+                request.group,
+                request.enrollment.as_deref(),
+            )
+            .await
+            .map_err(status)?;
+        Ok(Response::new(challenge.into()))
     }
 
+    #[tracing::instrument(
+        skip_all,
+        fields(auth_id = %request.get_ref().auth_id, user = tracing::field::Empty)
+    )]
     async fn verify_authentication(
         &self,
         request: Request<AuthenticationAnswerRequest>,
     ) -> Result<Response<AuthenticationAnswerResponse>, Status> {
-        let authentication_answer_request = request.get_ref();
+        let request = AnswerRequest::try_from(request.into_inner()).map_err(status)?;
 
-        let challenge_key = authentication_answer_request.auth_id.encode_to_vec();
-        let challenge_model = self.get_challenge_data(&challenge_key).await?;
+        let session_id = self
+            .auth
+            .verify(&request.auth_id, request.answer)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(
+            self.answer_response(&request.nonce, session_id),
+        ))
+    }
 
-        // == Params for verification ==
-        let solution = AuthService::from_hex_to_bigint(&authentication_answer_request.s);
-        let challenge = AuthService::from_hex_to_bigint(&challenge_model.challenge);
+    #[tracing::instrument(skip_all, fields(user = %request.get_ref().user))]
+    async fn non_interactive_authentication(
+        &self,
+        request: Request<NonInteractiveAuthenticationRequest>,
+    ) -> Result<Response<AuthenticationAnswerResponse>, Status> {
+        let answer = NonInteractiveAnswer::try_from(request.into_inner()).map_err(status)?;
 
-        let y1 = AuthService::from_hex_to_bigint(&challenge_model.user.y1);
-        let y2 = AuthService::from_hex_to_bigint(&challenge_model.user.y2);
+        let session_id = self
+            .auth
+            .verify_non_interactive_with_commitment(
+                &answer.user,
+                answer.slot,
+                answer.created_at,
+                answer
+                    .commitment
+                    .as_ref()
+                    .map(|(r1, r2)| (r1.as_str(), r2.as_str())),
+                &answer.s,
+                &answer.c,
+            )
+            .await
+            .map_err(status)?;
+        Ok(Response::new(
+            self.answer_response(&answer.nonce, session_id),
+        ))
+    }
 
-        let r1 = AuthService::from_hex_to_bigint(&challenge_model.commitment.0);
-        let r2 = AuthService::from_hex_to_bigint(&challenge_model.commitment.1);
+    /// A transcript that doesn't verify is `valid: false`, only malformed fields and challenges this
+    /// server's secret didn't sign are an error
+    #[tracing::instrument(skip_all, fields(group = request.get_ref().group))]
+    async fn verify_transcript(
+        &self,
+        request: Request<VerifyTranscriptRequest>,
+    ) -> Result<Response<VerifyTranscriptResponse>, Status> {
+        let transcript = Transcript::try_from(request.into_inner()).map_err(status)?;
 
-        let is_valid = &self
-            .cp_protocol
-            .verify_proof(solution, challenge, y1, y2, Some(r1), Some(r2))
-            .await;
+        let valid = self
+            .auth
+            .verify_transcript(&transcript)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(VerifyTranscriptResponse { valid }))
+    }
 
-        let session_id = AuthService::generate_session_id(&challenge_model.user);
+    #[tracing::instrument(skip_all, fields(user = %request.get_ref().user))]
+    async fn update_credentials(
+        &self,
+        request: Request<UpdateCredentialsRequest>,
+    ) -> Result<Response<UpdateCredentialsResponse>, Status> {
+        let update_request = request.into_inner();
+        // The group of the user isn't known yet, the keys are bounded by the largest one
+        field_size("y1", &update_request.y1, field_len(Group::Modp4096)).map_err(status)?;
+        field_size("y2", &update_request.y2, field_len(Group::Modp4096)).map_err(status)?;
+        let session_id = session_id(&update_request.session_id).map_err(status)?;
+        let user = validate_username(&update_request.user).map_err(status)?;
 
-        if *is_valid == true {
-            return Ok(Response::new(AuthenticationAnswerResponse { session_id }));
-        }
+        self.auth
+            .update_credentials(
+                &session_id,
+                &user,
+                update_request.slot_id,
+                update_request.y1,
+                update_request.y2,
+            )
+            .await
+            .map_err(status)?;
+        Ok(Response::new(UpdateCredentialsResponse {}))
+    }
 
-        return Err(Status::invalid_argument("Proof is not valid!"));
+    #[tracing::instrument(skip_all, fields(user = %request.get_ref().user))]
+    async fn user_exists(
+        &self,
+        request: Request<UserExistsRequest>,
+    ) -> Result<Response<UserExistsResponse>, Status> {
+        let user = validate_username(&request.get_ref().user).map_err(status)?;
+        let protocol = self.auth.user_exists(&user).await.map_err(status)?;
+
+        let algorithm = protocol.map_or(Algorithm::Unspecified, AuthService::algorithm);
+        Ok(Response::new(UserExistsResponse {
+            exists: protocol.is_some(),
+            algorithm: algorithm.into(),
+        }))
     }
 
-    async fn non_interactive_authentication(
+    #[tracing::instrument(skip_all, fields(user = %request.get_ref().user))]
+    async fn get_public_keys(
         &self,
-        request: Request<NonInteractiveAuthenticationRequest>,
-    ) -> Result<Response<AuthenticationAnswerResponse>, Status> {
-        let ecc = EccChaumPedersen::new();
-        let ni_request = request.get_ref();
+        request: Request<PublicKeysRequest>,
+    ) -> Result<Response<PublicKeysResponse>, Status> {
+        let request = request.into_inner();
+        let user = validate_username(&request.user).map_err(status)?;
+        let (record, keys) = self
+            .auth
+            .public_keys(&user, request.slot_id)
+            .await
+            .map_err(status)?;
 
-        let (solution, challenge, y1, y2, session_id) = self
-            .non_interactive_verification_params(&ni_request)
-            .await?;
+        Ok(Response::new(PublicKeysResponse {
+            algorithm: AuthService::algorithm(keys.algorithm.into()).into(),
+            y1: keys.y1,
+            y2: keys.y2,
+            group: record.group,
+        }))
+    }
 
-        if ecc
-            .verify_proof(solution, challenge, y1, y2, None, None)
+    #[tracing::instrument(skip_all, fields(user = %request.get_ref().user))]
+    async fn unregister(
+        &self,
+        request: Request<UnregisterRequest>,
+    ) -> Result<Response<UnregisterResponse>, Status> {
+        let request = Unregistration::try_from(request.into_inner()).map_err(status)?;
+
+        self.auth
+            .unregister(&request.user, &request.auth_id, request.answer)
             .await
-        {
-            return Ok(Response::new(AuthenticationAnswerResponse { session_id }));
-        }
+            .map_err(status)?;
+        Ok(Response::new(UnregisterResponse {}))
+    }
+
+    #[tracing::instrument(skip_all, fields(user = %request.get_ref().user))]
+    async fn add_key_slot(
+        &self,
+        request: Request<AddKeySlotRequest>,
+    ) -> Result<Response<AddKeySlotResponse>, Status> {
+        let request = NewKeySlot::try_from(request.into_inner()).map_err(status)?;
 
-        return Err(Status::invalid_argument("Proof is not valid!"));
+        let slot_id = self
+            .auth
+            .add_key_slot(&request.session_id, &request.user, request.y1, request.y2)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(AddKeySlotResponse { slot_id }))
+    }
+
+    #[tracing::instrument(skip_all, fields(user = %request.get_ref().user))]
+    async fn remove_key_slot(
+        &self,
+        request: Request<RemoveKeySlotRequest>,
+    ) -> Result<Response<RemoveKeySlotResponse>, Status> {
+        let request = request.into_inner();
+        let session_id = session_id(&request.session_id).map_err(status)?;
+        let user = validate_username(&request.user).map_err(status)?;
+
+        self.auth
+            .remove_key_slot(&session_id, &user, request.slot_id)
+            .await
+            .map_err(status)?;
+        Ok(Response::new(RemoveKeySlotResponse {}))
     }
 }
 
 impl AuthService {
-    pub fn new() -> Self {
+    /// See `LocalAuth::new`
+    pub fn new(clock: Arc<dyn Clock>) -> AuthResult<Self> {
+        LocalAuth::new(clock).map(Into::into)
+    }
+
+    /// See `LocalAuth::with_stateless_challenges`
+    pub fn with_stateless_challenges(self, stateless_challenges: bool) -> Self {
         Self {
-            db: RwLock::new(KeyValueStorage::open()),
-            cp_protocol: ChaumPedersen::new(P.clone(), G.clone(), H.clone()),
+            auth: self.auth.with_stateless_challenges(stateless_challenges),
+            ..self
         }
     }
 
-    async fn upsert_user(&self, user_key: &Vec<u8>, data: UserModel) -> Result<(), Status> {
-        let mut db = self.db.write().await;
-        db.upsert::<UserModel>(StorageTree::Auth, user_key, data)
-            .map_err(|e| Status::internal(format!("failed to upsert {}", e)))?;
-        Ok(())
+    /// See `LocalAuth::with_session_ttl`
+    pub fn with_session_ttl(self, session_ttl: u64) -> Self {
+        Self {
+            auth: self.auth.with_session_ttl(session_ttl),
+            ..self
+        }
     }
 
-    async fn get_user(&self, user_key: &Vec<u8>) -> Result<UserModel, Status> {
-        let db = self.db.read().await;
-        if !db.exists(StorageTree::Auth, &user_key) {
-            return Err(Status::not_found("user does not exist"));
+    /// See `LocalAuth::with_sliding_sessions`
+    pub fn with_sliding_sessions(self, sliding_sessions: bool) -> Self {
+        Self {
+            auth: self.auth.with_sliding_sessions(sliding_sessions),
+            ..self
         }
+    }
 
-        db.get::<UserModel>(StorageTree::Auth, &user_key)
-            .map_err(|_| Status::not_found("user not found"))
+    /// Requests larger than `bytes` are refused by tonic with `OUT_OF_RANGE` before they're
+    /// decoded, the fields of smaller ones are bounded by the group in the handlers
+    pub fn with_max_message_size(self, bytes: usize) -> Self {
+        Self {
+            max_message_size: bytes,
+            ..self
+        }
     }
 
-    async fn get_challenge_data(&self, challenge_key: &Vec<u8>) -> Result<ChallengeModel, Status> {
-        let db = self.db.read().await;
-        if !db.exists(StorageTree::Challenge, challenge_key) {
-            return Err(Status::not_found("challenge does not exist"));
+    /// Key the responses are signed with instead of an ephemeral one, see `zkp::signing`
+    pub fn with_signing_key(self, signing_key: SigningKey) -> Self {
+        Self {
+            signing_key,
+            ..self
         }
+    }
 
-        db.get::<ChallengeModel>(StorageTree::Challenge, challenge_key)
-            .map_err(|_| Status::not_found("challenge not found"))
+    /// Public key clients verify the responses with
+    pub fn verifying_key(&self) -> VerifyingKey {
+        self.signing_key.verifying_key()
     }
 
-    async fn upsert_challenge(
-        &self,
-        challenge_request: AuthenticationChallengeRequest,
-        user: UserModel,
-    ) -> Result<(String, String), Status> {
-        let r1: String = challenge_request.r1;
-        let r2: String = challenge_request.r2;
-
-        // Generate random challenge
-        let challenge = self.cp_protocol.verifier_generate_challenge();
-        let challenge_hex = &challenge.to_str_radix(16);
-
-        let challenge_model = ChallengeModel::new(challenge_hex.clone(), (r1, r2), user);
-
-        let auth_id = challenge_model.generate_auth_id();
-        let challenge_model_key = auth_id.encode_to_vec();
-
-        let mut db = self.db.write().await;
-        db.upsert::<ChallengeModel>(
-            StorageTree::Challenge,
-            &challenge_model_key,
-            challenge_model,
-        )
-        .map_err(|e| Status::internal(format!("failed to upsert {}", e)))?;
+    /// Server of the `Auth` service enforcing the message size limit
+    pub fn into_server(self: Arc<Self>) -> AuthServer<Self> {
+        let max_message_size = self.max_message_size;
+        AuthServer::from_arc(self)
+            .max_decoding_message_size(max_message_size)
+            .max_encoding_message_size(max_message_size)
+    }
 
-        Ok((challenge_hex.clone(), auth_id))
+    /// See `LocalAuth::self_test`
+    pub async fn self_test(&self) -> AuthResult<()> {
+        self.auth.self_test().await
     }
 
-    async fn non_interactive_verification_params(
-        &self,
-        ni_request: &NonInteractiveAuthenticationRequest,
-    ) -> Result<(Scalar, Scalar, RistrettoPoint, RistrettoPoint, String), Status> {
-        let user = self.get_user(&UserModel::user_id(&ni_request.user)).await?;
+    pub async fn flush(&self) -> AuthResult<()> {
+        self.auth.flush().await
+    }
+
+    /// Signature of `response` encoded with an empty signature
+    fn sign<M: Message>(&self, kind: SignedResponse, nonce: &[u8], response: &M) -> Vec<u8> {
+        signing::sign(&self.signing_key, kind, nonce, &response.encode_to_vec())
+    }
+
+    fn answer_response(&self, nonce: &[u8], session_id: SessionId) -> AuthenticationAnswerResponse {
+        let mut response = AuthenticationAnswerResponse {
+            session_id: session_id.to_string(),
+            signature: Vec::new(),
+            created_at: Some(timestamp(session_id.iat())),
+            expires_at: Some(timestamp(session_id.iat() + self.auth.session_ttl())),
+        };
+        response.signature = self.sign(SignedResponse::AuthenticationAnswer, nonce, &response);
+        response
+    }
+
+    fn algorithm(protocol: Protocol) -> Algorithm {
+        match protocol {
+            Protocol::Interactive => Algorithm::Interactive,
+            Protocol::NonInteractive => Algorithm::NonInteractive,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Once;
+
+    use metrics_util::debugging::{DebugValue, DebuggingRecorder, Snapshotter};
+    use num_bigint::BigInt;
+    use tonic::Code;
+    use tracing_test::traced_test;
+
+    use chaum_pedersen::chaum_pedersen::{ChaumPedersen, Group};
+    use chaum_pedersen::ecc_chaum_pedersen::EccChaumPedersen;
+    use chaum_pedersen::ChaumPedersenTrait;
+    use storage::db::KeyValueStorage;
+    use zkp::clock::SystemClock;
+    use zkp::local_auth::{CHALLENGE_TTL_SECS, SESSION_TTL_SECS};
+    use zkp::metrics::{LOGIN_FAILURE_TOTAL, LOGIN_SUCCESS_TOTAL, REGISTER_TOTAL};
+
+    use crate::service::convert::{requested_group, MAX_USERNAME_LEN};
+
+    use super::*;
+
+    fn service() -> AuthService {
+        LocalAuth::with_storage(KeyValueStorage::temporary()).into()
+    }
+
+    #[tokio::test]
+    #[traced_test]
+    async fn register_span() {
+        let service = service();
+        let request = Request::new(RegisterRequest {
+            user: "Nyan".to_string(),
+            y1: "2".to_string(),
+            y2: "3".to_string(),
+            group: 2048,
+            auth_id: String::new(),
+            s: String::new(),
+            idempotency_key: String::new(),
+            nonce: Vec::new(),
+        });
+
+        service.register(request).await.unwrap();
 
-        // == Params for verification ==
-        let solution: Scalar = serde_json::from_str(&ni_request.s).expect("invalid solution");
-        let challenge: Scalar = serde_json::from_str(&ni_request.c).expect("invalid challenge");
-        let y1: RistrettoPoint = serde_json::from_str(&user.y1).expect("invalid y1 RistrettoPoint");
-        let y2: RistrettoPoint = serde_json::from_str(&user.y2).expect("invalid y1 RistrettoPoint");
+        assert!(logs_contain("register{user=Nyan}"));
+        assert!(logs_contain("registration successful"));
+    }
+
+    /// Reads a counter recorded on the current thread, recorders are per thread so tests don't
+    /// see each other's metrics.
+    fn counter(name: &str) -> u64 {
+        static INSTALL: Once = Once::new();
+        INSTALL.call_once(|| DebuggingRecorder::per_thread().install().unwrap());
+
+        Snapshotter::current_thread_snapshot()
+            .map(|snapshot| snapshot.into_vec())
+            .unwrap_or_default()
+            .into_iter()
+            .find_map(|(key, _, _, value)| match value {
+                DebugValue::Counter(v) if key.key().name() == name => Some(v),
+                _ => None,
+            })
+            .unwrap_or(0)
+    }
+
+    #[tokio::test]
+    async fn failed_verification_metrics() {
+        assert_eq!(counter(LOGIN_FAILURE_TOTAL), 0);
+
+        let service = service();
+        service
+            .register(Request::new(RegisterRequest {
+                user: "Nyan".to_string(),
+                y1: "2".to_string(),
+                y2: "3".to_string(),
+                group: 2048,
+                auth_id: String::new(),
+                s: String::new(),
+                idempotency_key: String::new(),
+                nonce: Vec::new(),
+            }))
+            .await
+            .unwrap();
+        let challenge = service
+            .create_authentication_challenge(Request::new(AuthenticationChallengeRequest {
+                user: "Nyan".to_string(),
+                r1: "4".to_string(),
+                r2: "5".to_string(),
+                group: 2048,
+                enrollment: String::new(),
+                slot_id: 0,
+            }))
+            .await
+            .unwrap();
+
+        let status = service
+            .verify_authentication(Request::new(AuthenticationAnswerRequest {
+                auth_id: challenge.get_ref().auth_id.clone(),
+                s: "1".to_string(),
+                proof: vec![],
+                nonce: Vec::new(),
+            }))
+            .await
+            .unwrap_err();
+
+        assert_eq!(status.code(), Code::Unauthenticated);
+        assert_eq!(status.message(), "Proof is not valid!");
+        assert_eq!(counter(REGISTER_TOTAL), 1);
+        assert_eq!(counter(LOGIN_FAILURE_TOTAL), 1);
+        assert_eq!(counter(LOGIN_SUCCESS_TOTAL), 0);
+    }
+
+    #[tokio::test]
+    async fn username_validation() {
+        let service = service();
+        let register = |user: &str| {
+            service.register(Request::new(RegisterRequest {
+                user: user.to_string(),
+                y1: "2".to_string(),
+                y2: "3".to_string(),
+                group: 2048,
+                auth_id: String::new(),
+                s: String::new(),
+                idempotency_key: String::new(),
+                nonce: Vec::new(),
+            }))
+        };
+
+        for user in ["", "   ", &"n".repeat(MAX_USERNAME_LEN + 1)] {
+            let status = register(user).await.unwrap_err();
+            assert_eq!(status.code(), Code::InvalidArgument);
+        }
+
+        register(" Nyan ").await.unwrap();
+        // Trimmed to the same storage key
+        assert_eq!(
+            register("Nyan").await.unwrap_err().code(),
+            Code::AlreadyExists
+        );
+        let exists = service
+            .user_exists(Request::new(UserExistsRequest {
+                user: "Nyan\n".to_string(),
+            }))
+            .await
+            .unwrap();
+        assert!(exists.get_ref().exists);
+    }
+
+    /// Clock stuck at one instant
+    struct FixedClock(u64);
+
+    impl Clock for FixedClock {
+        fn now_unix(&self) -> u64 {
+            self.0
+        }
+    }
+
+    #[tokio::test]
+    async fn challenge_timestamps() {
+        let now = 1700000000;
+        let service: AuthService = LocalAuth::with_storage(KeyValueStorage::temporary())
+            .with_clock(Arc::new(FixedClock(now)))
+            .into();
+        service
+            .register(Request::new(RegisterRequest {
+                user: "Nyan".to_string(),
+                y1: "2".to_string(),
+                y2: "3".to_string(),
+                group: 2048,
+                auth_id: String::new(),
+                s: String::new(),
+                idempotency_key: String::new(),
+                nonce: Vec::new(),
+            }))
+            .await
+            .unwrap();
+        let challenge = service
+            .create_authentication_challenge(Request::new(AuthenticationChallengeRequest {
+                user: "Nyan".to_string(),
+                r1: "4".to_string(),
+                r2: "5".to_string(),
+                group: 2048,
+                enrollment: String::new(),
+                slot_id: 0,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+
+        let created_at = challenge.created_at.unwrap();
+        assert!((created_at.seconds - now as i64).abs() <= 1);
+        assert_eq!(created_at.nanos, 0);
+        assert_eq!(
+            challenge.expires_at.unwrap().seconds,
+            created_at.seconds + CHALLENGE_TTL_SECS as i64
+        );
+    }
+
+    #[tokio::test]
+    async fn verification_statuses() {
+        let service = service();
+        service
+            .register(Request::new(RegisterRequest {
+                user: "Nyan".to_string(),
+                y1: "2".to_string(),
+                y2: "3".to_string(),
+                group: 2048,
+                auth_id: String::new(),
+                s: String::new(),
+                idempotency_key: String::new(),
+                nonce: Vec::new(),
+            }))
+            .await
+            .unwrap();
+        let auth_id = service
+            .create_authentication_challenge(Request::new(AuthenticationChallengeRequest {
+                user: "Nyan".to_string(),
+                r1: "4".to_string(),
+                r2: "5".to_string(),
+                group: 2048,
+                enrollment: String::new(),
+                slot_id: 0,
+            }))
+            .await
+            .unwrap()
+            .into_inner()
+            .auth_id;
+        let verify = |auth_id: &str, s: String| {
+            service.verify_authentication(Request::new(AuthenticationAnswerRequest {
+                auth_id: auth_id.to_string(),
+                s,
+                proof: vec![],
+                nonce: Vec::new(),
+            }))
+        };
 
-        let session_id = AuthService::generate_session_id(&user);
+        // Malformed and out of range solutions never reach the verifier
+        let status = verify(&auth_id, "zz".to_string()).await.unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+        assert_eq!(status.message(), "malformed hex field s");
+        let q = Group::Modp2048.protocol().order().to_str_radix(16);
+        let status = verify(&auth_id, q).await.unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+        assert_eq!(status.message(), "Value is out of range");
 
-        Ok((solution, challenge, y1, y2, session_id))
+        // Well-formed but wrong
+        let status = verify(&auth_id, "1".to_string()).await.unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+
+        let status = verify("ab", "1".to_string()).await.unwrap_err();
+        assert_eq!(status.code(), Code::NotFound);
+
+        // Same for the non-interactive protocol
+        let ecc = EccChaumPedersen::new();
+        let (y1, y2) = ecc
+            .generate_public_keys(EccChaumPedersen::hash(b"cat"))
+            .await
+            .unwrap();
+        service
+            .register(Request::new(RegisterRequest {
+                user: "Tac".to_string(),
+                y1: EccChaumPedersen::to_base64(&y1),
+                y2: EccChaumPedersen::to_base64(&y2),
+                group: 2048,
+                auth_id: String::new(),
+                s: String::new(),
+                idempotency_key: String::new(),
+                nonce: Vec::new(),
+            }))
+            .await
+            .unwrap();
+        let scalar = serde_json::to_string(&EccChaumPedersen::hash(b"dog")).unwrap();
+        let answer = |c: &str| {
+            service.non_interactive_authentication(Request::new(
+                NonInteractiveAuthenticationRequest {
+                    user: "Tac".to_string(),
+                    c: c.to_string(),
+                    s: scalar.clone(),
+                    r1: String::new(),
+                    r2: String::new(),
+                    slot_id: 0,
+                    nonce: Vec::new(),
+                    created_at: Some(timestamp(SystemClock.now_unix())),
+                },
+            ))
+        };
+        let status = answer("nyan").await.unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+        let status = answer(&scalar).await.unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
     }
 
-    fn log_success<T: std::fmt::Display>(message: &str, value: T) {
-        info!("{} {}", message, value);
+    #[tokio::test]
+    async fn reregistration_required() {
+        let storage = KeyValueStorage::temporary();
+        let service = AuthService::from(LocalAuth::with_storage(storage.clone()));
+        service
+            .register(Request::new(RegisterRequest {
+                user: "Nyan".to_string(),
+                y1: "2".to_string(),
+                y2: "3".to_string(),
+                group: 2048,
+                auth_id: String::new(),
+                s: String::new(),
+                idempotency_key: String::new(),
+                nonce: Vec::new(),
+            }))
+            .await
+            .unwrap();
+
+        // Restarted with other generators of the group Nyan registered in
+        let rotated = ChaumPedersen::new(
+            Group::Modp2048.prime().clone(),
+            BigInt::from(4),
+            BigInt::from(9),
+        );
+        let auth = LocalAuth::with_params(storage, Group::Modp2048, rotated);
+        assert_eq!(auth.flag_stale_generators(), Ok(1));
+        let service = AuthService::from(auth);
+        // Issued like to any other user, the answer is refused
+        let auth_id = service
+            .create_authentication_challenge(Request::new(AuthenticationChallengeRequest {
+                user: "Nyan".to_string(),
+                r1: "4".to_string(),
+                r2: "5".to_string(),
+                group: 2048,
+                enrollment: String::new(),
+                slot_id: 0,
+            }))
+            .await
+            .unwrap()
+            .into_inner()
+            .auth_id;
+        let status = service
+            .verify_authentication(Request::new(AuthenticationAnswerRequest {
+                auth_id,
+                s: "1".to_string(),
+                proof: vec![],
+                nonce: Vec::new(),
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::FailedPrecondition);
+        assert_eq!(status.message(), "re-registration required");
     }
 
-    fn from_hex_to_bigint(input: &String) -> BigInt {
-        BigInt::from_str_radix(input, 16).expect("Failed to parse string as base-16 BigInt")
+    async fn user_exists(service: &AuthService, user: &str) -> UserExistsResponse {
+        service
+            .user_exists(Request::new(UserExistsRequest {
+                user: user.to_string(),
+            }))
+            .await
+            .unwrap()
+            .into_inner()
     }
 
-    fn generate_session_id(user: &UserModel) -> String {
-        // Could happen
-        let iat = SystemTime::now()
-            .duration_since(UNIX_EPOCH)
-            .expect("SystemTime set before UNIX EPOCH")
-            .as_secs();
+    #[tokio::test]
+    async fn user_exists_present_and_absent() {
+        let service = service();
+
+        let absent = user_exists(&service, "Nyan").await;
+        assert!(!absent.exists);
+        assert_eq!(absent.algorithm(), Algorithm::Unspecified);
+
+        service
+            .register(Request::new(RegisterRequest {
+                user: "Nyan".to_string(),
+                y1: "2".to_string(),
+                y2: "3".to_string(),
+                group: 2048,
+                auth_id: String::new(),
+                s: String::new(),
+                idempotency_key: String::new(),
+                nonce: Vec::new(),
+            }))
+            .await
+            .unwrap();
+        let present = user_exists(&service, "Nyan").await;
+        assert!(present.exists);
+        assert_eq!(present.algorithm(), Algorithm::Interactive);
+
+        let ecc = EccChaumPedersen::new();
+        let (y1, y2) = ecc
+            .generate_public_keys(EccChaumPedersen::hash(b"cat"))
+            .await
+            .unwrap();
+        service
+            .register(Request::new(RegisterRequest {
+                user: "Tac".to_string(),
+                y1: EccChaumPedersen::to_base64(&y1),
+                y2: EccChaumPedersen::to_base64(&y2),
+                group: 2048,
+                auth_id: String::new(),
+                s: String::new(),
+                idempotency_key: String::new(),
+                nonce: Vec::new(),
+            }))
+            .await
+            .unwrap();
+        let present = user_exists(&service, "Tac").await;
+        assert!(present.exists);
+        assert_eq!(present.algorithm(), Algorithm::NonInteractive);
+    }
+
+    #[tokio::test]
+    async fn bulk_register() {
+        let service = service();
+        let request = |user: &str, auth_id: &str| RegisterRequest {
+            user: user.to_string(),
+            y1: "2".to_string(),
+            y2: "3".to_string(),
+            group: 2048,
+            auth_id: auth_id.to_string(),
+            s: String::new(),
+            idempotency_key: String::new(),
+            nonce: Vec::new(),
+        };
+        service
+            .register(Request::new(request("Nyan", "")))
+            .await
+            .unwrap();
+
+        let results = service
+            .bulk_register(Request::new(BulkRegisterRequest {
+                users: vec![
+                    request("Tac", ""),
+                    request("Nyan", ""),
+                    request("", ""),
+                    request("Cat", "6f1c"),
+                    request("Tac", ""),
+                ],
+            }))
+            .await
+            .unwrap()
+            .into_inner()
+            .results;
+        let codes: Vec<Code> = results.iter().map(|result| result.code.into()).collect();
+        assert_eq!(
+            codes,
+            [
+                Code::Ok,
+                Code::AlreadyExists,
+                Code::InvalidArgument,
+                Code::InvalidArgument,
+                Code::AlreadyExists
+            ]
+        );
+        assert_eq!(results[1].user, "Nyan");
+        assert!(!results[0].enrollment.is_empty());
+        assert!(results[1..]
+            .iter()
+            .all(|result| result.enrollment.is_empty()));
+        assert!(user_exists(&service, "Tac").await.exists);
+        assert!(!user_exists(&service, "Cat").await.exists);
+    }
+
+    #[tokio::test]
+    async fn verify_transcript() {
+        let secret = zkp::secret::random();
+        let issuer: AuthService = LocalAuth::with_storage(KeyValueStorage::temporary())
+            .with_secret(secret.clone())
+            .with_stateless_challenges(true)
+            .into();
+        let cp = Group::Modp2048.protocol();
+        let x = cp.hash_to_scalar(b"cat");
+        let (y1, y2) = cp.generate_public_keys_sync(x.clone()).unwrap();
+        let hex = |v: &BigInt| v.to_str_radix(16);
+        issuer
+            .register(Request::new(RegisterRequest {
+                user: "Nyan".to_string(),
+                y1: hex(&y1),
+                y2: hex(&y2),
+                group: 2048,
+                auth_id: String::new(),
+                s: String::new(),
+                idempotency_key: String::new(),
+                nonce: Vec::new(),
+            }))
+            .await
+            .unwrap();
+        let (k, r1, r2) = cp.prover_commit_sync().unwrap();
+        let challenge = issuer
+            .create_authentication_challenge(Request::new(AuthenticationChallengeRequest {
+                user: "Nyan".to_string(),
+                r1: hex(&r1),
+                r2: hex(&r2),
+                group: 2048,
+                enrollment: String::new(),
+                slot_id: 0,
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        let c = BigInt::parse_bytes(challenge.c.as_bytes(), 16).unwrap();
+        let s = cp.prover_solve_challenge(k, c.clone(), x);
+        let transcript = VerifyTranscriptRequest {
+            group: 2048,
+            y1: hex(&y1),
+            y2: hex(&y2),
+            r1: hex(&r1),
+            r2: hex(&r2),
+            c: hex(&c),
+            s: hex(&s),
+            auth_id: challenge.auth_id.clone(),
+        };
+        // Never saw the challenge, it only shares the server secret with the issuer
+        let service: AuthService = LocalAuth::with_storage(KeyValueStorage::temporary())
+            .with_secret(secret)
+            .into();
+        let verify =
+            |request: VerifyTranscriptRequest| service.verify_transcript(Request::new(request));
+
+        assert!(verify(transcript.clone()).await.unwrap().into_inner().valid);
+        // Nothing is consumed, the same transcript verifies again
+        assert!(verify(transcript.clone()).await.unwrap().into_inner().valid);
+
+        let invalid = VerifyTranscriptRequest {
+            s: hex(&((&s + 1) % cp.order())),
+            ..transcript.clone()
+        };
+        assert!(!verify(invalid).await.unwrap().into_inner().valid);
+        let other_group = VerifyTranscriptRequest {
+            group: 3072,
+            ..transcript.clone()
+        };
+        assert!(!verify(other_group).await.unwrap().into_inner().valid);
+
+        // Simulated without `x`: pick `c` and `s`, then the commitment `r = g^s * y^c` verifies
+        let simulated_c = BigInt::from(5);
+        let simulated_s = BigInt::from(7);
+        let commitment = |g: &BigInt, y: &BigInt| {
+            hex(&(g.modpow(&simulated_s, &cp.p) * y.modpow(&simulated_c, &cp.p) % &*cp.p))
+        };
+        let simulated = VerifyTranscriptRequest {
+            r1: commitment(&cp.g, &y1),
+            r2: commitment(&cp.h, &y2),
+            c: hex(&simulated_c),
+            s: hex(&simulated_s),
+            ..transcript.clone()
+        };
+        assert!(!verify(simulated.clone()).await.unwrap().into_inner().valid);
+        let status = verify(VerifyTranscriptRequest {
+            auth_id: String::new(),
+            ..simulated.clone()
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+        assert_eq!(status.message(), "auth_id is required");
+        let (record, _) = challenge.auth_id.split_once('.').unwrap();
+        let forged = VerifyTranscriptRequest {
+            auth_id: format!("{}.{}", record, "00".repeat(32)),
+            ..simulated
+        };
+        let status = verify(forged).await.unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+        assert_eq!(status.message(), "invalid challenge token");
+
+        let malformed = VerifyTranscriptRequest {
+            c: "zz".to_string(),
+            ..transcript.clone()
+        };
+        let status = verify(malformed).await.unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+        assert_eq!(status.message(), "malformed hex field c");
+        let missing = VerifyTranscriptRequest {
+            r2: String::new(),
+            ..transcript.clone()
+        };
+        assert_eq!(
+            verify(missing).await.unwrap_err().code(),
+            Code::InvalidArgument
+        );
+        let not_a_key = VerifyTranscriptRequest {
+            y1: "1".to_string(),
+            ..transcript
+        };
+        assert_eq!(
+            verify(not_a_key).await.unwrap_err().code(),
+            Code::InvalidArgument
+        );
+    }
+
+    #[tokio::test]
+    async fn public_keys() {
+        let service = service();
+        let request = || {
+            Request::new(PublicKeysRequest {
+                user: "Nyan".to_string(),
+                slot_id: 0,
+            })
+        };
+
+        let e = service.get_public_keys(request()).await.unwrap_err();
+        assert_eq!(e.code(), Code::NotFound);
+
+        service
+            .register(Request::new(RegisterRequest {
+                user: "Nyan".to_string(),
+                y1: "2".to_string(),
+                y2: "3".to_string(),
+                group: 2048,
+                auth_id: String::new(),
+                s: String::new(),
+                idempotency_key: String::new(),
+                nonce: Vec::new(),
+            }))
+            .await
+            .unwrap();
+        let keys = service
+            .get_public_keys(request())
+            .await
+            .unwrap()
+            .into_inner();
+        assert_eq!((keys.y1.as_str(), keys.y2.as_str()), ("2", "3"));
+        assert_eq!(keys.group, 2048);
+        assert_eq!(keys.algorithm(), Algorithm::Interactive);
+
+        let e = service
+            .get_public_keys(Request::new(PublicKeysRequest {
+                user: "Nyan".to_string(),
+                slot_id: 1,
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(e.code(), Code::NotFound);
+    }
+
+    #[tokio::test]
+    async fn signed_responses() {
+        let key = signing::parse_signing_key(&hex::encode([7u8; 32])).unwrap();
+        let public = key.verifying_key();
+        let service = service().with_signing_key(key);
+        assert_eq!(service.verifying_key(), public);
+
+        let ecc = EccChaumPedersen::new();
+        let x = EccChaumPedersen::hash(b"cat");
+        let (y1, y2) = ecc.generate_public_keys(x).await.unwrap();
+        let registered = service
+            .register(Request::new(RegisterRequest {
+                user: "Nyan".to_string(),
+                y1: EccChaumPedersen::to_base64(&y1),
+                y2: EccChaumPedersen::to_base64(&y2),
+                group: 2048,
+                auth_id: String::new(),
+                s: String::new(),
+                idempotency_key: String::new(),
+                nonce: b"register".to_vec(),
+            }))
+            .await
+            .unwrap()
+            .into_inner();
+        let unsigned = RegisterResponse {
+            signature: Vec::new(),
+            ..registered.clone()
+        };
+        signing::verify(
+            &public,
+            SignedResponse::Register,
+            b"register",
+            &unsigned.encode_to_vec(),
+            &registered.signature,
+        )
+        .unwrap();
+
+        let now = SystemClock.now_unix();
+        let ecc = ecc.with_timestamp(now);
+        let (k, r1, r2) = ecc.prover_commit().await.unwrap();
+        let c = ecc.derive_challenge(&r1, &r2);
+        let request = NonInteractiveAuthenticationRequest {
+            user: "Nyan".to_string(),
+            c: serde_json::to_string(&c).unwrap(),
+            s: serde_json::to_string(&ecc.prover_solve_challenge(k, c, x)).unwrap(),
+            r1: EccChaumPedersen::to_base64(&r1),
+            r2: EccChaumPedersen::to_base64(&r2),
+            slot_id: 0,
+            nonce: b"login".to_vec(),
+            created_at: Some(timestamp(now)),
+        };
+        let session = service
+            .non_interactive_authentication(Request::new(request.clone()))
+            .await
+            .unwrap()
+            .into_inner();
+        let iat = session_id(&session.session_id).unwrap().iat();
+        assert_eq!(session.created_at.clone().unwrap().seconds, iat as i64);
+        assert_eq!(
+            session.expires_at.clone().unwrap().seconds,
+            (iat + SESSION_TTL_SECS) as i64
+        );
+        let unsigned = AuthenticationAnswerResponse {
+            signature: Vec::new(),
+            ..session.clone()
+        };
+        signing::verify(
+            &public,
+            SignedResponse::AuthenticationAnswer,
+            b"login",
+            &unsigned.encode_to_vec(),
+            &session.signature,
+        )
+        .unwrap();
+
+        let status = service
+            .non_interactive_authentication(Request::new(NonInteractiveAuthenticationRequest {
+                nonce: vec![0; signing::MAX_NONCE_LEN + 1],
+                ..request
+            }))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+        assert_eq!(status.message(), "nonce is longer than 64 bytes");
+    }
+
+    #[test]
+    fn status_codes() {
+        for (e, code) in [
+            (AuthError::AlreadyExists, Code::AlreadyExists),
+            (AuthError::KeySlotNotFound, Code::NotFound),
+            (AuthError::ChallengeNotFound, Code::NotFound),
+            (AuthError::ChallengeExpired, Code::DeadlineExceeded),
+            (AuthError::VerificationTimeout, Code::DeadlineExceeded),
+            (AuthError::EnrollmentOutOfWindow, Code::FailedPrecondition),
+            (AuthError::ProofOutOfWindow, Code::FailedPrecondition),
+            (AuthError::ReregistrationRequired, Code::FailedPrecondition),
+            (AuthError::InvalidProof, Code::Unauthenticated),
+            (AuthError::InvalidSession, Code::Unauthenticated),
+            (AuthError::SessionExpired, Code::Unauthenticated),
+            (AuthError::SessionMismatch, Code::PermissionDenied),
+        ] {
+            let message = e.to_string();
+            let status = status(e);
+            assert_eq!(status.code(), code);
+            assert_eq!(status.message(), message);
+        }
 
-        let combined = format!("{}||{}", user, iat);
-        let mut hasher = Sha256::new();
-        hasher.update(combined.as_bytes());
-        let result = hasher.finalize();
-        format!("{:02x}", result)
+        let status = status(requested_group(1024).unwrap_err());
+        assert_eq!(status.code(), Code::InvalidArgument);
+        assert_eq!(requested_group(0).unwrap(), Group::LEGACY);
     }
 }
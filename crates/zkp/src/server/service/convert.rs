@@ -0,0 +1,754 @@
+//! Validated domain views of the proto messages. Malformed fields are rejected while converting,
+//! before a request reaches `LocalAuth`.
+
+use std::time::{Duration, UNIX_EPOCH};
+
+use chaum_pedersen::chaum_pedersen::Group;
+use chaum_pedersen::ecc_chaum_pedersen::EccChaumPedersen;
+use chaum_pedersen::utils::decode_field;
+use storage::model::session_model::SessionId;
+use zkp::local_auth::{Answer, AuthError, AuthResult, Challenge, NewUser, Transcript};
+use zkp::signing::MAX_NONCE_LEN;
+
+use crate::service::zkp::{
+    AddKeySlotRequest, AuthenticationAnswerRequest, AuthenticationChallengeRequest,
+    AuthenticationChallengeResponse, NonInteractiveAuthenticationRequest, RegisterRequest,
+    UnregisterRequest, VerifyTranscriptRequest,
+};
+
+/// Requests from clients predating group selection leave it unset, they used the legacy group
+pub fn requested_group(group: u32) -> AuthResult<Group> {
+    if group == 0 {
+        return Ok(Group::LEGACY);
+    }
+    Ok(Group::from_bits(group)?)
+}
+
+/// JSON array of the 32 bytes of a Ristretto scalar, with room for whitespace between them
+const SCALAR_JSON_LEN: usize = 256;
+
+/// Base64 of a 32-byte compressed Ristretto point
+const POINT_BASE64_LEN: usize = 44;
+
+/// Longest user name in bytes
+pub const MAX_USERNAME_LEN: usize = 128;
+
+/// Rejects `value` by its length alone, so an oversized field is never decoded
+pub fn field_size(name: &str, value: &str, max_len: usize) -> AuthResult<()> {
+    if value.len() > max_len {
+        return Err(AuthError::InvalidArgument(format!(
+            "{} is longer than {} characters",
+            name, max_len
+        )));
+    }
+    Ok(())
+}
+
+/// Length of a field of `group` in hex, the longest encoding of a public key, commitment or
+/// solution. Base64 Ristretto points are shorter in every group.
+pub fn field_len(group: Group) -> usize {
+    2 * (group.bits() as usize / 8)
+}
+
+/// Hex field of at most the byte length of `group`
+fn hex_field(name: &str, value: &str, group: Group) -> AuthResult<()> {
+    field_size(name, value, field_len(group))?;
+    decode_field(value, group.bits() as usize / 8)
+        .map(|_| ())
+        .map_err(|_| AuthError::InvalidArgument(format!("malformed hex field {}", name)))
+}
+
+/// Public keys are hex for the interactive protocol and base64 Ristretto points for the
+/// non-interactive one
+fn public_key(name: &str, value: &str, group: Group) -> AuthResult<()> {
+    field_size(name, value, field_len(group))?;
+    if EccChaumPedersen::from_base64(value).is_ok() {
+        return Ok(());
+    }
+    hex_field(name, value, group)
+}
+
+/// Nonce the response is signed with, empty when the client doesn't verify the signature
+fn nonce(value: Vec<u8>) -> AuthResult<Vec<u8>> {
+    if value.len() > MAX_NONCE_LEN {
+        return Err(AuthError::InvalidArgument(format!(
+            "nonce is longer than {} bytes",
+            MAX_NONCE_LEN
+        )));
+    }
+    Ok(value)
+}
+
+fn required(name: &str, value: &str) -> AuthResult<()> {
+    if value.is_empty() {
+        return Err(AuthError::InvalidArgument(format!("{} is required", name)));
+    }
+    Ok(())
+}
+
+/// User name of a request with the surrounding whitespace trimmed, so `" Nyan "` and `"Nyan"`
+/// name the same user. Empty and all-whitespace names are rejected, as are names over
+/// `MAX_USERNAME_LEN` bytes.
+pub fn validate_username(user: &str) -> AuthResult<String> {
+    let user = user.trim();
+    required("user", user)?;
+    field_size("user", user, MAX_USERNAME_LEN)?;
+    Ok(user.to_string())
+}
+
+/// Session id of a request, a malformed one can't name a session so it's rejected like an unknown
+/// one
+pub fn session_id(value: &str) -> AuthResult<SessionId> {
+    required("session_id", value)?;
+    value.parse().map_err(|_| AuthError::InvalidSession)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistrationKind {
+    New,
+    /// Replayed for retries with the same key, see `LocalAuth::register_idempotent`
+    Idempotent(String),
+    /// New keys for an existing user, `answer` proves the current ones to the challenge issued
+    /// under `auth_id`
+    Rotation {
+        auth_id: String,
+        answer: Answer,
+    },
+}
+
+/// `RegisterRequest` with the group resolved and the keys checked for their encoding
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Registration {
+    pub user: String,
+    pub y1: String,
+    pub y2: String,
+    pub group: Group,
+    pub kind: RegistrationKind,
+    pub nonce: Vec<u8>,
+}
+
+impl TryFrom<RegisterRequest> for Registration {
+    type Error = AuthError;
+
+    fn try_from(request: RegisterRequest) -> AuthResult<Self> {
+        let group = requested_group(request.group)?;
+        let user = validate_username(&request.user)?;
+        public_key("y1", &request.y1, group)?;
+        public_key("y2", &request.y2, group)?;
+        let nonce = nonce(request.nonce)?;
+
+        // A rotation is checked against the current keys, the idempotency key doesn't apply
+        let kind = if !request.auth_id.is_empty() {
+            hex_field("s", &request.s, group)?;
+            RegistrationKind::Rotation {
+                auth_id: request.auth_id,
+                answer: Answer::Solution(request.s),
+            }
+        } else if !request.idempotency_key.is_empty() {
+            RegistrationKind::Idempotent(request.idempotency_key)
+        } else {
+            RegistrationKind::New
+        };
+
+        Ok(Self {
+            user,
+            y1: request.y1,
+            y2: request.y2,
+            group,
+            kind,
+            nonce,
+        })
+    }
+}
+
+impl Registration {
+    /// Entry of a `BulkRegisterRequest`, which only registers new users
+    pub fn into_new_user(self) -> AuthResult<NewUser> {
+        if let RegistrationKind::Rotation { .. } = self.kind {
+            return Err(AuthError::InvalidArgument(
+                "auth_id can't be set in a bulk registration".to_string(),
+            ));
+        }
+        Ok(NewUser {
+            user: self.user,
+            y1: self.y1,
+            y2: self.y2,
+            group: self.group,
+        })
+    }
+}
+
+/// `AuthenticationChallengeRequest` with the group resolved and the commitment checked
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChallengeRequest {
+    pub user: String,
+    /// Hex encoded `(r1, r2)`
+    pub commitment: (String, String),
+    pub group: Group,
+    pub enrollment: Option<String>,
+    /// Key slot the challenge is answered with, clients predating key slots leave it at the first
+    pub slot: u32,
+}
+
+impl TryFrom<AuthenticationChallengeRequest> for ChallengeRequest {
+    type Error = AuthError;
+
+    fn try_from(request: AuthenticationChallengeRequest) -> AuthResult<Self> {
+        let group = requested_group(request.group)?;
+        let user = validate_username(&request.user)?;
+        hex_field("r1", &request.r1, group)?;
+        hex_field("r2", &request.r2, group)?;
+
+        Ok(Self {
+            user,
+            commitment: (request.r1, request.r2),
+            group,
+            // Clients predating enrollment tokens leave it empty
+            enrollment: Some(request.enrollment).filter(|e| !e.is_empty()),
+            slot: request.slot_id,
+        })
+    }
+}
+
+/// Well-known `Timestamp` of the unix time `secs`
+pub fn timestamp(secs: u64) -> prost_types::Timestamp {
+    prost_types::Timestamp::from(UNIX_EPOCH + Duration::from_secs(secs))
+}
+
+impl From<Challenge> for AuthenticationChallengeResponse {
+    fn from(challenge: Challenge) -> Self {
+        Self {
+            created_at: Some(timestamp(challenge.created_at)),
+            expires_at: Some(timestamp(challenge.expires_at())),
+            c: challenge.c,
+            auth_id: challenge.auth_id,
+        }
+    }
+}
+
+/// `AuthenticationAnswerRequest`, the answer to the challenge issued under `auth_id`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnswerRequest {
+    pub auth_id: String,
+    pub answer: Answer,
+    pub nonce: Vec<u8>,
+}
+
+impl TryFrom<AuthenticationAnswerRequest> for AnswerRequest {
+    type Error = AuthError;
+
+    /// The group of the challenge isn't known yet, the solution is bounded by the largest one
+    fn try_from(request: AuthenticationAnswerRequest) -> AuthResult<Self> {
+        required("auth_id", &request.auth_id)?;
+        let nonce = nonce(request.nonce)?;
+
+        // The solution is either hex encoded or part of a binary proof
+        let answer = if request.proof.is_empty() {
+            hex_field("s", &request.s, Group::Modp4096)?;
+            Answer::Solution(request.s)
+        } else {
+            Answer::Proof(request.proof)
+        };
+
+        Ok(Self {
+            auth_id: request.auth_id,
+            answer,
+            nonce,
+        })
+    }
+}
+
+impl TryFrom<VerifyTranscriptRequest> for Transcript {
+    type Error = AuthError;
+
+    fn try_from(request: VerifyTranscriptRequest) -> AuthResult<Self> {
+        let group = requested_group(request.group)?;
+        for (name, value) in [
+            ("y1", &request.y1),
+            ("y2", &request.y2),
+            ("r1", &request.r1),
+            ("r2", &request.r2),
+            ("c", &request.c),
+            ("s", &request.s),
+        ] {
+            required(name, value)?;
+            hex_field(name, value, group)?;
+        }
+        required("auth_id", &request.auth_id)?;
+
+        Ok(Self {
+            auth_id: request.auth_id,
+            group,
+            y1: request.y1,
+            y2: request.y2,
+            r1: request.r1,
+            r2: request.r2,
+            c: request.c,
+            s: request.s,
+        })
+    }
+}
+
+/// `UnregisterRequest`, a delete has to be proven like a login
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unregistration {
+    pub user: String,
+    pub auth_id: String,
+    pub answer: Answer,
+}
+
+impl TryFrom<UnregisterRequest> for Unregistration {
+    type Error = AuthError;
+
+    fn try_from(request: UnregisterRequest) -> AuthResult<Self> {
+        let user = validate_username(&request.user)?;
+        required("auth_id", &request.auth_id)?;
+        hex_field("s", &request.s, Group::Modp4096)?;
+
+        Ok(Self {
+            user,
+            auth_id: request.auth_id,
+            answer: Answer::Solution(request.s),
+        })
+    }
+}
+
+/// `AddKeySlotRequest` with the keys checked for their encoding, the group of the user isn't known
+/// yet so interactive keys are bounded by the largest one
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewKeySlot {
+    pub session_id: SessionId,
+    pub user: String,
+    pub y1: String,
+    pub y2: String,
+}
+
+impl TryFrom<AddKeySlotRequest> for NewKeySlot {
+    type Error = AuthError;
+
+    fn try_from(request: AddKeySlotRequest) -> AuthResult<Self> {
+        let session_id = session_id(&request.session_id)?;
+        let user = validate_username(&request.user)?;
+        public_key("y1", &request.y1, Group::Modp4096)?;
+        public_key("y2", &request.y2, Group::Modp4096)?;
+
+        Ok(Self {
+            session_id,
+            user,
+            y1: request.y1,
+            y2: request.y2,
+        })
+    }
+}
+
+/// `NonInteractiveAuthenticationRequest` with the scalars and points checked for their encoding
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NonInteractiveAnswer {
+    pub user: String,
+    pub slot: u32,
+    /// Base64 encoded `(r1, r2)`, `None` for clients predating the commitment fields
+    pub commitment: Option<(String, String)>,
+    /// JSON encoded scalars
+    pub s: String,
+    pub c: String,
+    /// Seconds since the UNIX epoch the proof was made at
+    pub created_at: u64,
+    pub nonce: Vec<u8>,
+}
+
+impl TryFrom<NonInteractiveAuthenticationRequest> for NonInteractiveAnswer {
+    type Error = AuthError;
+
+    fn try_from(request: NonInteractiveAuthenticationRequest) -> AuthResult<Self> {
+        let user = validate_username(&request.user)?;
+        let nonce = nonce(request.nonce)?;
+        for (name, scalar) in [("s", &request.s), ("c", &request.c)] {
+            field_size(name, scalar, SCALAR_JSON_LEN)?;
+            EccChaumPedersen::scalar_from_json(scalar)
+                .map_err(|e| AuthError::InvalidArgument(format!("invalid {}: {}", name, e)))?;
+        }
+
+        let commitment = if request.r1.is_empty() && request.r2.is_empty() {
+            None
+        } else {
+            for (name, point) in [("r1", &request.r1), ("r2", &request.r2)] {
+                field_size(name, point, POINT_BASE64_LEN)?;
+                EccChaumPedersen::from_base64(point)
+                    .map_err(|e| AuthError::InvalidArgument(format!("invalid {}: {}", name, e)))?;
+            }
+            Some((request.r1, request.r2))
+        };
+        let created_at = request
+            .created_at
+            .and_then(|created_at| u64::try_from(created_at.seconds).ok())
+            .ok_or_else(|| AuthError::InvalidArgument("created_at is required".to_string()))?;
+
+        Ok(Self {
+            user,
+            slot: request.slot_id,
+            commitment,
+            s: request.s,
+            c: request.c,
+            created_at,
+            nonce,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use curve25519_dalek::constants::RISTRETTO_BASEPOINT_POINT;
+    use curve25519_dalek::Scalar;
+
+    use super::*;
+
+    fn register_request() -> RegisterRequest {
+        RegisterRequest {
+            user: "Nyan".to_string(),
+            y1: "1f".to_string(),
+            y2: "2a".to_string(),
+            group: 2048,
+            auth_id: String::new(),
+            s: String::new(),
+            idempotency_key: String::new(),
+            nonce: Vec::new(),
+        }
+    }
+
+    fn challenge_request() -> AuthenticationChallengeRequest {
+        AuthenticationChallengeRequest {
+            user: "Nyan".to_string(),
+            r1: "04".to_string(),
+            r2: "05".to_string(),
+            group: 0,
+            enrollment: String::new(),
+            slot_id: 0,
+        }
+    }
+
+    fn non_interactive_request() -> NonInteractiveAuthenticationRequest {
+        let point = EccChaumPedersen::to_base64(&RISTRETTO_BASEPOINT_POINT);
+        let scalar = serde_json::to_string(&Scalar::ONE).unwrap();
+        NonInteractiveAuthenticationRequest {
+            user: "Nyan".to_string(),
+            c: scalar.clone(),
+            s: scalar,
+            r1: point.clone(),
+            r2: point,
+            slot_id: 0,
+            nonce: Vec::new(),
+            created_at: Some(timestamp(1700000000)),
+        }
+    }
+
+    fn invalid_argument<T: std::fmt::Debug>(result: AuthResult<T>) -> String {
+        match result {
+            Err(AuthError::InvalidArgument(e)) => e,
+            other => panic!("expected InvalidArgument, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn username() {
+        let register = |user: &str| {
+            Registration::try_from(RegisterRequest {
+                user: user.to_string(),
+                ..register_request()
+            })
+        };
+        let challenge = |user: &str| {
+            ChallengeRequest::try_from(AuthenticationChallengeRequest {
+                user: user.to_string(),
+                ..challenge_request()
+            })
+        };
+
+        assert_eq!(register(" Nyan\t").unwrap().user, "Nyan");
+        assert_eq!(challenge(" Nyan\t").unwrap().user, "Nyan");
+        let longest = "n".repeat(MAX_USERNAME_LEN);
+        assert_eq!(register(&longest).unwrap().user, longest);
+
+        for user in ["", " \t\n"] {
+            assert_eq!(invalid_argument(register(user)), "user is required");
+            assert_eq!(invalid_argument(challenge(user)), "user is required");
+        }
+        let too_long = format!(" {} ", "n".repeat(MAX_USERNAME_LEN + 1));
+        assert_eq!(
+            invalid_argument(register(&too_long)),
+            "user is longer than 128 characters"
+        );
+        assert_eq!(
+            invalid_argument(challenge(&too_long)),
+            "user is longer than 128 characters"
+        );
+    }
+
+    #[test]
+    fn registration() {
+        let registration = Registration::try_from(register_request()).unwrap();
+        assert_eq!(registration.group, Group::Modp2048);
+        assert_eq!(registration.kind, RegistrationKind::New);
+
+        let idempotent = Registration::try_from(RegisterRequest {
+            idempotency_key: "6f1c".to_string(),
+            ..register_request()
+        })
+        .unwrap();
+        assert_eq!(
+            idempotent.kind,
+            RegistrationKind::Idempotent("6f1c".to_string())
+        );
+
+        let rotation = Registration::try_from(RegisterRequest {
+            auth_id: "ab".to_string(),
+            s: "0c".to_string(),
+            idempotency_key: "6f1c".to_string(),
+            ..register_request()
+        })
+        .unwrap();
+        assert_eq!(
+            rotation.kind,
+            RegistrationKind::Rotation {
+                auth_id: "ab".to_string(),
+                answer: Answer::Solution("0c".to_string()),
+            }
+        );
+
+        // Non-interactive keys
+        let point = EccChaumPedersen::to_base64(&RISTRETTO_BASEPOINT_POINT);
+        assert!(Registration::try_from(RegisterRequest {
+            y1: point.clone(),
+            y2: point,
+            ..register_request()
+        })
+        .is_ok());
+    }
+
+    #[test]
+    fn invalid_registration() {
+        let e = invalid_argument(Registration::try_from(RegisterRequest {
+            y2: "nyan".to_string(),
+            ..register_request()
+        }));
+        assert_eq!(e, "malformed hex field y2");
+
+        // Longer than the 2048-bit group allows, rejected before it's decoded
+        let e = invalid_argument(Registration::try_from(RegisterRequest {
+            y1: "1".repeat(2 * 256 + 1),
+            ..register_request()
+        }));
+        assert_eq!(e, "y1 is longer than 512 characters");
+        let e = invalid_argument(Registration::try_from(RegisterRequest {
+            y2: "A".repeat(1 << 20),
+            group: 4096,
+            ..register_request()
+        }));
+        assert_eq!(e, "y2 is longer than 1024 characters");
+        invalid_argument(Registration::try_from(RegisterRequest {
+            user: String::new(),
+            ..register_request()
+        }));
+        invalid_argument(Registration::try_from(RegisterRequest {
+            auth_id: "ab".to_string(),
+            s: "zz".to_string(),
+            ..register_request()
+        }));
+        invalid_argument(Registration::try_from(RegisterRequest {
+            group: 1024,
+            ..register_request()
+        }));
+    }
+
+    #[test]
+    fn challenge() {
+        let request = ChallengeRequest::try_from(challenge_request()).unwrap();
+        assert_eq!(request.group, Group::LEGACY);
+        assert_eq!(request.commitment, ("04".to_string(), "05".to_string()));
+        assert_eq!(request.enrollment, None);
+        assert_eq!(request.slot, 0);
+
+        let enrolled = ChallengeRequest::try_from(AuthenticationChallengeRequest {
+            enrollment: "1700000000.ab".to_string(),
+            slot_id: 2,
+            ..challenge_request()
+        })
+        .unwrap();
+        assert_eq!(enrolled.enrollment.as_deref(), Some("1700000000.ab"));
+        assert_eq!(enrolled.slot, 2);
+
+        let e = invalid_argument(ChallengeRequest::try_from(AuthenticationChallengeRequest {
+            r1: "0x04".to_string(),
+            ..challenge_request()
+        }));
+        assert_eq!(e, "malformed hex field r1");
+
+        let response = AuthenticationChallengeResponse::from(Challenge {
+            c: "0b".to_string(),
+            auth_id: "ab".to_string(),
+            created_at: 1700000000,
+        });
+        assert_eq!(
+            (response.c.as_str(), response.auth_id.as_str()),
+            ("0b", "ab")
+        );
+        assert_eq!(response.created_at.unwrap().seconds, 1700000000);
+        assert_eq!(response.expires_at.unwrap().seconds, 1700000300);
+    }
+
+    #[test]
+    fn answer() {
+        let answer = |s: &str, proof: Vec<u8>| {
+            AnswerRequest::try_from(AuthenticationAnswerRequest {
+                auth_id: "ab".to_string(),
+                s: s.to_string(),
+                proof,
+                nonce: Vec::new(),
+            })
+        };
+        assert_eq!(
+            answer("0c", Vec::new()).unwrap().answer,
+            Answer::Solution("0c".to_string())
+        );
+        // The binary proof takes precedence
+        assert_eq!(
+            answer("zz", vec![1, 2]).unwrap().answer,
+            Answer::Proof(vec![1, 2])
+        );
+
+        let e = invalid_argument(answer("zz", Vec::new()));
+        assert_eq!(e, "malformed hex field s");
+        invalid_argument(AnswerRequest::try_from(AuthenticationAnswerRequest {
+            auth_id: String::new(),
+            s: "0c".to_string(),
+            proof: Vec::new(),
+            nonce: Vec::new(),
+        }));
+    }
+
+    #[test]
+    fn unregistration() {
+        let unregister = |user: &str, auth_id: &str| {
+            Unregistration::try_from(UnregisterRequest {
+                user: user.to_string(),
+                auth_id: auth_id.to_string(),
+                s: "0c".to_string(),
+            })
+        };
+        assert_eq!(
+            unregister("Nyan", "ab").unwrap().answer,
+            Answer::Solution("0c".to_string())
+        );
+
+        // Without a challenge there's nothing to prove the delete with
+        assert_eq!(
+            invalid_argument(unregister("Nyan", "")),
+            "auth_id is required"
+        );
+        assert_eq!(invalid_argument(unregister("", "ab")), "user is required");
+    }
+
+    #[test]
+    fn new_key_slot() {
+        let request = || AddKeySlotRequest {
+            session_id: format!("{}.1700000000.{}", "ab".repeat(16), "cd".repeat(32)),
+            user: "Nyan".to_string(),
+            y1: "1f".to_string(),
+            y2: "2a".to_string(),
+        };
+        let slot = NewKeySlot::try_from(request()).unwrap();
+        assert_eq!((slot.y1.as_str(), slot.y2.as_str()), ("1f", "2a"));
+
+        // Non-interactive keys
+        let point = EccChaumPedersen::to_base64(&RISTRETTO_BASEPOINT_POINT);
+        assert!(NewKeySlot::try_from(AddKeySlotRequest {
+            y1: point.clone(),
+            y2: point,
+            ..request()
+        })
+        .is_ok());
+
+        assert_eq!(
+            invalid_argument(NewKeySlot::try_from(AddKeySlotRequest {
+                y2: "nyan".to_string(),
+                ..request()
+            })),
+            "malformed hex field y2"
+        );
+        assert_eq!(
+            invalid_argument(NewKeySlot::try_from(AddKeySlotRequest {
+                session_id: String::new(),
+                ..request()
+            })),
+            "session_id is required"
+        );
+        assert_eq!(
+            NewKeySlot::try_from(AddKeySlotRequest {
+                session_id: "ab.1700000000.cd".to_string(),
+                ..request()
+            }),
+            Err(AuthError::InvalidSession)
+        );
+    }
+
+    #[test]
+    fn non_interactive_answer() {
+        let answer = NonInteractiveAnswer::try_from(non_interactive_request()).unwrap();
+        assert!(answer.commitment.is_some());
+        assert_eq!(answer.created_at, 1700000000);
+
+        let legacy = NonInteractiveAnswer::try_from(NonInteractiveAuthenticationRequest {
+            r1: String::new(),
+            r2: String::new(),
+            ..non_interactive_request()
+        })
+        .unwrap();
+        assert_eq!(legacy.commitment, None);
+
+        // Only one half of the commitment
+        let e = invalid_argument(NonInteractiveAnswer::try_from(
+            NonInteractiveAuthenticationRequest {
+                r2: String::new(),
+                ..non_interactive_request()
+            },
+        ));
+        assert!(e.starts_with("invalid r2"));
+        let e = invalid_argument(NonInteractiveAnswer::try_from(
+            NonInteractiveAuthenticationRequest {
+                c: "nyan".to_string(),
+                ..non_interactive_request()
+            },
+        ));
+        assert!(e.starts_with("invalid c"));
+
+        let e = invalid_argument(NonInteractiveAnswer::try_from(
+            NonInteractiveAuthenticationRequest {
+                s: format!("[{}]", "0,".repeat(1 << 16)),
+                ..non_interactive_request()
+            },
+        ));
+        assert_eq!(e, "s is longer than 256 characters");
+        let e = invalid_argument(NonInteractiveAnswer::try_from(
+            NonInteractiveAuthenticationRequest {
+                r1: "A".repeat(45),
+                ..non_interactive_request()
+            },
+        ));
+        assert_eq!(e, "r1 is longer than 44 characters");
+
+        for created_at in [
+            None,
+            Some(prost_types::Timestamp {
+                seconds: -1,
+                nanos: 0,
+            }),
+        ] {
+            let e = invalid_argument(NonInteractiveAnswer::try_from(
+                NonInteractiveAuthenticationRequest {
+                    created_at,
+                    ..non_interactive_request()
+                },
+            ));
+            assert_eq!(e, "created_at is required");
+        }
+    }
+}
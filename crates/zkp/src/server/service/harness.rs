@@ -0,0 +1,486 @@
+use std::net::SocketAddr;
+use std::sync::Arc;
+
+use tokio::net::TcpListener;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::{Channel, Server};
+
+use storage::db::KeyValueStorage;
+use zkp::local_auth::LocalAuth;
+
+use crate::service::auth_service::AuthService;
+use crate::service::zkp::auth_client::AuthClient;
+
+/// Serves a fresh `AuthService` backed by temporary storage on an ephemeral local port and
+/// returns a client connected to it, the server is stopped with the test runtime.
+pub async fn spawn() -> AuthClient<Channel> {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr: SocketAddr = listener.local_addr().unwrap();
+    let service = AuthService::from(LocalAuth::with_storage(KeyValueStorage::temporary()));
+
+    tokio::spawn(async move {
+        Server::builder()
+            .add_service(Arc::new(service).into_server())
+            .serve_with_incoming(TcpListenerStream::new(listener))
+            .await
+            .unwrap();
+    });
+
+    AuthClient::connect(format!("http://{}", addr))
+        .await
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use curve25519_dalek::scalar::Scalar;
+    use num_bigint::{BigInt, Sign};
+    use sha2::{Digest, Sha512};
+    use tonic::Code;
+
+    use chaum_pedersen::chaum_pedersen::{ChaumPedersen, Group};
+    use chaum_pedersen::ecc_chaum_pedersen::EccChaumPedersen;
+    use chaum_pedersen::prover::Prover;
+    use chaum_pedersen::utils::bigint_from_hex;
+    use chaum_pedersen::ChaumPedersenTrait;
+
+    use zkp::clock::{Clock, SystemClock};
+
+    use crate::service::auth_service::DEFAULT_MAX_MESSAGE_SIZE;
+    use crate::service::convert::timestamp;
+    use crate::service::zkp::{
+        AddKeySlotRequest, AuthenticationAnswerRequest, AuthenticationChallengeRequest,
+        NonInteractiveAuthenticationRequest, PublicKeysRequest, RegisterRequest,
+        RemoveKeySlotRequest, UnregisterRequest,
+    };
+
+    use super::*;
+
+    /// Hex encoded public keys of `password`
+    async fn public_keys(password: &str, group: Group) -> (String, String) {
+        let (y1, y2) = group
+            .protocol()
+            .generate_public_keys(ChaumPedersen::hash(password.as_bytes()))
+            .await
+            .unwrap();
+        (y1.to_str_radix(16), y2.to_str_radix(16))
+    }
+
+    async fn register(client: &mut AuthClient<Channel>, user: &str, password: &str, group: Group) {
+        let (y1, y2) = public_keys(password, group).await;
+
+        client
+            .register(RegisterRequest {
+                user: user.to_string(),
+                y1,
+                y2,
+                group: group.bits(),
+                auth_id: String::new(),
+                s: String::new(),
+                idempotency_key: String::new(),
+                nonce: Vec::new(),
+            })
+            .await
+            .unwrap();
+    }
+
+    async fn login(
+        client: &mut AuthClient<Channel>,
+        user: &str,
+        password: &str,
+        group: Group,
+    ) -> Result<String, tonic::Status> {
+        slot_login(client, user, 0, password, group).await
+    }
+
+    /// Same as `login` but answered with the keys in `slot_id`
+    async fn slot_login(
+        client: &mut AuthClient<Channel>,
+        user: &str,
+        slot_id: u32,
+        password: &str,
+        group: Group,
+    ) -> Result<String, tonic::Status> {
+        let cp = group.protocol();
+        let mut prover = Prover::new(&cp);
+        let (r1, r2) = prover.commit().await.unwrap();
+
+        let challenge = client
+            .create_authentication_challenge(AuthenticationChallengeRequest {
+                user: user.to_string(),
+                r1: r1.to_str_radix(16),
+                r2: r2.to_str_radix(16),
+                group: group.bits(),
+                enrollment: String::new(),
+                slot_id,
+            })
+            .await?
+            .into_inner();
+
+        let c: BigInt = bigint_from_hex(&challenge.c).unwrap();
+        let s = prover
+            .respond(c, ChaumPedersen::hash(password.as_bytes()))
+            .unwrap();
+
+        let response = client
+            .verify_authentication(AuthenticationAnswerRequest {
+                auth_id: challenge.auth_id,
+                s: s.to_str_radix(16),
+                proof: vec![],
+                nonce: Vec::new(),
+            })
+            .await?;
+        Ok(response.into_inner().session_id)
+    }
+
+    /// Answers a challenge for `user` with `password` and deletes the user with it
+    async fn unregister(
+        client: &mut AuthClient<Channel>,
+        user: &str,
+        password: &str,
+        group: Group,
+    ) -> Result<(), tonic::Status> {
+        let cp = group.protocol();
+        let mut prover = Prover::new(&cp);
+        let (r1, r2) = prover.commit().await.unwrap();
+
+        let challenge = client
+            .create_authentication_challenge(AuthenticationChallengeRequest {
+                user: user.to_string(),
+                r1: r1.to_str_radix(16),
+                r2: r2.to_str_radix(16),
+                group: group.bits(),
+                enrollment: String::new(),
+                slot_id: 0,
+            })
+            .await?
+            .into_inner();
+
+        let c: BigInt = bigint_from_hex(&challenge.c).unwrap();
+        let s = prover
+            .respond(c, ChaumPedersen::hash(password.as_bytes()))
+            .unwrap();
+
+        client
+            .unregister(UnregisterRequest {
+                user: user.to_string(),
+                auth_id: challenge.auth_id,
+                s: s.to_str_radix(16),
+            })
+            .await?;
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn interactive_login() {
+        let mut client = spawn().await;
+        register(&mut client, "Nyan", "cat", Group::default()).await;
+
+        let session_id = login(&mut client, "Nyan", "cat", Group::default())
+            .await
+            .unwrap();
+        assert!(!session_id.is_empty());
+    }
+
+    #[tokio::test]
+    async fn group_mismatch() {
+        let mut client = spawn().await;
+        register(&mut client, "Nyan", "cat", Group::Modp4096).await;
+
+        // Fails like a wrong password, the group Nyan registered in isn't revealed
+        let status = login(&mut client, "Nyan", "cat", Group::Modp2048)
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+
+        assert!(login(&mut client, "Nyan", "cat", Group::Modp4096)
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn non_interactive_login() {
+        let mut client = spawn().await;
+        let ecc = EccChaumPedersen::new();
+        let x = EccChaumPedersen::hash(b"cat");
+        let (y1, y2) = ecc.generate_public_keys(x).await.unwrap();
+
+        client
+            .register(RegisterRequest {
+                user: "Nyan".to_string(),
+                y1: EccChaumPedersen::to_base64(&y1),
+                y2: EccChaumPedersen::to_base64(&y2),
+                group: Group::default().bits(),
+                auth_id: String::new(),
+                s: String::new(),
+                idempotency_key: String::new(),
+                nonce: Vec::new(),
+            })
+            .await
+            .unwrap();
+
+        let now = SystemClock.now_unix();
+        let ecc = ecc.with_timestamp(now);
+        let (k, r1, r2) = ecc.prover_commit().await.unwrap();
+        let c = ecc.derive_challenge(&r1, &r2);
+        let s = ecc.prover_solve_challenge(k, c, x);
+
+        let response = client
+            .non_interactive_authentication(NonInteractiveAuthenticationRequest {
+                user: "Nyan".to_string(),
+                c: serde_json::to_string(&c).unwrap(),
+                s: serde_json::to_string(&s).unwrap(),
+                r1: EccChaumPedersen::to_base64(&r1),
+                r2: EccChaumPedersen::to_base64(&r2),
+                slot_id: 0,
+                nonce: Vec::new(),
+                created_at: Some(timestamp(now)),
+            })
+            .await
+            .unwrap();
+        assert!(!response.get_ref().session_id.is_empty());
+    }
+
+    /// Keys registered by the first client, `x` was the bare SHA-512 of the password and the
+    /// interactive keys were generated in the 3072-bit group
+    #[tokio::test]
+    async fn baseline_keys() {
+        let mut client = spawn().await;
+        let digest = Sha512::digest(b"cat");
+
+        let cp = Group::LEGACY.protocol();
+        let x = BigInt::from_bytes_le(Sign::Plus, &digest);
+        let (y1, y2) = (cp.g.modpow(&x, &cp.p), cp.h.modpow(&x, &cp.p));
+        client
+            .register(RegisterRequest {
+                user: "Nyan".to_string(),
+                y1: y1.to_str_radix(16),
+                y2: y2.to_str_radix(16),
+                group: Group::LEGACY.bits(),
+                auth_id: String::new(),
+                s: String::new(),
+                idempotency_key: String::new(),
+                nonce: Vec::new(),
+            })
+            .await
+            .unwrap();
+        assert!(login(&mut client, "Nyan", "cat", Group::LEGACY)
+            .await
+            .is_ok());
+
+        let ecc = EccChaumPedersen::new();
+        let x = Scalar::from_bytes_mod_order_wide(&Sha512::digest(digest).into());
+        let (y1, y2) = ecc.generate_public_keys(x).await.unwrap();
+        client
+            .register(RegisterRequest {
+                user: "Cat".to_string(),
+                y1: EccChaumPedersen::to_base64(&y1),
+                y2: EccChaumPedersen::to_base64(&y2),
+                group: Group::default().bits(),
+                auth_id: String::new(),
+                s: String::new(),
+                idempotency_key: String::new(),
+                nonce: Vec::new(),
+            })
+            .await
+            .unwrap();
+
+        let now = SystemClock.now_unix();
+        let ecc = ecc.with_timestamp(now);
+        let (k, r1, r2) = ecc.prover_commit().await.unwrap();
+        let c = ecc.derive_challenge(&r1, &r2);
+        let s = ecc.prover_solve_challenge(k, c, EccChaumPedersen::hash(b"cat"));
+        let response = client
+            .non_interactive_authentication(NonInteractiveAuthenticationRequest {
+                user: "Cat".to_string(),
+                c: serde_json::to_string(&c).unwrap(),
+                s: serde_json::to_string(&s).unwrap(),
+                r1: EccChaumPedersen::to_base64(&r1),
+                r2: EccChaumPedersen::to_base64(&r2),
+                slot_id: 0,
+                nonce: Vec::new(),
+                created_at: Some(timestamp(now)),
+            })
+            .await;
+        assert!(response.is_ok());
+    }
+
+    #[tokio::test]
+    async fn wrong_password() {
+        let mut client = spawn().await;
+        register(&mut client, "Nyan", "cat", Group::default()).await;
+
+        let status = login(&mut client, "Nyan", "dog", Group::default())
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+    }
+
+    #[tokio::test]
+    async fn unknown_user() {
+        let mut client = spawn().await;
+
+        // Unknown users are indistinguishable from a wrong password
+        let status = login(&mut client, "Nyan", "cat", Group::default())
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+    }
+
+    #[tokio::test]
+    async fn unregistered_user() {
+        let mut client = spawn().await;
+        register(&mut client, "Nyan", "cat", Group::default()).await;
+        assert!(login(&mut client, "Nyan", "cat", Group::default())
+            .await
+            .is_ok());
+
+        // Anonymous and wrongly proven deletes are rejected
+        let status = client
+            .unregister(UnregisterRequest {
+                user: "Nyan".to_string(),
+                auth_id: String::new(),
+                s: "0c".to_string(),
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+        let status = unregister(&mut client, "Nyan", "dog", Group::default())
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+
+        unregister(&mut client, "Nyan", "cat", Group::default())
+            .await
+            .unwrap();
+        let status = client
+            .get_public_keys(PublicKeysRequest {
+                user: "Nyan".to_string(),
+                slot_id: 0,
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::NotFound);
+        // Challenges for unknown users are still issued, the login fails like a wrong password
+        let status = login(&mut client, "Nyan", "cat", Group::default())
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+
+        // The name is free again
+        register(&mut client, "Nyan", "dog", Group::default()).await;
+    }
+
+    #[tokio::test]
+    async fn oversized_fields() {
+        let mut client = spawn().await;
+        let (y1, y2) = public_keys("cat", Group::default()).await;
+        let request = RegisterRequest {
+            user: "Nyan".to_string(),
+            y1,
+            y2,
+            group: Group::default().bits(),
+            auth_id: String::new(),
+            s: String::new(),
+            idempotency_key: String::new(),
+            nonce: Vec::new(),
+        };
+
+        // Longer than any key of the group, refused by its length before it's decoded
+        let status = client
+            .register(RegisterRequest {
+                y1: "f".repeat(4 * 1024),
+                ..request.clone()
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+        assert_eq!(status.message(), "y1 is longer than 512 characters");
+
+        // Larger than the whole message may be, refused before it's buffered
+        let status = client
+            .register(RegisterRequest {
+                y1: "f".repeat(DEFAULT_MAX_MESSAGE_SIZE),
+                ..request.clone()
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::OutOfRange);
+
+        let status = client
+            .get_public_keys(PublicKeysRequest {
+                user: "Nyan".to_string(),
+                slot_id: 0,
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::NotFound);
+        client.register(request).await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn second_key_slot() {
+        let mut client = spawn().await;
+        register(&mut client, "Nyan", "cat", Group::default()).await;
+        let session_id = login(&mut client, "Nyan", "cat", Group::default())
+            .await
+            .unwrap();
+
+        // Another device with its own password
+        let (y1, y2) = public_keys("dog", Group::default()).await;
+        let slot_id = client
+            .add_key_slot(AddKeySlotRequest {
+                session_id: session_id.clone(),
+                user: "Nyan".to_string(),
+                y1,
+                y2,
+            })
+            .await
+            .unwrap()
+            .into_inner()
+            .slot_id;
+        assert_eq!(slot_id, 1);
+
+        assert!(
+            slot_login(&mut client, "Nyan", slot_id, "dog", Group::default())
+                .await
+                .is_ok()
+        );
+        assert!(login(&mut client, "Nyan", "cat", Group::default())
+            .await
+            .is_ok());
+        let status = slot_login(&mut client, "Nyan", slot_id, "cat", Group::default())
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+
+        // Only a session of the user changes its slots
+        let status = client
+            .remove_key_slot(RemoveKeySlotRequest {
+                session_id: "forged".to_string(),
+                user: "Nyan".to_string(),
+                slot_id: 0,
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+
+        client
+            .remove_key_slot(RemoveKeySlotRequest {
+                session_id,
+                user: "Nyan".to_string(),
+                slot_id: 0,
+            })
+            .await
+            .unwrap();
+        let status = login(&mut client, "Nyan", "cat", Group::default())
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+        assert!(
+            slot_login(&mut client, "Nyan", slot_id, "dog", Group::default())
+                .await
+                .is_ok()
+        );
+    }
+}
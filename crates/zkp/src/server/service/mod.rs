@@ -1,4 +1,7 @@
 pub mod auth_service;
+pub mod convert;
+#[cfg(test)]
+pub mod harness;
 
 pub mod zkp {
     tonic::include_proto!("zkp_auth");
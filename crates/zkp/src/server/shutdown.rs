@@ -0,0 +1,130 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::time::Duration;
+
+use tokio::net::TcpListener;
+use tokio::sync::oneshot;
+use tokio_stream::wrappers::TcpListenerStream;
+use tonic::transport::Server;
+use tracing::{info, warn};
+
+use crate::service::auth_service::AuthService;
+
+/// How long in-flight RPCs get to finish once a shutdown signal arrives
+pub const DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub type ServeResult = Result<(), Box<dyn std::error::Error + Send + Sync>>;
+
+/// Resolves on SIGINT or SIGTERM
+pub async fn signal() {
+    let interrupt = async {
+        tokio::signal::ctrl_c()
+            .await
+            .expect("failed to listen for SIGINT");
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+            .expect("failed to listen for SIGTERM")
+            .recv()
+            .await;
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = interrupt => info!("received SIGINT"),
+        _ = terminate => info!("received SIGTERM"),
+    }
+}
+
+/// Serves `service` on `listener` until `shutdown` resolves, then stops accepting connections,
+/// gives in-flight RPCs up to `drain_timeout` to finish and flushes storage.
+pub async fn serve(
+    listener: TcpListener,
+    service: Arc<AuthService>,
+    shutdown: impl Future<Output = ()>,
+    drain_timeout: Duration,
+) -> ServeResult {
+    let (signalled_tx, signalled_rx) = oneshot::channel();
+    let shutdown = async move {
+        shutdown.await;
+        info!("shutting down, no longer accepting connections");
+        let _ = signalled_tx.send(());
+    };
+
+    let mut builder = Server::builder();
+    #[cfg(feature = "otel")]
+    {
+        builder = builder.trace_fn(crate::otel::request_span);
+    }
+    let server = builder
+        .add_service(service.clone().into_server())
+        .serve_with_incoming_shutdown(TcpListenerStream::new(listener), shutdown);
+    tokio::pin!(server);
+
+    tokio::select! {
+        result = &mut server => result?,
+        Ok(()) = signalled_rx => {
+            match tokio::time::timeout(drain_timeout, &mut server).await {
+                Ok(result) => {
+                    result?;
+                    info!("in-flight requests drained");
+                }
+                Err(_) => warn!(?drain_timeout, "drain timed out, dropping in-flight requests"),
+            }
+        }
+    }
+
+    service.flush().await?;
+    info!("storage flushed");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use storage::db::KeyValueStorage;
+    use zkp::local_auth::LocalAuth;
+
+    use crate::service::zkp::auth_client::AuthClient;
+    use crate::service::zkp::UserExistsRequest;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn shutdown_completes() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let service = AuthService::from(LocalAuth::with_storage(KeyValueStorage::temporary()));
+        let (tx, rx) = oneshot::channel::<()>();
+        let server = tokio::spawn(serve(
+            listener,
+            Arc::new(service),
+            async {
+                let _ = rx.await;
+            },
+            Duration::from_secs(5),
+        ));
+
+        let mut client = AuthClient::connect(format!("http://{}", addr))
+            .await
+            .unwrap();
+        client
+            .user_exists(UserExistsRequest {
+                user: "Nyan".to_string(),
+            })
+            .await
+            .unwrap();
+
+        tx.send(()).unwrap();
+        tokio::time::timeout(Duration::from_secs(10), server)
+            .await
+            .expect("server did not shut down")
+            .unwrap()
+            .unwrap();
+        assert!(AuthClient::connect(format!("http://{}", addr))
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,137 @@
+//! Ed25519 signatures of the responses that hand out credentials, so a client configured with the
+//! public key of the server can tell the verifier from a machine in the middle. A response is
+//! signed with its `signature` field empty, together with the nonce of the request it answers so an
+//! old response can't be replayed for a new request.
+
+use std::fmt;
+
+use ed25519_dalek::{Signature, Signer, SigningKey, Verifier, VerifyingKey};
+use tracing::warn;
+
+use crate::secret;
+
+/// 32 byte Ed25519 seed of the server, hex or base64 encoded
+pub const SIGNING_KEY_ENV: &str = "ZKP_SIGNING_KEY";
+
+/// Public key of the server the client verifies the responses with, hex or base64 encoded
+pub const SERVER_PUBLIC_KEY_ENV: &str = "ZKP_SERVER_PUBLIC_KEY";
+
+/// Longest nonce a client may send, anything above is refused before it's signed
+pub const MAX_NONCE_LEN: usize = 64;
+
+const RESPONSE_SIGNATURE_DOMAIN: &[u8] = b"zkp-response-signature";
+
+/// The responses that are signed, `RegisterResponse` and `AuthenticationAnswerResponse` encode the
+/// same way so the kind is part of the message
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SignedResponse {
+    Register,
+    AuthenticationAnswer,
+}
+
+impl SignedResponse {
+    fn label(self) -> &'static [u8] {
+        match self {
+            SignedResponse::Register => b"register",
+            SignedResponse::AuthenticationAnswer => b"authentication-answer",
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum SignatureError {
+    /// The key isn't hex or base64 of 32 bytes, or isn't a point of the curve
+    InvalidKey,
+    /// The signature doesn't verify with the server key
+    Mismatch,
+}
+
+impl fmt::Display for SignatureError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SignatureError::InvalidKey => write!(f, "invalid Ed25519 key"),
+            SignatureError::Mismatch => write!(f, "response signature doesn't verify"),
+        }
+    }
+}
+
+impl std::error::Error for SignatureError {}
+
+/// Reads the key from `SIGNING_KEY_ENV`, if it is unset an ephemeral key is generated and clients
+/// have to be given its public key again after a restart
+pub fn signing_key_from_env() -> Result<SigningKey, SignatureError> {
+    match std::env::var(SIGNING_KEY_ENV) {
+        Ok(value) => parse_signing_key(&value),
+        Err(_) => {
+            warn!("{} is not set, using an ephemeral key", SIGNING_KEY_ENV);
+            Ok(random_signing_key())
+        }
+    }
+}
+
+pub fn parse_signing_key(value: &str) -> Result<SigningKey, SignatureError> {
+    let seed = secret::parse(value).map_err(|_| SignatureError::InvalidKey)?;
+    Ok(SigningKey::from_bytes(&seed))
+}
+
+pub fn parse_verifying_key(value: &str) -> Result<VerifyingKey, SignatureError> {
+    let bytes = secret::parse(value).map_err(|_| SignatureError::InvalidKey)?;
+    VerifyingKey::from_bytes(&bytes).map_err(|_| SignatureError::InvalidKey)
+}
+
+pub fn random_signing_key() -> SigningKey {
+    SigningKey::from_bytes(&secret::random())
+}
+
+fn message(kind: SignedResponse, nonce: &[u8], response: &[u8]) -> Vec<u8> {
+    let label = kind.label();
+    let mut message = RESPONSE_SIGNATURE_DOMAIN.to_vec();
+    for part in [label, nonce, response] {
+        message.extend_from_slice(&(part.len() as u64).to_be_bytes());
+        message.extend_from_slice(part);
+    }
+    message
+}
+
+/// Signs the encoded `response` to a request carrying `nonce`, `response` is encoded with an
+/// empty signature
+pub fn sign(key: &SigningKey, kind: SignedResponse, nonce: &[u8], response: &[u8]) -> Vec<u8> {
+    key.sign(&message(kind, nonce, response)).to_vec()
+}
+
+/// Checks `signature` the way `sign` made it
+pub fn verify(
+    key: &VerifyingKey,
+    kind: SignedResponse,
+    nonce: &[u8],
+    response: &[u8],
+    signature: &[u8],
+) -> Result<(), SignatureError> {
+    let signature = Signature::from_slice(signature).map_err(|_| SignatureError::Mismatch)?;
+    key.verify(&message(kind, nonce, response), &signature)
+        .map_err(|_| SignatureError::Mismatch)
+}
+
+#[cfg(test)]
+mod tests {
+    use base64::engine::general_purpose::STANDARD;
+    use base64::Engine;
+
+    use super::*;
+
+    #[test]
+    fn parse_keys() {
+        let key = parse_signing_key(&hex::encode([7u8; 32])).unwrap();
+        let public = STANDARD.encode(key.verifying_key().as_bytes());
+        assert_eq!(parse_verifying_key(&public).unwrap(), key.verifying_key());
+
+        assert_eq!(
+            parse_signing_key("nyan").unwrap_err(),
+            SignatureError::InvalidKey
+        );
+        assert_eq!(
+            parse_verifying_key(&hex::encode([7u8; 16])).unwrap_err(),
+            SignatureError::InvalidKey
+        );
+    }
+}
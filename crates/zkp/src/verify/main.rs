@@ -0,0 +1,32 @@
+use std::io::{self, Read};
+use std::process::ExitCode;
+
+/// Exit code of a well-formed proof that doesn't verify
+const INVALID: u8 = 1;
+/// Exit code of unreadable or malformed input
+const ERROR: u8 = 2;
+
+/// Reads a statement from stdin, see `zkp::offline` for the format, and prints whether its proof
+/// is valid. Exits with 0 if it is.
+fn main() -> ExitCode {
+    let mut input = String::new();
+    if let Err(e) = io::stdin().read_to_string(&mut input) {
+        eprintln!("failed to read stdin: {}", e);
+        return ExitCode::from(ERROR);
+    }
+
+    match zkp::offline::verify(&input) {
+        Ok(true) => {
+            println!("valid");
+            ExitCode::SUCCESS
+        }
+        Ok(false) => {
+            println!("invalid");
+            ExitCode::from(INVALID)
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::from(ERROR)
+        }
+    }
+}
@@ -0,0 +1,131 @@
+//! Known-good and known-bad proofs through `zkp::offline::verify`, the function behind `zkp-verify`
+
+use num_bigint::BigInt;
+use serde_json::{json, Value};
+
+use chaum_pedersen::chaum_pedersen::{ChaumPedersen, CpParams, Group};
+use chaum_pedersen::ecc_chaum_pedersen::EccChaumPedersen;
+use chaum_pedersen::proof::Proof;
+use chaum_pedersen::prover::Prover;
+use chaum_pedersen::ChaumPedersenTrait;
+use zkp::offline::verify;
+
+/// Statement for a transcript proving knowledge of `password` against the keys of `registered`
+fn bigint_statement(registered: &[u8], password: &[u8]) -> (Value, Proof) {
+    let cp = Group::Modp2048.protocol();
+    let (y1, y2) = cp
+        .generate_public_keys_sync(ChaumPedersen::hash(registered))
+        .unwrap();
+
+    let mut prover = Prover::new(&cp);
+    let (r1, r2) = prover.commit_sync().unwrap();
+    let c = cp.verifier_generate_challenge();
+    let s = prover
+        .respond(c.clone(), ChaumPedersen::hash(password))
+        .unwrap();
+    let proof = Proof::new(r1, r2, c, s);
+
+    let statement = json!({
+        "type": "bigint",
+        "params": CpParams::from(&cp),
+        "y1": y1.to_str_radix(16),
+        "y2": y2.to_str_radix(16),
+        "proof": hex::encode(proof.to_bytes(cp.byte_len()).unwrap()),
+    });
+    (statement, proof)
+}
+
+fn ecc_statement(registered: &[u8], password: &[u8]) -> Value {
+    let ecc = EccChaumPedersen::new();
+    let (y1, y2) = ecc
+        .generate_public_keys_sync(EccChaumPedersen::hash(registered))
+        .unwrap();
+
+    let (k, r1, r2) = ecc.prover_commit_sync().unwrap();
+    let c = ecc.derive_challenge(&r1, &r2);
+    let s = ecc.prover_solve_challenge(k, c, EccChaumPedersen::hash(password));
+
+    json!({
+        "type": "ecc",
+        "y1": EccChaumPedersen::to_base64(&y1),
+        "y2": EccChaumPedersen::to_base64(&y2),
+        "proof": hex::encode([c.to_bytes(), s.to_bytes()].concat()),
+    })
+}
+
+#[test]
+fn bigint_proofs() {
+    let (good, proof) = bigint_statement(b"cat", b"cat");
+    assert_eq!(verify(&good.to_string()), Ok(true));
+
+    let (bad, _) = bigint_statement(b"cat", b"dog");
+    assert_eq!(verify(&bad.to_string()), Ok(false));
+
+    // The same transcript as JSON fields
+    let mut fields = good.clone();
+    fields["proof"] = json!({
+        "r1": proof.r1.to_str_radix(16),
+        "r2": proof.r2.to_str_radix(16),
+        "c": proof.c.to_str_radix(16),
+        "s": (proof.s + BigInt::from(1)).to_str_radix(16),
+    });
+    assert_eq!(verify(&fields.to_string()), Ok(false));
+}
+
+#[test]
+fn ecc_proofs() {
+    let good = ecc_statement(b"cat", b"cat");
+    assert_eq!(verify(&good.to_string()), Ok(true));
+
+    let bad = ecc_statement(b"cat", b"dog");
+    assert_eq!(verify(&bad.to_string()), Ok(false));
+
+    // Bound to another context the challenge doesn't re-derive
+    let mut other_context = good.clone();
+    other_context["context"] = json!("another-deployment");
+    assert_eq!(verify(&other_context.to_string()), Ok(false));
+
+    // Same for a timestamp the proof wasn't made at
+    let mut other_timestamp = good.clone();
+    other_timestamp["created_at"] = json!(1700000000);
+    assert_eq!(verify(&other_timestamp.to_string()), Ok(false));
+}
+
+#[test]
+fn timestamped_ecc_proof() {
+    let ecc = EccChaumPedersen::new().with_timestamp(1700000000);
+    let x = EccChaumPedersen::hash(b"cat");
+    let (y1, y2) = ecc.generate_public_keys_sync(x).unwrap();
+    let (k, r1, r2) = ecc.prover_commit_sync().unwrap();
+    let c = ecc.derive_challenge(&r1, &r2);
+    let s = ecc.prover_solve_challenge(k, c, x);
+
+    let mut statement = json!({
+        "type": "ecc",
+        "created_at": 1700000000,
+        "y1": EccChaumPedersen::to_base64(&y1),
+        "y2": EccChaumPedersen::to_base64(&y2),
+        "proof": hex::encode([c.to_bytes(), s.to_bytes()].concat()),
+    });
+    assert_eq!(verify(&statement.to_string()), Ok(true));
+
+    statement.as_object_mut().unwrap().remove("created_at");
+    assert_eq!(verify(&statement.to_string()), Ok(false));
+}
+
+#[test]
+fn malformed_statements() {
+    let (mut truncated, _) = bigint_statement(b"cat", b"cat");
+    truncated["proof"] = json!("00ff");
+    let mut untagged = ecc_statement(b"cat", b"cat");
+    untagged["type"] = json!("rsa");
+
+    for input in [
+        "".to_string(),
+        "nyancat".to_string(),
+        truncated.to_string(),
+        untagged.to_string(),
+    ] {
+        assert!(verify(&input).is_err(), "{}", input);
+    }
+}